@@ -0,0 +1,118 @@
+//! Cost of the steering and integration math for one boid, with `cargo bench`.
+
+use bevy::math::Vec3;
+use bevy_lyon_boid::{
+    sim::{
+        integrate, integrate_verlet, steer_alignment, steer_cohesion, steer_separation,
+        MotionLimits, SteeringParams,
+    },
+    ResponseCurve,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const PARAMS: SteeringParams = SteeringParams {
+    max_speed: 120.,
+    max_force: 360.,
+    response_curve: ResponseCurve::Linear,
+};
+
+const LIMITS: MotionLimits = MotionLimits {
+    drag: 0.3,
+    max_turn_rate: 6.,
+    max_speed: 120.,
+};
+
+/// Positions and velocities of `count` neighbors in a ring around the origin.
+fn neighbors(count: usize) -> Vec<(Vec3, Vec3)> {
+    (0..count)
+        .map(|index| {
+            let angle = index as f32 / count as f32 * std::f32::consts::TAU;
+            let direction = Vec3::new(angle.cos(), angle.sin(), 0.);
+            (direction * 40., direction.cross(Vec3::Z) * 80.)
+        })
+        .collect()
+}
+
+fn flocking(c: &mut Criterion) {
+    let position = Vec3::ZERO;
+    let velocity = Vec3::new(60., 20., 0.);
+    let mut group = c.benchmark_group("flocking");
+    for count in [8, 32, 128] {
+        let neighbors = neighbors(count);
+        group.bench_with_input(
+            BenchmarkId::new("separation", count),
+            &neighbors,
+            |b, neighbors| {
+                b.iter(|| {
+                    steer_separation(
+                        black_box(position),
+                        black_box(velocity),
+                        neighbors.iter().copied(),
+                        60.,
+                        1.,
+                        &PARAMS,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("alignment", count),
+            &neighbors,
+            |b, neighbors| {
+                b.iter(|| {
+                    steer_alignment(
+                        black_box(velocity),
+                        neighbors.iter().map(|(_, velocity)| *velocity),
+                        &PARAMS,
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("cohesion", count),
+            &neighbors,
+            |b, neighbors| {
+                b.iter(|| {
+                    steer_cohesion(
+                        black_box(position),
+                        black_box(velocity),
+                        neighbors.iter().map(|(position, _)| *position),
+                        &PARAMS,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn integration(c: &mut Criterion) {
+    let velocity = Vec3::new(60., 20., 0.);
+    let acceleration = Vec3::new(-100., 250., 0.);
+    c.bench_function("integrate", |b| {
+        b.iter(|| {
+            integrate(
+                black_box(Vec3::ZERO),
+                black_box(velocity),
+                black_box(acceleration),
+                1. / 60.,
+                &LIMITS,
+            )
+        })
+    });
+    c.bench_function("integrate_verlet", |b| {
+        b.iter(|| {
+            integrate_verlet(
+                black_box(Vec3::ZERO),
+                black_box(velocity),
+                black_box(acceleration),
+                black_box(acceleration),
+                1. / 60.,
+                &LIMITS,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, flocking, integration);
+criterion_main!(benches);
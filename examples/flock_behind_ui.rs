@@ -0,0 +1,50 @@
+//! The flock drawn behind an app's own interface: the flock's camera only
+//! sees render layer 1 and is drawn first, then the app's camera draws a side
+//! panel over it without clearing the screen.
+
+use bevy::{core_pipeline::clear_color::ClearColorConfig, prelude::*, render::view::RenderLayers};
+use bevy_lyon_boid::{AppState, BoidPlugin, FlockView};
+
+fn main() {
+    App::new()
+        .insert_resource(FlockView {
+            layers: RenderLayers::layer(1),
+            priority: -1,
+            ..default()
+        })
+        .add_plugins(DefaultPlugins)
+        .add_state(AppState::Running)
+        .add_plugin(BoidPlugin)
+        .add_startup_system(spawn_panel)
+        .run();
+}
+
+fn spawn_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2dBundle {
+        camera_2d: Camera2d {
+            // keep what the flock's camera drew
+            clear_color: ClearColorConfig::None,
+        },
+        ..default()
+    });
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(240.), Val::Percent(100.)),
+                padding: UiRect::all(Val::Px(12.)),
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.15, 0.85).into(),
+            ..default()
+        })
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section(
+                "The flock flies behind this panel",
+                TextStyle {
+                    font: asset_server.load("fonts/Hack-Regular.ttf"),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
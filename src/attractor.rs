@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    lod::Lod,
+    predator::FLEE_PRIORITY,
+    render::{CursorPosition, FlockView, RenderOrder},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Point pulling boids in, or pushing them away with a negative `strength`.
+/// The force is `strength` at `falloff` world units and falls off with the
+/// square of the distance.
+#[derive(Component, Clone, Copy)]
+pub struct Attractor {
+    pub strength: f32,
+    pub falloff: f32,
+}
+
+impl Attractor {
+    pub fn attract() -> Self {
+        Self {
+            strength: 120.,
+            falloff: 100.,
+        }
+    }
+
+    pub fn repel() -> Self {
+        Self {
+            strength: -240.,
+            falloff: 60.,
+        }
+    }
+
+    pub fn is_repulsor(&self) -> bool {
+        self.strength < 0.
+    }
+
+    /// Force felt by a boid at `offset` from the attractor.
+    pub fn force(&self, offset: Vec2) -> Vec2 {
+        // closer than this, the force stops growing
+        let nearest = self.falloff / 4.;
+        let distance = offset.length().max(nearest);
+        -offset.normalize_or_zero() * self.strength * (self.falloff / distance).powi(2)
+    }
+}
+
+/// Spawns `attractor` at `position`, drawn as a green ring around a dot, or
+/// a red ring around a cross for a repulsor.
+pub fn spawn_attractor(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    attractor: Attractor,
+    position: Vec2,
+) -> Entity {
+    const RADIUS: f32 = 10.;
+
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::X * RADIUS);
+    builder.arc(Vec2::ZERO, Vec2::splat(RADIUS), std::f32::consts::TAU, 0.);
+    let color = if attractor.is_repulsor() {
+        let arm = RADIUS * 0.5;
+        builder.move_to(Vec2::new(-arm, -arm));
+        builder.line_to(Vec2::new(arm, arm));
+        builder.move_to(Vec2::new(-arm, arm));
+        builder.line_to(Vec2::new(arm, -arm));
+        Color::ORANGE_RED
+    } else {
+        builder.move_to(Vec2::X * 2.);
+        builder.arc(Vec2::ZERO, Vec2::splat(2.), std::f32::consts::TAU, 0.);
+        Color::LIME_GREEN
+    };
+
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &builder.build(),
+            DrawMode::Stroke(StrokeMode::new(color, 2.)),
+            Transform::from_translation(position.extend(order.target)),
+        ))
+        .insert(view.layers)
+        .insert(attractor)
+        .id()
+}
+
+pub(crate) fn attract_boids(
+    attractors: Query<(&Transform, &Attractor)>,
+    mut boids: Query<(&Transform, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+) {
+    if attractors.is_empty() {
+        return;
+    }
+    boids.par_for_each_mut(PAR_BATCH_SIZE, |(transform, mut forces, lod)| {
+        if lod.is_some_and(Lod::skips_steering) {
+            return;
+        }
+        let position = transform.translation.truncate();
+        let force: Vec2 = attractors
+            .iter()
+            .map(|(attractor_transform, attractor)| {
+                attractor.force(position - attractor_transform.translation.truncate())
+            })
+            .sum();
+        forces.behavior("attractors").add(force.extend(0.), 1.);
+    });
+}
+
+/// What holding a mouse button does to the flock in stirring mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stir {
+    /// Every boid heads for the cursor instead of its target.
+    Attract,
+    /// Every boid flees the cursor.
+    Repel,
+}
+
+/// Mouse mode toggled with `Q`: while on, holding the left button draws the
+/// whole flock to the cursor and holding the right one scatters it, the
+/// closer boids the harder. Clicks do nothing else meanwhile.
+#[derive(Resource)]
+pub struct CursorStir {
+    pub enabled: bool,
+    // distance to the cursor, in world units, at which the pull or push is
+    // half as strong as on it
+    pub falloff: f32,
+    // the button held and where the cursor is, this frame
+    active: Option<(Stir, Vec2)>,
+}
+
+impl Default for CursorStir {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            falloff: 200.,
+            active: None,
+        }
+    }
+}
+
+impl CursorStir {
+    /// How the flock is being stirred, and around which point.
+    pub fn active(&self) -> Option<(Stir, Vec2)> {
+        self.active
+    }
+
+    /// Whether boids head for the cursor rather than their own target.
+    pub fn attracts(&self) -> bool {
+        self.active.is_some_and(|(stir, _)| stir == Stir::Attract)
+    }
+}
+
+pub(crate) fn toggle_cursor_stir(keyboard: Res<Input<KeyCode>>, mut stir: ResMut<CursorStir>) {
+    if keyboard.just_pressed(KeyCode::Q) {
+        stir.enabled = !stir.enabled;
+        info!(
+            "stirring the flock with the mouse {}",
+            if stir.enabled { "on" } else { "off" }
+        );
+    }
+}
+
+pub(crate) fn stir_with_mouse(
+    mouse: Res<Input<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    mut stir: ResMut<CursorStir>,
+) {
+    let held = if !stir.enabled {
+        None
+    } else if mouse.pressed(MouseButton::Left) {
+        Some(Stir::Attract)
+    } else if mouse.pressed(MouseButton::Right) {
+        Some(Stir::Repel)
+    } else {
+        None
+    };
+    stir.active = held.zip(cursor.0);
+}
+
+/// Steers every boid towards the cursor, or away from it, while a button is
+/// held in stirring mode. Fleeing comes before the other behaviors, as from a
+/// predator.
+pub(crate) fn stir_boids(
+    config: Res<FlockConfig>,
+    stir: Option<Res<CursorStir>>,
+    mut boids: Query<(&Transform, &Physics, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+) {
+    let Some((mode, cursor)) = stir.as_ref().and_then(|stir| stir.active()) else {
+        return;
+    };
+    let falloff = stir.map_or(1., |stir| stir.falloff).max(f32::EPSILON);
+    boids.par_for_each_mut(PAR_BATCH_SIZE, |(transform, physics, mut forces, lod)| {
+        if lod.is_some_and(Lod::skips_steering) {
+            return;
+        }
+        let offset = cursor - transform.translation.truncate();
+        let weight = falloff / (falloff + offset.length());
+        let direction = offset.normalize_or_zero().extend(0.);
+        match mode {
+            Stir::Attract => {
+                let desired = direction * physics.max_speed;
+                let force = steer_towards(desired, physics, config.response_curve);
+                forces.behavior("cursor").add(force, weight);
+            }
+            Stir::Repel => {
+                let desired = -direction * physics.max_speed;
+                let force = steer_towards(desired, physics, config.response_curve);
+                forces
+                    .behavior("cursor")
+                    .add_with_priority(force, weight, FLEE_PRIORITY);
+            }
+        }
+    });
+}
+
+/// `Z` places an attractor at the cursor, `V` a repulsor.
+pub(crate) fn place_attractors(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    let attractor = if keyboard.just_pressed(KeyCode::Z) {
+        Attractor::attract()
+    } else if keyboard.just_pressed(KeyCode::V) {
+        Attractor::repel()
+    } else {
+        return;
+    };
+    if let Some(position) = cursor.0 {
+        spawn_attractor(&mut commands, &view, &order, attractor, position);
+    }
+}
+
+/// `K` removes every attractor and repulsor.
+pub(crate) fn clear_attractors(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    attractors: Query<Entity, With<Attractor>>,
+) {
+    if keyboard.just_pressed(KeyCode::K) {
+        for attractor in attractors.iter() {
+            commands.entity(attractor).despawn_recursive();
+        }
+    }
+}
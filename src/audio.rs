@@ -0,0 +1,107 @@
+use bevy::{audio::AudioSink, prelude::*};
+
+use crate::{
+    boid::BoidSpawned, flock::FlockConfig, predator::PredatorCaughtPrey, stats::FlockStats,
+    target::TargetReached,
+};
+
+/// Sounds played on simulation events, relative to the assets folder, `None`
+/// for silence, and a looping ambient whoosh which gets louder and higher as
+/// the flock speeds up. Events coming in bursts, like a whole flock spawning
+/// at once, play their sound once per `min_interval` seconds at most.
+#[derive(Resource)]
+pub struct SoundEffects {
+    pub boid_spawned: Option<String>,
+    pub target_reached: Option<String>,
+    pub prey_caught: Option<String>,
+    pub ambient: Option<String>,
+    pub volume: f32,
+    // ambient volume with the flock flying at `FlockConfig::max_speed`
+    pub ambient_volume: f32,
+    pub min_interval: f32,
+}
+
+impl Default for SoundEffects {
+    fn default() -> Self {
+        Self {
+            boid_spawned: Some("sounds/spawn.wav".to_string()),
+            target_reached: Some("sounds/reach.wav".to_string()),
+            prey_caught: Some("sounds/catch.wav".to_string()),
+            ambient: Some("sounds/whoosh.wav".to_string()),
+            volume: 0.5,
+            ambient_volume: 0.4,
+            min_interval: 0.08,
+        }
+    }
+}
+
+/// The ambient loop playing, to follow the flock's speed.
+#[derive(Resource)]
+pub(crate) struct Ambient(Handle<AudioSink>);
+
+pub(crate) fn play_ambient(
+    mut commands: Commands,
+    effects: Res<SoundEffects>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+) {
+    let Some(ambient) = &effects.ambient else {
+        return;
+    };
+    let sound = asset_server.load(ambient.as_str());
+    // silent until the flock moves
+    let sink = audio.play_with_settings(sound, PlaybackSettings::LOOP.with_volume(0.));
+    commands.insert_resource(Ambient(sinks.get_handle(sink)));
+}
+
+/// Plays the sound of each kind of event sent since the last frame.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn play_event_sounds(
+    time: Res<Time>,
+    effects: Res<SoundEffects>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    // when each sound last played
+    mut last_played: Local<[Option<f32>; 3]>,
+    mut spawned: EventReader<BoidSpawned>,
+    mut reached: EventReader<TargetReached>,
+    mut caught: EventReader<PredatorCaughtPrey>,
+) {
+    let now = time.elapsed_seconds();
+    let events = [
+        (spawned.iter().count(), &effects.boid_spawned),
+        (reached.iter().count(), &effects.target_reached),
+        (caught.iter().count(), &effects.prey_caught),
+    ];
+    for ((count, sound), last) in events.into_iter().zip(last_played.iter_mut()) {
+        let Some(sound) = sound else {
+            continue;
+        };
+        let rested = last.is_none_or(|last| now - last >= effects.min_interval);
+        if count == 0 || !rested {
+            continue;
+        }
+        audio.play_with_settings(
+            asset_server.load(sound.as_str()),
+            PlaybackSettings::ONCE.with_volume(effects.volume),
+        );
+        *last = Some(now);
+    }
+}
+
+/// Turns the ambient loop up, and its pitch, with the flock's average speed.
+pub(crate) fn follow_flock_speed(
+    effects: Res<SoundEffects>,
+    config: Res<FlockConfig>,
+    stats: Res<FlockStats>,
+    ambient: Option<Res<Ambient>>,
+    sinks: Res<Assets<AudioSink>>,
+) {
+    let Some(sink) = ambient.and_then(|ambient| sinks.get(&ambient.0)) else {
+        return;
+    };
+    let pace = (stats.average_speed / config.max_speed.max(f32::EPSILON)).clamp(0., 1.);
+    sink.set_volume(effects.ambient_volume * pace);
+    sink.set_speed(0.8 + 0.4 * pace);
+}
@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics, Steering},
+    flock::{FlockConfig, FlockId},
+    grid::GridEntry,
+    lod::Lod,
+    neighbors::{NeighborCache, NeighborIndex, SpatialIndex},
+    steering::{SteeringForces, PAR_BATCH_SIZE},
+    tick::SimTick,
+};
+
+/// Steering behavior of another crate, added to the flock with
+/// `AddSteeringBehavior::add_steering_behavior`. Every step, each boid asks
+/// it for a force, which `accumulate_steering` blends with the built-in
+/// behaviors at `weight` and `priority`, as it does theirs.
+///
+/// ```ignore
+/// struct Spin;
+///
+/// impl SteeringBehavior for Spin {
+///     fn steer(&self, boid: &BoidState, _: &WorldSnapshot) -> Vec3 {
+///         boid.physics.velocity.cross(Vec3::Z)
+///     }
+/// }
+///
+/// app.add_plugin(BoidPlugin).add_steering_behavior(Spin);
+/// ```
+pub trait SteeringBehavior: Send + Sync + 'static {
+    /// Force the behavior puts on `boid`, before weighting.
+    fn steer(&self, boid: &BoidState, world: &WorldSnapshot) -> Vec3;
+
+    fn weight(&self) -> f32 {
+        1.
+    }
+
+    /// See `SteeringForces::add_with_priority`.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Shown by the boid labels when the behavior steers a boid the most.
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// The boid a `SteeringBehavior` steers.
+pub struct BoidState<'a> {
+    pub entity: Entity,
+    pub transform: &'a Transform,
+    pub physics: &'a Physics,
+    pub steering: &'a Steering,
+    pub flock: FlockId,
+    pub neighbors: Option<&'a NeighborCache>,
+}
+
+/// What a `SteeringBehavior` sees of the rest of the simulation.
+pub struct WorldSnapshot<'a> {
+    pub config: &'a FlockConfig,
+    pub index: &'a SpatialIndex,
+    // simulated seconds since the start, and in this step
+    pub elapsed: f32,
+    pub step: f32,
+}
+
+impl WorldSnapshot<'_> {
+    /// Boids other than `boid` closer than `radius` to it, as of the start
+    /// of the step.
+    pub fn neighbors<'a>(
+        &'a self,
+        boid: &BoidState,
+        radius: f32,
+    ) -> impl Iterator<Item = &'a GridEntry> + 'a {
+        self.index
+            .others_within(boid.entity, boid.transform.translation, radius)
+    }
+
+    /// The crowd `boid` separates from and the flockmates it follows, from
+    /// its `NeighborCache`, which is cheaper than looking them up again.
+    pub fn cached_neighbors<'a>(
+        &'a self,
+        boid: &BoidState,
+    ) -> (Vec<&'a GridEntry>, Vec<&'a GridEntry>) {
+        boid.neighbors
+            .map(|cache| cache.resolve(self.index, boid.transform.translation))
+            .unwrap_or_default()
+    }
+}
+
+/// Behaviors added with `add_steering_behavior`, in the order they were.
+#[derive(Resource, Default)]
+pub(crate) struct SteeringBehaviors(Vec<Box<dyn SteeringBehavior>>);
+
+/// Adds a `SteeringBehavior` to the simulation, with `BoidPlugin` or
+/// `SimulationPlugin`, before or after either is added.
+pub trait AddSteeringBehavior {
+    fn add_steering_behavior(&mut self, behavior: impl SteeringBehavior) -> &mut Self;
+}
+
+impl AddSteeringBehavior for App {
+    fn add_steering_behavior(&mut self, behavior: impl SteeringBehavior) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(SteeringBehaviors::default)
+            .0
+            .push(Box::new(behavior));
+        self
+    }
+}
+
+/// Adds the forces of the `SteeringBehavior`s of other crates.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_steering_behaviors(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    behaviors: Res<SteeringBehaviors>,
+    lods: Query<&Lod>,
+    flocks: Query<&FlockId>,
+    caches: Query<&NeighborCache>,
+    mut boids: Query<(Entity, &Transform, &Physics, &Steering, &mut SteeringForces), With<Boid>>,
+) {
+    if behaviors.0.is_empty() {
+        return;
+    }
+    let world = WorldSnapshot {
+        config: &config,
+        index: &index,
+        elapsed: tick.elapsed(),
+        step: tick.step(),
+    };
+
+    boids.par_for_each_mut(
+        PAR_BATCH_SIZE,
+        |(entity, transform, physics, steering, mut forces)| {
+            if lods.get(entity).is_ok_and(Lod::skips_steering) {
+                return;
+            }
+            let boid = BoidState {
+                entity,
+                transform,
+                physics,
+                steering,
+                flock: flocks.get(entity).copied().unwrap_or_default(),
+                neighbors: caches.get(entity).ok(),
+            };
+            for behavior in &behaviors.0 {
+                let force = behavior.steer(&boid, &world);
+                forces.behavior(behavior.name()).add_with_priority(
+                    force,
+                    behavior.weight(),
+                    behavior.priority(),
+                );
+            }
+        },
+    );
+}
@@ -0,0 +1,481 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    energy::Energy,
+    flock::{FlockConfig, FlockId},
+    flocking::FlockingForce,
+    goal::SpawnStep,
+    lod::Lod,
+    neighbors::NeighborCache,
+    perception::Perception,
+    perching::Perching,
+    predator::Evade,
+    render::{Breathing, FlockView},
+    steering::{SteeringForces, Wander},
+    tick::{Interpolated, SimTick},
+    trail::Trail,
+};
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Boid;
+
+/// Stable number identifying a boid, handed out in spawn order.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoidId(pub u32);
+
+/// Free-form label marking a subset of the flock (scouts, leaders, injured...)
+/// so custom logic or coloring can single it out, see `boids_with_tag`.
+#[derive(Component, Clone, PartialEq, Eq, Hash)]
+pub struct Tag(pub String);
+
+/// Motion of a boid. Velocities are in world units per second, accelerations
+/// in world units per second squared and forces in mass times that: a boid
+/// of mass 1 is accelerated by exactly the forces it feels.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Physics {
+    pub velocity: Vec3,
+    pub acceleration: Vec3,
+    // acceleration of the step before, for `Integrator::VelocityVerlet`
+    pub previous_acceleration: Vec3,
+    pub max_speed: f32,
+    pub max_force: f32,
+    // radians per second the heading can turn by, infinite for none
+    pub max_turn_rate: f32,
+    // heavier boids are accelerated less by the same force
+    pub mass: f32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            acceleration: Vec3::ZERO,
+            previous_acceleration: Vec3::ZERO,
+            max_speed: 0.,
+            max_force: 0.,
+            max_turn_rate: f32::INFINITY,
+            mass: 1.,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Steering {
+    pub target: Vec3,
+    // boids further than this from the target ignore it
+    pub max_seek_range: f32,
+}
+
+/// How big a boid is next to `FlockConfig::boid_length` and `boid_width`,
+/// following its mass with `FlockConfig::scale_by_mass` and its energy with
+/// `FlockConfig::scale_by_energy`. It scales the boid as drawn, and the room
+/// it keeps from its neighbors.
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct BodySize(pub f32);
+
+impl Default for BodySize {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+impl BodySize {
+    /// Size of a boid of `mass` with `energy` left, under `config`.
+    pub fn of(config: &FlockConfig, mass: f32, energy: Option<&Energy>) -> Self {
+        let Self(size) = Self::of_mass(config.scale_by_mass, mass);
+        match (config.scale_by_energy, energy) {
+            // starving boids shrink to half their size
+            (true, Some(energy)) => Self(size * (0.5 + 0.5 * energy.fraction())),
+            _ => Self(size),
+        }
+    }
+
+    fn of_mass(scale_by_mass: bool, mass: f32) -> Self {
+        // the area of the boid grows with its mass
+        if scale_by_mass {
+            Self(mass.max(0.).sqrt())
+        } else {
+            Self(1.)
+        }
+    }
+}
+
+/// Boid singled out for inspection by the debug gizmos.
+#[derive(Component)]
+pub struct Selected;
+
+/// Outline boids are drawn with, pointing up. Corners are relative to the
+/// boid's size, x across its width and y along its length from -0.5 to 0.5:
+/// the default triangle has its tail corners at (-0.5, -0.5) and (0.5, -0.5)
+/// and its tip at (0, 0.5).
+#[derive(Clone, PartialEq, Debug, Reflect, FromReflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoidShape {
+    pub points: Vec<Vec2>,
+    // line from the center along the heading, in boid lengths, none at 0
+    pub heading_line: f32,
+    pub stroke_width: f32,
+    // size drawn over the size the boid flies with
+    pub scale: f32,
+}
+
+impl Default for BoidShape {
+    fn default() -> Self {
+        Self {
+            points: vec![
+                Vec2::new(-0.5, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0., 0.5),
+            ],
+            heading_line: 1.,
+            stroke_width: 1.,
+            scale: 1.,
+        }
+    }
+}
+
+impl BoidShape {
+    /// Outline of a boid `length` long and `width` wide.
+    pub(crate) fn path(&self, length: f32, width: f32) -> Path {
+        let size = Vec2::new(width, length) * self.scale;
+        let outline = shapes::Polygon {
+            points: self.points.iter().map(|point| *point * size).collect(),
+            closed: true,
+        };
+        let mut path = ShapePath::new().add(&outline);
+        if self.heading_line > 0. {
+            let tip = Vec2::new(0., size.y * self.heading_line);
+            path = path.add(&shapes::Line(Vec2::ZERO, tip));
+        }
+        path.build()
+    }
+}
+
+/// Components making up a boid, see `BoidBuilder`.
+#[derive(Bundle)]
+pub struct BoidBundle {
+    #[bundle]
+    pub shape: ShapeBundle,
+    pub physics: Physics,
+    pub steering: Steering,
+    pub perception: Perception,
+    pub flock: FlockId,
+    pub layers: RenderLayers,
+    pub boid: Boid,
+    pub size: BodySize,
+    forces: SteeringForces,
+    flocking: FlockingForce,
+    wander: Wander,
+    evade: Evade,
+    interpolated: Interpolated,
+    neighbors: NeighborCache,
+    trail: Trail,
+    lod: Lod,
+    perching: Perching,
+    spawned: SpawnStep,
+}
+
+/// Assembles a `BoidBundle`. Unless told otherwise boids start at the origin,
+/// standing still, in flock 0, sized and tuned like `FlockConfig::default()`.
+///
+/// ```ignore
+/// commands.spawn(BoidBuilder::new().at(position).flock(1).build());
+/// ```
+pub struct BoidBuilder {
+    position: Vec3,
+    velocity: Vec3,
+    flock: FlockId,
+    layers: RenderLayers,
+    max_speed: f32,
+    max_force: f32,
+    max_turn_rate: f32,
+    mass: f32,
+    scale_by_mass: bool,
+    neighbor_radius: f32,
+    field_of_view: f32,
+    length: f32,
+    width: f32,
+    shape: BoidShape,
+}
+
+impl Default for BoidBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoidBuilder {
+    pub fn new() -> Self {
+        let config = FlockConfig::default();
+        Self {
+            position: Vec3::ZERO,
+            velocity: Vec3::ZERO,
+            flock: FlockId::default(),
+            layers: RenderLayers::default(),
+            max_speed: config.max_speed,
+            max_force: config.max_force,
+            max_turn_rate: config.max_turn_rate,
+            mass: (config.spawn_mass.start + config.spawn_mass.end) / 2.,
+            scale_by_mass: config.scale_by_mass,
+            neighbor_radius: config.neighbor_radius,
+            field_of_view: config.field_of_view,
+            length: config.boid_length,
+            width: config.boid_width,
+            shape: config.shape.clone(),
+        }
+    }
+
+    /// Takes the size, speed, force and perception of the boid from `config`.
+    pub fn config(self, config: &FlockConfig) -> Self {
+        Self {
+            max_speed: config.max_speed,
+            max_force: config.max_force,
+            max_turn_rate: config.max_turn_rate,
+            mass: (config.spawn_mass.start + config.spawn_mass.end) / 2.,
+            scale_by_mass: config.scale_by_mass,
+            neighbor_radius: config.neighbor_radius,
+            field_of_view: config.field_of_view,
+            length: config.boid_length,
+            width: config.boid_width,
+            shape: config.shape.clone(),
+            ..self
+        }
+    }
+
+    /// Draws the boid where `view` draws the flock.
+    pub fn view(self, view: &FlockView) -> Self {
+        Self {
+            layers: view.layers,
+            ..self
+        }
+    }
+
+    pub fn at(self, position: Vec3) -> Self {
+        Self { position, ..self }
+    }
+
+    pub fn velocity(self, velocity: Vec3) -> Self {
+        Self { velocity, ..self }
+    }
+
+    pub fn mass(self, mass: f32) -> Self {
+        Self { mass, ..self }
+    }
+
+    pub fn flock(self, id: u32) -> Self {
+        Self {
+            flock: FlockId(id),
+            ..self
+        }
+    }
+
+    /// Draws the boid with `shape` rather than the shape of its config.
+    pub fn shape(self, shape: &BoidShape) -> Self {
+        Self {
+            shape: shape.clone(),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> BoidBundle {
+        // new boids are full of energy, if they have any
+        let size = BodySize::of_mass(self.scale_by_mass, self.mass);
+
+        BoidBundle {
+            shape: ShapeBundle {
+                path: self.shape.path(self.length, self.width),
+                mode: DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::BLUE),
+                    outline_mode: StrokeMode::new(Color::WHITE, self.shape.stroke_width),
+                },
+                transform: Transform::from_translation(self.position)
+                    .with_scale(Vec3::splat(size.0)),
+                ..default()
+            },
+            physics: Physics {
+                velocity: self.velocity,
+                acceleration: Vec3::default(),
+                previous_acceleration: Vec3::default(),
+                max_speed: self.max_speed,
+                max_force: self.max_force,
+                max_turn_rate: self.max_turn_rate,
+                mass: self.mass,
+            },
+            steering: Steering {
+                target: Vec3::new(0., 0., 0.),
+                max_seek_range: 500.,
+            },
+            perception: Perception {
+                radius: self.neighbor_radius,
+                field_of_view: self.field_of_view,
+            },
+            flock: self.flock,
+            layers: self.layers,
+            boid: Boid,
+            size,
+            forces: SteeringForces::default(),
+            flocking: FlockingForce::default(),
+            wander: Wander::default(),
+            evade: Evade::default(),
+            interpolated: Interpolated::new(self.position.truncate()),
+            neighbors: NeighborCache::default(),
+            trail: Trail::default(),
+            lod: Lod::default(),
+            perching: Perching::default(),
+            // stamped with the actual step once the boid is in the world
+            spawned: SpawnStep(0),
+        }
+    }
+}
+
+/// Spawns a boid drawn and configured like the rest of the flock.
+pub fn spawn_boid_at(
+    commands: &mut Commands,
+    view: &FlockView,
+    config: &FlockConfig,
+    position: Vec3,
+    velocity: Vec3,
+) -> Entity {
+    let boid = BoidBuilder::new()
+        .config(config)
+        .view(view)
+        .at(position)
+        .velocity(velocity);
+    commands.spawn(boid.build()).id()
+}
+
+/// Keeps the `BodySize` of boids in line with their mass and energy, and
+/// with the `Breathing` when it sets their spacing.
+pub(crate) fn grow_boids(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    breathing: Option<Res<Breathing>>,
+    energies: Query<&Energy>,
+    ids: Query<&BoidId>,
+    mut boids: Query<(Entity, &Physics, &mut BodySize), With<Boid>>,
+) {
+    let seconds = tick.elapsed();
+    for (entity, physics, mut size) in boids.iter_mut() {
+        let mut grown = BodySize::of(&config, physics.mass, energies.get(entity).ok());
+        if let (Some(breathing), Ok(id)) = (breathing.as_deref(), ids.get(entity)) {
+            if breathing.spacing {
+                grown.0 *= 1. + breathing.pulse(*id, seconds);
+            }
+        }
+        if grown.0 != size.0 {
+            *size = grown;
+        }
+    }
+}
+
+pub(crate) fn assign_boid_ids(
+    mut commands: Commands,
+    mut next_id: Local<u32>,
+    query: Query<Entity, (With<Boid>, Without<BoidId>)>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(BoidId(*next_id));
+        *next_id += 1;
+    }
+}
+
+/// Sent when a boid joins the simulation, however it was spawned.
+pub struct BoidSpawned {
+    pub entity: Entity,
+}
+
+pub(crate) fn announce_new_boids(
+    boids: Query<Entity, Added<Boid>>,
+    mut spawned: EventWriter<BoidSpawned>,
+) {
+    for entity in boids.iter() {
+        spawned.send(BoidSpawned { entity });
+    }
+}
+
+/// Entities among `boids` labelled with `tag`.
+pub fn boids_with_tag<'a, I>(boids: I, tag: &'a str) -> impl Iterator<Item = Entity> + 'a
+where
+    I: IntoIterator<Item = (Entity, &'a Tag)>,
+    I::IntoIter: 'a,
+{
+    boids
+        .into_iter()
+        .filter(move |(_, boid_tag)| boid_tag.0 == tag)
+        .map(|(entity, _)| entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_prototype_lyon::prelude::tess::path::PathEvent;
+
+    use super::*;
+
+    /// Corners of each sub-path of `path`, and whether it is closed.
+    fn subpaths(path: &Path) -> Vec<(Vec<Vec2>, bool)> {
+        let mut subpaths = Vec::new();
+        for event in path.0.iter() {
+            match event {
+                PathEvent::Begin { at } => subpaths.push((vec![Vec2::new(at.x, at.y)], false)),
+                PathEvent::Line { to, .. } => {
+                    let (points, _) = subpaths.last_mut().unwrap();
+                    points.push(Vec2::new(to.x, to.y));
+                }
+                PathEvent::End { close, .. } => subpaths.last_mut().unwrap().1 = close,
+                _ => panic!("boids are drawn with straight lines"),
+            }
+        }
+        subpaths
+    }
+
+    #[test]
+    fn no_heading_line_draws_the_triangle_alone() {
+        let shape = BoidShape {
+            heading_line: 0.,
+            ..default()
+        };
+        let subpaths = subpaths(&shape.path(50., 30.));
+        assert_eq!(subpaths.len(), 1);
+        let (points, closed) = &subpaths[0];
+        assert!(closed);
+        assert_eq!(
+            points,
+            &vec![
+                Vec2::new(-15., -25.),
+                Vec2::new(15., -25.),
+                Vec2::new(0., 25.)
+            ]
+        );
+    }
+
+    #[test]
+    fn heading_line_is_drawn_apart_from_the_triangle() {
+        let subpaths = subpaths(&BoidShape::default().path(50., 30.));
+        assert_eq!(subpaths.len(), 2);
+        assert_eq!(subpaths[1], (vec![Vec2::ZERO, Vec2::new(0., 50.)], false));
+    }
+
+    #[test]
+    fn boids_with_tag_picks_the_tagged_boids() {
+        let mut world = World::new();
+        let scouts = [
+            world.spawn((Boid, Tag("scout".to_string()))).id(),
+            world.spawn((Boid, Tag("scout".to_string()))).id(),
+        ];
+        world.spawn((Boid, Tag("decoy".to_string())));
+        world.spawn((Boid, Tag("scouts".to_string())));
+        world.spawn(Boid);
+
+        let mut tags = world.query::<(Entity, &Tag)>();
+        let mut tagged: Vec<Entity> = boids_with_tag(tags.iter(&world), "scout").collect();
+        tagged.sort();
+        assert_eq!(tagged, scouts);
+        assert_eq!(boids_with_tag(tags.iter(&world), "leader").count(), 0);
+    }
+}
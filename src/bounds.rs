@@ -0,0 +1,304 @@
+use bevy::{
+    prelude::*,
+    window::{WindowId, WindowResized},
+};
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    obstacle::AVOIDANCE_PRIORITY,
+    playfield::Playfield,
+    steering::{steer_towards, SteeringForces},
+    tick::Interpolated,
+};
+
+/// World-space rectangle the flock lives in.
+#[derive(Resource, Default)]
+pub struct Bounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Bounds {
+    /// Bounds covering a window of the given size.
+    fn of_window(width: f32, height: f32) -> Self {
+        // the camera sits at the origin so the window is centered on it
+        let half_size = Vec2::new(width, height) / 2.;
+        Self {
+            min: -half_size,
+            max: half_size,
+        }
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+
+    /// Whether the bounds have no area, and nothing fits inside them.
+    pub fn is_empty(&self) -> bool {
+        !(self.max - self.min).cmpgt(Vec2::ZERO).all()
+    }
+
+    /// Where a boid gone out to `position` comes back in: on the opposite
+    /// side when wrapping, or mirrored back inside with `velocity` sent back
+    /// in when bouncing. `None` while inside, or when `mode` does not bring
+    /// boids back.
+    pub(crate) fn bring_back(
+        &self,
+        mode: BoundaryMode,
+        position: Vec2,
+        velocity: &mut Vec3,
+    ) -> Option<Vec2> {
+        if self.is_empty() || self.contains(position) {
+            return None;
+        }
+
+        match mode {
+            BoundaryMode::Wrap => {
+                let size = self.max - self.min;
+                let offset = position - self.min;
+                Some(self.min + Vec2::new(offset.x.rem_euclid(size.x), offset.y.rem_euclid(size.y)))
+            }
+            BoundaryMode::Bounce => {
+                // mirror the overshoot back inside and send the boid back in
+                let mut bounced = position;
+                for axis in 0..2 {
+                    if position[axis] < self.min[axis] {
+                        bounced[axis] = 2. * self.min[axis] - position[axis];
+                        velocity[axis] = velocity[axis].abs();
+                    } else if position[axis] > self.max[axis] {
+                        bounced[axis] = 2. * self.max[axis] - position[axis];
+                        velocity[axis] = -velocity[axis].abs();
+                    }
+                }
+                Some(bounced.clamp(self.min, self.max))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What happens to boids reaching the edges of `Bounds`, unless a
+/// `Playfield` keeps them in instead. `B` cycles through the modes at
+/// runtime.
+#[derive(Resource, Reflect, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum BoundaryMode {
+    /// Boids fly on forever.
+    #[default]
+    None,
+    /// Boids are removed, for streams that enter on one side and leave on another.
+    Despawn,
+    /// Boids leaving on one side come back on the opposite one.
+    Wrap,
+    /// Boids bounce off the edges.
+    Bounce,
+    /// Boids steer away from the edges before reaching them, see
+    /// `FlockConfig::boundary_margin`.
+    Avoid,
+}
+
+impl BoundaryMode {
+    fn next(self) -> Self {
+        match self {
+            BoundaryMode::None => BoundaryMode::Wrap,
+            BoundaryMode::Wrap => BoundaryMode::Bounce,
+            BoundaryMode::Bounce => BoundaryMode::Avoid,
+            BoundaryMode::Avoid => BoundaryMode::Despawn,
+            BoundaryMode::Despawn => BoundaryMode::None,
+        }
+    }
+}
+
+/// Sent when a boid is removed from the simulation.
+pub struct BoidDespawned {
+    pub entity: Entity,
+}
+
+pub(crate) fn setup_bounds(windows: Res<Windows>, mut bounds: ResMut<Bounds>) {
+    // without a window, as when running headless, keep the bounds as inserted
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    let window_bounds = Bounds::of_window(window.width(), window.height());
+    if !window_bounds.is_empty() {
+        *bounds = window_bounds;
+    }
+}
+
+/// Keeps `Bounds` matching the primary window as it is resized, but not
+/// while it is minimized, when it has no size at all.
+pub(crate) fn resize_bounds(mut resized: EventReader<WindowResized>, mut bounds: ResMut<Bounds>) {
+    for event in resized.iter() {
+        let window_bounds = Bounds::of_window(event.width, event.height);
+        if event.id == WindowId::primary() && !window_bounds.is_empty() {
+            *bounds = window_bounds;
+        }
+    }
+}
+
+pub(crate) fn despawn_out_of_bounds(
+    mut commands: Commands,
+    mode: Res<BoundaryMode>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    query: Query<(Entity, &Transform), With<Boid>>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    // every boid would be out of empty bounds
+    if *mode != BoundaryMode::Despawn || playfield.is_active() || bounds.is_empty() {
+        return;
+    }
+
+    for (entity, transform) in query.iter() {
+        if !bounds.contains(transform.translation.truncate()) {
+            commands.entity(entity).despawn_recursive();
+            despawned.send(BoidDespawned { entity });
+        }
+    }
+}
+
+pub(crate) fn wrap_or_bounce(
+    mode: Res<BoundaryMode>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    mut query: Query<(&mut Transform, &mut Physics, &mut Interpolated), With<Boid>>,
+) {
+    // nowhere to wrap around or bounce back to
+    if !matches!(*mode, BoundaryMode::Wrap | BoundaryMode::Bounce)
+        || playfield.is_active()
+        || bounds.is_empty()
+    {
+        return;
+    }
+
+    // boids are brought back within the physics sub-steps already, this
+    // catches those pushed out since
+    for (mut transform, mut physics, mut interpolated) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        if bounds.contains(position) {
+            continue;
+        }
+        let Some(inside) = bounds.bring_back(*mode, position, &mut physics.velocity) else {
+            continue;
+        };
+        if *mode == BoundaryMode::Wrap {
+            // jump straight there instead of sliding across the window
+            interpolated.snap(inside);
+        }
+        transform.translation = inside.extend(transform.translation.z);
+    }
+}
+
+pub(crate) fn avoid_bounds(
+    mode: Res<BoundaryMode>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    config: Res<FlockConfig>,
+    mut query: Query<(&Transform, &Physics, &mut SteeringForces), With<Boid>>,
+) {
+    if *mode != BoundaryMode::Avoid || playfield.is_active() {
+        return;
+    }
+
+    let margin = config.boundary_margin.max(f32::EPSILON);
+    for (transform, physics, mut forces) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        // how deep into the margin the boid is on each side, from 0 to 1
+        let from_min = (1. - (position - bounds.min) / margin).clamp(Vec2::ZERO, Vec2::ONE);
+        let from_max = (1. - (bounds.max - position) / margin).clamp(Vec2::ZERO, Vec2::ONE);
+        let push = from_min - from_max;
+        if push == Vec2::ZERO {
+            continue;
+        }
+
+        let heading = physics.velocity.truncate().normalize_or_zero();
+        let desired = (heading + push).normalize_or_zero() * physics.max_speed;
+        let force = steer_towards(desired.extend(0.), physics, config.response_curve);
+        forces.behavior("bounds").add_with_priority(
+            force,
+            push.length().min(1.),
+            AVOIDANCE_PRIORITY,
+        );
+    }
+}
+
+pub(crate) fn cycle_boundary_mode(keyboard: Res<Input<KeyCode>>, mut mode: ResMut<BoundaryMode>) {
+    if keyboard.just_pressed(KeyCode::B) {
+        *mode = mode.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despawns_boids_leaving_the_bounds() {
+        let mut world = World::new();
+        world.insert_resource(BoundaryMode::Despawn);
+        world.insert_resource(Bounds::of_window(200., 200.));
+        world.insert_resource(Playfield::default());
+        world.insert_resource(Events::<BoidDespawned>::default());
+        let inside = world.spawn((Transform::default(), Boid)).id();
+        let outside = world.spawn((Transform::from_xyz(150., 0., 0.), Boid)).id();
+
+        SystemStage::single(despawn_out_of_bounds).run(&mut world);
+
+        assert!(world.get_entity(inside).is_some());
+        assert!(world.get_entity(outside).is_none());
+        let events = world.resource::<Events<BoidDespawned>>();
+        let despawned: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.entity)
+            .collect();
+        assert_eq!(despawned, vec![outside]);
+    }
+
+    #[test]
+    fn empty_bounds_leave_boids_be() {
+        for mode in [
+            BoundaryMode::Despawn,
+            BoundaryMode::Wrap,
+            BoundaryMode::Bounce,
+        ] {
+            let mut world = World::new();
+            world.insert_resource(mode);
+            world.insert_resource(Bounds::default());
+            world.insert_resource(Playfield::default());
+            world.insert_resource(Events::<BoidDespawned>::default());
+            let boid = world
+                .spawn((
+                    Boid,
+                    Transform::from_xyz(30., -20., 0.),
+                    Physics::default(),
+                    Interpolated::new(Vec2::new(30., -20.)),
+                ))
+                .id();
+
+            SystemStage::single(despawn_out_of_bounds).run(&mut world);
+            SystemStage::single(wrap_or_bounce).run(&mut world);
+
+            let transform = world.get::<Transform>(boid).unwrap();
+            assert_eq!(transform.translation, Vec3::new(30., -20., 0.));
+        }
+    }
+
+    #[test]
+    fn minimized_window_keeps_the_bounds() {
+        let mut world = World::new();
+        world.insert_resource(Bounds::of_window(800., 600.));
+        world.insert_resource(Events::<WindowResized>::default());
+        world.send_event(WindowResized {
+            id: WindowId::primary(),
+            width: 0.,
+            height: 0.,
+        });
+
+        SystemStage::single(resize_bounds).run(&mut world);
+
+        let bounds = world.resource::<Bounds>();
+        assert_eq!(bounds.max, Vec2::new(400., 300.));
+    }
+}
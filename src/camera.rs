@@ -0,0 +1,330 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::camera::Viewport,
+};
+use rand::seq::IteratorRandom;
+
+use crate::{
+    boid::{Boid, Selected},
+    render::{CursorPosition, FlockView, HoveredCamera, MainCamera},
+    target::MainTarget,
+};
+
+/// Panning and zooming of the main camera: `W`, `A`, `S`, `D` or dragging
+/// with the middle mouse button pans, the mouse wheel zooms around the
+/// cursor.
+#[derive(Resource)]
+pub struct CameraControls {
+    // logical pixels per second the keys pan by, whatever the zoom
+    pub pan_speed: f32,
+    // change of the projection scale per notch of the wheel
+    pub zoom_step: f32,
+    // closest and furthest zoom, as projection scales
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        Self {
+            pan_speed: 600.,
+            zoom_step: 0.1,
+            min_scale: 0.1,
+            max_scale: 20.,
+        }
+    }
+}
+
+/// Boid the main camera follows instead of being panned, toggled with `C`.
+/// `Shift+C` also turns the view with the boid, so it always points up.
+#[derive(Resource)]
+pub struct CameraFollow {
+    pub boid: Option<Entity>,
+    pub rotate: bool,
+    // how quickly the camera catches up with the boid, per second
+    pub smoothing: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            boid: None,
+            rotate: false,
+            smoothing: 5.,
+        }
+    }
+}
+
+/// Follows the selected boid, or a random one if none is, or goes back to
+/// the free camera.
+pub(crate) fn toggle_camera_follow(
+    keyboard: Res<Input<KeyCode>>,
+    mut follow: ResMut<CameraFollow>,
+    selected: Query<Entity, (With<Boid>, With<Selected>)>,
+    boids: Query<Entity, With<Boid>>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !keyboard.just_pressed(KeyCode::C) {
+        return;
+    }
+
+    if follow.boid.is_some() {
+        follow.boid = None;
+        if let Ok(mut transform) = camera.get_single_mut() {
+            transform.rotation = Quat::IDENTITY;
+        }
+        return;
+    }
+    // a camera choice, so it does not draw from the simulation's numbers
+    follow.boid = selected
+        .iter()
+        .next()
+        .or_else(|| boids.iter().choose(&mut rand::thread_rng()));
+    follow.rotate = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+}
+
+pub(crate) fn follow_boid(
+    time: Res<Time>,
+    mut follow: ResMut<CameraFollow>,
+    boids: Query<&Transform, (With<Boid>, Without<MainCamera>)>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(boid) = follow.boid else {
+        return;
+    };
+    let Ok(mut transform) = camera.get_single_mut() else {
+        return;
+    };
+    let Ok(boid) = boids.get(boid) else {
+        // the boid is gone, back to the free camera
+        follow.boid = None;
+        transform.rotation = Quat::IDENTITY;
+        return;
+    };
+
+    let blend = 1. - (-follow.smoothing * time.delta_seconds()).exp();
+    let position = transform
+        .translation
+        .truncate()
+        .lerp(boid.translation.truncate(), blend);
+    transform.translation = position.extend(transform.translation.z);
+    if follow.rotate {
+        transform.rotation = transform.rotation.slerp(boid.rotation, blend);
+    }
+}
+
+pub(crate) fn pan_camera(
+    time: Res<Time>,
+    controls: Res<CameraControls>,
+    follow: Res<CameraFollow>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let Ok((mut transform, projection)) = camera.get_single_mut() else {
+        return;
+    };
+    if follow.boid.is_some() {
+        motion.clear();
+        return;
+    }
+
+    let mut direction = Vec2::ZERO;
+    for (key, step) in [
+        (KeyCode::W, Vec2::Y),
+        (KeyCode::A, Vec2::NEG_X),
+        (KeyCode::S, Vec2::NEG_Y),
+        (KeyCode::D, Vec2::X),
+    ] {
+        if keyboard.pressed(key) {
+            direction += step;
+        }
+    }
+    let mut pan = direction.normalize_or_zero() * controls.pan_speed * time.delta_seconds();
+
+    // drag the world along with the cursor, which moves down the screen as y
+    // grows
+    let dragged: Vec2 = motion.iter().map(|motion| motion.delta).sum();
+    if mouse.pressed(MouseButton::Middle) {
+        pan += Vec2::new(-dragged.x, dragged.y);
+    }
+
+    transform.translation += (pan * projection.scale).extend(0.);
+}
+
+pub(crate) fn zoom_camera(
+    controls: Res<CameraControls>,
+    cursor: Res<CursorPosition>,
+    hovered: Res<HoveredCamera>,
+    mut split: ResMut<SplitScreen>,
+    mut wheel: EventReader<MouseWheel>,
+    mut camera: Query<(Entity, &mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    // pixels of a touchpad scroll making up one notch of a wheel
+    const PIXELS_PER_NOTCH: f32 = 50.;
+
+    let notches: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+    let Ok((entity, mut transform, mut projection)) = camera.get_single_mut() else {
+        return;
+    };
+    if notches == 0. {
+        return;
+    }
+    let zoom = (1. - controls.zoom_step).powf(notches);
+    if split.enabled && hovered.0.is_some_and(|hovered| hovered != entity) {
+        split.zoom = (split.zoom * zoom).clamp(controls.min_scale, controls.max_scale);
+        return;
+    }
+
+    let scale = (projection.scale * zoom).clamp(controls.min_scale, controls.max_scale);
+    // keep the point under the cursor in place
+    if let Some(anchor) = cursor.0 {
+        let offset = transform.translation.truncate() - anchor;
+        let translation = anchor + offset * scale / projection.scale;
+        transform.translation = translation.extend(transform.translation.z);
+    }
+    projection.scale = scale;
+}
+
+/// Splits the flock's view in two, toggled with `I`: the main camera keeps
+/// the left half, and a detail camera shows the right half zoomed in on the
+/// selected boid, or the main target with none selected. The mouse wheel
+/// zooms whichever half the cursor is over.
+#[derive(Resource)]
+pub struct SplitScreen {
+    pub enabled: bool,
+    // projection scale of the detail view, smaller is closer
+    pub zoom: f32,
+}
+
+impl Default for SplitScreen {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            zoom: 0.35,
+        }
+    }
+}
+
+/// Camera of the zoomed half of the `SplitScreen`.
+#[derive(Component)]
+pub struct DetailCamera;
+
+pub(crate) fn spawn_detail_camera(mut commands: Commands, view: Res<FlockView>) {
+    commands
+        .spawn(Camera2dBundle {
+            camera: Camera {
+                // on top of the main camera, and off until the screen is split
+                priority: view.priority + 1,
+                is_active: false,
+                ..default()
+            },
+            // clearing would wipe the main camera's half too
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        })
+        .insert(view.layers)
+        .insert(DetailCamera);
+}
+
+pub(crate) fn toggle_split_screen(keyboard: Res<Input<KeyCode>>, mut split: ResMut<SplitScreen>) {
+    if keyboard.just_pressed(KeyCode::I) {
+        split.enabled = !split.enabled;
+    }
+}
+
+fn set_viewport(camera: &mut Mut<Camera>, viewport: Option<Viewport>) {
+    let area = |viewport: &Option<Viewport>| {
+        viewport
+            .as_ref()
+            .map(|viewport| (viewport.physical_position, viewport.physical_size))
+    };
+    // cameras recompute their projection on every change
+    if area(&camera.viewport) != area(&viewport) {
+        camera.viewport = viewport;
+    }
+}
+
+/// Shares the flock's viewport, or the window, between the main and the
+/// detail camera, following the window as it is resized.
+pub(crate) fn layout_split_screen(
+    split: Res<SplitScreen>,
+    view: Res<FlockView>,
+    windows: Res<Windows>,
+    mut main: Query<&mut Camera, (With<MainCamera>, Without<DetailCamera>)>,
+    mut detail: Query<&mut Camera, With<DetailCamera>>,
+) {
+    let (Ok(mut main), Ok(mut detail)) = (main.get_single_mut(), detail.get_single_mut()) else {
+        return;
+    };
+    if detail.is_active != split.enabled {
+        detail.is_active = split.enabled;
+    }
+    if !split.enabled {
+        set_viewport(&mut main, view.viewport.clone());
+        return;
+    }
+
+    let (position, size) = match (&view.viewport, windows.get_primary()) {
+        (Some(viewport), _) => (viewport.physical_position, viewport.physical_size),
+        (None, Some(window)) => (
+            UVec2::ZERO,
+            UVec2::new(window.physical_width(), window.physical_height()),
+        ),
+        (None, None) => return,
+    };
+    let half = UVec2::new(size.x / 2, size.y);
+    let depth = view
+        .viewport
+        .as_ref()
+        .map_or(0. ..1., |viewport| viewport.depth.clone());
+    set_viewport(
+        &mut main,
+        Some(Viewport {
+            physical_position: position,
+            physical_size: half,
+            depth: depth.clone(),
+        }),
+    );
+    set_viewport(
+        &mut detail,
+        Some(Viewport {
+            physical_position: position + UVec2::new(half.x, 0),
+            physical_size: UVec2::new(size.x - half.x, size.y),
+            depth,
+        }),
+    );
+}
+
+/// Centers the detail camera on the selected boid, or the main target.
+pub(crate) fn aim_detail_camera(
+    split: Res<SplitScreen>,
+    selected: Query<&Transform, (With<Selected>, Without<DetailCamera>)>,
+    targets: Query<&Transform, (With<MainTarget>, Without<DetailCamera>)>,
+    mut detail: Query<(&mut Transform, &mut OrthographicProjection), With<DetailCamera>>,
+) {
+    if !split.enabled {
+        return;
+    }
+    let Ok((mut transform, mut projection)) = detail.get_single_mut() else {
+        return;
+    };
+    let focus = selected.iter().next().or_else(|| targets.iter().next());
+    if let Some(focus) = focus {
+        transform.translation = focus.translation.truncate().extend(transform.translation.z);
+    }
+    if projection.scale != split.zoom {
+        projection.scale = split.zoom;
+    }
+}
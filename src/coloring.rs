@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    energy::Energy,
+    flock::{FlockId, Flocks},
+};
+
+/// Steps each color scale is cut into, so boids are only recolored, and their
+/// shape tessellated again, when they cross one.
+const STEPS: f32 = 16.;
+
+/// What the fill color of the boids shows, cycled with `Y`.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BoidColoring {
+    /// The color of the boid's flock
+    #[default]
+    Flock,
+    /// Speed as a share of the boid's maximum, from blue when still to red
+    /// at full speed
+    Speed,
+    /// Where the boid is heading, around the hue wheel, so boids flying the
+    /// same way share a color
+    Heading,
+    /// Energy left, from red when exhausted to green when full, gray for
+    /// boids which do not tire
+    Energy,
+}
+
+impl BoidColoring {
+    fn next(self) -> Self {
+        match self {
+            Self::Flock => Self::Speed,
+            Self::Speed => Self::Heading,
+            Self::Heading => Self::Energy,
+            Self::Energy => Self::Flock,
+        }
+    }
+}
+
+pub(crate) fn cycle_boid_coloring(
+    keyboard: Res<Input<KeyCode>>,
+    mut coloring: ResMut<BoidColoring>,
+) {
+    if keyboard.just_pressed(KeyCode::Y) {
+        *coloring = coloring.next();
+        info!("coloring boids by {:?}", *coloring);
+    }
+}
+
+/// `value` from 0 to 1, rounded down to one of the `STEPS`.
+fn quantize(value: f32) -> f32 {
+    (value.clamp(0., 1.) * STEPS).floor() / STEPS
+}
+
+/// Fills the boids with the color of the `BoidColoring` channel. Flock
+/// colors are left to `apply_flock_settings`, once restored.
+pub(crate) fn color_boids(
+    coloring: Res<BoidColoring>,
+    flocks: Res<Flocks>,
+    energies: Query<&Energy>,
+    mut boids: Query<(Entity, &Physics, &FlockId, &mut DrawMode), With<Boid>>,
+) {
+    if *coloring == BoidColoring::Flock && !coloring.is_changed() {
+        return;
+    }
+
+    for (entity, physics, flock, mut mode) in boids.iter_mut() {
+        let color = match *coloring {
+            BoidColoring::Flock => flocks
+                .get(*flock)
+                .map_or(Color::BLUE, |settings| settings.color),
+            BoidColoring::Speed => {
+                let pace = physics.velocity.length() / physics.max_speed.max(f32::EPSILON);
+                Color::hsl(240. * (1. - quantize(pace)), 0.9, 0.5)
+            }
+            BoidColoring::Heading => {
+                let angle = physics.velocity.y.atan2(physics.velocity.x);
+                let turn = angle / std::f32::consts::TAU + 0.5;
+                Color::hsl(360. * quantize(turn), 0.8, 0.55)
+            }
+            BoidColoring::Energy => match energies.get(entity) {
+                Ok(energy) => {
+                    let left = energy.current / energy.max.max(f32::EPSILON);
+                    Color::hsl(120. * quantize(left), 0.9, 0.45)
+                }
+                Err(_) => Color::GRAY,
+            },
+        };
+
+        // reading does not mark the mode changed, only writing does
+        let DrawMode::Outlined { fill_mode, .. } = &*mode else {
+            continue;
+        };
+        if fill_mode.color == color {
+            continue;
+        }
+        if let DrawMode::Outlined { fill_mode, .. } = mode.as_mut() {
+            fill_mode.color = color;
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    boid::{BodySize, Boid, Physics, Steering},
+    bounds::{BoundaryMode, Bounds},
+    flock::{FlockConfig, FlockId, Flocks},
+    flocking::{flock_weights, BehaviorWeights},
+    lod::Lod,
+    neighbors::{NeighborCache, SpatialIndex},
+    obstacle::Obstacle,
+    playfield::Playfield,
+    population::Traits,
+    predator::{Evade, Predator},
+    steering::{steer_towards, Arbitration, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Interest in keeping the current heading, so boids with nothing else to
+/// go for fly on rather than stall.
+const MOMENTUM_INTEREST: f32 = 0.2;
+
+/// Slots this much more dangerous than the safest one are ruled out.
+const DANGER_TOLERANCE: f32 = 0.05;
+
+/// Interest and danger a boid sees in each of a few directions spread evenly
+/// around it, the first slot pointing along +x.
+///
+/// Rather than summing forces that may cancel out, a boid rules out the
+/// dangerous slots and heads for the most interesting of the rest.
+pub struct ContextMaps {
+    pub interest: Vec<f32>,
+    pub danger: Vec<f32>,
+}
+
+impl ContextMaps {
+    pub fn new(slots: usize) -> Self {
+        let slots = slots.max(1);
+        Self {
+            interest: vec![0.; slots],
+            danger: vec![0.; slots],
+        }
+    }
+
+    /// Unit vector the `slot`th slot points along.
+    pub fn direction(&self, slot: usize) -> Vec2 {
+        Vec2::from_angle(TAU * slot as f32 / self.interest.len() as f32)
+    }
+
+    /// Adds `weight` of interest in `direction`, fading to none on slots at
+    /// right angles to it.
+    pub fn add_interest(&mut self, direction: Vec2, weight: f32) {
+        let Some(direction) = direction.try_normalize() else {
+            return;
+        };
+        for slot in 0..self.interest.len() {
+            self.interest[slot] += weight * self.direction(slot).dot(direction).max(0.);
+        }
+    }
+
+    /// Raises the danger of slots towards `direction` to `weight`, fading to
+    /// none on slots at right angles to it.
+    pub fn add_danger(&mut self, direction: Vec2, weight: f32) {
+        let Some(direction) = direction.try_normalize() else {
+            return;
+        };
+        for slot in 0..self.danger.len() {
+            let danger = weight * self.direction(slot).dot(direction).max(0.);
+            self.danger[slot] = self.danger[slot].max(danger);
+        }
+    }
+
+    /// Direction of the most interesting slot among the least dangerous
+    /// ones, none if none of them is of any interest.
+    pub fn choose(&self) -> Option<Vec2> {
+        let safest = self.danger.iter().copied().fold(f32::INFINITY, f32::min);
+        (0..self.interest.len())
+            .filter(|slot| self.danger[*slot] <= safest + DANGER_TOLERANCE)
+            .filter(|slot| self.interest[*slot] > 0.)
+            .max_by(|a, b| self.interest[*a].total_cmp(&self.interest[*b]))
+            .map(|slot| self.direction(slot))
+    }
+}
+
+/// Steers boids with `Arbitration::Context` instead of the weighted sum of
+/// behaviors: the target, the flock's centroid and heading make the
+/// interest map, obstacles, predators, edges or the playfield and crowding
+/// neighbors the danger map. The other behaviors are left out.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn context_steering(
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    index: Res<SpatialIndex>,
+    mode: Res<BoundaryMode>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+    predators: Query<(&Transform, &Physics), With<Predator>>,
+    boids: Query<(&Transform, &Physics, &Steering, &NeighborCache), With<Boid>>,
+    evades: Query<&Evade>,
+    traits_query: Query<&Traits>,
+    overrides: Query<&BehaviorWeights>,
+    lods: Query<&Lod>,
+    mut forces: Query<(
+        Entity,
+        &mut SteeringForces,
+        Option<&FlockId>,
+        Option<&BodySize>,
+    )>,
+) {
+    if config.arbitration != Arbitration::Context {
+        return;
+    }
+
+    forces.par_for_each_mut(PAR_BATCH_SIZE, |(entity, mut forces, flock, size)| {
+        if lods.get(entity).is_ok_and(Lod::skips_steering) {
+            return;
+        }
+        let Ok((transform, physics, steering, neighbors)) = boids.get(entity) else {
+            return;
+        };
+        let position = transform.translation.truncate();
+        let velocity = physics.velocity.truncate();
+        let mut maps = ContextMaps::new(config.context_slots);
+
+        maps.add_interest(velocity, MOMENTUM_INTEREST);
+        if transform.translation.distance(steering.target) <= steering.max_seek_range {
+            maps.add_interest(steering.target.truncate() - position, 1.);
+        }
+
+        let size = size.map_or(1., |size| size.0);
+        let (crowd, flockmates) = neighbors.resolve(&index, transform.translation);
+        let (_, alignment_weight, cohesion_weight) =
+            flock_weights(&config, &flocks, flock.copied(), overrides.get(entity).ok());
+        let traits = traits_query.get(entity).copied().unwrap_or_default();
+        if !flockmates.is_empty() {
+            let count = flockmates.len() as f32;
+            let centroid = flockmates
+                .iter()
+                .map(|other| other.position.truncate())
+                .sum::<Vec2>()
+                / count;
+            let heading: Vec2 = flockmates
+                .iter()
+                .map(|other| other.velocity.truncate())
+                .sum();
+            maps.add_interest(centroid - position, cohesion_weight * traits.cohesion);
+            maps.add_interest(heading, alignment_weight * traits.alignment);
+        }
+
+        let separation_radius = (config.separation_radius * size).max(f32::EPSILON);
+        for other in crowd {
+            let offset = other.position.truncate() - position;
+            maps.add_danger(offset, 1. - offset.length() / separation_radius);
+        }
+
+        if let Ok(evade) = evades.get(entity) {
+            for (predator, predator_physics) in predators.iter() {
+                let offset = (predator.translation - transform.translation).truncate();
+                let distance = offset.length();
+                if distance >= evade.radius {
+                    continue;
+                }
+                // as `evade` does, mind where the predator is heading
+                let closing_speed = physics.max_speed + predator_physics.velocity.length();
+                let lookahead = distance / closing_speed.max(f32::EPSILON);
+                let predicted = offset + predator_physics.velocity.truncate() * lookahead;
+                let closeness = 1. - distance / evade.radius;
+                maps.add_danger(predicted, closeness);
+                maps.add_interest(-predicted, closeness);
+            }
+        }
+
+        let reach = physics.max_speed * config.avoidance_lookahead;
+        for slot in 0..maps.danger.len() {
+            let to = position + maps.direction(slot) * reach;
+            let hit = obstacles
+                .iter()
+                .filter_map(|(obstacle_transform, obstacle)| {
+                    obstacle.probe(obstacle_transform.translation.truncate(), position, to)
+                })
+                .map(|hit| hit.t)
+                .min_by(f32::total_cmp);
+            if let Some(t) = hit {
+                maps.danger[slot] = maps.danger[slot].max(1. - t);
+            }
+        }
+
+        if playfield.is_active() {
+            let push = playfield.push(position, config.boundary_margin);
+            maps.add_danger(-push, push.length().min(1.));
+        } else if *mode == BoundaryMode::Avoid {
+            // the same margins as `avoid_bounds`
+            let margin = config.boundary_margin.max(f32::EPSILON);
+            let from_min = (1. - (position - bounds.min) / margin).clamp(Vec2::ZERO, Vec2::ONE);
+            let from_max = (1. - (bounds.max - position) / margin).clamp(Vec2::ZERO, Vec2::ONE);
+            let push = from_min - from_max;
+            maps.add_danger(-push, push.length().min(1.));
+        }
+
+        // with nowhere worth going, coast
+        let desired = maps
+            .choose()
+            .map_or(velocity, |direction| direction * physics.max_speed);
+        let force = steer_towards(desired.extend(0.), physics, config.response_curve);
+        forces.behavior("context").replace_all(force);
+    });
+}
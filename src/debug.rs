@@ -0,0 +1,499 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics, Selected, Steering},
+    flock::FlockConfig,
+    flow_field::FlowField,
+    neighbors::{NeighborIndex, SpatialIndex},
+    perception::{perceived_neighbors, Perception},
+    render::{FlockView, Overlay, RenderOrder},
+    sim::average_heading,
+    species::{Reaction, Species, SpeciesRelations},
+    steering::{SteeringForces, RESPONSE_TIME},
+};
+
+/// Debug arrow drawn from the selected boid.
+#[derive(Component)]
+pub(crate) enum HeadingGizmo {
+    /// The boid's own heading.
+    Own,
+    /// The average heading of its neighbors, which alignment steers towards.
+    NeighborAverage,
+}
+
+/// Debug outline of the selected boid's view cone, see `Perception`.
+#[derive(Component)]
+pub(crate) struct PerceptionGizmo;
+
+pub(crate) fn spawn_heading_gizmos(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    for (gizmo, color) in [
+        (HeadingGizmo::Own, Color::WHITE),
+        (HeadingGizmo::NeighborAverage, Color::YELLOW),
+    ] {
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &PathBuilder::new().build(),
+                DrawMode::Stroke(StrokeMode::new(color, 2.)),
+                Transform::from_xyz(0., 0., order.overlays),
+            ))
+            .insert(view.layers)
+            .insert(Overlay)
+            .insert(gizmo);
+    }
+
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &PathBuilder::new().build(),
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(1., 1., 1., 0.3), 1.)),
+            Transform::from_xyz(0., 0., order.overlays),
+        ))
+        .insert(view.layers)
+        .insert(Overlay)
+        .insert(PerceptionGizmo);
+}
+
+pub(crate) fn draw_heading_gizmos(
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    relations: Res<SpeciesRelations>,
+    selected: Query<(Entity, Option<&Species>), With<Selected>>,
+    boids: Query<(Entity, &Transform, &Physics, Option<&Perception>), With<Boid>>,
+    mut gizmos: Query<(&HeadingGizmo, &mut Path)>,
+) {
+    const LENGTH: f32 = 60.;
+
+    let selected = selected.get_single().ok().and_then(|(entity, species)| {
+        let species = species.copied().unwrap_or_default();
+        boids.get(entity).ok().map(|boid| (boid, species))
+    });
+    for (gizmo, mut path) in gizmos.iter_mut() {
+        let heading = selected.and_then(|((entity, transform, physics, perception), species)| {
+            let heading = match gizmo {
+                HeadingGizmo::Own => physics.velocity.try_normalize(),
+                HeadingGizmo::NeighborAverage => {
+                    let radius =
+                        perception.map_or(config.neighbor_radius, |perception| perception.radius);
+                    average_heading(
+                        perceived_neighbors(
+                            &*index, entity, transform, physics, perception, radius,
+                        )
+                        .filter(|other| {
+                            relations.reaction(species, other.species) == Reaction::FlockWith
+                        })
+                        .map(|other| other.velocity),
+                    )
+                }
+            }?;
+            Some((transform.translation.truncate(), heading.truncate()))
+        });
+
+        *path = match heading {
+            Some((from, heading)) => arrow(from, from + heading * LENGTH),
+            None => PathBuilder::new().build(),
+        };
+    }
+}
+
+// seconds of motion shown by velocity arrows, and world units per unit of
+// force for force and acceleration arrows
+pub(crate) const VELOCITY_SCALE: f32 = 0.5;
+pub(crate) const FORCE_SCALE: f32 = 0.2;
+
+/// Overlay toggled with `F1`, drawing on every boid its velocity and
+/// acceleration, its perception radius and a line to the target it seeks.
+/// Each part can be switched off on its own.
+#[derive(Resource)]
+pub struct GizmoOverlay {
+    pub enabled: bool,
+    pub velocity: bool,
+    pub acceleration: bool,
+    pub perception: bool,
+    pub target: bool,
+}
+
+impl Default for GizmoOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            velocity: true,
+            acceleration: true,
+            perception: true,
+            target: true,
+        }
+    }
+}
+
+/// Part of the `GizmoOverlay`, the shapes of all boids in a single path.
+#[derive(Component, Clone, Copy)]
+pub(crate) enum OverlayPart {
+    Velocity,
+    Acceleration,
+    Perception,
+    Target,
+}
+
+pub(crate) fn spawn_gizmo_overlay(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    for (part, color) in [
+        (OverlayPart::Velocity, Color::GREEN),
+        (OverlayPart::Acceleration, Color::ORANGE),
+        (OverlayPart::Perception, Color::rgba(1., 1., 1., 0.15)),
+        (OverlayPart::Target, Color::rgba(1., 1., 0., 0.3)),
+    ] {
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &PathBuilder::new().build(),
+                DrawMode::Stroke(StrokeMode::new(color, 1.)),
+                Transform::from_xyz(0., 0., order.overlays),
+            ))
+            .insert(view.layers)
+            .insert(Overlay)
+            .insert(part);
+    }
+}
+
+pub(crate) fn toggle_gizmo_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut overlay: ResMut<GizmoOverlay>,
+) {
+    if keyboard.just_pressed(KeyCode::F1) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+pub(crate) fn draw_gizmo_overlay(
+    overlay: Res<GizmoOverlay>,
+    config: Res<FlockConfig>,
+    boids: Query<(&Transform, &Physics, &Steering, Option<&Perception>), With<Boid>>,
+    mut parts: Query<(&OverlayPart, &mut Path)>,
+) {
+    for (part, mut path) in parts.iter_mut() {
+        let mut builder = PathBuilder::new();
+        let shown = overlay.enabled
+            && match part {
+                OverlayPart::Velocity => overlay.velocity,
+                OverlayPart::Acceleration => overlay.acceleration,
+                OverlayPart::Perception => overlay.perception,
+                OverlayPart::Target => overlay.target,
+            };
+        if !shown {
+            *path = builder.build();
+            continue;
+        }
+
+        for (transform, physics, steering, perception) in boids.iter() {
+            let position = transform.translation.truncate();
+            match part {
+                OverlayPart::Velocity => {
+                    let to = position + physics.velocity.truncate() * VELOCITY_SCALE;
+                    add_arrow(&mut builder, position, to, 5.);
+                }
+                OverlayPart::Acceleration => {
+                    // forces gathered so far for the next step
+                    let to = position + physics.acceleration.truncate() * FORCE_SCALE;
+                    add_arrow(&mut builder, position, to, 5.);
+                }
+                OverlayPart::Perception => {
+                    let radius =
+                        perception.map_or(config.neighbor_radius, |perception| perception.radius);
+                    builder.move_to(position + Vec2::X * radius);
+                    builder.arc(position, Vec2::splat(radius), std::f32::consts::TAU, 0.);
+                }
+                OverlayPart::Target => {
+                    // boids out of range ignore the target, see `Steering`
+                    let target = steering.target.truncate();
+                    if position.distance(target) <= steering.max_seek_range {
+                        builder.move_to(position);
+                        builder.line_to(target);
+                    }
+                }
+            }
+        }
+        *path = builder.build();
+    }
+}
+
+/// Debug layer toggled with `F2`, drawing the `FlowField` and arrows for the
+/// motion of every boid. Each part can be switched off on its own.
+#[derive(Resource)]
+pub struct DebugDraw {
+    pub enabled: bool,
+    pub flow_field: bool,
+    pub velocity: bool,
+    // velocity the blended steering force turns the boid towards
+    pub desired_velocity: bool,
+    pub steering_force: bool,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flow_field: true,
+            velocity: true,
+            desired_velocity: true,
+            steering_force: true,
+        }
+    }
+}
+
+/// Part of the `DebugDraw` layer, all arrows of a kind in a single path.
+#[derive(Component, Clone, Copy)]
+pub(crate) enum DebugLayer {
+    FlowField,
+    Velocity,
+    DesiredVelocity,
+    SteeringForce,
+}
+
+pub(crate) fn spawn_debug_layer(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    for (layer, color) in [
+        (DebugLayer::FlowField, Color::rgba(0.5, 0.5, 1., 0.4)),
+        (DebugLayer::Velocity, Color::GREEN),
+        (DebugLayer::DesiredVelocity, Color::CYAN),
+        (DebugLayer::SteeringForce, Color::FUCHSIA),
+    ] {
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &PathBuilder::new().build(),
+                DrawMode::Stroke(StrokeMode::new(color, 1.)),
+                Transform::from_xyz(0., 0., order.overlays),
+            ))
+            .insert(view.layers)
+            .insert(Overlay)
+            .insert(layer);
+    }
+}
+
+pub(crate) fn toggle_debug_draw(keyboard: Res<Input<KeyCode>>, mut debug: ResMut<DebugDraw>) {
+    if keyboard.just_pressed(KeyCode::F2) {
+        debug.enabled = !debug.enabled;
+    }
+}
+
+pub(crate) fn draw_debug_layer(
+    debug: Res<DebugDraw>,
+    field: Option<Res<FlowField>>,
+    boids: Query<(&Transform, &Physics, &SteeringForces), With<Boid>>,
+    mut layers: Query<(&DebugLayer, &mut Path)>,
+) {
+    for (layer, mut path) in layers.iter_mut() {
+        let mut builder = PathBuilder::new();
+        let shown = debug.enabled
+            && match layer {
+                DebugLayer::FlowField => debug.flow_field,
+                DebugLayer::Velocity => debug.velocity,
+                DebugLayer::DesiredVelocity => debug.desired_velocity,
+                DebugLayer::SteeringForce => debug.steering_force,
+            };
+
+        match layer {
+            _ if !shown => {}
+            DebugLayer::FlowField => {
+                if let Some(field) = &field {
+                    let length = field.cell_size() * 0.4;
+                    for (center, direction) in field.cells() {
+                        let half = direction * length / 2.;
+                        add_arrow(&mut builder, center - half, center + half, 4.);
+                    }
+                }
+            }
+            _ => {
+                for (transform, physics, forces) in boids.iter() {
+                    let from = transform.translation.truncate();
+                    let arrow = match layer {
+                        DebugLayer::Velocity => physics.velocity * VELOCITY_SCALE,
+                        DebugLayer::DesiredVelocity => {
+                            (physics.velocity + forces.applied() * RESPONSE_TIME) * VELOCITY_SCALE
+                        }
+                        _ => forces.applied() * FORCE_SCALE,
+                    };
+                    add_arrow(&mut builder, from, from + arrow.truncate(), 5.);
+                }
+            }
+        }
+        *path = builder.build();
+    }
+}
+
+/// Outlines of the `SpatialIndex` cells or nodes holding boids, toggled with
+/// `F4`.
+#[derive(Resource, Default)]
+pub struct IndexOverlay {
+    pub visible: bool,
+}
+
+#[derive(Component)]
+pub(crate) struct IndexOutline;
+
+pub(crate) fn spawn_index_outline(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &PathBuilder::new().build(),
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(0.3, 1., 0.3, 0.3), 1.)),
+            Transform::from_xyz(0., 0., order.overlays),
+        ))
+        .insert(view.layers)
+        .insert(Overlay)
+        .insert(IndexOutline);
+}
+
+pub(crate) fn toggle_index_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut overlay: ResMut<IndexOverlay>,
+) {
+    if keyboard.just_pressed(KeyCode::F4) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+pub(crate) fn draw_index_overlay(
+    overlay: Res<IndexOverlay>,
+    index: Res<SpatialIndex>,
+    mut outlines: Query<&mut Path, With<IndexOutline>>,
+) {
+    let mut builder = PathBuilder::new();
+    if overlay.visible {
+        for rect in index.outlines() {
+            builder.move_to(rect.min);
+            builder.line_to(Vec2::new(rect.max.x, rect.min.y));
+            builder.line_to(rect.max);
+            builder.line_to(Vec2::new(rect.min.x, rect.max.y));
+            builder.close();
+        }
+    }
+    if let Ok(mut path) = outlines.get_single_mut() {
+        *path = builder.build();
+    }
+}
+
+pub(crate) fn draw_perception_gizmo(
+    selected: Query<(&Transform, &Physics, &Perception), With<Selected>>,
+    mut gizmos: Query<&mut Path, With<PerceptionGizmo>>,
+) {
+    for mut path in gizmos.iter_mut() {
+        *path = match selected.get_single() {
+            Ok((transform, physics, perception)) => cone(
+                transform.translation.truncate(),
+                physics
+                    .velocity
+                    .truncate()
+                    .try_normalize()
+                    .unwrap_or(Vec2::Y),
+                perception,
+            ),
+            Err(_) => PathBuilder::new().build(),
+        };
+    }
+}
+
+fn cone(center: Vec2, heading: Vec2, perception: &Perception) -> Path {
+    let field_of_view = perception.field_of_view.min(std::f32::consts::TAU);
+    let edge = Vec2::from_angle(-field_of_view / 2.).rotate(heading) * perception.radius;
+    let mut builder = PathBuilder::new();
+    builder.move_to(center);
+    builder.line_to(center + edge);
+    builder.arc(center, Vec2::splat(perception.radius), field_of_view, 0.);
+    builder.close();
+    builder.build()
+}
+
+fn arrow(from: Vec2, to: Vec2) -> Path {
+    let mut builder = PathBuilder::new();
+    add_arrow(&mut builder, from, to, 10.);
+    builder.build()
+}
+
+pub(crate) fn add_arrow(builder: &mut PathBuilder, from: Vec2, to: Vec2, head: f32) {
+    let back = (from - to).normalize_or_zero() * head;
+    builder.move_to(from);
+    builder.line_to(to);
+    builder.move_to(to + Vec2::from_angle(0.5).rotate(back));
+    builder.line_to(to);
+    builder.line_to(to + Vec2::from_angle(-0.5).rotate(back));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_prototype_lyon::prelude::tess::path::PathEvent;
+
+    use super::*;
+    use crate::{
+        neighbors::{rebuild_spatial_index, NeighborBackend},
+        sim::{steer_alignment, steer_towards, SteeringParams},
+        steering::ResponseCurve,
+    };
+
+    fn boid(world: &mut World, position: Vec3, velocity: Vec3) -> Entity {
+        world
+            .spawn((
+                Boid,
+                Transform::from_translation(position),
+                Physics {
+                    velocity,
+                    max_speed: 100.,
+                    max_force: 50.,
+                    ..default()
+                },
+            ))
+            .id()
+    }
+
+    #[test]
+    fn drawn_average_heading_is_the_alignment_heading() {
+        let mut world = World::new();
+        world.insert_resource(FlockConfig::default());
+        world.insert_resource(NeighborBackend::default());
+        world.insert_resource(SpatialIndex::default());
+        world.insert_resource(SpeciesRelations::default());
+        let selected = boid(&mut world, Vec3::ZERO, Vec3::new(40., 0., 0.));
+        world.entity_mut(selected).insert(Selected);
+        let neighbors = [Vec3::new(0., 30., 0.), Vec3::new(-10., 50., 0.)];
+        boid(&mut world, Vec3::new(10., 5., 0.), neighbors[0]);
+        boid(&mut world, Vec3::new(-8., 12., 0.), neighbors[1]);
+        let gizmo = world
+            .spawn((HeadingGizmo::NeighborAverage, PathBuilder::new().build()))
+            .id();
+
+        SystemStage::single(rebuild_spatial_index).run(&mut world);
+        SystemStage::single(draw_heading_gizmos).run(&mut world);
+
+        let path = world.get::<Path>(gizmo).unwrap();
+        let tip = path
+            .0
+            .iter()
+            .find_map(|event| match event {
+                PathEvent::Line { to, .. } => Some(Vec2::new(to.x, to.y)),
+                _ => None,
+            })
+            .unwrap();
+        let drawn = tip.normalize().extend(0.);
+        assert!(drawn.abs_diff_eq(average_heading(neighbors).unwrap(), 1e-5));
+
+        let params = SteeringParams {
+            max_speed: 100.,
+            max_force: 50.,
+            response_curve: ResponseCurve::Linear,
+        };
+        let velocity = Vec3::new(40., 0., 0.);
+        let alignment = steer_alignment(velocity, neighbors, &params);
+        let towards_drawn = steer_towards(drawn * params.max_speed, velocity, &params);
+        assert!(alignment.abs_diff_eq(towards_drawn, 1e-3));
+    }
+}
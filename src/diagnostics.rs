@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+
+use crate::stats::FlockStats;
+
+/// Registers diagnostics of the flock with bevy's `Diagnostics`, for
+/// `LogDiagnosticsPlugin` or any tool reading them. Needs `SimulationPlugin`.
+pub struct BoidDiagnosticsPlugin;
+
+impl BoidDiagnosticsPlugin {
+    pub const BOID_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(202573154461468078269900912346116192598);
+    pub const AVERAGE_SPEED: DiagnosticId =
+        DiagnosticId::from_u128(86022802842267618281169825414082469503);
+    // mean time a boid takes to gather its neighbors
+    pub const NEIGHBOR_QUERY_TIME: DiagnosticId =
+        DiagnosticId::from_u128(233741911690216866245450710120180158806);
+    // time the flocking system took on the last step
+    pub const FLOCKING_TIME: DiagnosticId =
+        DiagnosticId::from_u128(196779516971425043925901806075489310662);
+}
+
+impl Plugin for BoidDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Diagnostics>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(record_diagnostics);
+    }
+}
+
+/// Time spent by the steering systems on the last step.
+#[derive(Resource, Default)]
+pub(crate) struct SteeringTimings {
+    // summed over all boids
+    pub(crate) neighbor_queries: Duration,
+    pub(crate) queries: u32,
+    pub(crate) flocking: Duration,
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        BoidDiagnosticsPlugin::BOID_COUNT,
+        "boid_count",
+        20,
+    ));
+    diagnostics.add(Diagnostic::new(
+        BoidDiagnosticsPlugin::AVERAGE_SPEED,
+        "average_speed",
+        20,
+    ));
+    diagnostics.add(
+        Diagnostic::new(
+            BoidDiagnosticsPlugin::NEIGHBOR_QUERY_TIME,
+            "neighbor_query_time",
+            20,
+        )
+        .with_suffix("us"),
+    );
+    diagnostics.add(
+        Diagnostic::new(BoidDiagnosticsPlugin::FLOCKING_TIME, "flocking_time", 20)
+            .with_suffix("ms"),
+    );
+}
+
+fn record_diagnostics(
+    mut diagnostics: ResMut<Diagnostics>,
+    stats: Res<FlockStats>,
+    timings: Res<SteeringTimings>,
+) {
+    diagnostics.add_measurement(BoidDiagnosticsPlugin::BOID_COUNT, || {
+        stats.boid_count as f64
+    });
+    diagnostics.add_measurement(BoidDiagnosticsPlugin::AVERAGE_SPEED, || {
+        stats.average_speed as f64
+    });
+    if timings.queries > 0 {
+        diagnostics.add_measurement(BoidDiagnosticsPlugin::NEIGHBOR_QUERY_TIME, || {
+            timings.neighbor_queries.as_secs_f64() * 1e6 / timings.queries as f64
+        });
+    }
+    diagnostics.add_measurement(BoidDiagnosticsPlugin::FLOCKING_TIME, || {
+        timings.flocking.as_secs_f64() * 1e3
+    });
+}
@@ -0,0 +1,260 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, BoidBuilder},
+    bounds::{BoidDespawned, Bounds},
+    flock::{random_mass, FlockConfig},
+    render::{FlockView, RenderOrder},
+    rng::SimRng,
+    tick::SimTick,
+};
+
+#[derive(Clone, Copy)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Edge {
+    /// Direction pointing from the edge into the bounds.
+    pub fn inward(self) -> Vec2 {
+        match self {
+            Edge::Left => Vec2::X,
+            Edge::Right => Vec2::NEG_X,
+            Edge::Top => Vec2::NEG_Y,
+            Edge::Bottom => Vec2::Y,
+        }
+    }
+
+    /// Point at `t` (from 0 to 1) along this edge of `bounds`.
+    pub fn point(self, bounds: &Bounds, t: f32) -> Vec2 {
+        let along = bounds.min.lerp(bounds.max, t);
+        match self {
+            Edge::Left => Vec2::new(bounds.min.x, along.y),
+            Edge::Right => Vec2::new(bounds.max.x, along.y),
+            Edge::Top => Vec2::new(along.x, bounds.max.y),
+            Edge::Bottom => Vec2::new(along.x, bounds.min.y),
+        }
+    }
+}
+
+/// Continuously spawns boids along an edge of `Bounds`, heading inward.
+#[derive(Component)]
+pub struct Emitter {
+    pub edge: Edge,
+    // boids per second
+    pub rate: f32,
+    // world units per second
+    pub speed: f32,
+    // fraction of a boid carried over between steps
+    pending: f32,
+}
+
+impl Emitter {
+    pub fn new(edge: Edge, rate: f32, speed: f32) -> Self {
+        Self {
+            edge,
+            rate,
+            speed,
+            pending: 0.,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn emit_boids(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    mut rng: ResMut<SimRng>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    bounds: Res<Bounds>,
+    config: Res<FlockConfig>,
+    boids: Query<(), With<Boid>>,
+    mut emitters: Query<&mut Emitter>,
+) {
+    let mut boid_count = boids.iter().count();
+
+    for mut emitter in emitters.iter_mut() {
+        emitter.pending += emitter.rate * tick.step();
+        while emitter.pending >= 1. {
+            emitter.pending -= 1.;
+            if boid_count >= config.max_boids {
+                continue;
+            }
+
+            let position = emitter.edge.point(&bounds, rng.gen());
+            let velocity = emitter.edge.inward() * emitter.speed;
+            let boid = BoidBuilder::new()
+                .config(&config)
+                .view(&view)
+                .at(position.extend(order.boids))
+                .velocity(velocity.extend(0.))
+                .mass(random_mass(&config, rng.as_mut()));
+            commands.spawn(boid.build());
+            boid_count += 1;
+        }
+    }
+}
+
+/// Spawns boids from `position` at `rate` per second, flying at `velocity`
+/// turned by up to `spread` radians either way, as long as fewer than
+/// `max_alive` of its boids are about. With a `lifetime`, its boids despawn
+/// after that many seconds, so a stream of boids can run through a scene
+/// without filling it up.
+#[derive(Component)]
+pub struct BoidSpawner {
+    pub position: Vec2,
+    // boids per second
+    pub rate: f32,
+    pub max_alive: usize,
+    pub velocity: Vec2,
+    pub spread: f32,
+    // seconds
+    pub lifetime: Option<f32>,
+    // fraction of a boid carried over between steps
+    pending: f32,
+}
+
+impl BoidSpawner {
+    /// Spawner of up to 50 boids, heading within a few degrees of `velocity`,
+    /// which live until something else despawns them.
+    pub fn new(position: Vec2, velocity: Vec2, rate: f32) -> Self {
+        Self {
+            position,
+            rate,
+            max_alive: 50,
+            velocity,
+            spread: 0.25,
+            lifetime: None,
+            pending: 0.,
+        }
+    }
+}
+
+/// Spawner a boid came from.
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+/// Seconds a boid has left before it despawns.
+#[derive(Component)]
+pub struct Lifetime(pub f32);
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_spawners(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    mut rng: ResMut<SimRng>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    config: Res<FlockConfig>,
+    boids: Query<Option<&SpawnedBy>, With<Boid>>,
+    mut spawners: Query<(Entity, &mut BoidSpawner)>,
+) {
+    let mut boid_count = 0;
+    let mut alive: HashMap<Entity, usize> = HashMap::default();
+    for spawned_by in boids.iter() {
+        boid_count += 1;
+        if let Some(SpawnedBy(spawner)) = spawned_by {
+            *alive.entry(*spawner).or_default() += 1;
+        }
+    }
+
+    for (entity, mut spawner) in spawners.iter_mut() {
+        spawner.pending += spawner.rate * tick.step();
+        let alive = alive.entry(entity).or_default();
+        while spawner.pending >= 1. {
+            spawner.pending -= 1.;
+            if boid_count >= config.max_boids || *alive >= spawner.max_alive {
+                continue;
+            }
+
+            let turn = if spawner.spread > 0. {
+                rng.gen_range(-spawner.spread..=spawner.spread)
+            } else {
+                0.
+            };
+            let velocity = Vec2::from_angle(turn).rotate(spawner.velocity);
+            let boid = BoidBuilder::new()
+                .config(&config)
+                .view(&view)
+                .at(spawner.position.extend(order.boids))
+                .velocity(velocity.extend(0.))
+                .mass(random_mass(&config, rng.as_mut()));
+            let mut boid = commands.spawn(boid.build());
+            boid.insert(SpawnedBy(entity));
+            if let Some(lifetime) = spawner.lifetime {
+                boid.insert(Lifetime(lifetime));
+            }
+            boid_count += 1;
+            *alive += 1;
+        }
+    }
+}
+
+/// Despawns boids whose `Lifetime` ran out.
+pub(crate) fn expire_boids(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    mut despawned: EventWriter<BoidDespawned>,
+    mut boids: Query<(Entity, &mut Lifetime)>,
+) {
+    for (entity, mut lifetime) in boids.iter_mut() {
+        lifetime.0 -= tick.step();
+        if lifetime.0 <= 0. {
+            commands.entity(entity).despawn_recursive();
+            despawned.send(BoidDespawned { entity });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitting_world(rate: f32, max_boids: usize) -> World {
+        let mut world = World::new();
+        world.insert_resource(SimTick::default());
+        world.insert_resource(SimRng::seeded(0));
+        world.insert_resource(FlockView::default());
+        world.insert_resource(RenderOrder::default());
+        world.insert_resource(Bounds {
+            min: Vec2::splat(-100.),
+            max: Vec2::splat(100.),
+        });
+        world.insert_resource(FlockConfig {
+            max_boids,
+            ..default()
+        });
+        world.spawn(Emitter::new(Edge::Left, rate, 50.));
+        world
+    }
+
+    fn boid_count(world: &mut World) -> usize {
+        world.query_filtered::<(), With<Boid>>().iter(world).count()
+    }
+
+    #[test]
+    fn emits_rate_boids_per_second() {
+        let mut world = emitting_world(30., 1000);
+        let mut stage = SystemStage::single(emit_boids);
+        // one second of steps
+        for _ in 0..60 {
+            stage.run(&mut world);
+        }
+        assert_eq!(boid_count(&mut world), 30);
+    }
+
+    #[test]
+    fn stops_at_max_boids() {
+        let mut world = emitting_world(60., 10);
+        let mut stage = SystemStage::single(emit_boids);
+        for _ in 0..60 {
+            stage.run(&mut world);
+        }
+        assert_eq!(boid_count(&mut world), 10);
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    bounds::BoidDespawned,
+    flock::FlockConfig,
+    steering::SteeringForces,
+    tick::SimTick,
+};
+
+/// Energy a boid lives on, drained by flying fast and steering hard. Once it
+/// runs out the boid is `Exhausted`: it stops steering, glides to a halt for
+/// `glide` seconds and despawns, sending `BoidExhausted`.
+///
+/// Boids spawned while `FlockConfig::energy` is set get it on their own.
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct Energy {
+    pub current: f32,
+    pub max: f32,
+    // drained per second for each world unit per second of speed
+    pub speed_cost: f32,
+    // drained per second for each unit of steering force applied
+    pub force_cost: f32,
+    // seconds an exhausted boid glides before despawning
+    pub glide: f32,
+}
+
+impl Energy {
+    /// Full energy of `max`.
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            ..default()
+        }
+    }
+
+    /// Share of the energy left, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. {
+            (self.current / self.max).clamp(0., 1.)
+        } else {
+            0.
+        }
+    }
+}
+
+impl Default for Energy {
+    fn default() -> Self {
+        Self {
+            current: 100.,
+            max: 100.,
+            speed_cost: 0.01,
+            force_cost: 0.002,
+            glide: 2.,
+        }
+    }
+}
+
+/// Marks a boid out of energy, gliding until it despawns.
+#[derive(Component)]
+pub struct Exhausted {
+    // seconds left before the boid despawns
+    pub remaining: f32,
+}
+
+/// Sent when a boid despawns for lack of energy, along with `BoidDespawned`.
+pub struct BoidExhausted {
+    pub entity: Entity,
+}
+
+/// Gives new boids the energy of `FlockConfig::energy`, if any.
+pub(crate) fn energize_new_boids(
+    mut commands: Commands,
+    config: Res<FlockConfig>,
+    boids: Query<Entity, (Added<Boid>, Without<Energy>)>,
+) {
+    let Some(max) = config.energy else {
+        return;
+    };
+    for boid in boids.iter() {
+        commands.entity(boid).insert(Energy::new(max));
+    }
+}
+
+/// Drains energy for the step just simulated, then lets exhausted boids
+/// glide and despawns those done gliding.
+pub(crate) fn spend_energy(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    mut boids: Query<(Entity, &mut Physics, &SteeringForces, &mut Energy), Without<Exhausted>>,
+    mut exhausted: Query<(Entity, &mut Physics, &mut Exhausted)>,
+    mut exhausted_events: EventWriter<BoidExhausted>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    // share of its speed a gliding boid loses per second, on top of the drag
+    const GLIDE_BRAKING: f32 = 1.;
+
+    let dt = tick.step();
+    for (entity, mut physics, mut exhausted) in exhausted.iter_mut() {
+        physics.acceleration = Vec3::ZERO;
+        physics.velocity *= (-GLIDE_BRAKING * dt).exp();
+        exhausted.remaining -= dt;
+        if exhausted.remaining <= 0. {
+            commands.entity(entity).despawn_recursive();
+            exhausted_events.send(BoidExhausted { entity });
+            despawned.send(BoidDespawned { entity });
+        }
+    }
+
+    for (entity, mut physics, forces, mut energy) in boids.iter_mut() {
+        let effort = energy.speed_cost * physics.velocity.length()
+            + energy.force_cost * forces.applied().length();
+        energy.current = (energy.current - effort * dt).max(0.);
+        if energy.current == 0. {
+            physics.acceleration = Vec3::ZERO;
+            commands.entity(entity).insert(Exhausted {
+                remaining: energy.glide,
+            });
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::ops::Range;
+
+use bevy::{ecs::system::CommandQueue, math::Rect, prelude::*, utils::HashMap};
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, BoidBuilder, BoidShape, Physics, Selected},
+    bounds::BoidDespawned,
+    neighbors::{NeighborIndex, SpatialIndex},
+    perception::Perception,
+    population::{PopulationHistory, Traits},
+    render::{CursorPosition, FlockView, RenderOrder},
+    rng::SimRng,
+    stats::FlockStats,
+    steering::{Arbitration, ResponseCurve},
+};
+
+/// Shared settings of the flock.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FlockConfig {
+    // boids spawned on startup and on reset
+    pub count: usize,
+    // where the initial boids are scattered
+    pub spawn_area: Rect,
+    // initial speeds are picked at random from this range
+    pub spawn_speed: Range<f32>,
+    // and masses from this one
+    pub spawn_mass: Range<f32>,
+    // draw heavier boids bigger, and keep them further apart, see `BodySize`
+    pub scale_by_mass: bool,
+    // and well fed boids bigger than starving ones
+    pub scale_by_energy: bool,
+    // world units per second
+    pub max_speed: f32,
+    // world units per second squared
+    pub max_force: f32,
+    // radians per second, so boids bank into turns instead of flipping round
+    pub max_turn_rate: f32,
+    // share of its velocity a boid loses per second with nothing steering
+    // it, so it slows down rather than coasting forever
+    pub drag: f32,
+    // boids without an `Arrive` of their own slow down within this distance
+    // of their target rather than overshoot it; none to seek at full speed
+    pub arrive: Option<f32>,
+    // boids without a `Pursue` of their own aim where moving targets will be
+    // up to this many seconds ahead; none to aim where they are
+    pub pursue: Option<f32>,
+    // boids slow down behind one straight ahead within this distance, to
+    // file through gaps rather than jam them; none to press on regardless
+    pub queueing: Option<f32>,
+    // push overlapping boids apart as solid circles of `collision_radius`,
+    // on top of the soft separation
+    pub hard_collisions: bool,
+    pub collision_radius: f32,
+    // steer clear of neighbors with `ReciprocalAvoidance`, for flocks
+    // without settings of their own
+    pub reciprocal_avoidance: bool,
+    // how quickly drawn headings catch up with the velocity, per second;
+    // lower is smoother, infinite snaps them
+    pub rotation_smoothing: f32,
+    // spawners stop adding boids once the flock reaches this size
+    pub max_boids: usize,
+    // energy new boids start with, see `Energy`; without, they never tire
+    pub energy: Option<f32>,
+    // boids closer than this to each other are neighbors, which the flock
+    // aligns with and gathers around
+    pub neighbor_radius: f32,
+    // boids only heed this many of their nearest neighbors, as starlings
+    // watch about seven, so dense crowds do not blur into a mean
+    pub max_neighbors: Option<usize>,
+    // angle of the cone new boids see their neighbors in, see `Perception`
+    pub field_of_view: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+    // boids closer than this push each other apart
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    // extra push per unit of closing speed (relative to max speed), so boids
+    // rushing at each other separate harder than ones drifting together
+    pub separation_closing_weight: f32,
+    // neighbor-driven forces are recomputed every this many frames and
+    // reused in between, trading accuracy for speed in huge flocks
+    pub neighbor_stride: u32,
+    // neighbors are looked up again every this many steps, staggered across
+    // the flock, and only read where they are now in between (see
+    // `NeighborCache`)
+    pub neighbor_refresh: u32,
+    // size of newly spawned boids, from tail to tip and across the tail
+    pub boid_length: f32,
+    pub boid_width: f32,
+    // outline newly spawned boids are drawn with
+    pub shape: BoidShape,
+    pub response_curve: ResponseCurve,
+    // how the steering behaviors are combined
+    pub arbitration: Arbitration,
+    // directions boids weigh up with `Arbitration::Context`
+    pub context_slots: usize,
+    // how far ahead boids look for obstacles, in seconds of travel
+    pub avoidance_lookahead: f32,
+    // distance from the edges at which `BoundaryMode::Avoid` starts turning
+    // boids around
+    pub boundary_margin: f32,
+}
+
+impl Default for FlockConfig {
+    fn default() -> Self {
+        Self {
+            count: 50,
+            spawn_area: Rect::new(-300., -300., 300., 300.),
+            spawn_speed: 30. ..120.,
+            spawn_mass: 1. ..1.,
+            scale_by_mass: false,
+            scale_by_energy: false,
+            max_speed: 120.,
+            max_force: 360.,
+            max_turn_rate: 6.,
+            drag: 0.3,
+            arrive: None,
+            pursue: None,
+            queueing: None,
+            hard_collisions: false,
+            collision_radius: 12.,
+            reciprocal_avoidance: false,
+            rotation_smoothing: 15.,
+            max_boids: 200,
+            energy: None,
+            neighbor_radius: 100.,
+            max_neighbors: None,
+            field_of_view: 270f32.to_radians(),
+            alignment_weight: 1.,
+            cohesion_weight: 1.,
+            separation_radius: 60.,
+            separation_weight: 1.5,
+            separation_closing_weight: 1.,
+            neighbor_stride: 1,
+            neighbor_refresh: 1,
+            boid_length: 50.,
+            boid_width: 30.,
+            shape: BoidShape::default(),
+            response_curve: ResponseCurve::Linear,
+            arbitration: Arbitration::WeightedSum,
+            context_slots: 16,
+            avoidance_lookahead: 1.,
+            boundary_margin: 100.,
+        }
+    }
+}
+
+/// Flock a boid belongs to. Boids only align with and gather around members
+/// of their own flock, and take its `FlockSettings` from `Flocks`. Boids
+/// without one are in flock 0.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlockId(pub u32);
+
+/// Settings of one flock, overriding those of `FlockConfig` for its members.
+#[derive(Clone)]
+pub struct FlockSettings {
+    pub color: Color,
+    pub max_speed: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+    pub separation_weight: f32,
+    // see `ReciprocalAvoidance`
+    pub reciprocal_avoidance: bool,
+    // outline of its members, that of `FlockConfig` without
+    pub shape: Option<BoidShape>,
+}
+
+impl FlockSettings {
+    /// Settings matching `config`, drawn in `color`.
+    pub fn from_config(config: &FlockConfig, color: Color) -> Self {
+        Self {
+            color,
+            max_speed: config.max_speed,
+            alignment_weight: config.alignment_weight,
+            cohesion_weight: config.cohesion_weight,
+            separation_weight: config.separation_weight,
+            reciprocal_avoidance: config.reciprocal_avoidance,
+            shape: None,
+        }
+    }
+}
+
+/// Per-flock settings. When it holds any flock, the initial boids are shared
+/// out evenly between them.
+#[derive(Resource, Default)]
+pub struct Flocks {
+    settings: HashMap<FlockId, FlockSettings>,
+}
+
+impl Flocks {
+    pub fn insert(&mut self, id: FlockId, settings: FlockSettings) {
+        self.settings.insert(id, settings);
+    }
+
+    pub fn get(&self, id: FlockId) -> Option<&FlockSettings> {
+        self.settings.get(&id)
+    }
+
+    /// Ids of the flocks, in increasing order.
+    pub fn ids(&self) -> Vec<FlockId> {
+        let mut ids: Vec<FlockId> = self.settings.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// Sent to despawn the flock and spawn it afresh, see `reset_flock`.
+pub struct ResetFlock;
+
+pub(crate) fn spawn_flock(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    mut rng: ResMut<SimRng>,
+) {
+    spawn_initial_boids(&mut commands, &view, &order, &config, &flocks, &mut rng);
+}
+
+/// Spawns a boid somewhere in `FlockConfig::spawn_area`, heading anywhere at
+/// a speed picked from `FlockConfig::spawn_speed`, with a mass picked from
+/// `FlockConfig::spawn_mass`.
+pub(crate) fn spawn_random_boid(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    config: &FlockConfig,
+    rng: &mut impl Rng,
+) -> Entity {
+    let area = config.spawn_area;
+    let position = Vec2::new(
+        rng.gen_range(area.min.x..=area.max.x),
+        rng.gen_range(area.min.y..=area.max.y),
+    );
+    let boid = BoidBuilder::new()
+        .config(config)
+        .view(view)
+        .at(position.extend(order.boids))
+        .velocity(random_velocity(config, rng).extend(0.))
+        .mass(random_mass(config, rng));
+    commands.spawn(boid.build()).id()
+}
+
+/// Mass picked from `FlockConfig::spawn_mass`.
+pub(crate) fn random_mass(config: &FlockConfig, rng: &mut impl Rng) -> f32 {
+    if config.spawn_mass.is_empty() {
+        config.spawn_mass.start
+    } else {
+        rng.gen_range(config.spawn_mass.clone())
+    }
+}
+
+/// Velocity heading anywhere at a speed picked from
+/// `FlockConfig::spawn_speed`.
+pub(crate) fn random_velocity(config: &FlockConfig, rng: &mut impl Rng) -> Vec2 {
+    let heading = Vec2::from_angle(rng.gen_range(0. ..std::f32::consts::TAU));
+    let speed = if config.spawn_speed.is_empty() {
+        config.spawn_speed.start
+    } else {
+        rng.gen_range(config.spawn_speed.clone())
+    };
+    heading * speed
+}
+
+/// Pressing `E` spawns a burst of boids at the cursor, holding it keeps
+/// spawning them, as long as the flock is under `FlockConfig::max_boids`.
+/// It was `S` until the camera took `W`, `A`, `S`, `D` to pan.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spawn_at_cursor(
+    mut commands: Commands,
+    mut pending: Local<f32>,
+    time: Res<Time>,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    config: Res<FlockConfig>,
+    mut rng: ResMut<SimRng>,
+    boids: Query<(), With<Boid>>,
+) {
+    const BURST: usize = 5;
+    // boids per second while the key is held
+    const RATE: f32 = 20.;
+
+    let Some(position) = cursor.0 else {
+        return;
+    };
+    let count = if keyboard.just_pressed(KeyCode::E) {
+        *pending = 0.;
+        BURST
+    } else if keyboard.pressed(KeyCode::E) {
+        *pending += RATE * time.delta_seconds();
+        let count = *pending as usize;
+        *pending -= count as f32;
+        count
+    } else {
+        return;
+    };
+
+    let room = config.max_boids.saturating_sub(boids.iter().count());
+    for _ in 0..count.min(room) {
+        let boid = BoidBuilder::new()
+            .config(&config)
+            .view(&view)
+            .at(position.extend(order.boids))
+            .velocity(random_velocity(&config, rng.as_mut()).extend(0.))
+            .mass(random_mass(&config, rng.as_mut()));
+        commands.spawn(boid.build());
+    }
+}
+
+fn spawn_initial_boids(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    config: &FlockConfig,
+    flocks: &Flocks,
+    rng: &mut SimRng,
+) {
+    let flock_ids = flocks.ids();
+    for i in 0..config.count {
+        let boid = spawn_random_boid(commands, view, order, config, rng);
+        if i == 0 {
+            commands.entity(boid).insert(Selected);
+        }
+        if !flock_ids.is_empty() {
+            commands.entity(boid).insert(flock_ids[i % flock_ids.len()]);
+        }
+    }
+}
+
+/// Right clicking despawns the boid nearest to the cursor, if any is close
+/// enough.
+pub(crate) fn despawn_nearest_boid(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    boids: Query<(Entity, &Transform), With<Boid>>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    // world units around the cursor a boid can be picked in
+    const PICK_RADIUS: f32 = 30.;
+
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some(position) = cursor.0 else {
+        return;
+    };
+
+    let nearest = boids
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(position)))
+        .filter(|(_, distance)| *distance <= PICK_RADIUS)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((entity, _)) = nearest {
+        commands.entity(entity).despawn_recursive();
+        despawned.send(BoidDespawned { entity });
+    }
+}
+
+/// Despawns every boid and spawns the initial flock again, as on startup,
+/// starting the flock's stats and population history over.
+///
+/// Needs exclusive world access: call it from an exclusive system, or send a
+/// `ResetFlock` event and it runs at the end of the frame.
+pub fn reset_flock(world: &mut World) {
+    let boids: Vec<Entity> = world
+        .query_filtered::<Entity, With<Boid>>()
+        .iter(world)
+        .collect();
+    for boid in boids {
+        despawn_with_children_recursive(world, boid);
+    }
+    world.resource_mut::<SpatialIndex>().clear();
+    *world.resource_mut::<FlockStats>() = FlockStats::default();
+    world.resource_mut::<PopulationHistory>().clear();
+
+    world.resource_scope(|world, mut rng: Mut<SimRng>| {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        spawn_initial_boids(
+            &mut commands,
+            world.resource::<FlockView>(),
+            world.resource::<RenderOrder>(),
+            world.resource::<FlockConfig>(),
+            world.resource::<Flocks>(),
+            &mut rng,
+        );
+        queue.apply(world);
+    });
+}
+
+pub(crate) fn request_reset(keyboard: Res<Input<KeyCode>>, mut reset: EventWriter<ResetFlock>) {
+    if keyboard.just_pressed(KeyCode::R) {
+        reset.send(ResetFlock);
+    }
+}
+
+pub(crate) fn reset_on_request(world: &mut World) {
+    let requested = world.resource_mut::<Events<ResetFlock>>().drain().count() > 0;
+    if requested {
+        reset_flock(world);
+    }
+}
+
+/// Gives boids the speed, color and shape of their flock, when they join one
+/// or the flock settings change.
+pub(crate) fn apply_flock_settings(
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    joined: Query<(), Changed<FlockId>>,
+    traits: Query<&Traits>,
+    mut paths: Query<&mut Path, With<Boid>>,
+    mut boids: Query<(Entity, &FlockId, &mut Physics, &mut DrawMode), With<Boid>>,
+) {
+    for (entity, flock, mut physics, mut draw_mode) in boids.iter_mut() {
+        if !flocks.is_changed() && !joined.contains(entity) {
+            continue;
+        }
+        let Some(settings) = flocks.get(*flock) else {
+            continue;
+        };
+
+        let traits = traits.get(entity).copied().unwrap_or_default();
+        physics.max_speed = settings.max_speed * traits.speed;
+        if let DrawMode::Outlined { fill_mode, .. } = draw_mode.as_mut() {
+            fill_mode.color = settings.color;
+        }
+
+        let Some(shape) = &settings.shape else {
+            continue;
+        };
+        if let DrawMode::Outlined { outline_mode, .. } = draw_mode.as_mut() {
+            outline_mode.options.line_width = shape.stroke_width;
+        }
+        // boids drawn as sprites have no path
+        if let Ok(mut path) = paths.get_mut(entity) {
+            *path = shape.path(config.boid_length, config.boid_width);
+        }
+    }
+}
+
+/// Carries changes of `FlockConfig` over to the boids already flying, so it
+/// can be tuned while the simulation runs.
+pub(crate) fn apply_flock_config(
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    traits: Query<&Traits>,
+    mut boids: Query<(Entity, &mut Physics, &mut Perception, Option<&FlockId>), With<Boid>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    for (entity, mut physics, mut perception, flock) in boids.iter_mut() {
+        let traits = traits.get(entity).copied().unwrap_or_default();
+        physics.max_speed = match flock.and_then(|flock| flocks.get(*flock)) {
+            Some(settings) => settings.max_speed,
+            None => config.max_speed,
+        } * traits.speed;
+        physics.max_force = config.max_force;
+        physics.max_turn_rate = config.max_turn_rate;
+        perception.radius = config.neighbor_radius;
+        perception.field_of_view = config.field_of_view;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_repopulates_the_flock() {
+        let mut world = World::new();
+        world.insert_resource(FlockConfig {
+            count: 12,
+            ..default()
+        });
+        world.insert_resource(Flocks::default());
+        world.insert_resource(FlockView::default());
+        world.insert_resource(RenderOrder::default());
+        world.insert_resource(SimRng::seeded(0));
+        world.insert_resource(SpatialIndex::default());
+        world.insert_resource(FlockStats {
+            boid_count: 3,
+            average_speed: 20.,
+            ..default()
+        });
+        world.insert_resource(PopulationHistory::default());
+        for _ in 0..3 {
+            world.spawn((Boid, Transform::default()));
+        }
+
+        reset_flock(&mut world);
+
+        let boids = world
+            .query_filtered::<(), With<Boid>>()
+            .iter(&world)
+            .count();
+        assert_eq!(boids, 12);
+        let stats = world.resource::<FlockStats>();
+        assert_eq!(stats.boid_count, 0);
+        assert_eq!(stats.average_speed, 0.);
+    }
+}
@@ -0,0 +1,432 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, Physics},
+    diagnostics::SteeringTimings,
+    flock::{FlockConfig, Flocks},
+    flocking::{self, FlockingForce},
+    neighbors::{self, NeighborBackend, NeighborCache, SpatialIndex},
+    physics::Integrator,
+    pipeline::{self, BoidPipeline, BoidSet},
+    rng::{self, SimRng},
+    sim::MotionLimits,
+    species::SpeciesRelations,
+    steering::{self, steer_towards, SteeringForces, SteeringTrace, SteeringTracing},
+    tick::{self, FixedUpdateStage, SimTick, SimulationControl},
+};
+
+/// Runs the flock in three dimensions, in place of `BoidPlugin`: boids are
+/// cones flying about the `Volume` box, flocking as set by `FlockConfig` and
+/// turning back from its walls within `FlockConfig::boundary_margin`, seen
+/// through a perspective camera which the mouse orbits (left drag) and zooms
+/// (wheel). Needs `DefaultPlugins`, and the `3d` feature.
+///
+/// The boids perceive each other and flock through the same systems as in
+/// 2D, in the same `BoidSet`s; only keeping them in the volume and moving
+/// them are done here.
+pub struct Flock3dPlugin;
+
+impl Plugin for Flock3dPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlockConfig>()
+            .init_resource::<Flocks>()
+            .init_resource::<Volume>()
+            .init_resource::<SimTick>()
+            .init_resource::<SimulationControl>()
+            .init_resource::<BoidPipeline>()
+            .init_resource::<Integrator>()
+            .init_resource::<SpatialIndex>()
+            .init_resource::<NeighborBackend>()
+            .init_resource::<SpeciesRelations>()
+            .init_resource::<SteeringTimings>()
+            .init_resource::<SteeringTracing>()
+            .init_resource::<OrbitCamera>()
+            .add_event::<SteeringTrace>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, rng::seed_rng)
+            .add_startup_system(setup_scene)
+            .add_startup_system(spawn_flock)
+            .add_stage_before(
+                CoreStage::Update,
+                FixedUpdateStage,
+                SystemStage::parallel().with_run_criteria(tick::fixed_tick),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Integrate)
+                    .with_run_criteria(pipeline::integrating)
+                    .with_system(integrate),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Perception)
+                    .after(BoidSet::Integrate)
+                    .with_run_criteria(pipeline::perceiving)
+                    .with_system(neighbors::rebuild_spatial_index)
+                    .with_system(
+                        neighbors::refresh_neighbor_caches.after(neighbors::rebuild_spatial_index),
+                    ),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Steering)
+                    .after(BoidSet::Perception)
+                    .with_run_criteria(pipeline::steering)
+                    .with_system(flocking::flocking)
+                    .with_system(contain)
+                    .with_system(
+                        steering::accumulate_steering
+                            .after(flocking::flocking)
+                            .after(contain),
+                    ),
+            )
+            .add_system(orbit_camera);
+    }
+}
+
+/// Box the 3D flock flies in, centered on the origin.
+#[derive(Resource)]
+pub struct Volume {
+    pub half_size: Vec3,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            half_size: Vec3::new(500., 300., 300.),
+        }
+    }
+}
+
+/// Marks a boid of the 3D flock.
+#[derive(Component)]
+pub struct Boid3d;
+
+/// Where the camera looks at the flock from, around the origin.
+#[derive(Resource)]
+pub(crate) struct OrbitCamera {
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            yaw: 0.4,
+            pitch: 0.3,
+            distance: 1600.,
+        }
+    }
+}
+
+impl OrbitCamera {
+    fn transform(&self) -> Transform {
+        let rotation = Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.);
+        Transform::from_translation(rotation * Vec3::Z * self.distance)
+            .looking_at(Vec3::ZERO, Vec3::Y)
+    }
+}
+
+/// Cone `height` long pointing up the y axis, centered on the origin, with
+/// flat shading.
+fn cone(radius: f32, height: f32, segments: usize) -> Mesh {
+    let apex = Vec3::Y * height / 2.;
+    let rim = |i: usize| {
+        let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+        Vec3::new(angle.cos() * radius, -height / 2., angle.sin() * radius)
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    for i in 0..segments {
+        let (a, b) = (rim(i), rim(i + 1));
+        // a side, then the slice of the base under it
+        for triangle in [[a, apex, b], [a, b, Vec3::Y * -height / 2.]] {
+            let normal = (triangle[1] - triangle[0])
+                .cross(triangle[2] - triangle[0])
+                .normalize();
+            for corner in triangle {
+                positions.push(corner.to_array());
+                normals.push(normal.to_array());
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32((0..positions.len() as u32).collect())));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh
+}
+
+fn setup_scene(
+    mut commands: Commands,
+    volume: Res<Volume>,
+    orbit: Res<OrbitCamera>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn(Camera3dBundle {
+        transform: orbit.transform(),
+        ..default()
+    });
+    commands.spawn(DirectionalLightBundle {
+        transform: Transform::from_xyz(1., 2., 1.).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+    commands.insert_resource(AmbientLight {
+        brightness: 0.3,
+        ..default()
+    });
+
+    // the edges of the volume
+    let size = volume.half_size * 2.;
+    let edge = materials.add(Color::DARK_GRAY.into());
+    for axis in 0..3 {
+        let mut extents = Vec3::splat(2.);
+        extents[axis] = size[axis];
+        let mesh = meshes.add(shape::Box::new(extents.x, extents.y, extents.z).into());
+        for (u, v) in [(-1., -1.), (-1., 1.), (1., -1.), (1., 1.)] {
+            let mut corner = Vec3::ZERO;
+            corner[(axis + 1) % 3] = u * volume.half_size[(axis + 1) % 3];
+            corner[(axis + 2) % 3] = v * volume.half_size[(axis + 2) % 3];
+            commands.spawn(PbrBundle {
+                mesh: mesh.clone(),
+                material: edge.clone(),
+                transform: Transform::from_translation(corner),
+                ..default()
+            });
+        }
+    }
+}
+
+fn spawn_flock(
+    mut commands: Commands,
+    config: Res<FlockConfig>,
+    volume: Res<Volume>,
+    mut rng: ResMut<SimRng>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mesh = meshes.add(cone(config.boid_width / 2., config.boid_length, 12));
+    let material = materials.add(Color::ORANGE.into());
+    for _ in 0..config.count {
+        let position = Vec3::new(
+            rng.gen_range(-1. ..=1.),
+            rng.gen_range(-1. ..=1.),
+            rng.gen_range(-1. ..=1.),
+        ) * volume.half_size;
+        let heading = Vec3::new(
+            rng.gen_range(-1. ..=1.),
+            rng.gen_range(-1. ..=1.),
+            rng.gen_range(-1. ..=1.),
+        )
+        .normalize_or_zero();
+        commands
+            .spawn(PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(position),
+                ..default()
+            })
+            .insert(Physics {
+                velocity: heading * config.max_speed,
+                max_speed: config.max_speed,
+                max_force: config.max_force,
+                ..default()
+            })
+            .insert((
+                Boid,
+                Boid3d,
+                NeighborCache::default(),
+                FlockingForce::default(),
+                SteeringForces::default(),
+            ));
+    }
+}
+
+/// Steers boids back from the walls of the volume, the harder the deeper
+/// into `FlockConfig::boundary_margin` they are.
+fn contain(
+    config: Res<FlockConfig>,
+    volume: Res<Volume>,
+    mut boids: Query<(&Transform, &Physics, &mut SteeringForces), With<Boid3d>>,
+) {
+    let margin = config.boundary_margin.max(f32::EPSILON);
+    for (transform, physics, mut forces) in boids.iter_mut() {
+        let position = transform.translation;
+        // how deep into the margin the boid is on each side, from 0 to 1
+        let from_min = (1. - (position + volume.half_size) / margin).clamp(Vec3::ZERO, Vec3::ONE);
+        let from_max = (1. - (volume.half_size - position) / margin).clamp(Vec3::ZERO, Vec3::ONE);
+        let push = from_min - from_max;
+        if push == Vec3::ZERO {
+            continue;
+        }
+        let desired = (physics.velocity.normalize_or_zero() + push).normalize_or_zero();
+        forces.behavior("containment").add(
+            steer_towards(desired * physics.max_speed, physics, config.response_curve),
+            push.length().min(1.) * 2.,
+        );
+    }
+}
+
+/// Moves the boids by the `Integrator`, pointing them where they fly, and
+/// keeps them inside the volume should they slip past its walls.
+fn integrate(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    integrator: Res<Integrator>,
+    volume: Res<Volume>,
+    mut boids: Query<(&mut Transform, &mut Physics), With<Boid3d>>,
+) {
+    let dt = tick.step();
+    let blend = 1. - (-config.rotation_smoothing * dt).exp();
+    for (mut transform, mut physics) in boids.iter_mut() {
+        // turn rates are limited in the plane only, so not here
+        let limits = MotionLimits {
+            drag: config.drag,
+            max_turn_rate: f32::INFINITY,
+            max_speed: physics.max_speed,
+        };
+        let (position, mut velocity) = integrator.integrate(
+            transform.translation,
+            physics.velocity,
+            physics.acceleration,
+            physics.previous_acceleration,
+            dt,
+            &limits,
+        );
+        let inside = position.clamp(-volume.half_size, volume.half_size);
+        for axis in 0..3 {
+            if inside[axis] != position[axis] {
+                velocity[axis] = -velocity[axis];
+            }
+        }
+
+        transform.translation = inside;
+        if let Some(heading) = velocity.try_normalize() {
+            let rotation = Quat::from_rotation_arc(Vec3::Y, heading);
+            transform.rotation = transform.rotation.slerp(rotation, blend);
+        }
+        physics.velocity = velocity;
+        physics.previous_acceleration = physics.acceleration;
+        physics.acceleration = Vec3::ZERO;
+    }
+}
+
+/// Dragging with the left mouse button orbits the camera around the volume,
+/// the wheel moves it closer or further.
+fn orbit_camera(
+    mouse: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut orbit: ResMut<OrbitCamera>,
+    mut cameras: Query<&mut Transform, With<Camera3d>>,
+) {
+    // radians per pixel dragged
+    const SENSITIVITY: f32 = 0.005;
+
+    let dragged: Vec2 = motion.iter().map(|motion| motion.delta).sum();
+    let scrolled: f32 = wheel.iter().map(|wheel| wheel.y).sum();
+    if mouse.pressed(MouseButton::Left) && dragged != Vec2::ZERO {
+        orbit.yaw -= dragged.x * SENSITIVITY;
+        orbit.pitch = (orbit.pitch + dragged.y * SENSITIVITY).clamp(-1.5, 1.5);
+    }
+    if scrolled != 0. {
+        orbit.distance = (orbit.distance * 0.9_f32.powf(scrolled)).clamp(200., 5000.);
+    }
+    if !orbit.is_changed() {
+        return;
+    }
+    for mut camera in cameras.iter_mut() {
+        *camera = orbit.transform();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+
+    use super::*;
+
+    fn spawn_boid(world: &mut World, position: Vec3, velocity: Vec3) -> Entity {
+        world
+            .spawn((
+                Boid,
+                Boid3d,
+                Transform::from_translation(position),
+                Physics {
+                    velocity,
+                    max_speed: 100.,
+                    max_force: 50.,
+                    ..default()
+                },
+                NeighborCache::default(),
+                FlockingForce::default(),
+                SteeringForces::default(),
+            ))
+            .id()
+    }
+
+    fn steer(world: &mut World) {
+        ComputeTaskPool::init(TaskPool::default);
+        world.insert_resource(FlockConfig::default());
+        world.insert_resource(Flocks::default());
+        world.insert_resource(Volume::default());
+        world.insert_resource(SimTick::default());
+        world.insert_resource(SpatialIndex::default());
+        world.insert_resource(NeighborBackend::default());
+        world.insert_resource(SpeciesRelations::default());
+        world.insert_resource(SteeringTimings::default());
+        world.insert_resource(SteeringTracing::default());
+        world.insert_resource(Events::<SteeringTrace>::default());
+        SystemStage::single_threaded()
+            .with_system(neighbors::rebuild_spatial_index)
+            .with_system(neighbors::refresh_neighbor_caches.after(neighbors::rebuild_spatial_index))
+            .with_system(flocking::flocking.after(neighbors::refresh_neighbor_caches))
+            .with_system(contain)
+            .with_system(
+                steering::accumulate_steering
+                    .after(flocking::flocking)
+                    .after(contain),
+            )
+            .run(world);
+    }
+
+    #[test]
+    fn boids_stacked_in_depth_push_apart_in_depth() {
+        let mut world = World::new();
+        let below = spawn_boid(&mut world, Vec3::new(0., 0., -5.), Vec3::X * 50.);
+        let above = spawn_boid(&mut world, Vec3::new(0., 0., 5.), Vec3::X * 50.);
+        steer(&mut world);
+
+        let pushed = |entity| world.get::<Physics>(entity).unwrap().acceleration;
+        assert!(pushed(below).z < 0.);
+        assert!(pushed(above).z > 0.);
+    }
+
+    #[test]
+    fn boids_along_a_wall_turn_away_from_it() {
+        let mut world = World::new();
+        let half_size = Volume::default().half_size;
+        let boid = spawn_boid(
+            &mut world,
+            Vec3::new(0., 0., half_size.z - 1.),
+            Vec3::X * 100.,
+        );
+        steer(&mut world);
+
+        let forces = world.get::<SteeringForces>(boid).unwrap();
+        assert_eq!(forces.dominant(), Some("containment"));
+        assert!(world.get::<Physics>(boid).unwrap().acceleration.z < 0.);
+    }
+}
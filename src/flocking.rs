@@ -0,0 +1,296 @@
+// std's panics in browsers
+use bevy::{prelude::*, utils::Instant};
+
+use crate::{
+    boid::{BodySize, Boid, BoidId, Physics},
+    diagnostics::SteeringTimings,
+    flock::{FlockConfig, FlockId, Flocks},
+    grid::GridEntry,
+    lod::Lod,
+    neighbors::{NeighborCache, SpatialIndex},
+    perception::{nearest, perceived_neighbors, Perception},
+    population::Traits,
+    sim,
+    species::{Reaction, Species, SpeciesRelations},
+    steering::{steering_params, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Last forces computed from a boid's neighbors, reused on frames where the
+/// neighbors are not looked at again (see `FlockConfig::neighbor_stride`).
+#[derive(Component, Default)]
+pub(crate) struct FlockingForce {
+    separation: Vec3,
+    alignment: Vec3,
+    cohesion: Vec3,
+}
+
+/// Priority of separation in `SteeringForces`, above the default so crowding
+/// boids push apart even while they steer hard towards something else.
+const SEPARATION_PRIORITY: i32 = 1;
+
+/// Flocking weights of one boid, in place of those of its flock, for
+/// flocks mixing very cohesive boids with loners. Weights left to `None`
+/// are still taken from the flock. `Traits` scale them all the same.
+#[derive(Component, Clone, Copy, Default, Reflect)]
+#[reflect(Component)]
+pub struct BehaviorWeights {
+    pub separation: Option<f32>,
+    pub alignment: Option<f32>,
+    pub cohesion: Option<f32>,
+}
+
+/// Separation, alignment and cohesion weights of a boid of `flock`, from
+/// its `BehaviorWeights` where it has them, else from the `FlockSettings`
+/// of its flock if it has any, else from `FlockConfig`.
+pub(crate) fn flock_weights(
+    config: &FlockConfig,
+    flocks: &Flocks,
+    flock: Option<FlockId>,
+    overrides: Option<&BehaviorWeights>,
+) -> (f32, f32, f32) {
+    let (separation, alignment, cohesion) = match flock.and_then(|flock| flocks.get(flock)) {
+        Some(settings) => (
+            settings.separation_weight,
+            settings.alignment_weight,
+            settings.cohesion_weight,
+        ),
+        None => (
+            config.separation_weight,
+            config.alignment_weight,
+            config.cohesion_weight,
+        ),
+    };
+    let overrides = overrides.copied().unwrap_or_default();
+    (
+        overrides.separation.unwrap_or(separation),
+        overrides.alignment.unwrap_or(alignment),
+        overrides.cohesion.unwrap_or(cohesion),
+    )
+}
+
+/// Radii within which a boid of `size` heeds its crowd and its flockmates:
+/// the separation radius, growing with its size, and how far it perceives.
+pub(crate) fn neighbor_radii(
+    config: &FlockConfig,
+    perception: Option<&Perception>,
+    size: f32,
+) -> (f32, f32) {
+    (
+        config.separation_radius * size,
+        perception.map_or(config.neighbor_radius, |perception| perception.radius),
+    )
+}
+
+/// Neighbors a boid heeds: the crowd it separates from and the flockmates
+/// it aligns with and gathers around, within their `neighbor_radii`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn influencing_neighbors<'a>(
+    index: &'a SpatialIndex,
+    config: &FlockConfig,
+    relations: &SpeciesRelations,
+    entity: Entity,
+    transform: &'a Transform,
+    physics: &'a Physics,
+    perception: Option<&'a Perception>,
+    species: Species,
+    flock: FlockId,
+    size: f32,
+) -> (Vec<&'a GridEntry>, Vec<&'a GridEntry>) {
+    let (crowd_radius, flock_radius) = neighbor_radii(config, perception, size);
+    let neighbors = |radius| {
+        perceived_neighbors(index, entity, transform, physics, perception, radius)
+            .filter(|other| relations.reaction(species, other.species) == Reaction::FlockWith)
+    };
+    let position = transform.translation;
+    let crowd = nearest(neighbors(crowd_radius), position, config.max_neighbors);
+    // only members of the same flock are followed
+    let flockmates = nearest(
+        neighbors(flock_radius).filter(|other| other.flock == flock),
+        position,
+        config.max_neighbors,
+    );
+    (crowd, flockmates)
+}
+
+/// Separation, alignment and cohesion: the forces a boid feels from its
+/// neighbors.
+///
+/// Boids are handled in parallel, each reading the neighbors of its
+/// `NeighborCache` from the `SpatialIndex` built earlier in the step and
+/// writing only its own forces.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn flocking(
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    index: Res<SpatialIndex>,
+    mut frame: Local<u32>,
+    boids: Query<(&Transform, &Physics, &NeighborCache), With<Boid>>,
+    ids: Query<&BoidId>,
+    lods: Query<&Lod>,
+    traits_query: Query<&Traits>,
+    overrides: Query<&BehaviorWeights>,
+    sizes: Query<&BodySize>,
+    mut cache: Query<(
+        Entity,
+        &mut FlockingForce,
+        &mut SteeringForces,
+        Option<&FlockId>,
+    )>,
+    mut timings: ResMut<SteeringTimings>,
+) {
+    let started = Instant::now();
+    let stride = config.neighbor_stride.max(1);
+    let frame_number = *frame;
+    *frame = frame.wrapping_add(1);
+
+    cache.par_for_each_mut(PAR_BATCH_SIZE, |(entity, mut cached, mut forces, flock)| {
+        // spread the recomputations evenly over the stride
+        let due = ids.get(entity).map_or(true, |id| {
+            frame_number.wrapping_add(id.0).is_multiple_of(stride)
+        }) && !lods.get(entity).is_ok_and(Lod::skips_steering);
+        if let (true, Ok((transform, physics, neighbors))) = (due, boids.get(entity)) {
+            let size = sizes.get(entity).map_or(1., |size| size.0);
+            let (crowd, flockmates) = neighbors.resolve(&index, transform.translation);
+            *cached = FlockingForce {
+                separation: separation_force(
+                    transform,
+                    physics,
+                    crowd.iter().copied(),
+                    config.separation_radius * size,
+                    &config,
+                ),
+                alignment: alignment_force(physics, flockmates.iter().copied(), &config),
+                cohesion: cohesion_force(transform, physics, flockmates.iter().copied(), &config),
+            };
+        }
+
+        let (separation_weight, alignment_weight, cohesion_weight) =
+            flock_weights(&config, &flocks, flock.copied(), overrides.get(entity).ok());
+        let traits = traits_query.get(entity).copied().unwrap_or_default();
+        forces.behavior("separation").add_with_priority(
+            cached.separation,
+            separation_weight * traits.separation,
+            SEPARATION_PRIORITY,
+        );
+        forces
+            .behavior("alignment")
+            .add(cached.alignment, alignment_weight * traits.alignment);
+        forces
+            .behavior("cohesion")
+            .add(cached.cohesion, cohesion_weight * traits.cohesion);
+    });
+
+    timings.flocking = started.elapsed();
+}
+
+fn separation_force<'a>(
+    transform: &Transform,
+    physics: &Physics,
+    neighbors: impl Iterator<Item = &'a GridEntry>,
+    radius: f32,
+    config: &FlockConfig,
+) -> Vec3 {
+    sim::steer_separation(
+        transform.translation,
+        physics.velocity,
+        neighbors.map(|other| (other.position, other.velocity)),
+        radius,
+        config.separation_closing_weight,
+        &steering_params(physics, config.response_curve),
+    )
+}
+
+fn alignment_force<'a>(
+    physics: &Physics,
+    neighbors: impl Iterator<Item = &'a GridEntry>,
+    config: &FlockConfig,
+) -> Vec3 {
+    sim::steer_alignment(
+        physics.velocity,
+        neighbors.map(|other| other.velocity),
+        &steering_params(physics, config.response_curve),
+    )
+}
+
+fn cohesion_force<'a>(
+    transform: &Transform,
+    physics: &Physics,
+    neighbors: impl Iterator<Item = &'a GridEntry>,
+    config: &FlockConfig,
+) -> Vec3 {
+    sim::steer_cohesion(
+        transform.translation,
+        physics.velocity,
+        neighbors.map(|other| other.position),
+        &steering_params(physics, config.response_curve),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+
+    use super::*;
+    use crate::neighbors::{rebuild_spatial_index, NeighborBackend};
+
+    fn spawn_boid(world: &mut World, id: u32, position: Vec3) -> Entity {
+        world
+            .spawn((
+                Boid,
+                BoidId(id),
+                Transform::from_translation(position),
+                Physics {
+                    max_speed: 100.,
+                    max_force: 50.,
+                    ..default()
+                },
+                FlockingForce::default(),
+                SteeringForces::default(),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn stride_recomputes_only_on_its_step() {
+        ComputeTaskPool::init(TaskPool::default);
+        let mut world = World::new();
+        world.insert_resource(FlockConfig {
+            neighbor_stride: 3,
+            ..default()
+        });
+        world.insert_resource(Flocks::default());
+        world.insert_resource(NeighborBackend::default());
+        world.insert_resource(SpatialIndex::default());
+        world.insert_resource(SteeringTimings::default());
+        let boid = spawn_boid(&mut world, 0, Vec3::ZERO);
+        let neighbor = spawn_boid(&mut world, 1, Vec3::new(40., 0., 0.));
+        world.entity_mut(boid).insert(NeighborCache {
+            crowd: vec![neighbor],
+            flockmates: vec![neighbor],
+            crowd_radius: 50.,
+            flockmates_radius: 50.,
+        });
+
+        let mut rebuild = SystemStage::single(rebuild_spatial_index);
+        let mut flock = SystemStage::single(flocking);
+        let mut separations = Vec::new();
+        for step in 0..7 {
+            // the neighbor comes closer every step
+            let distance = 40. - step as f32 * 5.;
+            world.get_mut::<Transform>(neighbor).unwrap().translation.x = distance;
+            rebuild.run(&mut world);
+            flock.run(&mut world);
+            separations.push(world.get::<FlockingForce>(boid).unwrap().separation);
+        }
+
+        // recomputed on steps 0, 3 and 6, held in between
+        assert_eq!(separations[1], separations[0]);
+        assert_eq!(separations[2], separations[0]);
+        assert_ne!(separations[3], separations[2]);
+        assert_eq!(separations[4], separations[3]);
+        assert_eq!(separations[5], separations[3]);
+        assert_ne!(separations[6], separations[5]);
+        assert!(separations[6].length() > separations[3].length());
+        assert!(separations[3].length() > separations[0].length());
+    }
+}
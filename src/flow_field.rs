@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use noise::{NoiseFn, Perlin};
+
+use crate::{
+    boid::Physics,
+    bounds::Bounds,
+    flock::FlockConfig,
+    steering::{steer_towards, SteeringForces},
+};
+
+/// Grid of directions covering `Bounds`. Boids with `FollowFlowField` steer
+/// along the direction of the cell they are in, which gives crowd-like
+/// streams. Not inserted by default.
+#[derive(Resource)]
+pub struct FlowField {
+    origin: Vec2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    directions: Vec<Vec2>,
+}
+
+impl FlowField {
+    /// Field with a direction per cell given by `direction` at the cell's
+    /// center. Cells are at least one world unit wide, and empty bounds get
+    /// a single cell.
+    pub fn from_fn(bounds: &Bounds, cell_size: f32, direction: impl Fn(Vec2) -> Vec2) -> Self {
+        let cell_size = cell_size.max(1.);
+        let size = bounds.max - bounds.min;
+        let columns = (size.x / cell_size).ceil().max(1.) as usize;
+        let rows = (size.y / cell_size).ceil().max(1.) as usize;
+        let directions = (0..columns * rows)
+            .map(|index| {
+                let cell = Vec2::new((index % columns) as f32, (index / columns) as f32);
+                let center = bounds.min + (cell + 0.5) * cell_size;
+                direction(center).normalize_or_zero()
+            })
+            .collect();
+        Self {
+            origin: bounds.min,
+            cell_size,
+            columns,
+            rows,
+            directions,
+        }
+    }
+
+    /// Smoothly swirling field, `scale` world units per noise period.
+    pub fn from_noise(bounds: &Bounds, cell_size: f32, seed: u32, scale: f32) -> Self {
+        let noise = Perlin::new(seed);
+        Self::from_fn(bounds, cell_size, |center| {
+            let p = center / scale;
+            let angle = noise.get([p.x as f64, p.y as f64]) as f32 * std::f32::consts::TAU;
+            Vec2::from_angle(angle)
+        })
+    }
+
+    /// Field pointing every cell towards `goal`.
+    pub fn towards(bounds: &Bounds, cell_size: f32, goal: Vec2) -> Self {
+        Self::from_fn(bounds, cell_size, |center| goal - center)
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Center and direction of every cell.
+    pub fn cells(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        self.directions
+            .iter()
+            .enumerate()
+            .map(|(index, direction)| {
+                let cell = Vec2::new((index % self.columns) as f32, (index / self.columns) as f32);
+                (self.origin + (cell + 0.5) * self.cell_size, *direction)
+            })
+    }
+
+    /// Direction of the cell `position` is in, `None` outside the field.
+    pub fn sample(&self, position: Vec2) -> Option<Vec2> {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        if cell.x < 0. || cell.y < 0. {
+            return None;
+        }
+        let (column, row) = (cell.x as usize, cell.y as usize);
+        (column < self.columns && row < self.rows)
+            .then(|| self.directions[row * self.columns + column])
+    }
+}
+
+/// Makes a boid follow the `FlowField`, weighted by `weight` against its
+/// other behaviors.
+#[derive(Component)]
+pub struct FollowFlowField {
+    pub weight: f32,
+}
+
+impl Default for FollowFlowField {
+    fn default() -> Self {
+        Self { weight: 1. }
+    }
+}
+
+pub(crate) fn follow_flow_field(
+    config: Res<FlockConfig>,
+    field: Option<Res<FlowField>>,
+    mut query: Query<(&Transform, &Physics, &FollowFlowField, &mut SteeringForces)>,
+) {
+    let Some(field) = field else {
+        return;
+    };
+
+    for (transform, physics, follow, mut forces) in query.iter_mut() {
+        let Some(direction) = field.sample(transform.translation.truncate()) else {
+            continue;
+        };
+        let desired = direction.extend(0.) * physics.max_speed;
+        forces.behavior("flow field").add(
+            steer_towards(desired, physics, config.response_curve),
+            follow.weight,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degenerate_fields_still_have_a_cell() {
+        let bounds = Bounds {
+            min: Vec2::splat(-100.),
+            max: Vec2::splat(100.),
+        };
+        let field = FlowField::from_fn(&bounds, 0., |_| Vec2::X);
+        assert!(field.cell_size() >= 1.);
+        assert_eq!(field.sample(Vec2::ZERO), Some(Vec2::X));
+
+        let field = FlowField::from_fn(&Bounds::default(), 20., |_| Vec2::Y);
+        assert_eq!(field.cells().count(), 1);
+        assert_eq!(field.sample(Vec2::ZERO), Some(Vec2::Y));
+    }
+}
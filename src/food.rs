@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::Physics,
+    bounds::Bounds,
+    energy::{Energy, Exhausted},
+    flock::FlockConfig,
+    render::{CursorPosition, FlockView, RenderOrder},
+    rng::SimRng,
+    sim,
+    steering::{steering_params, SteeringForces},
+    tick::SimTick,
+};
+
+/// Morsel restoring the energy of the boid that eats it.
+#[derive(Component, Clone, Copy)]
+pub struct Food {
+    pub energy: f32,
+}
+
+/// Sent when `boid` eats `food`.
+pub struct FoodEaten {
+    pub boid: Entity,
+    pub food: Entity,
+}
+
+/// How food is scattered and sought. Boids with `Energy` below `hunger` of
+/// their maximum seek the nearest food within `sense_radius`, and eat food
+/// they come within `eat_radius` of.
+#[derive(Resource)]
+pub struct FoodSettings {
+    // seconds between morsels dropped at random in the bounds, none without
+    pub drop_interval: Option<f32>,
+    // no more are dropped past this many
+    pub max_food: usize,
+    // energy restored by a morsel
+    pub energy: f32,
+    // share of its maximum energy under which a boid looks for food
+    pub hunger: f32,
+    pub sense_radius: f32,
+    pub eat_radius: f32,
+    pub seek_weight: f32,
+}
+
+impl Default for FoodSettings {
+    fn default() -> Self {
+        Self {
+            drop_interval: None,
+            max_food: 30,
+            energy: 40.,
+            hunger: 0.5,
+            sense_radius: 300.,
+            eat_radius: 15.,
+            seek_weight: 2.,
+        }
+    }
+}
+
+/// Spawns a morsel of food at `position`.
+pub fn spawn_food(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    position: Vec2,
+    energy: f32,
+) -> Entity {
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &shapes::Circle {
+                radius: 5.,
+                ..default()
+            },
+            DrawMode::Fill(FillMode::color(Color::YELLOW_GREEN)),
+            Transform::from_translation(position.extend(order.obstacles)),
+        ))
+        .insert(view.layers)
+        .insert(Food { energy })
+        .id()
+}
+
+/// Drops food at random in the bounds, every `FoodSettings::drop_interval`
+/// simulated seconds.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn drop_food(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    settings: Res<FoodSettings>,
+    mut rng: ResMut<SimRng>,
+    mut pending: Local<f32>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    bounds: Res<Bounds>,
+    food: Query<(), With<Food>>,
+) {
+    let Some(interval) = settings.drop_interval else {
+        return;
+    };
+    *pending += tick.step() / interval.max(f32::EPSILON);
+    let mut count = food.iter().count();
+    while *pending >= 1. {
+        *pending -= 1.;
+        if count >= settings.max_food {
+            continue;
+        }
+        let position = Vec2::new(
+            rng.gen_range(bounds.min.x..=bounds.max.x),
+            rng.gen_range(bounds.min.y..=bounds.max.y),
+        );
+        spawn_food(&mut commands, &view, &order, position, settings.energy);
+        count += 1;
+    }
+}
+
+/// `Ctrl` and a left click drop food at the cursor.
+pub(crate) fn drop_food_on_click(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    settings: Res<FoodSettings>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    if !mouse.just_pressed(MouseButton::Left)
+        || !keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl])
+    {
+        return;
+    }
+    if let Some(position) = cursor.0 {
+        spawn_food(&mut commands, &view, &order, position, settings.energy);
+    }
+}
+
+/// Hungry boids steer towards the nearest food they sense.
+pub(crate) fn seek_food(
+    config: Res<FlockConfig>,
+    settings: Res<FoodSettings>,
+    food: Query<&Transform, With<Food>>,
+    mut boids: Query<(&Transform, &Physics, &Energy, &mut SteeringForces), Without<Exhausted>>,
+) {
+    if food.is_empty() {
+        return;
+    }
+    for (transform, physics, energy, mut forces) in boids.iter_mut() {
+        if energy.fraction() >= settings.hunger {
+            continue;
+        }
+        let position = transform.translation;
+        let nearest = food
+            .iter()
+            .map(|food| food.translation.truncate())
+            .filter(|food| food.distance(position.truncate()) <= settings.sense_radius)
+            .min_by(|a, b| {
+                let (a, b) = (
+                    a.distance(position.truncate()),
+                    b.distance(position.truncate()),
+                );
+                a.total_cmp(&b)
+            });
+        let Some(nearest) = nearest else {
+            continue;
+        };
+        let params = steering_params(physics, config.response_curve);
+        let force = sim::steer_seek(
+            position,
+            physics.velocity,
+            nearest.extend(position.z),
+            &params,
+        );
+        forces.behavior("food").add(force, settings.seek_weight);
+    }
+}
+
+/// Boids short of energy within reach of food eat it, restoring their
+/// energy, one boid per morsel.
+pub(crate) fn eat_food(
+    mut commands: Commands,
+    settings: Res<FoodSettings>,
+    food: Query<(Entity, &Transform, &Food)>,
+    mut boids: Query<(Entity, &Transform, &mut Energy), Without<Exhausted>>,
+    mut eaten: EventWriter<FoodEaten>,
+) {
+    for (entity, food_transform, food) in food.iter() {
+        let position = food_transform.translation.truncate();
+        let eater = boids.iter_mut().find(|(_, transform, energy)| {
+            energy.current < energy.max
+                && transform.translation.truncate().distance(position) <= settings.eat_radius
+        });
+        if let Some((boid, _, mut energy)) = eater {
+            energy.current = (energy.current + food.energy).min(energy.max);
+            commands.entity(entity).despawn_recursive();
+            eaten.send(FoodEaten { boid, food: entity });
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::{FlockConfig, FlockId},
+    leader::Leader,
+    steering::{steer_towards, SteeringForces},
+    target::{MainTarget, TargetMotion},
+};
+
+/// Weight of the pull towards a boid's slot in `SteeringForces`, above the
+/// flocking forces so the shape holds.
+const FORMATION_WEIGHT: f32 = 2.;
+
+/// Distance from its slot within which a boid slows down to settle in it.
+const SLOWING_RADIUS: f32 = 80.;
+
+/// Shape a `Formation` holds its boids in, relative to its anchor heading
+/// up the y axis.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FormationShape {
+    /// Two arms trailing back from the anchor, as geese fly.
+    #[default]
+    V,
+    /// A filled triangle behind the anchor.
+    Wedge,
+    /// Side by side with the anchor.
+    Line,
+    /// Evenly around the anchor.
+    Ring,
+    /// Rows and columns behind the anchor.
+    Grid,
+}
+
+impl FormationShape {
+    pub fn next(self) -> Option<Self> {
+        match self {
+            FormationShape::V => Some(FormationShape::Wedge),
+            FormationShape::Wedge => Some(FormationShape::Line),
+            FormationShape::Line => Some(FormationShape::Ring),
+            FormationShape::Ring => Some(FormationShape::Grid),
+            FormationShape::Grid => None,
+        }
+    }
+
+    /// Offsets of `count` slots, `spacing` apart, the nearest to the anchor
+    /// first.
+    pub fn offsets(self, count: usize, spacing: f32) -> Vec<Vec2> {
+        match self {
+            FormationShape::V => (0..count)
+                .map(|slot| {
+                    let rank = (slot / 2 + 1) as f32;
+                    let side = if slot % 2 == 0 { -1. } else { 1. };
+                    Vec2::new(side, -1.) * rank * spacing * std::f32::consts::FRAC_1_SQRT_2
+                })
+                .collect(),
+            FormationShape::Wedge => {
+                let mut offsets = Vec::with_capacity(count);
+                let mut row = 1;
+                while offsets.len() < count {
+                    for column in 0..=row {
+                        let x = (column as f32 - row as f32 / 2.) * spacing;
+                        offsets.push(Vec2::new(x, -(row as f32) * spacing));
+                    }
+                    row += 1;
+                }
+                offsets.truncate(count);
+                offsets
+            }
+            FormationShape::Line => (0..count)
+                .map(|slot| {
+                    let rank = (slot / 2 + 1) as f32;
+                    let side = if slot % 2 == 0 { -1. } else { 1. };
+                    Vec2::X * side * rank * spacing
+                })
+                .collect(),
+            FormationShape::Ring => {
+                // wide enough for the slots to be `spacing` apart
+                let radius = (spacing * count as f32 / TAU).max(spacing);
+                (0..count)
+                    .map(|slot| {
+                        let angle = TAU * slot as f32 / count as f32;
+                        Vec2::from_angle(angle).rotate(Vec2::NEG_Y) * radius
+                    })
+                    .collect()
+            }
+            FormationShape::Grid => {
+                let columns = (count as f32).sqrt().ceil().max(1.) as usize;
+                (0..count)
+                    .map(|slot| {
+                        let (row, column) = (slot / columns, slot % columns);
+                        let x = (column as f32 - (columns - 1) as f32 / 2.) * spacing;
+                        Vec2::new(x, -((row + 1) as f32) * spacing)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Holds the boids of `flock` in `shape` around `anchor`, a target or a
+/// boid, turned the way it heads. Each boid is given a slot and steers to
+/// it, instead of heading for the target itself. The formation is removed
+/// when its anchor is.
+#[derive(Component)]
+pub struct Formation {
+    pub shape: FormationShape,
+    pub spacing: f32,
+    pub anchor: Entity,
+    pub flock: FlockId,
+    // sorted, to tell when boids join or leave
+    members: Vec<Entity>,
+    // shape the slots were last handed out for
+    shaped: Option<FormationShape>,
+    // kept while the anchor stands still
+    heading: Vec2,
+}
+
+impl Formation {
+    pub fn new(shape: FormationShape, anchor: Entity, flock: FlockId) -> Self {
+        Self {
+            shape,
+            spacing: 60.,
+            anchor,
+            flock,
+            members: Vec::new(),
+            shaped: None,
+            heading: Vec2::Y,
+        }
+    }
+}
+
+/// Slot a boid holds in a `Formation`, at `offset` from its anchor heading
+/// up the y axis.
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct FormationSlot {
+    pub formation: Entity,
+    pub offset: Vec2,
+}
+
+/// Where the anchor of a formation is and how it moves.
+fn anchor_motion(
+    anchor: Entity,
+    transforms: &Query<&Transform>,
+    physics: &Query<&Physics>,
+    motions: &Query<&TargetMotion>,
+) -> Option<(Vec3, Vec3)> {
+    let position = transforms.get(anchor).ok()?.translation;
+    let velocity = physics
+        .get(anchor)
+        .map(|physics| physics.velocity)
+        .or_else(|_| motions.get(anchor).map(|motion| motion.velocity))
+        .unwrap_or(Vec3::ZERO);
+    Some((position, velocity))
+}
+
+/// Hands out the slots of every formation whose shape or members changed,
+/// each slot in turn to the nearest boid left, and drops formations whose
+/// anchor is gone.
+pub(crate) fn assign_slots(
+    mut commands: Commands,
+    mut formations: Query<(Entity, &mut Formation)>,
+    boids: Query<(Entity, &Transform, Option<&FlockId>), With<Boid>>,
+    slots: Query<&FormationSlot>,
+    transforms: Query<&Transform>,
+    physics: Query<&Physics>,
+    motions: Query<&TargetMotion>,
+) {
+    for (entity, mut formation) in formations.iter_mut() {
+        let Some((anchor, velocity)) =
+            anchor_motion(formation.anchor, &transforms, &physics, &motions)
+        else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        if let Some(heading) = velocity.truncate().try_normalize() {
+            formation.heading = heading;
+        }
+
+        let mut members: Vec<(Entity, Vec3)> = boids
+            .iter()
+            .filter(|(boid, _, flock)| {
+                *boid != formation.anchor && flock.copied().unwrap_or_default() == formation.flock
+            })
+            .map(|(boid, transform, _)| (boid, transform.translation))
+            .collect();
+        let mut ids: Vec<Entity> = members.iter().map(|(boid, _)| *boid).collect();
+        ids.sort();
+        if formation.shaped == Some(formation.shape) && ids == formation.members {
+            continue;
+        }
+
+        let offsets = formation.shape.offsets(members.len(), formation.spacing);
+        for offset in offsets {
+            let position = anchor.truncate() + turned(formation.heading, offset);
+            let nearest = members
+                .iter()
+                .enumerate()
+                .min_by(|(_, (_, a)), (_, (_, b))| {
+                    a.truncate()
+                        .distance_squared(position)
+                        .total_cmp(&b.truncate().distance_squared(position))
+                })
+                .map(|(index, _)| index);
+            let Some(nearest) = nearest else {
+                break;
+            };
+            let (boid, _) = members.swap_remove(nearest);
+            let assignment = FormationSlot {
+                formation: entity,
+                offset,
+            };
+            if slots.get(boid).ok() != Some(&assignment) {
+                commands.entity(boid).insert(assignment);
+            }
+        }
+        formation.members = ids;
+        formation.shaped = Some(formation.shape);
+    }
+
+    // boids whose formation is gone, or that left it
+    for (boid, _, _) in boids.iter() {
+        let Ok(slot) = slots.get(boid) else {
+            continue;
+        };
+        let member = formations
+            .get(slot.formation)
+            .is_ok_and(|(_, formation)| formation.members.binary_search(&boid).is_ok());
+        if !member {
+            commands.entity(boid).remove::<FormationSlot>();
+        }
+    }
+}
+
+/// `offset` of a slot turned from heading up the y axis to `heading`.
+fn turned(heading: Vec2, offset: Vec2) -> Vec2 {
+    Vec2::new(heading.y, -heading.x).rotate(offset)
+}
+
+/// Steers boids to their slot, moving along with the anchor.
+pub(crate) fn hold_formation(
+    config: Res<FlockConfig>,
+    formations: Query<&Formation>,
+    transforms: Query<&Transform>,
+    physics_query: Query<&Physics>,
+    motions: Query<&TargetMotion>,
+    mut boids: Query<(&Transform, &Physics, &FormationSlot, &mut SteeringForces)>,
+) {
+    for (transform, physics, slot, mut forces) in boids.iter_mut() {
+        let Ok(formation) = formations.get(slot.formation) else {
+            continue;
+        };
+        let Some((anchor, velocity)) =
+            anchor_motion(formation.anchor, &transforms, &physics_query, &motions)
+        else {
+            continue;
+        };
+        let target = anchor.truncate() + turned(formation.heading, slot.offset);
+        let offset = target - transform.translation.truncate();
+        let speed = physics.max_speed * (offset.length() / SLOWING_RADIUS).min(1.);
+        let desired = (velocity.truncate() + offset.normalize_or_zero() * speed)
+            .clamp_length_max(physics.max_speed);
+        forces.behavior("formation").add(
+            steer_towards(desired.extend(0.), physics, config.response_curve),
+            FORMATION_WEIGHT,
+        );
+    }
+}
+
+/// `O` gathers flock 0 into a V around its leader, or the main target
+/// without one, then cycles through the shapes and back to no formation.
+pub(crate) fn cycle_formation(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut formations: Query<(Entity, &mut Formation)>,
+    leaders: Query<(Entity, Option<&FlockId>), With<Leader>>,
+    main_target: Query<Entity, With<MainTarget>>,
+) {
+    if !keyboard.just_pressed(KeyCode::O) {
+        return;
+    }
+    if let Ok((entity, mut formation)) = formations.get_single_mut() {
+        match formation.shape.next() {
+            Some(shape) => formation.shape = shape,
+            None => commands.entity(entity).despawn_recursive(),
+        }
+        return;
+    }
+
+    let flock = FlockId::default();
+    let anchor = leaders
+        .iter()
+        .find(|(_, leader_flock)| leader_flock.copied().unwrap_or_default() == flock)
+        .map(|(leader, _)| leader)
+        .or_else(|| main_target.iter().next());
+    if let Some(anchor) = anchor {
+        commands.spawn(Formation::new(FormationShape::default(), anchor, flock));
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::{
+    boid::{Boid, Steering},
+    bounds::BoidDespawned,
+    emitter::Emitter,
+    flock::FlockConfig,
+    hud::Hud,
+    predator::Flee,
+    preset::Preset,
+    state::AppState,
+    stats::FlockStats,
+    target::{MainTarget, TargetReached},
+};
+
+/// Boids per second coming in from the edges when a capture game starts, and
+/// how many more each second after that.
+pub(crate) const START_RATE: f32 = 0.5;
+const RATE_RAMP: f32 = 0.05;
+
+/// Boids closer than this to the target run from it.
+const FLEE_RADIUS: f32 = 150.;
+
+/// Score of the capture game of `Preset::Capture`: boids flee the target,
+/// which the player steers with the mouse, and every boid it touches is
+/// captured. Boids come in from the edges faster and faster, and the game is
+/// over once there are `FlockConfig::max_boids` of them.
+#[derive(Resource, Default)]
+pub struct CaptureScore {
+    pub captured: u32,
+    // seconds played so far
+    pub survived: f32,
+    pub over: bool,
+}
+
+/// Run criterion of the capture game's systems.
+pub(crate) fn in_capture_game(preset: Option<Res<Preset>>) -> ShouldRun {
+    match preset.as_deref() {
+        Some(Preset::Capture) => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+/// Has new boids run from the target rather than seek it.
+pub(crate) fn scare_new_boids(
+    mut commands: Commands,
+    mut boids: Query<(Entity, &mut Steering), Added<Boid>>,
+) {
+    for (entity, mut steering) in boids.iter_mut() {
+        steering.max_seek_range = 0.;
+        commands.entity(entity).insert(Flee {
+            point: Vec3::ZERO,
+            radius: FLEE_RADIUS,
+        });
+    }
+}
+
+pub(crate) fn flee_target(
+    targets: Query<&Transform, With<MainTarget>>,
+    mut boids: Query<&mut Flee, With<Boid>>,
+) {
+    let Ok(target) = targets.get_single() else {
+        return;
+    };
+    for mut flee in boids.iter_mut() {
+        flee.point = target.translation;
+    }
+}
+
+/// Captures the boids reaching the target.
+pub(crate) fn capture_boids(
+    mut commands: Commands,
+    mut score: ResMut<CaptureScore>,
+    mut reached: EventReader<TargetReached>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    for TargetReached { entity, .. } in reached.iter() {
+        if score.over {
+            continue;
+        }
+        commands.entity(*entity).despawn_recursive();
+        despawned.send(BoidDespawned { entity: *entity });
+        score.captured += 1;
+    }
+}
+
+/// Counts the time played, spawns boids faster as it goes on and ends the
+/// game, pausing it, once the flock is full.
+pub(crate) fn ramp_up(
+    time: Res<Time>,
+    config: Res<FlockConfig>,
+    stats: Res<FlockStats>,
+    mut state: ResMut<State<AppState>>,
+    mut score: ResMut<CaptureScore>,
+    mut emitters: Query<&mut Emitter>,
+) {
+    if score.over || *state.current() != AppState::Running {
+        return;
+    }
+    score.survived += time.delta_seconds();
+    let rate = (START_RATE + RATE_RAMP * score.survived) / emitters.iter().len().max(1) as f32;
+    for mut emitter in emitters.iter_mut() {
+        emitter.rate = rate;
+    }
+
+    if stats.boid_count >= config.max_boids {
+        score.over = true;
+        let _ = state.set(AppState::Paused);
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Scoreboard;
+
+pub(crate) fn spawn_scoreboard(
+    mut commands: Commands,
+    hud: Res<Hud>,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(hud.font.as_str()),
+                    font_size: 28.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(8.),
+                    left: Val::Percent(45.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(Visibility::INVISIBLE)
+        .insert(Scoreboard);
+}
+
+/// Shows the score during capture games, hides it otherwise.
+pub(crate) fn update_scoreboard(
+    preset: Option<Res<Preset>>,
+    score: Res<CaptureScore>,
+    mut text: Query<(&mut Text, &mut Visibility), With<Scoreboard>>,
+) {
+    let Ok((mut text, mut visibility)) = text.get_single_mut() else {
+        return;
+    };
+    visibility.is_visible = preset.as_deref() == Some(&Preset::Capture);
+    if !visibility.is_visible {
+        return;
+    }
+
+    let survived = score.survived as u32;
+    text.sections[0].value = format!(
+        "{} captured, {}:{:02}",
+        score.captured,
+        survived / 60,
+        survived % 60
+    );
+    if score.over {
+        text.sections[0]
+            .value
+            .push_str("\noverrun! Escape for the menu");
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, BoidBuilder},
+    bounds::Bounds,
+    flock::{random_mass, random_velocity, FlockConfig},
+    preset::{LoadPreset, Preset},
+    render::{FlockView, RenderOrder},
+    rng::SimRng,
+    state::AppState,
+    target::{MainTarget, TargetMode, TargetPin},
+};
+
+/// Controls for playing with a gamepad instead of the mouse and keyboard.
+/// The left stick flies the main target at up to `target_speed` world units
+/// per second, which leaves it to the gamepad as `TargetMode::Scripted`
+/// until `Tab` hands it back. The south button (A, cross) spawns boids at the
+/// target, start pauses and resumes, and the bumpers switch to the previous
+/// or next preset.
+#[derive(Resource)]
+pub struct GamepadControls {
+    pub target_speed: f32,
+    // stick tilts below this are ignored, as drift
+    pub dead_zone: f32,
+}
+
+impl Default for GamepadControls {
+    fn default() -> Self {
+        Self {
+            target_speed: 400.,
+            dead_zone: 0.15,
+        }
+    }
+}
+
+/// Tilt of the left stick of any gamepad, the strongest one if several are
+/// pushed, `Vec2::ZERO` within the dead zone.
+fn left_stick(gamepads: &Gamepads, axes: &Axis<GamepadAxis>, dead_zone: f32) -> Vec2 {
+    gamepads
+        .iter()
+        .map(|gamepad| {
+            let axis = |kind| axes.get(GamepadAxis::new(gamepad, kind)).unwrap_or(0.);
+            Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )
+        })
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .filter(|stick| stick.length() > dead_zone)
+        .map_or(Vec2::ZERO, |stick| stick.clamp_length_max(1.))
+}
+
+fn just_pressed(
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+    kind: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, kind)))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn drive_target_with_stick(
+    time: Res<Time>,
+    controls: Res<GamepadControls>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    bounds: Res<Bounds>,
+    pin: Res<TargetPin>,
+    mut mode: ResMut<TargetMode>,
+    mut targets: Query<&mut Transform, With<MainTarget>>,
+) {
+    let stick = left_stick(&gamepads, &axes, controls.dead_zone);
+    if stick == Vec2::ZERO || pin.pinned {
+        return;
+    }
+    if *mode != TargetMode::Scripted {
+        *mode = TargetMode::Scripted;
+    }
+
+    for mut target in targets.iter_mut() {
+        let moved =
+            target.translation.truncate() + stick * controls.target_speed * time.delta_seconds();
+        let position = moved.clamp(bounds.min, bounds.max);
+        target.translation = position.extend(target.translation.z);
+    }
+}
+
+/// Buttons of the gamepad: spawning boids at the target, pausing and
+/// switching presets.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn press_gamepad_buttons(
+    mut commands: Commands,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    config: Res<FlockConfig>,
+    preset: Option<Res<Preset>>,
+    mut rng: ResMut<SimRng>,
+    mut state: ResMut<State<AppState>>,
+    mut load: EventWriter<LoadPreset>,
+    boids: Query<(), With<Boid>>,
+    targets: Query<&Transform, With<MainTarget>>,
+) {
+    // boids spawned by a press
+    const BURST: usize = 5;
+
+    let pressed = |kind| just_pressed(&gamepads, &buttons, kind);
+    if pressed(GamepadButtonType::South) {
+        if let Ok(target) = targets.get_single() {
+            let room = config.max_boids.saturating_sub(boids.iter().count());
+            for _ in 0..BURST.min(room) {
+                let boid = BoidBuilder::new()
+                    .config(&config)
+                    .view(&view)
+                    .at(target.translation.truncate().extend(order.boids))
+                    .velocity(random_velocity(&config, rng.as_mut()).extend(0.))
+                    .mass(random_mass(&config, rng.as_mut()));
+                commands.spawn(boid.build());
+            }
+        }
+    }
+
+    if pressed(GamepadButtonType::Start) {
+        let next = match state.current() {
+            AppState::Running => AppState::Paused,
+            AppState::Paused | AppState::MainMenu => AppState::Running,
+        };
+        let _ = state.set(next);
+    }
+
+    let step = match (
+        pressed(GamepadButtonType::LeftTrigger),
+        pressed(GamepadButtonType::RightTrigger),
+    ) {
+        (true, false) => Preset::ALL.len() - 1,
+        (false, true) => 1,
+        _ => return,
+    };
+    let current = preset.map_or(0, |preset| {
+        Preset::ALL
+            .iter()
+            .position(|other| *other == *preset)
+            .unwrap_or(0)
+    });
+    load.send(LoadPreset(
+        Preset::ALL[(current + step) % Preset::ALL.len()],
+    ));
+}
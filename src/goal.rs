@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::Boid,
+    bounds::BoidDespawned,
+    render::{FlockView, RenderOrder},
+    tick::SimTick,
+};
+
+/// Area absorbing the boids flying into it, centered on the entity's
+/// `Transform`. Each boid absorbed is despawned and reported with
+/// `BoidReachedGoal`, so a spawner on one side and a goal on the other
+/// measure how well a flock gets through whatever lies in between.
+#[derive(Component, Clone)]
+pub enum GoalZone {
+    Circle { radius: f32 },
+    Rect { half_size: Vec2 },
+}
+
+impl GoalZone {
+    /// Whether a point `offset` from the center of the zone is inside it.
+    pub fn contains(&self, offset: Vec2) -> bool {
+        match self {
+            GoalZone::Circle { radius } => offset.length() <= *radius,
+            GoalZone::Rect { half_size } => offset.abs().cmple(*half_size).all(),
+        }
+    }
+}
+
+/// Sent when a `GoalZone` absorbs a boid, along with `BoidDespawned`.
+pub struct BoidReachedGoal {
+    pub entity: Entity,
+    pub goal: Entity,
+    // simulated seconds since the boid spawned
+    pub travel_time: f32,
+}
+
+/// Simulation step a boid spawned on.
+#[derive(Component)]
+pub struct SpawnStep(pub u64);
+
+/// Spawns a goal zone drawn like the rest of the scene.
+pub fn spawn_goal_zone(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    zone: GoalZone,
+    position: Vec2,
+) -> Entity {
+    let mode = DrawMode::Outlined {
+        fill_mode: FillMode::color(Color::rgba(0.2, 0.9, 0.4, 0.2)),
+        outline_mode: StrokeMode::new(Color::rgb(0.2, 0.9, 0.4), 2.),
+    };
+    let transform = Transform::from_translation(position.extend(order.obstacles));
+    let shape = match &zone {
+        GoalZone::Circle { radius } => GeometryBuilder::build_as(
+            &shapes::Circle {
+                radius: *radius,
+                ..default()
+            },
+            mode,
+            transform,
+        ),
+        GoalZone::Rect { half_size } => GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: *half_size * 2.,
+                ..default()
+            },
+            mode,
+            transform,
+        ),
+    };
+
+    commands.spawn(shape).insert(view.layers).insert(zone).id()
+}
+
+/// Sets the `SpawnStep` of new boids, which they are spawned with rather
+/// than given, as they may be despawned in the step they are stamped.
+pub(crate) fn stamp_spawn_step(tick: Res<SimTick>, mut boids: Query<&mut SpawnStep, Added<Boid>>) {
+    for mut spawned in boids.iter_mut() {
+        spawned.0 = tick.steps();
+    }
+}
+
+pub(crate) fn absorb_boids(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    goals: Query<(Entity, &Transform, &GoalZone)>,
+    boids: Query<(Entity, &Transform, Option<&SpawnStep>), With<Boid>>,
+    mut despawned: EventWriter<BoidDespawned>,
+    mut reached: EventWriter<BoidReachedGoal>,
+) {
+    for (entity, transform, spawned) in boids.iter() {
+        let position = transform.translation.truncate();
+        let goal = goals
+            .iter()
+            .find(|(_, goal, zone)| zone.contains(position - goal.translation.truncate()));
+        let Some((goal, ..)) = goal else {
+            continue;
+        };
+
+        commands.entity(entity).despawn_recursive();
+        despawned.send(BoidDespawned { entity });
+        let steps = spawned.map_or(0, |spawned| tick.steps().saturating_sub(spawned.0));
+        reached.send(BoidReachedGoal {
+            entity,
+            goal,
+            travel_time: steps as f32 * tick.step(),
+        });
+    }
+}
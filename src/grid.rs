@@ -0,0 +1,126 @@
+use bevy::{math::Rect, prelude::*, utils::HashMap};
+
+use crate::{flock::FlockId, neighbors::NeighborIndex, species::Species};
+
+/// Snapshot of a boid stored in a `NeighborIndex`.
+#[derive(Clone, Copy)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub species: Species,
+    pub flock: FlockId,
+}
+
+/// Boids bucketed into square cells by position, so neighbor lookups only
+/// visit the cells around a boid instead of the whole flock.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<GridEntry>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(100.)
+    }
+}
+
+/// Smallest cell size, so a zero or negative one cannot divide by zero.
+const MIN_CELL_SIZE: f32 = 1.;
+
+impl SpatialGrid {
+    /// Empty grid of cells `cell_size` wide, or `MIN_CELL_SIZE` if smaller.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(MIN_CELL_SIZE),
+            cells: HashMap::default(),
+        }
+    }
+
+    fn cell(&self, position: Vec3) -> IVec2 {
+        (position.truncate() / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn insert(&mut self, entry: GridEntry) {
+        let cell = self.cell(entry.position);
+        self.cells.entry(cell).or_default().push(entry);
+    }
+}
+
+impl NeighborIndex for SpatialGrid {
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    fn rebuild(&mut self, entries: Vec<GridEntry>, radius: f32) {
+        // one cell per neighborhood keeps lookups to the surrounding cells
+        self.cell_size = radius.max(MIN_CELL_SIZE);
+        self.cells.clear();
+        for entry in entries {
+            self.insert(entry);
+        }
+    }
+
+    fn neighbors_within(
+        &self,
+        position: Vec3,
+        radius: f32,
+    ) -> Box<dyn Iterator<Item = &GridEntry> + '_> {
+        let min = self.cell(position - Vec3::splat(radius));
+        let max = self.cell(position + Vec3::splat(radius));
+        Box::new(
+            (min.y..=max.y)
+                .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .filter(move |entry| entry.position.distance(position) < radius),
+        )
+    }
+
+    fn outlines(&self) -> Vec<Rect> {
+        self.cells
+            .keys()
+            .map(|cell| {
+                let min = cell.as_vec2() * self.cell_size;
+                Rect::from_corners(min, min + Vec2::splat(self.cell_size))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: u32, position: Vec3) -> GridEntry {
+        GridEntry {
+            entity: Entity::from_raw(index),
+            position,
+            velocity: Vec3::ZERO,
+            species: Species::default(),
+            flock: FlockId::default(),
+        }
+    }
+
+    #[test]
+    fn zero_cell_size_is_clamped() {
+        for cell_size in [0., -5., f32::NAN] {
+            let mut grid = SpatialGrid::new(cell_size);
+            grid.insert(entry(0, Vec3::new(3., 4., 0.)));
+            grid.insert(entry(1, Vec3::new(30., 0., 0.)));
+            let near: Vec<_> = grid
+                .neighbors_within(Vec3::ZERO, 10.)
+                .map(|entry| entry.entity)
+                .collect();
+            assert_eq!(near, vec![Entity::from_raw(0)]);
+        }
+    }
+
+    #[test]
+    fn rebuild_with_zero_radius_keeps_a_cell_size() {
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(vec![entry(0, Vec3::new(0.5, 0.5, 0.))], 0.);
+        assert_eq!(grid.neighbors_within(Vec3::ZERO, 1.).count(), 1);
+        assert!(grid.outlines().iter().all(|rect| rect.width() > 0.));
+    }
+}
@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::Boid,
+    bounds::Bounds,
+    render::{FlockView, RenderOrder},
+    tick::SimTick,
+};
+
+/// What the heatmap shows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum HeatmapMode {
+    /// Visits over the whole run
+    #[default]
+    Visits,
+    /// Boids in each cell right now, smoothed over a fraction of a second
+    Density,
+}
+
+/// Counts how often boids occupied each cell over the whole run, revealing
+/// long-term traffic patterns such as channels between obstacles. It also
+/// follows the current density of each cell, where clusters and lanes
+/// forming right now show up.
+#[derive(Resource)]
+pub struct VisitHeatmap {
+    origin: Vec2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    counts: Vec<u32>,
+    density: Vec<f32>,
+    pub visible: bool,
+    pub mode: HeatmapMode,
+}
+
+impl VisitHeatmap {
+    fn new(bounds: &Bounds, cell_size: f32) -> Self {
+        let cell_size = cell_size.max(1.);
+        let size = bounds.max - bounds.min;
+        let columns = (size.x / cell_size).ceil().max(1.) as usize;
+        let rows = (size.y / cell_size).ceil().max(1.) as usize;
+        Self {
+            origin: bounds.min,
+            cell_size,
+            columns,
+            rows,
+            counts: vec![0; columns * rows],
+            density: vec![0.; columns * rows],
+            visible: false,
+            mode: HeatmapMode::default(),
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> Option<usize> {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        if cell.x < 0. || cell.y < 0. {
+            return None;
+        }
+        let (column, row) = (cell.x as usize, cell.y as usize);
+        (column < self.columns && row < self.rows).then_some(row * self.columns + column)
+    }
+
+    fn cell_center(&self, index: usize) -> Vec2 {
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+        self.origin + (Vec2::new(column, row) + 0.5) * self.cell_size
+    }
+
+    /// Forgets the visits so far.
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+
+    /// Heat of each cell in the current mode, from 0 to 1 for the busiest.
+    fn heat(&self) -> Vec<f32> {
+        let values: Vec<f32> = match self.mode {
+            HeatmapMode::Visits => self.counts.iter().map(|&count| count as f32).collect(),
+            HeatmapMode::Density => self.density.clone(),
+        };
+        let busiest = values.iter().copied().fold(0., f32::max).max(1.);
+        values.into_iter().map(|value| value / busiest).collect()
+    }
+}
+
+/// Cell of the `VisitHeatmap` drawn on screen.
+#[derive(Component)]
+pub(crate) struct HeatmapCell(usize);
+
+pub(crate) fn setup_visit_heatmap(
+    mut commands: Commands,
+    bounds: Res<Bounds>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    let heatmap = VisitHeatmap::new(&bounds, 40.);
+    let cell = shapes::Rectangle {
+        extents: Vec2::splat(heatmap.cell_size),
+        ..default()
+    };
+    for index in 0..heatmap.counts.len() {
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &cell,
+                DrawMode::Fill(FillMode::color(Color::NONE)),
+                Transform::from_translation(heatmap.cell_center(index).extend(order.background)),
+            ))
+            .insert(Visibility::INVISIBLE)
+            .insert(view.layers)
+            .insert(HeatmapCell(index));
+    }
+    commands.insert_resource(heatmap);
+}
+
+pub(crate) fn accumulate_visits(
+    tick: Res<SimTick>,
+    mut heatmap: ResMut<VisitHeatmap>,
+    query: Query<&Transform, With<Boid>>,
+) {
+    // how quickly the density catches up with the boids, per second
+    const DENSITY_SMOOTHING: f32 = 4.;
+
+    let mut present = vec![0u32; heatmap.density.len()];
+    for transform in query.iter() {
+        if let Some(cell) = heatmap.cell(transform.translation.truncate()) {
+            heatmap.counts[cell] += 1;
+            present[cell] += 1;
+        }
+    }
+    let blend = 1. - (-DENSITY_SMOOTHING * tick.step()).exp();
+    for (density, count) in heatmap.density.iter_mut().zip(present) {
+        *density += (count as f32 - *density) * blend;
+    }
+}
+
+pub(crate) fn toggle_visit_heatmap(
+    keyboard: Res<Input<KeyCode>>,
+    mut heatmap: ResMut<VisitHeatmap>,
+) {
+    if !keyboard.just_pressed(KeyCode::H) {
+        return;
+    }
+
+    if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        heatmap.clear();
+        return;
+    }
+    // off, then visits, then density, then off again
+    match (heatmap.visible, heatmap.mode) {
+        (false, _) => {
+            heatmap.visible = true;
+            heatmap.mode = HeatmapMode::Visits;
+        }
+        (true, HeatmapMode::Visits) => heatmap.mode = HeatmapMode::Density,
+        (true, HeatmapMode::Density) => heatmap.visible = false,
+    }
+}
+
+pub(crate) fn draw_visit_heatmap(
+    heatmap: Res<VisitHeatmap>,
+    mut cells: Query<(&HeatmapCell, &mut DrawMode, &mut Visibility)>,
+) {
+    if !heatmap.visible {
+        if heatmap.is_changed() {
+            for (_, _, mut visibility) in cells.iter_mut() {
+                visibility.is_visible = false;
+            }
+        }
+        return;
+    }
+
+    let heat = heatmap.heat();
+    for (cell, mut mode, mut visibility) in cells.iter_mut() {
+        let heat = heat[cell.0];
+        let color = match heatmap.mode {
+            HeatmapMode::Visits => Color::rgba(1., 0.4, 0., 0.6 * heat),
+            HeatmapMode::Density => Color::rgba(0.1, 0.7, 1., 0.6 * heat),
+        };
+        *mode = DrawMode::Fill(FillMode::color(color));
+        // faded out cells are not worth drawing
+        visibility.is_visible = heat > 0.01;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visits_fall_in_the_confined_region() {
+        let mut world = World::new();
+        world.insert_resource(SimTick::default());
+        let bounds = Bounds {
+            min: Vec2::splat(-200.),
+            max: Vec2::splat(200.),
+        };
+        world.insert_resource(VisitHeatmap::new(&bounds, 40.));
+        // boids kept within the cells from (40, -40) to (120, 0)
+        let region = |position: Vec2| {
+            (40. ..120.).contains(&position.x) && (-40. ..0.).contains(&position.y)
+        };
+        for i in 0..10 {
+            let position = Vec2::new(42. + i as f32 * 7.5, -35. + i as f32 * 3.);
+            assert!(region(position));
+            world.spawn((Boid, Transform::from_translation(position.extend(0.))));
+        }
+
+        let mut stage = SystemStage::single(accumulate_visits);
+        for _ in 0..5 {
+            stage.run(&mut world);
+        }
+
+        let heatmap = world.resource::<VisitHeatmap>();
+        assert_eq!(heatmap.counts.iter().sum::<u32>(), 50);
+        for (index, &count) in heatmap.counts.iter().enumerate() {
+            let center = heatmap.cell_center(index);
+            assert_eq!(count > 0, region(center), "cell at {center}");
+        }
+    }
+
+    #[test]
+    fn degenerate_grids_still_have_a_cell() {
+        let bounds = Bounds {
+            min: Vec2::splat(-100.),
+            max: Vec2::splat(100.),
+        };
+        let heatmap = VisitHeatmap::new(&bounds, 0.);
+        assert!(heatmap.cell_size >= 1.);
+        assert!(heatmap.cell(Vec2::ZERO).is_some());
+
+        let heatmap = VisitHeatmap::new(&Bounds::default(), 40.);
+        assert_eq!(heatmap.counts.len(), 1);
+        assert_eq!(heatmap.cell(Vec2::ZERO), Some(0));
+    }
+}
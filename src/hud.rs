@@ -0,0 +1,108 @@
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    ecs::entity::Entities,
+    prelude::*,
+};
+
+use crate::{
+    flock::FlockConfig,
+    stats::FlockStats,
+    tick::{SimTick, SimulationControl},
+};
+
+/// Text in the top left corner, toggled with `F3`, showing the frame rate,
+/// the entity count, the flocking weights and the `FlockStats`.
+#[derive(Resource)]
+pub struct Hud {
+    pub visible: bool,
+    // path of the font, relative to the assets folder
+    pub font: String,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            font: "fonts/Hack-Regular.ttf".to_string(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct HudText;
+
+pub(crate) fn spawn_hud(mut commands: Commands, hud: Res<Hud>, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(hud.font.as_str()),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(8.),
+                    left: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(HudText);
+}
+
+pub(crate) fn toggle_hud(keyboard: Res<Input<KeyCode>>, mut hud: ResMut<Hud>) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        hud.visible = !hud.visible;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_hud(
+    hud: Res<Hud>,
+    diagnostics: Res<Diagnostics>,
+    entities: &Entities,
+    config: Res<FlockConfig>,
+    tick: Res<SimTick>,
+    control: Res<SimulationControl>,
+    stats: Res<FlockStats>,
+    mut text: Query<(&mut Text, &mut Visibility), With<HudText>>,
+) {
+    let Ok((mut text, mut visibility)) = text.get_single_mut() else {
+        return;
+    };
+    visibility.is_visible = hud.visible;
+    if !hud.visible {
+        return;
+    }
+
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .map_or("-".to_string(), |fps| format!("{fps:.0}"));
+    let state = if control.paused { ", paused" } else { "" };
+    let nearest_neighbor = stats
+        .nearest_neighbor_distance
+        .map_or("-".to_string(), |distance| format!("{distance:.1}"));
+    text.sections[0].value = format!(
+        "{fps} fps, {} entities, {} boids\n\
+         step {}, {}x{state}\n\
+         separation {:.2}, alignment {:.2}, cohesion {:.2}\n\
+         speed {:.1}, polarization {:.2}\n\
+         spread {:.1}, nearest neighbor {nearest_neighbor}",
+        entities.len(),
+        stats.boid_count,
+        tick.steps(),
+        control.time_scale,
+        config.separation_weight,
+        config.alignment_weight,
+        config.cohesion_weight,
+        stats.average_speed,
+        stats.polarization,
+        stats.spread,
+    );
+}
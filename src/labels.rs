@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    energy::Energy,
+    hud::Hud,
+    render::{CursorPosition, FlockView, MainCamera, Overlay, RenderOrder},
+    steering::SteeringForces,
+};
+
+/// Text above the `count` boids nearest the cursor, toggled with `F7`: the
+/// boid's entity, its speed, the steering behavior pulling at it the most
+/// and, for boids which tire, the energy left. Only the nearest few are
+/// labeled so the text stays readable in a dense flock.
+#[derive(Resource)]
+pub struct BoidLabels {
+    pub visible: bool,
+    pub count: usize,
+}
+
+impl Default for BoidLabels {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            count: 8,
+        }
+    }
+}
+
+/// One of the texts drawn over the boids, handed to a different boid each
+/// frame.
+#[derive(Component)]
+pub(crate) struct BoidLabel;
+
+pub(crate) fn toggle_boid_labels(keyboard: Res<Input<KeyCode>>, mut labels: ResMut<BoidLabels>) {
+    if keyboard.just_pressed(KeyCode::F7) {
+        labels.visible = !labels.visible;
+    }
+}
+
+/// Moves a label above each of the boids nearest the cursor, upright and of
+/// the same size on screen however the main camera turns or zooms, spawning
+/// more labels when `BoidLabels::count` asks for them.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_boid_labels(
+    mut commands: Commands,
+    settings: Res<BoidLabels>,
+    cursor: Res<CursorPosition>,
+    hud: Res<Hud>,
+    asset_server: Res<AssetServer>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    boids: Query<(Entity, &Transform, &Physics, &SteeringForces), With<Boid>>,
+    energies: Query<&Energy>,
+    mut labels: Query<(Entity, &mut Text, &mut Visibility), With<BoidLabel>>,
+    // looked up for the labels only
+    mut transforms: Query<&mut Transform, (Without<Boid>, Without<MainCamera>)>,
+) {
+    // world units between a boid and the bottom of its label
+    const OFFSET: f32 = 14.;
+
+    let spawned = labels.iter().len();
+    if settings.visible && spawned < settings.count {
+        let style = TextStyle {
+            font: asset_server.load(hud.font.as_str()),
+            font_size: 12.,
+            color: Color::WHITE,
+        };
+        for _ in spawned..settings.count {
+            commands
+                .spawn(Text2dBundle {
+                    text: Text::from_section("", style.clone())
+                        .with_alignment(TextAlignment::BOTTOM_CENTER),
+                    transform: Transform::from_xyz(0., 0., order.overlays),
+                    visibility: Visibility::INVISIBLE,
+                    ..default()
+                })
+                .insert(view.layers)
+                .insert(Overlay)
+                .insert(BoidLabel);
+        }
+    }
+
+    let (Some(position), Ok((camera_transform, projection))) = (cursor.0, camera.get_single())
+    else {
+        for (.., mut visibility) in labels.iter_mut() {
+            visibility.is_visible = false;
+        }
+        return;
+    };
+    let mut nearest: Vec<_> = match settings.visible {
+        true => boids
+            .iter()
+            .map(|boid @ (_, transform, ..)| {
+                let distance = transform.translation.truncate().distance_squared(position);
+                (distance, boid)
+            })
+            .collect(),
+        false => Vec::new(),
+    };
+    nearest.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    nearest.truncate(settings.count);
+
+    let up = camera_transform.rotation * Vec3::Y;
+    let mut boids = nearest.into_iter();
+    for (label, mut text, mut visibility) in labels.iter_mut() {
+        let Ok(mut transform) = transforms.get_mut(label) else {
+            continue;
+        };
+        let Some((_, (entity, boid, physics, forces))) = boids.next() else {
+            visibility.is_visible = false;
+            continue;
+        };
+        visibility.is_visible = true;
+        let anchor = boid.translation + up * OFFSET;
+        transform.translation = anchor.truncate().extend(transform.translation.z);
+        transform.rotation = camera_transform.rotation;
+        transform.scale = Vec3::new(projection.scale, projection.scale, 1.);
+
+        let mut value = format!(
+            "{:?} {:.0} u/s\n{}",
+            entity,
+            physics.velocity.length(),
+            forces.dominant().unwrap_or("coasting"),
+        );
+        if let Ok(energy) = energies.get(entity) {
+            let left = energy.current / energy.max.max(f32::EPSILON);
+            value.push_str(&format!(" {:.0}% energy", left * 100.));
+        }
+        // setting the same text again would lay it out again
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
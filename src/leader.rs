@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics, Selected, Steering},
+    flock::{FlockConfig, FlockId},
+    formation::FormationSlot,
+    render::FlockView,
+    sim,
+    steering::{steer_towards, steering_params, SteeringForces},
+};
+
+/// Boid the rest of its flock follows, in a trail behind it, instead of
+/// heading for the target themselves. One per flock.
+#[derive(Component, Clone, Copy)]
+pub struct Leader;
+
+/// Follower of the `Leader` of its flock.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct Following(pub Entity);
+
+/// How boids follow their leader: they aim `distance` behind it, slowing
+/// down within `slowing_radius` of that spot, and get out of its way when
+/// closer than `clearance` to the path it will take in the next `distance`.
+/// With `auto`, flocks without a leader get the boid nearest their target.
+#[derive(Resource)]
+pub struct LeaderSettings {
+    pub auto: bool,
+    pub distance: f32,
+    pub slowing_radius: f32,
+    pub clearance: f32,
+    pub weight: f32,
+}
+
+impl Default for LeaderSettings {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            distance: 60.,
+            slowing_radius: 100.,
+            clearance: 30.,
+            weight: 1.5,
+        }
+    }
+}
+
+/// Ring drawn around leaders.
+#[derive(Component)]
+pub(crate) struct LeaderMarker;
+
+/// Has the boids of every flock with a leader follow it, appointing leaders
+/// first if `LeaderSettings::auto` is on. Flocks with several leaders keep
+/// the oldest one.
+pub(crate) fn designate_leaders(
+    mut commands: Commands,
+    settings: Res<LeaderSettings>,
+    leaders: Query<(Entity, Option<&FlockId>), With<Leader>>,
+    boids: Query<(Entity, &Transform, &Steering, Option<&FlockId>), With<Boid>>,
+    following: Query<&Following>,
+) {
+    let mut flock_leaders: BTreeMap<FlockId, Entity> = BTreeMap::new();
+    for (entity, flock) in leaders.iter() {
+        let leader = flock_leaders
+            .entry(flock.copied().unwrap_or_default())
+            .or_insert(entity);
+        *leader = (*leader).min(entity);
+    }
+
+    if settings.auto {
+        let mut candidates: BTreeMap<FlockId, (Entity, f32)> = BTreeMap::new();
+        for (entity, transform, steering, flock) in boids.iter() {
+            let flock = flock.copied().unwrap_or_default();
+            if flock_leaders.contains_key(&flock) {
+                continue;
+            }
+            let distance = transform.translation.distance(steering.target);
+            let candidate = candidates.entry(flock).or_insert((entity, distance));
+            if distance < candidate.1 {
+                *candidate = (entity, distance);
+            }
+        }
+        for (flock, (entity, _)) in candidates {
+            commands.entity(entity).insert(Leader);
+            flock_leaders.insert(flock, entity);
+        }
+    }
+
+    for (entity, _, _, flock) in boids.iter() {
+        let leader = flock_leaders
+            .get(&flock.copied().unwrap_or_default())
+            .copied()
+            .filter(|leader| *leader != entity)
+            .map(Following);
+        match (leader, following.get(entity).ok().copied()) {
+            (Some(leader), current) if current != Some(leader) => {
+                commands.entity(entity).insert(leader);
+            }
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<Following>();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Steers followers to the spot behind their leader, matching its velocity,
+/// and out of the way ahead of it. Followers in a formation keep to their
+/// slot instead.
+pub(crate) fn follow_leader(
+    config: Res<FlockConfig>,
+    settings: Res<LeaderSettings>,
+    leaders: Query<(&Transform, &Physics)>,
+    mut followers: Query<
+        (&Transform, &Physics, &Following, &mut SteeringForces),
+        Without<FormationSlot>,
+    >,
+) {
+    for (transform, physics, Following(leader), mut forces) in followers.iter_mut() {
+        let Ok((leader_transform, leader_physics)) = leaders.get(*leader) else {
+            continue;
+        };
+        let position = transform.translation;
+        let leader_position = leader_transform.translation;
+        let heading = leader_physics
+            .velocity
+            .try_normalize()
+            .unwrap_or(leader_transform.rotation * Vec3::Y);
+
+        let behind = leader_position - heading * settings.distance;
+        let offset = behind - position;
+        let catch_up = physics.max_speed * (offset.length() / settings.slowing_radius).min(1.);
+        let desired = (leader_physics.velocity + offset.normalize_or_zero() * catch_up)
+            .clamp_length_max(physics.max_speed);
+        forces.behavior("follow leader").add(
+            steer_towards(desired, physics, config.response_curve),
+            settings.weight,
+        );
+
+        // nearest point of the leader's path ahead
+        let along = (position - leader_position)
+            .dot(heading)
+            .clamp(0., settings.distance);
+        let on_path = leader_position + heading * along;
+        if along > 0. && position.distance(on_path) < settings.clearance {
+            let params = steering_params(physics, config.response_curve);
+            let force = sim::steer_flee(position, physics.velocity, on_path, &params);
+            forces.behavior("make way").add(force, settings.weight);
+        }
+    }
+}
+
+/// `L` makes the selected boid the leader of its flock, or demotes it if it
+/// already leads. `Shift+L` switches the automatic leaders on and off.
+pub(crate) fn choose_leader(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut settings: ResMut<LeaderSettings>,
+    selected: Query<(Entity, Option<&FlockId>), With<Selected>>,
+    leaders: Query<(Entity, Option<&FlockId>), With<Leader>>,
+) {
+    if !keyboard.just_pressed(KeyCode::L) {
+        return;
+    }
+    if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        settings.auto = !settings.auto;
+        return;
+    }
+    let Ok((entity, flock)) = selected.get_single() else {
+        return;
+    };
+    if leaders.contains(entity) {
+        commands.entity(entity).remove::<Leader>();
+        return;
+    }
+    for (other, other_flock) in leaders.iter() {
+        if other_flock == flock {
+            commands.entity(other).remove::<Leader>();
+        }
+    }
+    commands.entity(entity).insert(Leader);
+}
+
+/// Rings new leaders, and takes the ring off boids that stop leading.
+pub(crate) fn mark_leaders(
+    mut commands: Commands,
+    config: Res<FlockConfig>,
+    view: Res<FlockView>,
+    new_leaders: Query<Entity, Added<Leader>>,
+    demoted: RemovedComponents<Leader>,
+    markers: Query<(Entity, &Parent), With<LeaderMarker>>,
+) {
+    let demoted: Vec<Entity> = demoted.iter().collect();
+    for (marker, parent) in markers.iter() {
+        if demoted.contains(&parent.get()) {
+            commands.entity(marker).despawn_recursive();
+        }
+    }
+
+    let radius = config.boid_length * 0.6;
+    for leader in new_leaders.iter() {
+        commands.entity(leader).with_children(|parent| {
+            parent
+                .spawn(GeometryBuilder::build_as(
+                    &shapes::Circle {
+                        radius,
+                        ..default()
+                    },
+                    DrawMode::Stroke(StrokeMode::new(Color::GOLD, 2.)),
+                    // just under the boid
+                    Transform::from_xyz(0., 0., -0.1),
+                ))
+                .insert(view.layers)
+                .insert(LeaderMarker);
+        });
+    }
+}
@@ -0,0 +1,758 @@
+//! Boid flocking simulation for bevy, drawn with lyon.
+//!
+//! Add [`BoidPlugin`] to an app to get a flock chasing a cursor-driven target,
+//! or [`SimulationPlugin`] to run the flock without drawing it.
+
+use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
+use bevy_prototype_lyon::prelude::*;
+
+mod attractor;
+#[cfg(feature = "audio")]
+mod audio;
+mod behavior;
+mod boid;
+mod bounds;
+mod camera;
+mod coloring;
+mod context_steering;
+mod debug;
+mod diagnostics;
+mod emitter;
+mod energy;
+mod flock;
+#[cfg(feature = "3d")]
+mod flock3d;
+mod flocking;
+mod flow_field;
+mod food;
+mod formation;
+mod game;
+mod gamepad;
+mod goal;
+mod grid;
+mod heatmap;
+mod hud;
+mod labels;
+mod leader;
+mod lod;
+mod map;
+mod minimap;
+mod navigation;
+mod neighbors;
+mod obstacle;
+mod orca;
+#[cfg(feature = "egui")]
+mod panel;
+mod path;
+mod perception;
+mod perching;
+mod pheromone;
+mod physics;
+mod pipeline;
+mod playfield;
+mod population;
+mod predator;
+mod preset;
+mod quadtree;
+mod queueing;
+mod recorder;
+mod render;
+mod replay;
+mod rewind;
+mod rng;
+mod run;
+// browsers have no file system to save to
+#[cfg(not(target_arch = "wasm32"))]
+mod screenshot;
+#[cfg(feature = "scripting")]
+mod script;
+mod selection;
+pub mod sim;
+mod sim_config;
+mod snapshot;
+mod species;
+mod state;
+mod stats;
+mod steering;
+mod target;
+mod tick;
+mod trail;
+mod tuning;
+mod turbulence;
+mod wind;
+
+pub use attractor::{spawn_attractor, Attractor, CursorStir, Stir};
+#[cfg(feature = "audio")]
+pub use audio::SoundEffects;
+pub use behavior::{AddSteeringBehavior, BoidState, SteeringBehavior, WorldSnapshot};
+pub use boid::{
+    boids_with_tag, spawn_boid_at, BodySize, Boid, BoidBuilder, BoidBundle, BoidId, BoidShape,
+    BoidSpawned, Physics, Selected, Steering, Tag,
+};
+pub use bounds::{BoidDespawned, BoundaryMode, Bounds};
+pub use camera::{CameraControls, CameraFollow, DetailCamera, SplitScreen};
+pub use coloring::BoidColoring;
+pub use context_steering::ContextMaps;
+pub use debug::{DebugDraw, GizmoOverlay, IndexOverlay};
+pub use diagnostics::BoidDiagnosticsPlugin;
+pub use emitter::{BoidSpawner, Edge, Emitter, Lifetime, SpawnedBy};
+pub use energy::{BoidExhausted, Energy, Exhausted};
+pub use flock::{reset_flock, FlockConfig, FlockId, FlockSettings, Flocks, ResetFlock};
+#[cfg(feature = "3d")]
+pub use flock3d::{Boid3d, Flock3dPlugin, Volume};
+pub use flocking::BehaviorWeights;
+pub use flow_field::{FlowField, FollowFlowField};
+pub use food::{spawn_food, Food, FoodEaten, FoodSettings};
+pub use formation::{Formation, FormationShape, FormationSlot};
+pub use game::CaptureScore;
+pub use gamepad::GamepadControls;
+pub use goal::{spawn_goal_zone, BoidReachedGoal, GoalZone, SpawnStep};
+pub use grid::{GridEntry, SpatialGrid};
+pub use heatmap::{HeatmapMode, VisitHeatmap};
+pub use hud::Hud;
+pub use labels::BoidLabels;
+pub use leader::{Following, Leader, LeaderSettings};
+pub use lod::{Lod, LodSettings};
+pub use map::{MapWall, ObstacleMap, ObstacleMapFile};
+pub use minimap::Minimap;
+pub use navigation::Navigation;
+pub use neighbors::{NeighborBackend, NeighborCache, NeighborIndex, SpatialIndex};
+pub use obstacle::{spawn_obstacle, Obstacle};
+pub use orca::ReciprocalAvoidance;
+pub use path::{FollowPath, PathMode};
+pub use perception::Perception;
+pub use perching::{PerchSettings, Perching};
+pub use pheromone::{Pheromones, ScentGrid};
+pub use physics::{apply_force, Integrator, Substeps};
+pub use pipeline::{BoidPipeline, BoidSet};
+pub use playfield::Playfield;
+pub use population::{PopulationHistory, Reproduction, Traits};
+pub use predator::{
+    flee_from, spawn_predator, Evade, Flee, Predator, PredatorCaughtPrey, CATCH_DISTANCE,
+};
+pub use preset::{load_preset, LoadPreset, Preset};
+pub use quadtree::Quadtree;
+pub use recorder::MetricsRecorder;
+pub use render::{
+    BoidRendering, Breathing, CursorPosition, FlockView, MainCamera, Overlay, RenderOrder,
+};
+pub use replay::{FlightRecorder, Replay};
+pub use rewind::RewindBuffer;
+pub use rng::{SimRng, SimSeed};
+pub use run::RunDuration;
+#[cfg(not(target_arch = "wasm32"))]
+pub use screenshot::ScreenshotDir;
+#[cfg(feature = "scripting")]
+pub use script::{SteeringScript, SteeringScriptFile};
+pub use selection::Inspection;
+pub use sim::average_heading;
+pub use sim_config::{SimConfig, SimConfigFile, SimFlock};
+pub use snapshot::{SavedBoid, SavedObstacle, Snapshot, SnapshotFile};
+pub use species::{Reaction, Species, SpeciesRelations};
+pub use state::AppState;
+pub use stats::FlockStats;
+pub use steering::{
+    accumulate_steering, steer_towards, Arbitration, Arrive, Pursue, ResponseCurve,
+    SteeringContribution, SteeringForces, SteeringTrace, SteeringTracing, Wander,
+};
+pub use target::{
+    Autopilot, MainTarget, Target, TargetMode, TargetMotion, TargetPin, TargetReached,
+    TARGET_REACHED_DISTANCE,
+};
+pub use tick::{FixedUpdateStage, InterpolateStage, SimTick, SimulationControl};
+pub use trail::{Trail, TrailSettings};
+pub use tuning::{TunedWeight, Tuning};
+pub use turbulence::Turbulence;
+pub use wind::{Wind, WindGusts};
+
+/// Runs the simulation alone: flock, target, steering and physics, without
+/// drawing or input. Enough for headless runs, on top of `MinimalPlugins`.
+///
+/// Physics and steering run in `FixedUpdateStage`, at the rate set by
+/// `SimTick`, and boid transforms are interpolated for drawing in
+/// `InterpolateStage`; both stages run before `CoreStage::Update`. The
+/// `BoidSet` labels order other systems around them, `BoidPipeline` turns
+/// parts of them off.
+///
+/// Resources inserted before adding the plugin (`FlockConfig`, `Wind`...) are
+/// kept, the others start from their defaults.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wind>()
+            .init_resource::<TargetPin>()
+            .init_resource::<TargetMode>()
+            .init_resource::<Autopilot>()
+            .init_resource::<FlockView>()
+            .init_resource::<Bounds>()
+            .init_resource::<BoundaryMode>()
+            .init_resource::<Integrator>()
+            .init_resource::<Substeps>()
+            .init_resource::<BoidPipeline>()
+            .init_resource::<FlockConfig>()
+            .init_resource::<RenderOrder>()
+            .init_resource::<SpatialIndex>()
+            .init_resource::<NeighborBackend>()
+            .init_resource::<SimTick>()
+            .init_resource::<SimulationControl>()
+            .init_resource::<CursorPosition>()
+            .init_resource::<SpeciesRelations>()
+            .init_resource::<Flocks>()
+            .init_resource::<LodSettings>()
+            .init_resource::<FlockStats>()
+            .init_resource::<FoodSettings>()
+            .init_resource::<Reproduction>()
+            .init_resource::<PopulationHistory>()
+            .init_resource::<PerchSettings>()
+            .init_resource::<Turbulence>()
+            .init_resource::<ReciprocalAvoidance>()
+            .init_resource::<LeaderSettings>()
+            .init_resource::<Playfield>()
+            .init_resource::<Navigation>()
+            .init_resource::<Pheromones>()
+            .init_resource::<ScentGrid>()
+            .init_resource::<behavior::SteeringBehaviors>()
+            .init_resource::<navigation::NavGrid>()
+            .init_resource::<diagnostics::SteeringTimings>()
+            .init_resource::<SteeringTracing>()
+            .register_type::<Boid>()
+            .register_type::<Physics>()
+            .register_type::<BodySize>()
+            .register_type::<Energy>()
+            .register_type::<Traits>()
+            .register_type::<BehaviorWeights>()
+            .register_type::<Steering>()
+            .register_type::<FlockConfig>()
+            .register_type::<BoundaryMode>()
+            .register_type::<Integrator>()
+            .register_type::<Substeps>()
+            .register_type::<SimTick>()
+            .register_type::<SimulationControl>()
+            .register_type::<FlockStats>()
+            .add_event::<BoidSpawned>()
+            .add_event::<BoidDespawned>()
+            .add_event::<BoidExhausted>()
+            .add_event::<TargetReached>()
+            .add_event::<PredatorCaughtPrey>()
+            .add_event::<FoodEaten>()
+            .add_event::<BoidReachedGoal>()
+            .add_event::<ResetFlock>()
+            .add_event::<LoadPreset>()
+            .add_event::<SteeringTrace>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, rng::seed_rng)
+            .add_startup_system(target::spawn_target)
+            .add_startup_system(flock::spawn_flock)
+            // once the bounds fit the window
+            .add_startup_system_to_stage(StartupStage::PostStartup, preset::spawn_preset_scene)
+            .add_system(boid::assign_boid_ids)
+            .add_system(map::spawn_map_walls)
+            .add_system(boid::announce_new_boids)
+            .add_stage_before(
+                CoreStage::Update,
+                FixedUpdateStage,
+                SystemStage::parallel().with_run_criteria(tick::fixed_tick),
+            )
+            .add_stage_after(
+                FixedUpdateStage,
+                InterpolateStage,
+                SystemStage::single(tick::interpolate_transforms),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .with_system(tick::begin_tick)
+                    .with_system(
+                        replay::play_back
+                            .after(tick::begin_tick)
+                            .before(tick::end_tick),
+                    )
+                    .with_system(
+                        tick::end_tick
+                            .after(steering::accumulate_steering)
+                            .after(wind::apply_wind)
+                            .after(predator::chase_nearest_boid)
+                            .after(predator::catch_prey)
+                            .after(target::reach_target)
+                            .after(goal::absorb_boids)
+                            .after(bounds::despawn_out_of_bounds)
+                            .after(emitter::emit_boids)
+                            .after(emitter::run_spawners)
+                            .after(energy::spend_energy)
+                            .after(population::reproduce)
+                            .after(perching::perch)
+                            .after(orca::avoid_reciprocally),
+                    )
+                    .with_system(
+                        boid::grow_boids
+                            .after(energy::spend_energy)
+                            .before(tick::end_tick),
+                    )
+                    .with_system(stats::update_flock_stats.after(tick::end_tick))
+                    .with_system(pheromone::lay_scent.after(tick::end_tick))
+                    .with_system(recorder::record_metrics.after(stats::update_flock_stats))
+                    .with_system(population::record_population.after(stats::update_flock_stats))
+                    .with_system(replay::record_flight.after(tick::end_tick))
+                    .with_system(rewind::record_rewind_frame.after(tick::end_tick)),
+            )
+            // the rest of the simulation, left out while replaying
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .with_run_criteria(replay::simulating)
+                    .with_system(lod::schedule_lod.after(tick::begin_tick))
+                    .with_system(target::drive_target.after(tick::begin_tick))
+                    .with_system(target::track_target_motion.after(target::drive_target))
+                    .with_system(target::seek_target.after(target::track_target_motion))
+                    .with_system(wind::apply_wind.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        leader::designate_leaders
+                            .after(target::seek_target)
+                            .before(energy::spend_energy)
+                            .before(pipeline::DespawnBoids),
+                    )
+                    .with_system(
+                        formation::assign_slots
+                            .after(target::seek_target)
+                            .before(energy::spend_energy)
+                            .before(pipeline::DespawnBoids),
+                    )
+                    .with_system(predator::chase_nearest_boid.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        predator::catch_prey
+                            .label(pipeline::DespawnBoids)
+                            .after(bounds::wrap_or_bounce),
+                    )
+                    .with_system(
+                        target::reach_target
+                            .after(target::seek_target)
+                            .after(bounds::wrap_or_bounce)
+                            .before(energy::spend_energy)
+                            .before(pipeline::DespawnBoids),
+                    )
+                    .with_system(goal::stamp_spawn_step.after(tick::begin_tick))
+                    .with_system(
+                        goal::absorb_boids
+                            .label(pipeline::DespawnBoids)
+                            .after(bounds::wrap_or_bounce),
+                    )
+                    .with_system(
+                        bounds::despawn_out_of_bounds
+                            .label(pipeline::DespawnBoids)
+                            .after(bounds::wrap_or_bounce),
+                    )
+                    .with_system(
+                        perching::perch
+                            .after(steering::accumulate_steering)
+                            .after(wind::apply_wind),
+                    )
+                    .with_system(
+                        food::eat_food
+                            .after(bounds::wrap_or_bounce)
+                            .before(energy::spend_energy),
+                    )
+                    .with_system(
+                        energy::spend_energy
+                            .after(steering::accumulate_steering)
+                            .after(wind::apply_wind)
+                            .before(pipeline::DespawnBoids),
+                    )
+                    // systems drawing from `SimRng` run in a set order, so
+                    // seeded runs repeat
+                    .with_system(
+                        emitter::emit_boids
+                            .after(target::drive_target)
+                            .before(steering::wander),
+                    )
+                    .with_system(
+                        emitter::run_spawners
+                            .after(emitter::emit_boids)
+                            .before(steering::wander),
+                    )
+                    .with_system(
+                        food::drop_food
+                            .after(emitter::run_spawners)
+                            .before(steering::wander),
+                    )
+                    .with_system(
+                        emitter::expire_boids
+                            .label(pipeline::DespawnBoids)
+                            .before(tick::end_tick),
+                    )
+                    .with_system(
+                        population::reproduce
+                            .after(food::eat_food)
+                            .after(food::drop_food)
+                            .before(steering::wander),
+                    ),
+            )
+            // moving the boids by the forces
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Integrate)
+                    .with_run_criteria(pipeline::integrating)
+                    .with_system(physics::physics_system.after(lod::schedule_lod))
+                    .with_system(bounds::wrap_or_bounce.after(physics::physics_system)),
+            )
+            // what each boid knows of the others
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Perception)
+                    .after(BoidSet::Integrate)
+                    .with_run_criteria(pipeline::perceiving)
+                    .with_system(neighbors::rebuild_spatial_index.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        neighbors::refresh_neighbor_caches.after(neighbors::rebuild_spatial_index),
+                    )
+                    .with_system(navigation::build_nav_grid),
+            )
+            // forces the boids steer with
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(BoidSet::Steering)
+                    .after(BoidSet::Perception)
+                    .with_run_criteria(pipeline::steering)
+                    .with_system(
+                        steering::seek
+                            .after(bounds::wrap_or_bounce)
+                            .after(target::seek_target),
+                    )
+                    .with_system(flocking::flocking.after(neighbors::rebuild_spatial_index))
+                    .with_system(
+                        behavior::apply_steering_behaviors.after(neighbors::rebuild_spatial_index),
+                    )
+                    .with_system(
+                        steering::wander
+                            .after(bounds::wrap_or_bounce)
+                            .after(target::seek_target),
+                    )
+                    .with_system(
+                        leader::follow_leader
+                            .after(bounds::wrap_or_bounce)
+                            .after(leader::designate_leaders),
+                    )
+                    .with_system(
+                        formation::hold_formation
+                            .after(bounds::wrap_or_bounce)
+                            .after(formation::assign_slots),
+                    )
+                    .with_system(queueing::queue.after(neighbors::rebuild_spatial_index))
+                    .with_system(predator::flee.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        navigation::plan_paths
+                            .after(navigation::build_nav_grid)
+                            .after(bounds::wrap_or_bounce)
+                            .before(energy::spend_energy)
+                            .before(pipeline::DespawnBoids),
+                    )
+                    .with_system(path::follow_path.after(bounds::wrap_or_bounce))
+                    .with_system(flow_field::follow_flow_field.after(bounds::wrap_or_bounce))
+                    .with_system(species::react_to_species.after(neighbors::rebuild_spatial_index))
+                    .with_system(predator::evade.after(bounds::wrap_or_bounce))
+                    .with_system(obstacle::avoid_obstacles.after(bounds::wrap_or_bounce))
+                    .with_system(bounds::avoid_bounds.after(bounds::wrap_or_bounce))
+                    .with_system(playfield::contain.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        steering::accumulate_steering
+                            .after(obstacle::avoid_obstacles)
+                            .after(bounds::avoid_bounds)
+                            .after(playfield::contain)
+                            .after(predator::flee)
+                            .after(path::follow_path)
+                            .after(flow_field::follow_flow_field)
+                            .after(food::seek_food)
+                            .after(attractor::attract_boids)
+                            .after(attractor::stir_boids)
+                            .after(turbulence::apply_turbulence)
+                            .after(pheromone::follow_scent)
+                            .after(behavior::apply_steering_behaviors)
+                            .after(species::react_to_species)
+                            .after(predator::evade)
+                            .after(steering::seek)
+                            .after(steering::wander)
+                            .after(flocking::flocking)
+                            .after(leader::follow_leader)
+                            .after(formation::hold_formation)
+                            .after(queueing::queue)
+                            .after(context_steering::context_steering),
+                    )
+                    .with_system(food::seek_food.after(bounds::wrap_or_bounce))
+                    .with_system(attractor::attract_boids.after(bounds::wrap_or_bounce))
+                    .with_system(attractor::stir_boids.after(bounds::wrap_or_bounce))
+                    .with_system(turbulence::apply_turbulence.after(bounds::wrap_or_bounce))
+                    .with_system(pheromone::follow_scent.after(bounds::wrap_or_bounce))
+                    .with_system(
+                        context_steering::context_steering.after(neighbors::rebuild_spatial_index),
+                    )
+                    .with_system(
+                        orca::avoid_reciprocally
+                            .after(steering::accumulate_steering)
+                            .after(wind::apply_wind)
+                            .before(perching::perch)
+                            .before(energy::spend_energy),
+                    ),
+            )
+            .add_system(flock::apply_flock_settings)
+            .add_system(flock::apply_flock_config)
+            .add_system(lod::update_lod)
+            .add_system(run::exit_after_run_duration)
+            .add_system_to_stage(CoreStage::Last, recorder::flush_metrics_on_exit)
+            .add_system_to_stage(CoreStage::Last, replay::flush_recording_on_exit)
+            .add_system(preset::join_flow_field)
+            .add_system(energy::energize_new_boids)
+            .add_system(flock::reset_on_request.at_end())
+            .add_system(preset::load_preset_on_request.at_end());
+    }
+}
+
+/// Adds `SimulationPlugin` and draws the flock, with a camera, debug
+/// overlays and keyboard and mouse controls, to get a flock chasing a
+/// cursor-driven target.
+///
+/// Resources inserted before adding the plugin (`FlockView`...) are kept, the
+/// others start from their defaults. Also adds lyon's `ShapePlugin`, which
+/// must not be added a second time, with the `egui` feature `EguiPlugin` and
+/// a panel to tune the flock, and with the `inspector` feature a world
+/// inspector.
+pub struct BoidPlugin;
+
+impl Plugin for BoidPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<State<AppState>>() {
+            app.add_state(AppState::MainMenu);
+        }
+
+        app.add_plugin(SimulationPlugin)
+            .add_plugin(ShapePlugin)
+            .init_resource::<DebugDraw>()
+            .init_resource::<GizmoOverlay>()
+            .init_resource::<IndexOverlay>()
+            .init_resource::<TrailSettings>()
+            .init_resource::<Hud>()
+            .init_resource::<CaptureScore>()
+            .init_resource::<GamepadControls>()
+            .init_resource::<SnapshotFile>()
+            .init_resource::<CameraControls>()
+            .init_resource::<CameraFollow>()
+            .init_resource::<SplitScreen>()
+            .init_resource::<render::HoveredCamera>()
+            .init_resource::<Inspection>()
+            .init_resource::<Tuning>()
+            .init_resource::<BoidRendering>()
+            .init_resource::<BoidColoring>()
+            .init_resource::<Minimap>()
+            .init_resource::<BoidLabels>()
+            .init_resource::<CursorStir>()
+            .init_resource::<RewindBuffer>()
+            .add_asset::<SimConfig>()
+            .init_asset_loader::<sim_config::SimConfigLoader>()
+            .add_asset::<ObstacleMap>()
+            .init_asset_loader::<map::ObstacleMapLoader>()
+            .add_startup_system(bounds::setup_bounds)
+            .add_startup_system(sim_config::load_sim_config)
+            .add_startup_system(render::setup_camera)
+            .add_startup_system(camera::spawn_detail_camera)
+            .add_startup_system(render::setup_boid_sprite)
+            .add_startup_system(debug::spawn_heading_gizmos)
+            .add_startup_system(obstacle::spawn_obstacle_sketch)
+            .add_startup_system(playfield::spawn_playfield_outline)
+            .add_startup_system(debug::spawn_debug_layer)
+            .add_startup_system(debug::spawn_gizmo_overlay)
+            .add_startup_system(debug::spawn_index_outline)
+            .add_startup_system(trail::spawn_trail_bands)
+            .add_startup_system(hud::spawn_hud)
+            .add_startup_system(game::spawn_scoreboard)
+            .add_startup_system(population::spawn_population_graph)
+            .add_startup_system(wind::spawn_wind_arrow)
+            .add_startup_system(minimap::spawn_minimap)
+            .add_startup_system(selection::spawn_inspection)
+            .add_startup_system(tuning::spawn_tuning_flash)
+            .add_startup_system(heatmap::setup_visit_heatmap.after(bounds::setup_bounds))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .with_system(heatmap::accumulate_visits.after(tick::end_tick))
+                    .with_system(trail::record_trails.after(tick::end_tick)),
+            )
+            .add_system(coloring::cycle_boid_coloring)
+            .add_system_set(
+                SystemSet::new()
+                    .label(BoidSet::Render)
+                    .with_run_criteria(pipeline::rendering)
+                    .with_system(render::scale_boids)
+                    .with_system(
+                        coloring::color_boids
+                            .after(coloring::cycle_boid_coloring)
+                            .after(flock::apply_flock_settings),
+                    )
+                    .with_system(render::draw_boids_as_sprites.after(coloring::color_boids))
+                    .with_system(render::tint_sprites.after(render::draw_boids_as_sprites)),
+            )
+            .add_system(map::load_obstacle_map)
+            .add_system(map::apply_obstacle_map.after(map::load_obstacle_map))
+            .add_system(
+                sim_config::apply_sim_config
+                    .before(flock::apply_flock_settings)
+                    .before(flock::apply_flock_config),
+            )
+            .add_system(camera::pan_camera.before(render::track_cursor))
+            .add_system(camera::zoom_camera.before(render::track_cursor))
+            .add_system(camera::toggle_camera_follow.before(camera::follow_boid))
+            .add_system(
+                camera::follow_boid
+                    .after(camera::zoom_camera)
+                    .before(render::track_cursor),
+            )
+            .add_system(camera::toggle_split_screen)
+            .add_system(camera::layout_split_screen.after(camera::toggle_split_screen))
+            .add_system(
+                camera::aim_detail_camera
+                    .after(camera::toggle_split_screen)
+                    .after(camera::zoom_camera)
+                    .before(render::track_cursor),
+            )
+            .add_system(render::track_cursor)
+            .add_system(
+                wind::draw_wind_arrow
+                    .after(camera::follow_boid)
+                    .after(camera::pan_camera),
+            )
+            .add_system(minimap::toggle_minimap)
+            .add_system(
+                minimap::draw_minimap
+                    .after(minimap::toggle_minimap)
+                    .after(camera::follow_boid)
+                    .after(camera::pan_camera)
+                    .after(camera::layout_split_screen),
+            )
+            .add_system(labels::toggle_boid_labels)
+            .add_system(
+                labels::draw_boid_labels
+                    .after(labels::toggle_boid_labels)
+                    .after(render::track_cursor)
+                    .after(camera::follow_boid)
+                    .after(camera::pan_camera),
+            )
+            // clicks on the menu are not meant for the world
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(state::outside_menu)
+                    .with_system(target::move_target.after(render::track_cursor))
+                    .with_system(flock::spawn_at_cursor.after(render::track_cursor))
+                    .with_system(attractor::place_attractors.after(render::track_cursor)),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(state::clicking_world)
+                    .with_system(obstacle::sketch_obstacles.after(render::track_cursor))
+                    .with_system(playfield::sketch_playfield.after(render::track_cursor))
+                    .with_system(target::place_targets.after(render::track_cursor))
+                    .with_system(flock::despawn_nearest_boid.after(render::track_cursor))
+                    .with_system(selection::select_on_click.after(render::track_cursor))
+                    .with_system(food::drop_food_on_click.after(render::track_cursor)),
+            )
+            .add_system(attractor::toggle_cursor_stir)
+            .add_system(
+                attractor::stir_with_mouse
+                    .after(attractor::toggle_cursor_stir)
+                    .after(render::track_cursor),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(state::spawn_menu))
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(state::despawn_menu))
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu).with_system(state::press_menu_buttons),
+            )
+            .add_system(state::switch_state)
+            .add_system(state::pause_outside_running)
+            .add_system(obstacle::clear_obstacles)
+            .add_system(attractor::clear_attractors)
+            .add_system(leader::choose_leader)
+            .add_system(formation::cycle_formation)
+            .add_system(navigation::toggle_navigation)
+            .add_system(leader::mark_leaders.after(leader::choose_leader))
+            .add_system(bounds::cycle_boundary_mode)
+            .add_system(bounds::resize_bounds)
+            .add_system(target::toggle_target_pin.before(target::move_target))
+            .add_system(target::cycle_target_mode.before(target::move_target))
+            .add_system(predator::spawn_predator_on_key)
+            .add_system(gamepad::drive_target_with_stick.after(target::cycle_target_mode))
+            .add_system(gamepad::press_gamepad_buttons)
+            .add_system(preset::switch_preset_on_key)
+            .add_system(selection::draw_inspection.after(selection::select_on_click))
+            .add_system(debug::draw_heading_gizmos)
+            .add_system(debug::draw_perception_gizmo)
+            .add_system(path::draw_path_lines)
+            .add_system(playfield::draw_playfield.after(playfield::sketch_playfield))
+            .add_system(debug::toggle_debug_draw)
+            .add_system(debug::draw_debug_layer.after(debug::toggle_debug_draw))
+            .add_system(debug::toggle_gizmo_overlay)
+            .add_system(debug::toggle_index_overlay)
+            .add_system(debug::draw_index_overlay.after(debug::toggle_index_overlay))
+            .add_system(neighbors::cycle_neighbor_backend)
+            .add_system(hud::toggle_hud)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game::in_capture_game)
+                    .with_system(game::scare_new_boids)
+                    .with_system(game::flee_target)
+                    // new boids can be captured in the frame they are scared in
+                    .with_system(game::capture_boids.after(game::scare_new_boids))
+                    .with_system(game::ramp_up),
+            )
+            .add_system(game::update_scoreboard)
+            .add_system(hud::update_hud.after(hud::toggle_hud))
+            .add_system(population::draw_population_graph.after(hud::toggle_hud))
+            .add_system(trail::toggle_trails)
+            .add_system(trail::draw_trails.after(trail::toggle_trails))
+            .add_system(debug::draw_gizmo_overlay.after(debug::toggle_gizmo_overlay))
+            .add_system(render::apply_render_order.after(target::move_target))
+            .add_system(flock::request_reset)
+            .add_system(
+                tuning::tune_with_keys
+                    .before(flock::apply_flock_settings)
+                    .before(flock::apply_flock_config),
+            )
+            .add_system(snapshot::quick_save)
+            .add_system(snapshot::quick_load)
+            .add_system(tick::control_simulation)
+            .add_system(rewind::rewind.before(state::pause_outside_running))
+            .add_system(heatmap::toggle_visit_heatmap)
+            .add_system(heatmap::draw_visit_heatmap.after(heatmap::toggle_visit_heatmap))
+            .add_system(pheromone::toggle_pheromones)
+            .add_system(pheromone::draw_scent.after(pheromone::toggle_pheromones));
+
+        // the frame rate shown on the HUD
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugin(FrameTimeDiagnosticsPlugin);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugin(screenshot::ScreenshotPlugin);
+        #[cfg(feature = "egui")]
+        app.add_plugin(bevy_egui::EguiPlugin)
+            .add_system(panel::parameter_panel);
+        #[cfg(feature = "inspector")]
+        app.add_plugin(bevy_inspector_egui::quick::WorldInspectorPlugin);
+        #[cfg(feature = "scripting")]
+        app.add_asset::<SteeringScript>()
+            .init_asset_loader::<script::SteeringScriptLoader>()
+            .init_resource::<script::ScriptEngine>()
+            .add_startup_system(script::load_steering_script)
+            .add_system_to_stage(
+                FixedUpdateStage,
+                script::run_steering_script
+                    .label(BoidSet::Steering)
+                    .with_run_criteria(pipeline::steering)
+                    .after(neighbors::rebuild_spatial_index)
+                    .before(steering::accumulate_steering),
+            );
+        #[cfg(feature = "audio")]
+        app.init_resource::<SoundEffects>()
+            .add_startup_system(audio::play_ambient)
+            .add_system(audio::play_event_sounds)
+            .add_system(audio::follow_flock_speed);
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, BoidId},
+    render::MainCamera,
+    tick::SimTick,
+};
+
+/// How coarsely a boid is simulated. Boids out of view, or far from the
+/// camera once zoomed out, are reduced: they steer only every
+/// `LodSettings::stride` steps, holding their last force in between, snap to
+/// their heading instead of turning smoothly and leave no trail.
+#[derive(Component, Default)]
+pub struct Lod {
+    reduced: bool,
+    // steering is skipped on the current step
+    skipping: bool,
+}
+
+impl Lod {
+    pub fn is_reduced(&self) -> bool {
+        self.reduced
+    }
+
+    /// Whether steering behaviors can leave this boid alone on the current
+    /// step, `accumulate_steering` reapplying the force of the last one.
+    pub fn skips_steering(&self) -> bool {
+        self.skipping
+    }
+}
+
+/// When boids get reduced, see `Lod`.
+#[derive(Resource)]
+pub struct LodSettings {
+    pub enabled: bool,
+    // steps between two steering updates of a reduced boid
+    pub stride: u32,
+    // distance past the edges of the view still counted as in view
+    pub margin: f32,
+    // once zoomed out, boids further than this from the center of the view
+    // are reduced even if in view
+    pub far_distance: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stride: 4,
+            margin: 50.,
+            far_distance: 1500.,
+        }
+    }
+}
+
+/// Reduces the boids out of view of the main camera. Without a camera
+/// drawing anywhere, as when running headless, every boid is fully simulated.
+pub(crate) fn update_lod(
+    settings: Res<LodSettings>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut boids: Query<(&Transform, &mut Lod), With<Boid>>,
+) {
+    let view = camera_query
+        .get_single()
+        .ok()
+        .filter(|_| settings.enabled)
+        .and_then(|(camera, camera_transform, projection)| {
+            camera.logical_viewport_size()?;
+            let min = camera.ndc_to_world(camera_transform, Vec3::new(-1., -1., 0.))?;
+            let max = camera.ndc_to_world(camera_transform, Vec3::new(1., 1., 0.))?;
+            let zoomed_out = projection.scale > 1.;
+            Some((
+                Rect::from_corners(min.truncate(), max.truncate()),
+                zoomed_out,
+            ))
+        });
+
+    for (transform, mut lod) in boids.iter_mut() {
+        let reduced = view.is_some_and(|(rect, zoomed_out)| {
+            let position = transform.translation.truncate();
+            let out_of_view =
+                (position - position.clamp(rect.min, rect.max)).length() > settings.margin;
+            let far = zoomed_out && position.distance(rect.center()) > settings.far_distance;
+            out_of_view || far
+        });
+        lod.reduced = reduced;
+    }
+}
+
+/// Picks the reduced boids skipping steering on this step, spreading them
+/// evenly over the stride.
+pub(crate) fn schedule_lod(
+    settings: Res<LodSettings>,
+    tick: Res<SimTick>,
+    mut boids: Query<(&mut Lod, Option<&BoidId>)>,
+) {
+    let stride = u64::from(settings.stride.max(1));
+    for (mut lod, id) in boids.iter_mut() {
+        let offset = id.map_or(0, |id| u64::from(id.0));
+        lod.skipping = lod.reduced && !(tick.steps() + offset).is_multiple_of(stride);
+    }
+}
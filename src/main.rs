@@ -1,195 +1,549 @@
-use bevy::{prelude::*, render::camera::RenderTarget, window::PresentMode};
-use bevy_prototype_lyon::prelude::*;
+use std::path::PathBuf;
 
-fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            window: WindowDescriptor {
-                title: "Flock".to_string(),
-                width: 800.0,
-                height: 800.0,
-                present_mode: PresentMode::AutoVsync,
-                ..default()
-            },
-            ..default()
-        }))
-        .add_plugin(ShapePlugin)
-        .add_startup_system(setup_camera)
-        .add_startup_system(spawn_target)
-        .add_startup_system(spawn_boid)
-        .add_system(physics_system)
-        .add_system(seek_target)
-        .add_system(move_target)
-        .add_system(steering.after(physics_system))
-        .run();
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    log::LogPlugin,
+    prelude::*,
+    window::PresentMode,
+};
+use bevy_lyon_boid::{
+    AppState, Arbitration, BoidDiagnosticsPlugin, BoidPlugin, BoidRendering, Bounds, Breathing,
+    Edge, Emitter, FlightRecorder, FlockConfig, Flocks, FoodSettings, Integrator, LeaderSettings,
+    MetricsRecorder, Navigation, ObstacleMap, ObstacleMapFile, PerchSettings, Pheromones, Preset,
+    Replay, Reproduction, RunDuration, SimConfig, SimConfigFile, SimSeed, SimTick,
+    SimulationControl, SimulationPlugin, Substeps, TargetMode, Turbulence, Wind, WindGusts,
+};
+use clap::{Parser, ValueEnum};
+
+/// Boid flocking simulation.
+#[derive(Parser)]
+struct Cli {
+    /// Boids in the initial flock
+    #[arg(long)]
+    boids: Option<usize>,
+    /// Seed of the random numbers, for reproducible runs
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Width of the window, or of the world when headless
+    #[arg(long, default_value_t = 800.)]
+    width: f32,
+    /// Height of the window, or of the world when headless
+    #[arg(long, default_value_t = 800.)]
+    height: f32,
+    /// Run the simulation alone, without a window or drawing, as fast as
+    /// possible
+    #[arg(long)]
+    headless: bool,
+    /// Start simulating right away instead of on the menu
+    #[arg(long)]
+    no_menu: bool,
+    /// Draw the boids as batched sprites rather than outlined shapes, for
+    /// flocks of thousands
+    #[arg(long)]
+    sprites: bool,
+    /// Let the target drive itself instead of following the cursor
+    #[arg(long)]
+    autopilot: bool,
+    /// Boids tire, eat food dropped around them and split when well fed
+    #[arg(long)]
+    ecosystem: bool,
+    /// The boids' breathing pulses the room they keep from their neighbors
+    /// too, instead of only how they are drawn
+    #[arg(long)]
+    breathing_spacing: bool,
+    /// Boids diving at the bottom edge land there for a while
+    #[arg(long)]
+    perching: bool,
+    /// The boid nearest the target leads each flock, the others trail it
+    #[arg(long)]
+    leader: bool,
+    /// Boids find their way to the target around obstacles, as `M` does
+    #[arg(long)]
+    navigate: bool,
+    /// Steady wind blowing east on top of the gusts, in world units per
+    /// second squared
+    #[arg(long, default_value_t = 0.)]
+    wind: f32,
+    /// Boids lay scent trails and follow the strongest ones, as `Shift+U`
+    /// does
+    #[arg(long)]
+    pheromones: bool,
+    /// Share of the scent evaporating every second
+    #[arg(long, value_name = "RATE", default_value_t = 0.2)]
+    evaporation: f32,
+    /// Fly the flock in three dimensions instead
+    #[cfg(feature = "3d")]
+    #[arg(long = "3d")]
+    three_d: bool,
+    /// Steering behavior script to run, relative to the assets folder
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "PATH")]
+    script: Option<String>,
+    /// Chaotic eddies stirring the flock, in world units per second squared
+    #[arg(long, default_value_t = 0.)]
+    turbulence: f32,
+    /// How the steering behaviors are combined, overriding the preset
+    #[arg(long, value_enum)]
+    steering: Option<SteeringArg>,
+    /// Boids slow down within this distance of their target instead of
+    /// overshooting it
+    #[arg(long, value_name = "RADIUS")]
+    arrive: Option<f32>,
+    /// Boids aim where the target will be, up to this many seconds ahead
+    #[arg(long, value_name = "SECONDS")]
+    pursue: Option<f32>,
+    /// How the physics step moves the boids
+    #[arg(long, value_enum, default_value_t = IntegratorArg::SemiImplicit)]
+    integrator: IntegratorArg,
+    /// Most sub-steps a physics step is cut into for fast boids, 1 for none
+    #[arg(long, value_name = "COUNT", default_value_t = 8)]
+    max_substeps: u32,
+    /// Scene to start with, switched later with Shift and a number key
+    #[arg(long, value_enum, default_value_t = PresetArg::Seek)]
+    preset: PresetArg,
+    /// Walls drawn in a text or image map, relative to the assets folder, as
+    /// `maps/maze.map.txt`
+    #[arg(long, value_name = "PATH")]
+    map: Option<String>,
+    /// Edge a stream of boids keeps coming from, on top of the preset's
+    #[arg(long, value_enum, value_name = "EDGE")]
+    emitter: Option<EdgeArg>,
+    /// Exit after this many simulated seconds
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["run_for_wall", "ticks"])]
+    run_for: Option<f32>,
+    /// Exit after this many real seconds
+    #[arg(long, value_name = "SECONDS", conflicts_with = "ticks")]
+    run_for_wall: Option<f32>,
+    /// Exit after this many simulation steps
+    #[arg(long, value_name = "STEPS")]
+    ticks: Option<u64>,
+    /// Log the frame rate and flock diagnostics every second
+    #[arg(long)]
+    diagnostics: bool,
+    /// Write metrics of every simulation step to this CSV file
+    #[arg(long, value_name = "FILE")]
+    metrics_csv: Option<PathBuf>,
+    /// Record the boids of every simulation step to this file
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Play back a file written with --record instead of simulating
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
 }
 
-fn setup_camera(mut commands: Commands) {
-    // Add a camera so we can see the debug-render.
-    commands.spawn(Camera2dBundle::default()).insert(MainCamera);
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EdgeArg {
+    Left,
+    Right,
+    Top,
+    Bottom,
 }
 
-#[derive(Component)]
-struct MainCamera;
+impl From<EdgeArg> for Edge {
+    fn from(arg: EdgeArg) -> Self {
+        match arg {
+            EdgeArg::Left => Edge::Left,
+            EdgeArg::Right => Edge::Right,
+            EdgeArg::Top => Edge::Top,
+            EdgeArg::Bottom => Edge::Bottom,
+        }
+    }
+}
 
-#[derive(Component)]
-struct Boid;
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PresetArg {
+    /// The flock chases the cursor past an obstacle
+    Seek,
+    /// Hundreds of small boids
+    BigFlock,
+    /// The seek scene with a predator hunting the flock
+    Predator,
+    /// A stream of boids between rocks and walls
+    ObstacleCourse,
+    /// Boids drifting along noise currents
+    FlowField,
+    /// Two rooms joined by a door, the flock queueing through it
+    Doorway,
+    /// Catch the fleeing boids before they overrun the screen
+    Capture,
+    /// A maze the flock finds its way through
+    Maze,
+}
 
-#[derive(Component, Default)]
-struct Physics {
-    velocity: Vec3,
-    acceleration: Vec3,
-    max_speed: f32,
-    max_force: f32,
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SteeringArg {
+    /// Forces of every behavior weighted and summed
+    WeightedSum,
+    /// Boids pick the best direction from interest and danger maps
+    Context,
 }
 
-#[derive(Component, Default)]
-struct Steering {
-    target: Vec3,
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum IntegratorArg {
+    /// Positions move at the velocity before the step
+    Euler,
+    /// Positions move at the velocity after the step
+    SemiImplicit,
+    /// Positions move at the velocity halfway through the step
+    Verlet,
 }
 
-#[derive(Component)]
-struct Target;
+impl From<IntegratorArg> for Integrator {
+    fn from(arg: IntegratorArg) -> Self {
+        match arg {
+            IntegratorArg::Euler => Integrator::Euler,
+            IntegratorArg::SemiImplicit => Integrator::SemiImplicitEuler,
+            IntegratorArg::Verlet => Integrator::VelocityVerlet,
+        }
+    }
+}
 
-fn spawn_target(mut commands: Commands) {
-    let shape = shapes::Rectangle {
-        extents: Vec2 { x: 10., y: 10. },
-        ..Default::default()
-    };
+impl From<SteeringArg> for Arbitration {
+    fn from(arg: SteeringArg) -> Self {
+        match arg {
+            SteeringArg::WeightedSum => Arbitration::WeightedSum,
+            SteeringArg::Context => Arbitration::Context,
+        }
+    }
+}
 
-    commands
-        .spawn(GeometryBuilder::build_as(
-            &shape,
-            DrawMode::Outlined {
-                fill_mode: FillMode::color(Color::RED),
-                outline_mode: StrokeMode::new(Color::WHITE, 1.),
-            },
-            Transform::from_xyz(0., 0., 10.),
-        ))
-        .insert(Target);
+impl From<PresetArg> for Preset {
+    fn from(arg: PresetArg) -> Self {
+        match arg {
+            PresetArg::Seek => Preset::SeekDemo,
+            PresetArg::BigFlock => Preset::BigFlock,
+            PresetArg::Predator => Preset::PredatorHunt,
+            PresetArg::ObstacleCourse => Preset::ObstacleCourse,
+            PresetArg::FlowField => Preset::FlowField,
+            PresetArg::Doorway => Preset::Doorway,
+            PresetArg::Capture => Preset::Capture,
+            PresetArg::Maze => Preset::Maze,
+        }
+    }
 }
 
-fn spawn_boid(mut commands: Commands) {
-    let triangle = shapes::Polygon {
-        points: vec![
-            Vec2::new(-15., -25.),
-            Vec2::new(15., -25.),
-            Vec2::new(0., 25.),
-        ],
-        closed: true,
-    };
-    let line = shapes::Line(Vec2::new(0., 0.), Vec2::new(0., 50.));
+fn main() {
+    let cli = Cli::parse();
+    let mut app = App::new();
 
-    commands
-        .spawn(GeometryBuilder::new().add(&triangle).add(&line).build(
-            DrawMode::Outlined {
-                fill_mode: FillMode::color(Color::BLUE),
-                outline_mode: StrokeMode::new(Color::WHITE, 1.),
+    // the 3D flock has a plugin of its own, taking none of the 2D options
+    #[cfg(feature = "3d")]
+    if cli.three_d {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            window: WindowDescriptor {
+                title: "Flock".to_string(),
+                width: cli.width,
+                height: cli.height,
+                ..default()
             },
-            Transform::from_xyz(200., 0., 100.),
-        ))
-        .insert(Physics {
-            velocity: Vec3::new(10., -10., 0.),
-            acceleration: Vec3::default(),
-            max_speed: 2.,
-            max_force: 0.1,
-        })
-        .insert(Steering {
-            target: Vec3::new(0., 0., 0.),
-        })
-        .insert(Boid);
-}
+            ..default()
+        }))
+        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)));
+        if let Some(seed) = cli.seed {
+            app.insert_resource(SimSeed(seed));
+        }
+        if let Some(count) = cli.boids {
+            app.insert_resource(FlockConfig { count, ..default() });
+        }
+        app.add_plugin(bevy_lyon_boid::Flock3dPlugin).run();
+        return;
+    }
+
+    if cli.headless {
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(LogPlugin::default())
+            .add_plugin(SimulationPlugin)
+            // without a window to fit, the world is the requested size
+            .insert_resource(Bounds {
+                min: Vec2::new(-cli.width, -cli.height) / 2.,
+                max: Vec2::new(cli.width, cli.height) / 2.,
+            })
+            .insert_resource(SimulationControl {
+                lockstep: true,
+                ..default()
+            });
+    } else {
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    window: WindowDescriptor {
+                        title: "Flock".to_string(),
+                        width: cli.width,
+                        height: cli.height,
+                        present_mode: PresentMode::AutoVsync,
+                        // in a browser, fill the element holding the canvas
+                        fit_canvas_to_parent: true,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .set(AssetPlugin {
+                    watch_for_changes: true,
+                    ..default()
+                }),
+        )
+        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)));
+        // timed runs and replays are meant to run unattended
+        let unattended = cli.run_for.is_some()
+            || cli.run_for_wall.is_some()
+            || cli.ticks.is_some()
+            || cli.replay.is_some();
+        if cli.no_menu || unattended {
+            app.add_state(AppState::Running);
+        }
+        app.add_plugin(BoidPlugin);
+    }
 
-fn physics_system(mut query: Query<(&mut Transform, &mut Physics, With<Boid>)>) {
-    for (mut transform, mut physics, _) in query.iter_mut() {
-        let previous_acceleration = physics.acceleration;
-        let previous_velocity = physics.velocity;
-        let previous_position = transform.translation;
-        let max_speed = physics.max_speed;
+    app.insert_resource(Wind {
+        gusts: Some(WindGusts::new(
+            cli.seed.unwrap_or_default() as u32,
+            300.,
+            72.,
+            0.1,
+        )),
+        strength: cli.wind,
+        ..default()
+    })
+    .insert_resource(Turbulence::new(
+        (cli.seed.unwrap_or_default() as u32).wrapping_add(1),
+        cli.turbulence,
+        60.,
+        0.5,
+    ))
+    .insert_resource(Pheromones {
+        enabled: cli.pheromones,
+        evaporation: cli.evaporation,
+        ..default()
+    })
+    .insert_resource(Integrator::from(cli.integrator))
+    .insert_resource(Substeps {
+        max: cli.max_substeps,
+        ..default()
+    })
+    .insert_resource(Breathing {
+        amplitude: 0.05,
+        frequency: 0.5,
+        spacing: cli.breathing_spacing,
+    });
 
-        let new_velocity = previous_velocity + previous_acceleration;
-        let new_position = previous_position + new_velocity;
+    let preset = Preset::from(cli.preset);
+    app.insert_resource(preset);
+    if let Some(map) = cli.map.as_deref().or(preset.map()) {
+        match cli.headless {
+            // without an asset server, the map is read once on startup
+            true => read_obstacle_map(&mut app, map),
+            false => {
+                app.insert_resource(ObstacleMapFile(map.to_string()));
+            }
+        }
+    }
+    if let Some(edge) = cli.emitter {
+        app.world.spawn(Emitter::new(edge.into(), 2., 120.));
+    }
+    match cli.boids {
+        // a flock set up from the command line is not overridden by the file,
+        // so runs can be reproduced from the shell alone
+        Some(count) => {
+            let config = preset.config();
+            app.insert_resource(FlockConfig {
+                count,
+                max_boids: config.max_boids.max(count),
+                ..config
+            });
+        }
+        // the config file tunes the default flock, other presets and the
+        // ecosystem bring their own
+        None if cli.ecosystem || !matches!(preset, Preset::SeekDemo | Preset::PredatorHunt) => {
+            app.insert_resource(preset.config());
+        }
+        // without an asset server, the file is read once on startup
+        None if cli.headless => read_sim_config(&mut app, "assets/sim.ron", cli.seed),
+        // tweak the flock in assets/sim.ron while the demo runs
+        None => {
+            // the asset server only loads the file a few frames in, after the
+            // first flock is spawned with the seed
+            #[cfg(not(target_arch = "wasm32"))]
+            if let (
+                None,
+                Ok(SimConfig {
+                    seed: Some(seed), ..
+                }),
+            ) = (cli.seed, read_sim_file("assets/sim.ron"))
+            {
+                app.insert_resource(SimSeed(seed));
+            }
+            app.insert_resource(SimConfigFile("sim.ron".to_string()));
+        }
+    }
 
-        let angle_between_positions = angle_to_direction(&new_velocity);
+    // scripts are loaded as assets, so only with a window
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &cli.script {
+        app.insert_resource(bevy_lyon_boid::SteeringScriptFile(script.clone()));
+    }
 
-        transform.translation = new_position;
-        transform.rotation = Quat::from_rotation_z(angle_between_positions);
-        physics.velocity = new_velocity.clamp_length_max(max_speed);
+    if cli.ecosystem {
+        let config = app
+            .world
+            .remove_resource::<FlockConfig>()
+            .unwrap_or_else(|| preset.config());
+        app.insert_resource(FlockConfig {
+            energy: Some(100.),
+            scale_by_energy: true,
+            ..config
+        })
+        .insert_resource(FoodSettings {
+            drop_interval: Some(0.5),
+            ..default()
+        })
+        .insert_resource(Reproduction {
+            threshold: Some(0.9),
+            ..default()
+        });
+    }
 
-        physics.acceleration = Vec3::ZERO;
+    if let Some(steering) = cli.steering {
+        let config = app
+            .world
+            .remove_resource::<FlockConfig>()
+            .unwrap_or_else(|| preset.config());
+        app.insert_resource(FlockConfig {
+            arbitration: steering.into(),
+            ..config
+        });
     }
-}
 
-fn angle_to_direction(new_velocity: &Vec3) -> f32 {
-    if *new_velocity == Vec3::ZERO {
-        0.
-    } else {
-        new_velocity.angle_between(Vec3::Y) * -new_velocity.x.signum()
+    if cli.arrive.is_some() || cli.pursue.is_some() {
+        let config = app
+            .world
+            .remove_resource::<FlockConfig>()
+            .unwrap_or_else(|| preset.config());
+        app.insert_resource(FlockConfig {
+            arrive: cli.arrive.or(config.arrive),
+            pursue: cli.pursue.or(config.pursue),
+            ..config
+        });
     }
-}
 
-fn seek_target(
-    mut boid_query: Query<(&mut Steering, With<Boid>)>,
-    target_query: Query<(&Transform, With<Target>)>,
-) {
-    let (target, _) = target_query.single();
-    for (mut steering, _) in boid_query.iter_mut() {
-        steering.target = target.translation;
+    if cli.perching {
+        app.insert_resource(PerchSettings {
+            enabled: true,
+            ..default()
+        });
     }
-}
 
-fn move_target(
-    // need to get window dimensions
-    windows: Res<Windows>,
-    // query to get camera transform
-    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    mut target_query: Query<(&mut Transform, With<Target>)>,
-) {
-    // get the camera info and transform
-    // assuming there is exactly one main camera entity, so query::single() is OK
-    let (camera, camera_transform) = camera_query.single();
-
-    // get the window that the camera is displaying to (or the primary window)
-    let window = if let RenderTarget::Window(id) = camera.target {
-        windows.get(id).unwrap()
-    } else {
-        windows.get_primary().unwrap()
-    };
+    if cli.leader {
+        app.insert_resource(LeaderSettings {
+            auto: true,
+            ..default()
+        });
+    }
 
-    // check if the cursor is inside the window and get its position
-    if let Some(screen_pos) = window.cursor_position() {
-        // get the size of the window
-        let window_size = Vec2::new(window.width() as f32, window.height() as f32);
+    if cli.navigate {
+        app.insert_resource(Navigation {
+            enabled: true,
+            ..default()
+        });
+    }
 
-        // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
-        let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
+    if cli.sprites {
+        app.insert_resource(BoidRendering::Sprites);
+    }
 
-        // matrix for undoing the projection and camera transform
-        let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    if cli.autopilot {
+        app.insert_resource(TargetMode::Autopilot);
+    }
 
-        // use it to convert ndc to world-space coordinates
-        let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+    if let Some(seed) = cli.seed {
+        app.insert_resource(SimSeed(seed));
+    }
 
-        // reduce it to a 2D value
-        let world_pos: Vec2 = world_pos.truncate();
-        let mut target = target_query.single_mut().0;
+    if let Some(seconds) = cli.run_for {
+        app.insert_resource(RunDuration::Simulated(seconds));
+    } else if let Some(seconds) = cli.run_for_wall {
+        app.insert_resource(RunDuration::WallClock(seconds));
+    } else if let Some(steps) = cli.ticks {
+        app.insert_resource(RunDuration::Steps(steps));
+    }
 
-        target.translation = world_pos.extend(0.);
+    if (cli.diagnostics || cli.metrics_csv.is_some())
+        && !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>()
+    {
+        app.init_resource::<Diagnostics>()
+            .add_plugin(FrameTimeDiagnosticsPlugin);
+    }
+    if cli.diagnostics {
+        app.add_plugin(BoidDiagnosticsPlugin)
+            .add_plugin(LogDiagnosticsPlugin::default());
+    }
+    if let Some(path) = &cli.record {
+        match FlightRecorder::create(path, SimTick::default().rate) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(error) => eprintln!("could not create {}: {error}", path.display()),
+        }
+    }
+    if let Some(path) = &cli.replay {
+        match Replay::load(path) {
+            Ok(replay) => {
+                app.insert_resource(replay);
+            }
+            Err(error) => {
+                eprintln!("could not read {}: {error}", path.display());
+                return;
+            }
+        }
+    }
+    if let Some(path) = &cli.metrics_csv {
+        match MetricsRecorder::create(path) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(error) => eprintln!("could not create {}: {error}", path.display()),
+        }
     }
-}
 
-fn steering(mut query: Query<(&Transform, &Steering, &mut Physics, With<Boid>)>) {
-    for (transform, steering, mut physics, _) in query.iter_mut() {
-        let mut desired = steering.target - transform.translation;
-        desired = desired.normalize();
-        desired = desired * physics.max_speed;
+    app.run();
+}
 
-        let steer = (desired - physics.velocity).clamp_length_max(physics.max_force);
-        apply_force(physics.as_mut(), &steer);
+/// Inserts the obstacle map at `path` in the assets, if it can be read.
+fn read_obstacle_map(app: &mut App, path: &str) {
+    let path = format!("assets/{path}");
+    let map = std::fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| ObstacleMap::read(&path, &bytes));
+    match map {
+        Ok(map) => {
+            app.insert_resource(map);
+        }
+        Err(error) => eprintln!("could not read {path}, leaving its walls out: {error}"),
     }
 }
 
-fn apply_force(physics: &mut Physics, force: &Vec3) {
-    physics.acceleration = physics.acceleration + *force;
+fn read_sim_file(path: &str) -> Result<SimConfig, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    SimConfig::from_ron(&text).map_err(|error| error.to_string())
+}
+
+/// Applies the config file at `path`, if it can be read, before the flock is
+/// spawned. Its seed is used unless one was given on the command line.
+fn read_sim_config(app: &mut App, path: &str, seed: Option<u64>) {
+    let sim_config = match read_sim_file(path) {
+        Ok(sim_config) => sim_config,
+        Err(error) => {
+            eprintln!("could not read {path}, using the default flock: {error}");
+            return;
+        }
+    };
+
+    let mut config = FlockConfig::default();
+    let mut flocks = Flocks::default();
+    sim_config.apply(&mut config, &mut flocks);
+    app.insert_resource(config)
+        .insert_resource(flocks)
+        .insert_resource(sim_config.playfield());
+    if let (None, Some(file_seed)) = (seed, sim_config.seed) {
+        app.insert_resource(SimSeed(file_seed));
+    }
 }
@@ -0,0 +1,264 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    math::Rect,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_resource::TextureFormat,
+        texture::{CompressedImageFormats, ImageType},
+    },
+    utils::{BoxedFuture, HashMap},
+};
+
+use crate::{
+    bounds::Bounds,
+    obstacle::{spawn_obstacle, Obstacle},
+    render::{FlockView, RenderOrder},
+};
+
+/// Walls laid out on a grid, drawn in a text file ending in `.map.txt`,
+/// where `#` is a wall and any other character open floor, or in an image
+/// ending in `.map.png`, where dark pixels are walls and light or
+/// transparent ones floor. Inserted as a resource, its walls are spawned as
+/// `Obstacle`s stretched over the bounds, with square cells, and replaced
+/// whenever it changes.
+#[derive(Resource, Clone, TypeUuid)]
+#[uuid = "c4a7e2d9-51f3-4b8e-a0d6-93e1f5b27c48"]
+pub struct ObstacleMap {
+    pub width: usize,
+    pub height: usize,
+    // whether each cell is a wall, row by row from the top
+    walls: Vec<bool>,
+}
+
+impl ObstacleMap {
+    /// Map of a text grid, as wide as its longest line.
+    pub fn from_text(text: &str) -> Self {
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut walls = vec![false; width * lines.len()];
+        for (y, line) in lines.iter().enumerate() {
+            for (x, cell) in line.chars().enumerate() {
+                walls[y * width + x] = cell == '#';
+            }
+        }
+        Self {
+            width,
+            height: lines.len(),
+            walls,
+        }
+    }
+
+    /// Map of an image, one cell per pixel.
+    pub fn from_image(image: &Image) -> Result<Self, String> {
+        if !matches!(
+            image.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+        ) {
+            return Err(format!(
+                "maps are 8 bit images, not {:?}",
+                image.texture_descriptor.format
+            ));
+        }
+        let size = image.texture_descriptor.size;
+        let walls = image
+            .data
+            .chunks_exact(4)
+            .map(|pixel| {
+                let luma = (pixel[0] as f32 + pixel[1] as f32 + pixel[2] as f32) / (3. * 255.);
+                pixel[3] >= 128 && luma < 0.5
+            })
+            .collect();
+        Ok(Self {
+            width: size.width as usize,
+            height: size.height as usize,
+            walls,
+        })
+    }
+
+    /// Map of the contents of the file at `path`, an image or text by its
+    /// extension.
+    pub fn read(path: &str, bytes: &[u8]) -> Result<Self, String> {
+        if !path.to_lowercase().ends_with(".png") {
+            return Ok(Self::from_text(&String::from_utf8_lossy(bytes)));
+        }
+        let image = Image::from_buffer(
+            bytes,
+            ImageType::Extension("png"),
+            CompressedImageFormats::NONE,
+            true,
+        )
+        .map_err(|error| error.to_string())?;
+        Self::from_image(&image)
+    }
+
+    pub fn is_wall(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.walls[y * self.width + x]
+    }
+
+    /// The walls as rectangles, in cells from the top left corner: each run
+    /// of walls along a row, merged with the identical runs of the rows
+    /// below.
+    pub fn wall_rects(&self) -> Vec<Rect> {
+        let mut rects: Vec<Rect> = Vec::new();
+        // rectangles reaching the row above, by the columns they span
+        let mut open: HashMap<(usize, usize), usize> = HashMap::new();
+        for y in 0..self.height {
+            let mut reaching = HashMap::new();
+            let mut x = 0;
+            while x < self.width {
+                if !self.is_wall(x, y) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while self.is_wall(x, y) {
+                    x += 1;
+                }
+                let index = match open.get(&(start, x)) {
+                    Some(&index) => {
+                        rects[index].max.y += 1.;
+                        index
+                    }
+                    None => {
+                        rects.push(Rect::new(start as f32, y as f32, x as f32, y as f32 + 1.));
+                        rects.len() - 1
+                    }
+                };
+                reaching.insert((start, x), index);
+            }
+            open = reaching;
+        }
+        rects
+    }
+}
+
+/// Path of an `ObstacleMap` to load, relative to the assets folder, with
+/// `BoidPlugin`. The map is loaded again whenever the file changes if the
+/// `AssetPlugin` watches for changes.
+#[derive(Resource)]
+pub struct ObstacleMapFile(pub String);
+
+#[derive(Resource)]
+pub(crate) struct ObstacleMapHandle(Handle<ObstacleMap>);
+
+/// Wall spawned from the `ObstacleMap`.
+#[derive(Component)]
+pub struct MapWall;
+
+#[derive(Default)]
+pub(crate) struct ObstacleMapLoader;
+
+impl AssetLoader for ObstacleMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_string_lossy();
+            let map = ObstacleMap::read(&path, bytes).map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        // plain images are left to the image loader
+        &["map.txt", "map.png"]
+    }
+}
+
+pub(crate) fn load_obstacle_map(
+    mut commands: Commands,
+    file: Option<Res<ObstacleMapFile>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(file) = file else {
+        return;
+    };
+    if file.is_changed() {
+        let handle = asset_server.load(file.0.as_str());
+        commands.insert_resource(ObstacleMapHandle(handle));
+    }
+}
+
+/// Inserts the map of `ObstacleMapFile` once loaded, and again whenever the
+/// file changes.
+pub(crate) fn apply_obstacle_map(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<ObstacleMap>>,
+    handle: Option<Res<ObstacleMapHandle>>,
+    maps: Res<Assets<ObstacleMap>>,
+) {
+    let Some(handle) = handle else {
+        events.clear();
+        return;
+    };
+    let loaded = events
+        .iter()
+        .filter(|event| match event {
+            AssetEvent::Created { handle: loaded } | AssetEvent::Modified { handle: loaded } => {
+                *loaded == handle.0
+            }
+            AssetEvent::Removed { .. } => false,
+        })
+        .count();
+    // a map loaded before, picked again, sends no event
+    if loaded == 0 && !handle.is_changed() {
+        return;
+    }
+    if let Some(map) = maps.get(&handle.0) {
+        commands.insert_resource(map.clone());
+    }
+}
+
+/// Replaces the walls with those of the `ObstacleMap` when it changes.
+pub(crate) fn spawn_map_walls(
+    mut commands: Commands,
+    map: Option<Res<ObstacleMap>>,
+    bounds: Res<Bounds>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    walls: Query<Entity, With<MapWall>>,
+) {
+    let Some(map) = map else {
+        return;
+    };
+    if !map.is_changed() || map.width == 0 || map.height == 0 {
+        return;
+    }
+    for wall in walls.iter() {
+        commands.entity(wall).despawn_recursive();
+    }
+
+    let size = bounds.max - bounds.min;
+    let cell = (size.x / map.width as f32).min(size.y / map.height as f32);
+    let top_left = (bounds.min + bounds.max) / 2.
+        + Vec2::new(-(map.width as f32), map.height as f32) * cell / 2.;
+    for rect in map.wall_rects() {
+        // rows count down from the top
+        let min = top_left + Vec2::new(rect.min.x, -rect.max.y) * cell;
+        let max = top_left + Vec2::new(rect.max.x, -rect.min.y) * cell;
+        let center = (min + max) / 2.;
+        let half = (max - min) / 2.;
+        let points = vec![
+            -half,
+            Vec2::new(half.x, -half.y),
+            half,
+            Vec2::new(-half.x, half.y),
+        ];
+        let wall = spawn_obstacle(
+            &mut commands,
+            &view,
+            &order,
+            Obstacle::Polygon { points },
+            center,
+        );
+        commands.entity(wall).insert(MapWall);
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::Boid,
+    bounds::Bounds,
+    render::{FlockView, MainCamera, Overlay, RenderOrder},
+};
+
+/// Map in the bottom left corner of the view, toggled with `F6`: every boid
+/// is a dot on it, along with the bounds and the rectangle the main camera
+/// sees, so the flock is never lost however far the view is panned or
+/// zoomed. It covers the bounds, the flock and the view, whichever is
+/// larger.
+#[derive(Resource)]
+pub struct Minimap {
+    pub visible: bool,
+    // side of the map, in pixels
+    pub size: f32,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            size: 160.,
+        }
+    }
+}
+
+/// Pins the parts of the minimap to the corner of the view.
+#[derive(Component)]
+pub(crate) struct MinimapFrame;
+
+/// The parts of the minimap, drawn over one another.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MinimapLayer {
+    Background,
+    Bounds,
+    Boids,
+    View,
+}
+
+pub(crate) fn spawn_minimap(mut commands: Commands, order: Res<RenderOrder>, view: Res<FlockView>) {
+    let layers = [
+        (
+            MinimapLayer::Background,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::rgba(0., 0., 0., 0.6)),
+                outline_mode: StrokeMode::new(Color::rgba(1., 1., 1., 0.4), 1.),
+            },
+        ),
+        (
+            MinimapLayer::Bounds,
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(0.6, 0.8, 1., 0.5), 1.)),
+        ),
+        (
+            MinimapLayer::Boids,
+            DrawMode::Fill(FillMode::color(Color::rgba(1., 1., 1., 0.9))),
+        ),
+        (
+            MinimapLayer::View,
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(1., 0.85, 0.3, 0.9), 1.)),
+        ),
+    ];
+
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            0.,
+            0.,
+            order.overlays,
+        )))
+        .insert(Overlay)
+        .insert(MinimapFrame)
+        .with_children(|frame| {
+            for (depth, (layer, mode)) in layers.into_iter().enumerate() {
+                frame
+                    .spawn(GeometryBuilder::build_as(
+                        &PathBuilder::new().build(),
+                        mode,
+                        Transform::from_xyz(0., 0., depth as f32 * 0.1),
+                    ))
+                    .insert(view.layers)
+                    .insert(layer);
+            }
+        });
+}
+
+pub(crate) fn toggle_minimap(keyboard: Res<Input<KeyCode>>, mut minimap: ResMut<Minimap>) {
+    if keyboard.just_pressed(KeyCode::F6) {
+        minimap.visible = !minimap.visible;
+    }
+}
+
+/// Keeps the minimap pinned to the corner of the main camera's view, like
+/// the wind arrow, and draws it again every frame.
+pub(crate) fn draw_minimap(
+    minimap: Res<Minimap>,
+    bounds: Res<Bounds>,
+    camera: Query<(&Camera, &Transform, &OrthographicProjection), With<MainCamera>>,
+    boids: Query<&Transform, (With<Boid>, Without<MinimapFrame>)>,
+    mut frame: Query<&mut Transform, (With<MinimapFrame>, Without<MainCamera>)>,
+    mut visibility: Query<&mut Visibility, With<MinimapFrame>>,
+    mut layers: Query<(&MinimapLayer, &mut Path)>,
+) {
+    // in pixels: the gap to the corner, the space kept around the map's
+    // contents and the side of a boid's dot
+    const MARGIN: f32 = 16.;
+    const PADDING: f32 = 6.;
+    const DOT: f32 = 2.;
+
+    let Ok((camera, camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+    let (Ok(mut transform), Ok(mut visibility)) =
+        (frame.get_single_mut(), visibility.get_single_mut())
+    else {
+        return;
+    };
+    visibility.is_visible = minimap.visible;
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    if !minimap.visible {
+        return;
+    }
+
+    let half = minimap.size / 2.;
+    let corner = -viewport / 2. + Vec2::splat(MARGIN + half);
+    let offset = camera_transform.rotation * (corner * projection.scale).extend(0.);
+    transform.translation = (camera_transform.translation + offset)
+        .truncate()
+        .extend(transform.translation.z);
+    transform.rotation = camera_transform.rotation;
+    transform.scale = Vec3::new(projection.scale, projection.scale, 1.);
+
+    // the corners of what the camera sees, in the world
+    let center = camera_transform.translation.truncate();
+    let seen = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)].map(|(x, y)| {
+        let local = Vec2::new(x, y) * viewport / 2. * projection.scale;
+        center + (camera_transform.rotation * local.extend(0.)).truncate()
+    });
+    let positions: Vec<Vec2> = boids
+        .iter()
+        .map(|boid| boid.translation.truncate())
+        .collect();
+
+    let (mut min, mut max) = (bounds.min, bounds.max);
+    for point in seen.iter().chain(&positions) {
+        min = min.min(*point);
+        max = max.max(*point);
+    }
+    let middle = (min + max) / 2.;
+    let scale = (half - PADDING) * 2. / (max - min).max_element().max(1.);
+    // the map turns with the view, so undo the camera's rotation
+    let upright = camera_transform.rotation.inverse();
+    let to_map = |point: Vec2| {
+        let local = (point - middle) * scale;
+        (upright * local.extend(0.)).truncate()
+    };
+
+    let square = |builder: &mut PathBuilder, center: Vec2, half: f32| {
+        builder.move_to(center + Vec2::new(-half, -half));
+        builder.line_to(center + Vec2::new(half, -half));
+        builder.line_to(center + Vec2::new(half, half));
+        builder.line_to(center + Vec2::new(-half, half));
+        builder.close();
+    };
+    let outline = |builder: &mut PathBuilder, corners: &[Vec2; 4]| {
+        builder.move_to(to_map(corners[0]));
+        for corner in &corners[1..] {
+            builder.line_to(to_map(*corner));
+        }
+        builder.close();
+    };
+    let bounds_corners = [
+        bounds.min,
+        Vec2::new(bounds.max.x, bounds.min.y),
+        bounds.max,
+        Vec2::new(bounds.min.x, bounds.max.y),
+    ];
+    for (layer, mut path) in layers.iter_mut() {
+        let mut builder = PathBuilder::new();
+        match layer {
+            MinimapLayer::Background => square(&mut builder, Vec2::ZERO, half),
+            MinimapLayer::Bounds => outline(&mut builder, &bounds_corners),
+            MinimapLayer::Boids => {
+                for position in &positions {
+                    square(&mut builder, to_map(*position), DOT / 2.);
+                }
+            }
+            MinimapLayer::View => outline(&mut builder, &seen),
+        }
+        *path = builder.build();
+    }
+}
@@ -0,0 +1,299 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Steering},
+    bounds::Bounds,
+    obstacle::Obstacle,
+    path::{FollowPath, PathMode},
+    playfield::Playfield,
+};
+
+/// Path finding to the target around obstacles, for mazes where avoidance
+/// alone leaves boids stuck in dead ends. The bounds are cut into square
+/// cells of `cell_size`, those closer than `clearance` to an obstacle, or
+/// outside the playfield, are blocked, and each boid gets a `FollowPath`
+/// along the shortest way through the free ones (A*). Paths are planned
+/// again when the target moves by more than a cell or the obstacles change.
+/// `M` switches it on and off.
+#[derive(Resource)]
+pub struct Navigation {
+    pub enabled: bool,
+    pub cell_size: f32,
+    pub clearance: f32,
+    // a waypoint closer than this counts as reached, wide enough for a
+    // crowd funneling through it
+    pub waypoint_radius: f32,
+}
+
+impl Default for Navigation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cell_size: 20.,
+            clearance: 25.,
+            waypoint_radius: 60.,
+        }
+    }
+}
+
+/// Plan a boid's `FollowPath` was made from.
+#[derive(Component)]
+pub(crate) struct Navigating {
+    target: Vec2,
+    grid: u32,
+}
+
+/// Boids done with their path that stray further than this many cells from
+/// the target plan a new one.
+const STRAY_CELLS: f32 = 5.;
+
+/// Cost of a step to a side cell, and to a diagonal one.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Blocked and free cells of the bounds, see `Navigation`.
+#[derive(Resource, Default)]
+pub(crate) struct NavGrid {
+    origin: Vec2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+    // bumped on every rebuild, to tell paths planned on an older grid
+    version: u32,
+}
+
+impl NavGrid {
+    /// Cell holding `position`, the nearest one outside the grid.
+    fn cell(&self, position: Vec2) -> (usize, usize) {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        (
+            (cell.x.max(0.) as usize).min(self.columns - 1),
+            (cell.y.max(0.) as usize).min(self.rows - 1),
+        )
+    }
+
+    fn center(&self, (column, row): (usize, usize)) -> Vec2 {
+        self.origin + (Vec2::new(column as f32, row as f32) + 0.5) * self.cell_size
+    }
+
+    fn index(&self, (column, row): (usize, usize)) -> usize {
+        row * self.columns + column
+    }
+
+    /// Whether the straight line from `from` to `to` keeps to free cells,
+    /// leaving out the cells of its ends.
+    fn clear_between(&self, from: Vec2, to: Vec2) -> bool {
+        let (start, end) = (self.cell(from), self.cell(to));
+        let steps = (from.distance(to) / (self.cell_size * 0.5)).ceil() as usize;
+        (1..steps).all(|step| {
+            let cell = self.cell(from.lerp(to, step as f32 / steps as f32));
+            cell == start || cell == end || !self.blocked[self.index(cell)]
+        })
+    }
+
+    /// Shortest way from `from` to `to` through free cells, as waypoints
+    /// ending on `to`, or `None` if the cell of `to` cannot be reached.
+    pub(crate) fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        if self.blocked.is_empty() {
+            return None;
+        }
+        let (start, goal) = (self.cell(from), self.cell(to));
+        let (start_index, goal_index) = (self.index(start), self.index(goal));
+        let estimate = |(column, row): (usize, usize)| {
+            let dx = column.abs_diff(goal.0) as u32;
+            let dy = row.abs_diff(goal.1) as u32;
+            STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+        };
+
+        let mut costs = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        costs[start_index] = 0;
+        open.push(Reverse((estimate(start), start_index)));
+        while let Some(Reverse((_, current))) = open.pop() {
+            if current == goal_index {
+                break;
+            }
+            let (column, row) = (current % self.columns, current / self.columns);
+            for (dx, dy) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ] {
+                let (Some(x), Some(y)) = (
+                    column.checked_add_signed(dx).filter(|x| *x < self.columns),
+                    row.checked_add_signed(dy).filter(|y| *y < self.rows),
+                ) else {
+                    continue;
+                };
+                let next = self.index((x, y));
+                if self.blocked[next] && next != goal_index {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                // no cutting corners past a blocked cell
+                if diagonal
+                    && (self.blocked[self.index((x, row))] || self.blocked[self.index((column, y))])
+                {
+                    continue;
+                }
+                let step = if diagonal {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let cost = costs[current] + step;
+                if cost < costs[next] {
+                    costs[next] = cost;
+                    came_from[next] = current;
+                    open.push(Reverse((cost + estimate((x, y)), next)));
+                }
+            }
+        }
+        if costs[goal_index] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        let mut current = goal_index;
+        while current != start_index {
+            cells.push(self.center((current % self.columns, current / self.columns)));
+            current = came_from[current];
+        }
+        cells.reverse();
+        match cells.last_mut() {
+            Some(last) => *last = to,
+            None => cells.push(to),
+        }
+
+        // skip the waypoints in sight of the previous one
+        let mut waypoints = Vec::new();
+        let mut anchor = from;
+        let mut next = 0;
+        while next < cells.len() {
+            let furthest = (next + 1..cells.len())
+                .rev()
+                .find(|index| self.clear_between(anchor, cells[*index]))
+                .unwrap_or(next);
+            anchor = cells[furthest];
+            waypoints.push(anchor);
+            next = furthest + 1;
+        }
+        Some(waypoints)
+    }
+}
+
+/// Rebuilds the grid when obstacles are placed or removed, or the bounds,
+/// playfield or navigation settings change.
+pub(crate) fn build_nav_grid(
+    navigation: Res<Navigation>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    mut grid: ResMut<NavGrid>,
+    mut obstacle_count: Local<usize>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+    changed: Query<(), Changed<Obstacle>>,
+) {
+    if !navigation.enabled {
+        return;
+    }
+    let count = obstacles.iter().len();
+    let stale = grid.version == 0
+        || navigation.is_changed()
+        || bounds.is_changed()
+        || playfield.is_changed()
+        || !changed.is_empty()
+        || count != *obstacle_count;
+    if !stale {
+        return;
+    }
+    *obstacle_count = count;
+
+    let cell_size = navigation.cell_size.max(1.);
+    let size = ((bounds.max - bounds.min) / cell_size)
+        .ceil()
+        .max(Vec2::ONE);
+    let mut rebuilt = NavGrid {
+        origin: bounds.min,
+        cell_size,
+        columns: size.x as usize,
+        rows: size.y as usize,
+        blocked: Vec::new(),
+        version: grid.version.wrapping_add(1).max(1),
+    };
+    rebuilt.blocked = (0..rebuilt.rows)
+        .flat_map(|row| (0..rebuilt.columns).map(move |column| (column, row)))
+        .map(|cell| {
+            let center = rebuilt.center(cell);
+            (playfield.is_active() && !playfield.contains(center))
+                || obstacles.iter().any(|(transform, obstacle)| {
+                    obstacle.distance(transform.translation.truncate(), center)
+                        < navigation.clearance
+                })
+        })
+        .collect();
+    *grid = rebuilt;
+}
+
+/// Gives boids a path to their target, planned again once it goes stale or
+/// they stray from the end of it, and takes it away when navigation is
+/// switched off or the target is out of range. Boids for which there is no
+/// way keep seeking the target.
+pub(crate) fn plan_paths(
+    mut commands: Commands,
+    navigation: Res<Navigation>,
+    grid: Res<NavGrid>,
+    boids: Query<(Entity, &Transform, &Steering, Option<&Navigating>), With<Boid>>,
+    paths: Query<&FollowPath>,
+) {
+    for (entity, transform, steering, navigating) in boids.iter() {
+        let in_range = transform.translation.distance(steering.target) <= steering.max_seek_range;
+        if !navigation.enabled || !in_range {
+            if navigating.is_some() {
+                commands.entity(entity).remove::<(Navigating, FollowPath)>();
+            }
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let target = steering.target.truncate();
+        let strayed = paths.get(entity).is_ok_and(|path| path.current().is_none())
+            && position.distance(target) > navigation.cell_size * STRAY_CELLS;
+        let stale = strayed
+            || navigating.is_none_or(|navigating| {
+                navigating.grid != grid.version
+                    || navigating.target.distance(target) > navigation.cell_size
+            });
+        if !stale {
+            continue;
+        }
+        let mut boid = commands.entity(entity);
+        // removed first, so the new path counts as added and gets redrawn
+        boid.remove::<FollowPath>().insert(Navigating {
+            target,
+            grid: grid.version,
+        });
+        if let Some(waypoints) = grid.find_path(position, target) {
+            boid.insert(FollowPath::new(
+                waypoints,
+                PathMode::Once,
+                navigation.waypoint_radius,
+            ));
+        }
+    }
+}
+
+/// `M` switches navigation on and off.
+pub(crate) fn toggle_navigation(keyboard: Res<Input<KeyCode>>, mut navigation: ResMut<Navigation>) {
+    if keyboard.just_pressed(KeyCode::M) {
+        navigation.enabled = !navigation.enabled;
+    }
+}
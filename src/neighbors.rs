@@ -0,0 +1,319 @@
+use std::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+// std's panics in browsers
+use bevy::{math::Rect, prelude::*, utils::HashMap, utils::Instant};
+
+use crate::{
+    boid::{BodySize, Boid, BoidId, Physics},
+    diagnostics::SteeringTimings,
+    flock::{FlockConfig, FlockId},
+    flocking::{influencing_neighbors, neighbor_radii},
+    grid::{GridEntry, SpatialGrid},
+    lod::Lod,
+    perception::Perception,
+    quadtree::Quadtree,
+    species::{Species, SpeciesRelations},
+    steering::PAR_BATCH_SIZE,
+    tick::SimTick,
+};
+
+/// Structure boids are sorted into to find their neighbors quickly.
+pub trait NeighborIndex: Send + Sync {
+    /// Forgets every boid.
+    fn clear(&mut self);
+
+    /// Replaces the indexed boids with `entries`. `radius` is the largest
+    /// radius lookups usually ask for, for structures that can be tuned to
+    /// it.
+    fn rebuild(&mut self, entries: Vec<GridEntry>, radius: f32);
+
+    /// Boids closer than `radius` to `position`.
+    fn neighbors_within(
+        &self,
+        position: Vec3,
+        radius: f32,
+    ) -> Box<dyn Iterator<Item = &GridEntry> + '_>;
+
+    /// Boids other than `entity` closer than `radius` to `position`.
+    fn others_within(
+        &self,
+        entity: Entity,
+        position: Vec3,
+        radius: f32,
+    ) -> Box<dyn Iterator<Item = &GridEntry> + '_> {
+        Box::new(
+            self.neighbors_within(position, radius)
+                .filter(move |entry| entry.entity != entity),
+        )
+    }
+
+    /// Areas of the structure holding boids (grid cells, tree leaves...),
+    /// for debugging.
+    fn outlines(&self) -> Vec<Rect>;
+}
+
+/// Which `NeighborIndex` the `SpatialIndex` uses. `N` switches between them.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NeighborBackend {
+    /// `SpatialGrid`, sized to the neighbor radius.
+    #[default]
+    Grid,
+    /// `Quadtree`, better at very uneven flocks.
+    Quadtree,
+}
+
+impl NeighborBackend {
+    pub fn next(self) -> Self {
+        match self {
+            NeighborBackend::Grid => NeighborBackend::Quadtree,
+            NeighborBackend::Quadtree => NeighborBackend::Grid,
+        }
+    }
+
+    fn index(self) -> Box<dyn NeighborIndex> {
+        match self {
+            NeighborBackend::Grid => Box::<SpatialGrid>::default(),
+            NeighborBackend::Quadtree => Box::<Quadtree>::default(),
+        }
+    }
+}
+
+/// Every boid as of the start of the current step, in the `NeighborIndex`
+/// picked by `NeighborBackend`.
+#[derive(Resource)]
+pub struct SpatialIndex {
+    backend: NeighborBackend,
+    index: Box<dyn NeighborIndex>,
+    // the same entries by boid, for `NeighborCache`
+    entries: HashMap<Entity, GridEntry>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        let backend = NeighborBackend::default();
+        Self {
+            backend,
+            index: backend.index(),
+            entries: HashMap::default(),
+        }
+    }
+}
+
+impl SpatialIndex {
+    /// The entry of `entity`, if it was indexed.
+    pub fn get(&self, entity: Entity) -> Option<&GridEntry> {
+        self.entries.get(&entity)
+    }
+}
+
+impl NeighborIndex for SpatialIndex {
+    fn clear(&mut self) {
+        self.index.clear();
+        self.entries.clear();
+    }
+
+    fn rebuild(&mut self, entries: Vec<GridEntry>, radius: f32) {
+        self.entries = entries.iter().map(|entry| (entry.entity, *entry)).collect();
+        self.index.rebuild(entries, radius);
+    }
+
+    fn neighbors_within(
+        &self,
+        position: Vec3,
+        radius: f32,
+    ) -> Box<dyn Iterator<Item = &GridEntry> + '_> {
+        self.index.neighbors_within(position, radius)
+    }
+
+    fn outlines(&self) -> Vec<Rect> {
+        self.index.outlines()
+    }
+}
+
+pub(crate) fn rebuild_spatial_index(
+    config: Res<FlockConfig>,
+    backend: Res<NeighborBackend>,
+    mut index: ResMut<SpatialIndex>,
+    boids: Query<(Entity, &Transform, &Physics), With<Boid>>,
+    membership: Query<(Option<&Species>, Option<&FlockId>)>,
+) {
+    if index.backend != *backend {
+        *index = SpatialIndex {
+            backend: *backend,
+            index: backend.index(),
+            entries: HashMap::default(),
+        };
+    }
+
+    let entries = boids
+        .iter()
+        .map(|(entity, transform, physics)| {
+            let (species, flock) = membership.get(entity).unwrap_or_default();
+            GridEntry {
+                entity,
+                position: transform.translation,
+                velocity: physics.velocity,
+                species: species.copied().unwrap_or_default(),
+                flock: flock.copied().unwrap_or_default(),
+            }
+        })
+        .collect();
+    index.rebuild(
+        entries,
+        config.neighbor_radius.max(config.separation_radius),
+    );
+}
+
+/// Neighbors a boid heeds, as looked up in the `SpatialIndex`: the crowd it
+/// separates from and the flockmates it aligns with and gathers around. They
+/// are looked up again every `FlockConfig::neighbor_refresh` steps,
+/// staggered across the flock, and only read from the index in between, so
+/// boids see where their neighbors are now if not who they are.
+#[derive(Component, Default)]
+pub struct NeighborCache {
+    pub crowd: Vec<Entity>,
+    pub flockmates: Vec<Entity>,
+    // radii the crowd and flockmates were looked up within
+    pub crowd_radius: f32,
+    pub flockmates_radius: f32,
+}
+
+impl NeighborCache {
+    /// The crowd and flockmates of a boid at `position` as of the start of
+    /// the step, leaving out those since despawned or gone further than the
+    /// radius they were looked up within.
+    pub fn resolve<'a>(
+        &self,
+        index: &'a SpatialIndex,
+        position: Vec3,
+    ) -> (Vec<&'a GridEntry>, Vec<&'a GridEntry>) {
+        let entries = |entities: &[Entity], radius: f32| {
+            entities
+                .iter()
+                .filter_map(|entity| index.get(*entity))
+                .filter(|entry| entry.position.distance(position) < radius)
+                .collect()
+        };
+        (
+            entries(&self.crowd, self.crowd_radius),
+            entries(&self.flockmates, self.flockmates_radius),
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn refresh_neighbor_caches(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    relations: Res<SpeciesRelations>,
+    boids: Query<(&Transform, &Physics, Option<&Perception>), With<Boid>>,
+    ids: Query<&BoidId>,
+    lods: Query<&Lod>,
+    memberships: Query<(Option<&Species>, Option<&FlockId>, Option<&BodySize>)>,
+    mut caches: Query<(Entity, &mut NeighborCache)>,
+    mut timings: ResMut<SteeringTimings>,
+) {
+    // summed over the parallel tasks
+    let query_nanos = AtomicU64::new(0);
+    let queries = AtomicU32::new(0);
+    let refresh = config.neighbor_refresh.max(1) as u64;
+    let step = tick.steps();
+
+    caches.par_for_each_mut(PAR_BATCH_SIZE, |(entity, mut cache)| {
+        // new boids look right away, the others in turns
+        let due = ids
+            .get(entity)
+            .map_or(true, |id| (step + id.0 as u64).is_multiple_of(refresh));
+        if !due || lods.get(entity).is_ok_and(Lod::skips_steering) {
+            return;
+        }
+        let Ok((transform, physics, perception)) = boids.get(entity) else {
+            return;
+        };
+        let (species, flock, size) = memberships.get(entity).unwrap_or_default();
+        let size = size.map_or(1., |size| size.0);
+
+        let started = Instant::now();
+        let (crowd, flockmates) = influencing_neighbors(
+            &index,
+            &config,
+            &relations,
+            entity,
+            transform,
+            physics,
+            perception,
+            species.copied().unwrap_or_default(),
+            flock.copied().unwrap_or_default(),
+            size,
+        );
+        cache.crowd = crowd.iter().map(|other| other.entity).collect();
+        cache.flockmates = flockmates.iter().map(|other| other.entity).collect();
+        (cache.crowd_radius, cache.flockmates_radius) = neighbor_radii(&config, perception, size);
+        query_nanos.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        queries.fetch_add(1, Ordering::Relaxed);
+    });
+
+    timings.neighbor_queries = Duration::from_nanos(query_nanos.into_inner());
+    timings.queries = queries.into_inner();
+}
+
+pub(crate) fn cycle_neighbor_backend(
+    keyboard: Res<Input<KeyCode>>,
+    mut backend: ResMut<NeighborBackend>,
+) {
+    if keyboard.just_pressed(KeyCode::N) {
+        *backend = backend.next();
+        info!("neighbor search: {:?}", *backend);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entity: Entity, position: Vec3) -> GridEntry {
+        GridEntry {
+            entity,
+            position,
+            velocity: Vec3::ZERO,
+            species: Species::default(),
+            flock: FlockId::default(),
+        }
+    }
+
+    #[test]
+    fn resolve_drops_neighbors_gone_out_of_range() {
+        let (near, drifted, gone) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let cache = NeighborCache {
+            crowd: vec![near, drifted, gone],
+            flockmates: vec![near, drifted, gone],
+            crowd_radius: 30.,
+            flockmates_radius: 100.,
+        };
+        // since the cache was refreshed, one neighbor flew off and one was
+        // despawned
+        let mut index = SpatialIndex::default();
+        index.rebuild(
+            vec![
+                entry(near, Vec3::new(20., 0., 0.)),
+                entry(drifted, Vec3::new(0., 60., 0.)),
+            ],
+            100.,
+        );
+
+        let (crowd, flockmates) = cache.resolve(&index, Vec3::ZERO);
+        let entities = |entries: Vec<&GridEntry>| -> Vec<Entity> {
+            entries.iter().map(|entry| entry.entity).collect()
+        };
+        assert_eq!(entities(crowd), vec![near]);
+        assert_eq!(entities(flockmates), vec![near, drifted]);
+    }
+}
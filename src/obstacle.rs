@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    lod::Lod,
+    playfield::polygon_contains,
+    render::{CursorPosition, FlockView, Overlay, RenderOrder},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Priority of obstacle and edge avoidance in `SteeringForces`, above
+/// everything else: boids cannot fly through walls, not even to escape a
+/// predator.
+pub(crate) const AVOIDANCE_PRIORITY: i32 = 3;
+
+/// Room kept between a boid's center and obstacles, about half a boid.
+const CLEARANCE: f32 = 25.;
+
+/// Solid shape boids steer around, centered on the entity's `Transform`.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub enum Obstacle {
+    Circle {
+        radius: f32,
+    },
+    /// Closed polygon, points relative to the entity.
+    Polygon {
+        points: Vec<Vec2>,
+    },
+    /// Open chain of segments, points relative to the entity.
+    Polyline {
+        points: Vec<Vec2>,
+    },
+}
+
+/// Preview of the obstacle being drawn with the mouse.
+#[derive(Component)]
+pub(crate) struct ObstacleSketch;
+
+/// Obstacle being drawn: where the drag started and where it is now, and the
+/// points it went through when drawing a polyline.
+#[derive(Default)]
+pub(crate) struct Sketch {
+    start: Option<Vec2>,
+    end: Vec2,
+    polyline: bool,
+    points: Vec<Vec2>,
+}
+
+/// Where a look-ahead probe first touches an obstacle.
+pub(crate) struct Hit {
+    // fraction of the probe travelled before the hit, from 0 to 1
+    pub t: f32,
+    // surface normal at the hit, pointing out of the obstacle
+    pub normal: Vec2,
+}
+
+impl Obstacle {
+    /// First intersection of the segment from `from` to `to` with this
+    /// obstacle placed at `center`. Circles are grown by `CLEARANCE`, polygon
+    /// edges are probed as they are.
+    pub(crate) fn probe(&self, center: Vec2, from: Vec2, to: Vec2) -> Option<Hit> {
+        match self {
+            Obstacle::Circle { radius } => probe_circle(center, radius + CLEARANCE, from, to),
+            Obstacle::Polygon { points } => {
+                let edges = points.iter().zip(points.iter().cycle().skip(1));
+                probe_edges(edges, center, from, to)
+            }
+            Obstacle::Polyline { points } => {
+                let edges = points.iter().zip(points.iter().skip(1));
+                probe_edges(edges, center, from, to)
+            }
+        }
+    }
+
+    /// Where a boid moving from `from` to `to` runs into this obstacle placed
+    /// at `center`, as `probe` finds it. Boids already inside, caught there
+    /// as the obstacle was drawn, are let out.
+    pub(crate) fn sweep(&self, center: Vec2, from: Vec2, to: Vec2) -> Option<Hit> {
+        let inside = match self {
+            Obstacle::Circle { radius } => from.distance(center) < radius + CLEARANCE,
+            Obstacle::Polygon { points } => polygon_contains(points, from - center),
+            Obstacle::Polyline { .. } => false,
+        };
+        match inside {
+            true => None,
+            false => self.probe(center, from, to),
+        }
+    }
+
+    /// Distance from `point` to this obstacle placed at `center`, 0 inside
+    /// it.
+    pub(crate) fn distance(&self, center: Vec2, point: Vec2) -> f32 {
+        let local = point - center;
+        match self {
+            Obstacle::Circle { radius } => (local.length() - radius).max(0.),
+            Obstacle::Polygon { points } => {
+                if polygon_contains(points, local) {
+                    return 0.;
+                }
+                let edges = points.iter().zip(points.iter().cycle().skip(1));
+                distance_to_edges(edges, local)
+            }
+            Obstacle::Polyline { points } => {
+                let edges = points.iter().zip(points.iter().skip(1));
+                distance_to_edges(edges, local)
+            }
+        }
+    }
+}
+
+fn distance_to_edges<'a>(edges: impl Iterator<Item = (&'a Vec2, &'a Vec2)>, point: Vec2) -> f32 {
+    edges
+        .map(|(a, b)| {
+            let edge = *b - *a;
+            let t =
+                ((point - *a).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+            point.distance(*a + edge * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+pub(crate) fn probe_edges<'a>(
+    edges: impl Iterator<Item = (&'a Vec2, &'a Vec2)>,
+    center: Vec2,
+    from: Vec2,
+    to: Vec2,
+) -> Option<Hit> {
+    edges
+        .filter_map(|(a, b)| probe_edge(center + *a, center + *b, from, to))
+        .min_by(|a, b| a.t.total_cmp(&b.t))
+}
+
+fn probe_circle(center: Vec2, radius: f32, from: Vec2, to: Vec2) -> Option<Hit> {
+    let probe = to - from;
+    let length = probe.length();
+    if length <= f32::EPSILON {
+        return None;
+    }
+
+    // closest approach of the probe line to the center
+    let along = (center - from).dot(probe) / length;
+    let closest = from + probe / length * along.clamp(0., length);
+    let offset = closest - center;
+    if offset.length() >= radius {
+        return None;
+    }
+
+    // head-on probes get an arbitrary but consistent side
+    let normal = offset
+        .try_normalize()
+        .unwrap_or_else(|| probe.perp().normalize());
+    Some(Hit {
+        t: (along / length).clamp(0., 1.),
+        normal,
+    })
+}
+
+fn probe_edge(a: Vec2, b: Vec2, from: Vec2, to: Vec2) -> Option<Hit> {
+    let probe = to - from;
+    let edge = b - a;
+    let denominator = probe.perp_dot(edge);
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let t = (a - from).perp_dot(edge) / denominator;
+    let u = (a - from).perp_dot(probe) / denominator;
+    if !(0. ..=1.).contains(&t) || !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    // face the normal towards the boid, whatever the winding of the polygon
+    let mut normal = edge.perp().normalize();
+    if normal.dot(probe) > 0. {
+        normal = -normal;
+    }
+    Some(Hit { t, normal })
+}
+
+/// Spawns an obstacle drawn like the rest of the scene.
+pub fn spawn_obstacle(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    obstacle: Obstacle,
+    position: Vec2,
+) -> Entity {
+    let solid = DrawMode::Outlined {
+        fill_mode: FillMode::color(Color::DARK_GRAY),
+        outline_mode: StrokeMode::new(Color::GRAY, 2.),
+    };
+    let (geometry, mode) = match &obstacle {
+        Obstacle::Circle { radius } => (
+            GeometryBuilder::new().add(&shapes::Circle {
+                radius: *radius,
+                ..default()
+            }),
+            solid,
+        ),
+        Obstacle::Polygon { points } => (
+            GeometryBuilder::new().add(&shapes::Polygon {
+                points: points.clone(),
+                closed: true,
+            }),
+            solid,
+        ),
+        Obstacle::Polyline { points } => (
+            GeometryBuilder::new().add(&shapes::Polygon {
+                points: points.clone(),
+                closed: false,
+            }),
+            DrawMode::Stroke(StrokeMode::new(Color::GRAY, 4.)),
+        ),
+    };
+
+    commands
+        .spawn(geometry.build(
+            mode,
+            Transform::from_translation(position.extend(order.obstacles)),
+        ))
+        .insert(view.layers)
+        .insert(obstacle)
+        .id()
+}
+
+/// Steers boids sideways off obstacles lying along their velocity, harder the
+/// closer the obstacle is.
+pub(crate) fn avoid_obstacles(
+    config: Res<FlockConfig>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+    mut boids: Query<(&Transform, &Physics, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+) {
+    boids.par_for_each_mut(PAR_BATCH_SIZE, |(transform, physics, mut forces, lod)| {
+        if lod.is_some_and(Lod::skips_steering) {
+            return;
+        }
+        let from = transform.translation.truncate();
+        let to = from + physics.velocity.truncate() * config.avoidance_lookahead;
+        let hit = obstacles
+            .iter()
+            .filter_map(|(obstacle_transform, obstacle)| {
+                obstacle.probe(obstacle_transform.translation.truncate(), from, to)
+            })
+            .min_by(|a, b| a.t.total_cmp(&b.t));
+        let Some(hit) = hit else {
+            return;
+        };
+
+        // keep the part of the velocity along the surface and push out of it
+        let velocity = physics.velocity.truncate();
+        let along = velocity - hit.normal * velocity.dot(hit.normal);
+        let desired = (along.normalize_or_zero() + hit.normal).normalize() * physics.max_speed;
+        let force = steer_towards(desired.extend(0.), physics, config.response_curve);
+        forces
+            .behavior("obstacles")
+            .add_with_priority(force, 1. - hit.t, AVOIDANCE_PRIORITY);
+    });
+}
+
+pub(crate) fn spawn_obstacle_sketch(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &PathBuilder::new().build(),
+            DrawMode::Stroke(StrokeMode::new(Color::GRAY, 2.)),
+            Transform::from_xyz(0., 0., order.overlays),
+        ))
+        .insert(view.layers)
+        .insert(Overlay)
+        .insert(ObstacleSketch);
+}
+
+/// Drag with the left mouse button to draw a circle obstacle, hold `Shift`
+/// while dragging to draw a polyline instead.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sketch_obstacles(
+    mut commands: Commands,
+    mut sketch: Local<Sketch>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    mut preview: Query<&mut Path, With<ObstacleSketch>>,
+) {
+    // points closer than this are merged, and smaller circles are dropped
+    const MIN_STEP: f32 = 10.;
+
+    // `Ctrl` clicks drop food instead, and `Alt` drags draw the playfield
+    let control = keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let alt = keyboard.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if mouse.just_pressed(MouseButton::Left) && !control && !alt {
+        *sketch = Sketch {
+            start: cursor.0,
+            end: cursor.0.unwrap_or_default(),
+            polyline: keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]),
+            points: cursor.0.into_iter().collect(),
+        };
+    }
+    let Some(start) = sketch.start else {
+        return;
+    };
+
+    // keep the last known position while the cursor is outside the view
+    let end = cursor.0.unwrap_or(sketch.end);
+    sketch.end = end;
+    if sketch.polyline && end.distance(*sketch.points.last().unwrap()) >= MIN_STEP {
+        sketch.points.push(end);
+    }
+
+    let mut path = PathBuilder::new();
+    path.move_to(start);
+    if sketch.polyline {
+        for point in &sketch.points {
+            path.line_to(*point);
+        }
+    } else {
+        let radius = start.distance(end);
+        path.line_to(end);
+        path.move_to(start + Vec2::X * radius);
+        path.arc(start, Vec2::splat(radius), std::f32::consts::TAU, 0.);
+    }
+    if let Ok(mut preview) = preview.get_single_mut() {
+        *preview = path.build();
+    }
+
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    if let Ok(mut preview) = preview.get_single_mut() {
+        *preview = PathBuilder::new().build();
+    }
+    let sketch = std::mem::take(&mut *sketch);
+    let obstacle = if sketch.polyline {
+        if sketch.points.len() < 2 {
+            return;
+        }
+        let points = sketch.points.iter().map(|point| *point - start).collect();
+        Obstacle::Polyline { points }
+    } else {
+        let radius = start.distance(end);
+        if radius < MIN_STEP {
+            return;
+        }
+        Obstacle::Circle { radius }
+    };
+    spawn_obstacle(&mut commands, &view, &order, obstacle, start);
+}
+
+pub(crate) fn clear_obstacles(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    obstacles: Query<Entity, With<Obstacle>>,
+) {
+    if keyboard.just_pressed(KeyCode::X) {
+        for obstacle in obstacles.iter() {
+            commands.entity(obstacle).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::{FlockConfig, FlockId, Flocks},
+    grid::GridEntry,
+    lod::Lod,
+    neighbors::{NeighborIndex, SpatialIndex},
+    perception::nearest,
+    steering::PAR_BATCH_SIZE,
+    tick::SimTick,
+};
+
+/// Optimal reciprocal collision avoidance (ORCA), for flocks with
+/// `FlockSettings::reciprocal_avoidance` on: once steered, each boid picks
+/// the velocity closest to the one it wants that keeps it clear of its
+/// neighbors for `time_horizon` seconds, assuming they do their share.
+/// Boids do all the avoiding around neighbors that do not avoid in turn.
+///
+/// Unlike separation, no force is summed: streams crossing or squeezing
+/// through a gap slide past each other instead of jittering or overlapping.
+/// Boids are taken as circles of `FlockConfig::collision_radius`.
+#[derive(Resource)]
+pub struct ReciprocalAvoidance {
+    pub time_horizon: f32,
+    // neighbors further than this are ignored
+    pub neighbor_radius: f32,
+    // and only this many of the nearest ones are heeded
+    pub max_neighbors: usize,
+}
+
+impl Default for ReciprocalAvoidance {
+    fn default() -> Self {
+        Self {
+            time_horizon: 1.5,
+            neighbor_radius: 150.,
+            max_neighbors: 10,
+        }
+    }
+}
+
+/// Half-plane of the velocities permitted by one neighbor: those on the left
+/// of the line through `point` along `direction`.
+#[derive(Clone, Copy)]
+struct Line {
+    point: Vec2,
+    direction: Vec2,
+}
+
+/// Whether boids of `flock` avoid reciprocally.
+fn avoids(config: &FlockConfig, flocks: &Flocks, flock: FlockId) -> bool {
+    flocks
+        .get(flock)
+        .map_or(config.reciprocal_avoidance, |settings| {
+            settings.reciprocal_avoidance
+        })
+}
+
+/// Velocities a boid at `position` moving at `velocity` may take to stay
+/// clear of `other` for `time_horizon` seconds, taking `share` of the
+/// avoiding upon itself.
+fn orca_line(
+    position: Vec2,
+    velocity: Vec2,
+    other: &GridEntry,
+    radius: f32,
+    time_horizon: f32,
+    dt: f32,
+    share: f32,
+) -> Line {
+    let relative_position = other.position.truncate() - position;
+    let relative_velocity = velocity - other.velocity.truncate();
+    let distance_squared = relative_position.length_squared();
+    let combined_radius = radius * 2.;
+    let combined_radius_squared = combined_radius * combined_radius;
+
+    let (direction, u) = if distance_squared > combined_radius_squared {
+        // from the velocity obstacle's cut-off center to the relative velocity
+        let w = relative_velocity - relative_position / time_horizon;
+        let w_length_squared = w.length_squared();
+        let along = w.dot(relative_position);
+        if along < 0. && along * along > combined_radius_squared * w_length_squared {
+            // closest to the cut-off circle
+            let w_length = w_length_squared.sqrt();
+            let unit_w = w / w_length;
+            let direction = Vec2::new(unit_w.y, -unit_w.x);
+            (
+                direction,
+                unit_w * (combined_radius / time_horizon - w_length),
+            )
+        } else {
+            // closest to one of the legs
+            let leg = (distance_squared - combined_radius_squared).sqrt();
+            let direction = if relative_position.perp_dot(w) > 0. {
+                Vec2::new(
+                    relative_position.x * leg - relative_position.y * combined_radius,
+                    relative_position.x * combined_radius + relative_position.y * leg,
+                ) / distance_squared
+            } else {
+                -Vec2::new(
+                    relative_position.x * leg + relative_position.y * combined_radius,
+                    -relative_position.x * combined_radius + relative_position.y * leg,
+                ) / distance_squared
+            };
+            let u = direction * relative_velocity.dot(direction) - relative_velocity;
+            (direction, u)
+        }
+    } else {
+        // already overlapping: get apart within the step
+        let w = relative_velocity - relative_position / dt;
+        let w_length = w.length();
+        // right on top of each other, pick a side
+        let unit_w = w.try_normalize().unwrap_or(Vec2::X);
+        let direction = Vec2::new(unit_w.y, -unit_w.x);
+        (direction, unit_w * (combined_radius / dt - w_length))
+    };
+
+    Line {
+        point: velocity + u * share,
+        direction,
+    }
+}
+
+/// Best velocity on the `line`th line within `max_speed`, keeping to the
+/// lines before it, if there is one.
+fn solve_on_line(
+    lines: &[Line],
+    line: usize,
+    max_speed: f32,
+    preferred: Vec2,
+    direction_only: bool,
+) -> Option<Vec2> {
+    let Line { point, direction } = lines[line];
+    let along = point.dot(direction);
+    let discriminant = along * along + max_speed * max_speed - point.length_squared();
+    if discriminant < 0. {
+        // the line misses the speed circle
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let mut left = -along - root;
+    let mut right = -along + root;
+
+    for other in &lines[..line] {
+        let denominator = direction.perp_dot(other.direction);
+        let numerator = other.direction.perp_dot(point - other.point);
+        if denominator.abs() <= f32::EPSILON {
+            // parallel lines, either all or nothing left
+            if numerator < 0. {
+                return None;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= 0. {
+            right = right.min(t);
+        } else {
+            left = left.max(t);
+        }
+        if left > right {
+            return None;
+        }
+    }
+
+    let t = if direction_only {
+        if preferred.dot(direction) > 0. {
+            right
+        } else {
+            left
+        }
+    } else {
+        direction.dot(preferred - point).clamp(left, right)
+    };
+    Some(point + direction * t)
+}
+
+/// Velocity within `max_speed` closest to `preferred`, or furthest along it
+/// with `direction_only`, satisfying every line. On failure, the index of
+/// the line that could not be satisfied and the best velocity up to it.
+fn solve(
+    lines: &[Line],
+    max_speed: f32,
+    preferred: Vec2,
+    direction_only: bool,
+) -> Result<Vec2, (usize, Vec2)> {
+    let mut result = if direction_only {
+        preferred * max_speed
+    } else {
+        preferred.clamp_length_max(max_speed)
+    };
+    for (index, line) in lines.iter().enumerate() {
+        if line.direction.perp_dot(line.point - result) > 0. {
+            match solve_on_line(lines, index, max_speed, preferred, direction_only) {
+                Some(velocity) => result = velocity,
+                None => return Err((index, result)),
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// When the lines leave no room, the velocity violating them the least,
+/// starting over from the `first` line the solver gave up on.
+fn solve_crowded(lines: &[Line], first: usize, max_speed: f32, mut result: Vec2) -> Vec2 {
+    let mut distance = 0.;
+    for index in first..lines.len() {
+        let line = lines[index];
+        if line.direction.perp_dot(line.point - result) <= distance {
+            continue;
+        }
+        let projected: Vec<Line> = lines[..index]
+            .iter()
+            .filter_map(|other| {
+                let determinant = line.direction.perp_dot(other.direction);
+                let point = if determinant.abs() <= f32::EPSILON {
+                    if line.direction.dot(other.direction) > 0. {
+                        // same direction, no tighter than this line
+                        return None;
+                    }
+                    (line.point + other.point) / 2.
+                } else {
+                    let t = other.direction.perp_dot(line.point - other.point) / determinant;
+                    line.point + line.direction * t
+                };
+                Some(Line {
+                    point,
+                    direction: (other.direction - line.direction).normalize_or_zero(),
+                })
+            })
+            .collect();
+        let inward = Vec2::new(-line.direction.y, line.direction.x);
+        if let Ok(velocity) = solve(&projected, max_speed, inward, true) {
+            result = velocity;
+        }
+        distance = line.direction.perp_dot(line.point - result);
+    }
+    result
+}
+
+/// Turns the steered velocity of boids that avoid reciprocally into the
+/// nearest collision-free one, through their acceleration. Runs after every
+/// force of the step is applied.
+pub(crate) fn avoid_reciprocally(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    settings: Res<ReciprocalAvoidance>,
+    index: Res<SpatialIndex>,
+    lods: Query<&Lod>,
+    mut boids: Query<(Entity, &Transform, &mut Physics, Option<&FlockId>), With<Boid>>,
+) {
+    if !config.reciprocal_avoidance
+        && !flocks
+            .ids()
+            .into_iter()
+            .any(|flock| avoids(&config, &flocks, flock))
+    {
+        return;
+    }
+    let dt = tick.step();
+    if dt <= 0. {
+        return;
+    }
+    let time_horizon = settings.time_horizon.max(f32::EPSILON);
+
+    boids.par_for_each_mut(PAR_BATCH_SIZE, |(entity, transform, mut physics, flock)| {
+        if !avoids(&config, &flocks, flock.copied().unwrap_or_default())
+            || lods.get(entity).is_ok_and(Lod::skips_steering)
+        {
+            return;
+        }
+        let position = transform.translation;
+        let neighbors = nearest(
+            index.others_within(entity, position, settings.neighbor_radius),
+            position,
+            Some(settings.max_neighbors),
+        );
+        if neighbors.is_empty() {
+            return;
+        }
+
+        let velocity = physics.velocity.truncate();
+        let lines: Vec<Line> = neighbors
+            .iter()
+            .map(|other| {
+                let share = match avoids(&config, &flocks, other.flock) {
+                    true => 0.5,
+                    false => 1.,
+                };
+                orca_line(
+                    position.truncate(),
+                    velocity,
+                    other,
+                    config.collision_radius,
+                    time_horizon,
+                    dt,
+                    share,
+                )
+            })
+            .collect();
+        let preferred = velocity + physics.acceleration.truncate() * dt;
+        let avoiding = match solve(&lines, physics.max_speed, preferred, false) {
+            Ok(avoiding) => avoiding,
+            Err((first, best)) => solve_crowded(&lines, first, physics.max_speed, best),
+        };
+        physics.acceleration = ((avoiding - velocity) / dt).extend(physics.acceleration.z);
+    });
+}
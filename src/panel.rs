@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use rand::seq::IteratorRandom;
+
+use crate::{
+    boid::{Boid, Selected},
+    bounds::{BoidDespawned, BoundaryMode},
+    flock::{spawn_random_boid, FlockConfig},
+    render::{FlockView, RenderOrder},
+    rng::SimRng,
+};
+
+const BOUNDARY_MODES: [(BoundaryMode, &str); 5] = [
+    (BoundaryMode::None, "none"),
+    (BoundaryMode::Wrap, "wrap"),
+    (BoundaryMode::Bounce, "bounce"),
+    (BoundaryMode::Avoid, "avoid"),
+    (BoundaryMode::Despawn, "despawn"),
+];
+
+/// Side panel with sliders for the main `FlockConfig` settings, applied to the
+/// running simulation as they are dragged.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn parameter_panel(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    mut config: ResMut<FlockConfig>,
+    mut mode: ResMut<BoundaryMode>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mut rng: ResMut<SimRng>,
+    boids: Query<Entity, With<Boid>>,
+    unselected: Query<Entity, (With<Boid>, Without<Selected>)>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    let boid_count = boids.iter().count();
+    let mut count = boid_count;
+    let mut new_mode = *mode;
+    let mut changed = false;
+
+    // only flag the config as changed when a slider actually moved, so boids
+    // are not updated every frame
+    let edited = config.bypass_change_detection();
+    egui::SidePanel::left("flock_parameters").show(egui_context.ctx_mut(), |ui| {
+        ui.heading("Flock");
+        let mut slider = |ui: &mut egui::Ui, value: &mut f32, range, text| {
+            changed |= ui.add(egui::Slider::new(value, range).text(text)).changed();
+        };
+        slider(ui, &mut edited.max_speed, 0.0..=500.0, "max speed");
+        slider(ui, &mut edited.max_force, 0.0..=2000.0, "max force");
+        slider(ui, &mut edited.separation_weight, 0.0..=5.0, "separation");
+        slider(ui, &mut edited.alignment_weight, 0.0..=5.0, "alignment");
+        slider(ui, &mut edited.cohesion_weight, 0.0..=5.0, "cohesion");
+        slider(
+            ui,
+            &mut edited.neighbor_radius,
+            10.0..=400.0,
+            "perception radius",
+        );
+        ui.add(egui::Slider::new(&mut count, 0..=edited.max_boids).text("boids"));
+        changed |= ui
+            .checkbox(&mut edited.reciprocal_avoidance, "reciprocal avoidance")
+            .changed();
+
+        egui::ComboBox::from_label("boundary")
+            .selected_text(
+                BOUNDARY_MODES
+                    .iter()
+                    .find(|(mode, _)| *mode == new_mode)
+                    .map_or("", |(_, name)| *name),
+            )
+            .show_ui(ui, |ui| {
+                for (mode, name) in BOUNDARY_MODES {
+                    ui.selectable_value(&mut new_mode, mode, name);
+                }
+            });
+    });
+
+    if changed {
+        config.set_changed();
+    }
+    if new_mode != *mode {
+        *mode = new_mode;
+    }
+
+    if count > boid_count {
+        for _ in boid_count..count {
+            spawn_random_boid(&mut commands, &view, &order, &config, rng.as_mut());
+        }
+    } else if count < boid_count {
+        for boid in unselected
+            .iter()
+            .choose_multiple(rng.as_mut(), boid_count - count)
+        {
+            commands.entity(boid).despawn_recursive();
+            despawned.send(BoidDespawned { entity: boid });
+        }
+    }
+}
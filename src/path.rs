@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::Physics,
+    flock::FlockConfig,
+    render::{FlockView, Overlay, RenderOrder},
+    steering::{steer_towards, SteeringForces},
+};
+
+/// What a boid does once it reaches the last waypoint of its `FollowPath`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PathMode {
+    /// Stop following the path.
+    Once,
+    /// Head back to the first waypoint.
+    Loop,
+    /// Go through the waypoints again in reverse order, and so on.
+    PingPong,
+}
+
+/// Route a boid steers along, waypoint after waypoint. Named so as not to
+/// clash with lyon's `Path`.
+#[derive(Component)]
+pub struct FollowPath {
+    pub waypoints: Vec<Vec2>,
+    pub mode: PathMode,
+    // a waypoint closer than this counts as reached
+    pub radius: f32,
+    // waypoint being headed to
+    current: usize,
+    // walking the waypoints backwards, in ping-pong mode
+    reversed: bool,
+    finished: bool,
+}
+
+impl FollowPath {
+    pub fn new(waypoints: Vec<Vec2>, mode: PathMode, radius: f32) -> Self {
+        Self {
+            finished: waypoints.is_empty(),
+            waypoints,
+            mode,
+            radius,
+            current: 0,
+            reversed: false,
+        }
+    }
+
+    /// Waypoint being headed to, `None` once a `PathMode::Once` path is done.
+    pub fn current(&self) -> Option<Vec2> {
+        if self.finished {
+            return None;
+        }
+        self.waypoints.get(self.current).copied()
+    }
+
+    fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+        match self.mode {
+            PathMode::Once if self.current == last => self.finished = true,
+            PathMode::Loop if self.current == last => self.current = 0,
+            PathMode::PingPong if last == 0 => {}
+            PathMode::PingPong if self.reversed && self.current == 0 => {
+                self.reversed = false;
+                self.current = 1;
+            }
+            PathMode::PingPong if !self.reversed && self.current == last => {
+                self.reversed = true;
+                self.current = last - 1;
+            }
+            PathMode::PingPong if self.reversed => self.current -= 1,
+            _ => self.current += 1,
+        }
+    }
+}
+
+/// Line drawing the route of the `FollowPath` on the entity it belongs to.
+#[derive(Component)]
+pub(crate) struct PathLine(Entity);
+
+pub(crate) fn follow_path(
+    config: Res<FlockConfig>,
+    mut query: Query<(&Transform, &Physics, &mut FollowPath, &mut SteeringForces)>,
+) {
+    for (transform, physics, mut path, mut forces) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        if path
+            .current()
+            .is_some_and(|waypoint| waypoint.distance(position) < path.radius)
+        {
+            path.advance();
+        }
+        let Some(waypoint) = path.current() else {
+            continue;
+        };
+
+        let desired = (waypoint - position).normalize_or_zero() * physics.max_speed;
+        forces.behavior("path").add(
+            steer_towards(desired.extend(0.), physics, config.response_curve),
+            1.,
+        );
+    }
+}
+
+pub(crate) fn draw_path_lines(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    paths: Query<(Entity, &FollowPath), Added<FollowPath>>,
+    lines: Query<(Entity, &PathLine)>,
+    owners: Query<(), With<FollowPath>>,
+) {
+    for (owner, path) in paths.iter() {
+        // a new path replaces the line of the previous one
+        for (line, PathLine(line_owner)) in lines.iter() {
+            if *line_owner == owner {
+                commands.entity(line).despawn_recursive();
+            }
+        }
+        let mut points = path.waypoints.clone();
+        if path.mode == PathMode::Loop {
+            points.extend(path.waypoints.first());
+        }
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &shapes::Polygon {
+                    points,
+                    closed: false,
+                },
+                DrawMode::Stroke(StrokeMode::new(Color::rgba(0.5, 1., 0.5, 0.5), 1.)),
+                Transform::from_xyz(0., 0., order.overlays),
+            ))
+            .insert(view.layers)
+            .insert(Overlay)
+            .insert(PathLine(owner));
+    }
+
+    // the line goes with the path
+    for (line, PathLine(owner)) in lines.iter() {
+        if !owners.contains(*owner) {
+            commands.entity(line).despawn_recursive();
+        }
+    }
+}
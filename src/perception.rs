@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+
+use crate::{boid::Physics, grid::GridEntry, neighbors::NeighborIndex};
+
+/// What a boid can see of its neighbors: those closer than `radius` inside a
+/// cone of `field_of_view` radians centered on its heading, like real birds
+/// that cannot see right behind them.
+#[derive(Component, Clone, Copy)]
+pub struct Perception {
+    pub radius: f32,
+    // full angle of the cone, `TAU` sees all around
+    pub field_of_view: f32,
+}
+
+impl Perception {
+    /// Whether a boid at `position` moving along `velocity` sees `other`.
+    /// Boids standing still have no heading and see all around.
+    pub fn sees(&self, position: Vec3, velocity: Vec3, other: Vec3) -> bool {
+        let heading = velocity.truncate();
+        let offset = (other - position).truncate();
+        if heading == Vec2::ZERO || offset == Vec2::ZERO {
+            return true;
+        }
+        heading.angle_between(offset).abs() <= self.field_of_view / 2.
+    }
+}
+
+/// Neighbors of `entity` within `radius` that its `perception`, if any, lets
+/// it see. Without perception every neighbor in range is seen.
+pub(crate) fn perceived_neighbors<'a>(
+    index: &'a dyn NeighborIndex,
+    entity: Entity,
+    transform: &'a Transform,
+    physics: &'a Physics,
+    perception: Option<&'a Perception>,
+    radius: f32,
+) -> impl Iterator<Item = &'a GridEntry> {
+    let position = transform.translation;
+    index
+        .others_within(entity, position, radius)
+        .filter(move |other| {
+            perception.is_none_or(|perception| {
+                perception.sees(position, physics.velocity, other.position)
+            })
+        })
+}
+
+/// The `max` entries of `neighbors` nearest to `position`, or all of them
+/// without a cap, in no particular order.
+pub(crate) fn nearest<'a>(
+    neighbors: impl Iterator<Item = &'a GridEntry>,
+    position: Vec3,
+    max: Option<usize>,
+) -> Vec<&'a GridEntry> {
+    let mut neighbors: Vec<&GridEntry> = neighbors.collect();
+    if let Some(max) = max.filter(|max| *max < neighbors.len()) {
+        // partial sort: only the nearest `max` need to be set apart
+        let distance = |entry: &&GridEntry| entry.position.distance_squared(position);
+        if max > 0 {
+            neighbors.select_nth_unstable_by(max - 1, |a, b| distance(a).total_cmp(&distance(b)));
+        }
+        neighbors.truncate(max);
+    }
+    neighbors
+}
@@ -0,0 +1,117 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, Physics},
+    bounds::Bounds,
+    rng::SimRng,
+    sim,
+    tick::SimTick,
+};
+
+/// Reynolds' perching: boids diving within `height` of the bottom of the
+/// `Bounds` land there, sit still for a duration picked from `duration` and
+/// take off again. Off unless `enabled`.
+#[derive(Resource)]
+pub struct PerchSettings {
+    pub enabled: bool,
+    pub height: f32,
+    // seconds spent on the ground
+    pub duration: Range<f32>,
+    // seconds after taking off before a boid may land again
+    pub cooldown: f32,
+}
+
+impl Default for PerchSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 20.,
+            duration: 1. ..4.,
+            cooldown: 2.,
+        }
+    }
+}
+
+/// Where a boid is in its perching cycle. Perched boids ignore steering.
+#[derive(Component, Clone, Copy, PartialEq, Default, Debug)]
+pub enum Perching {
+    #[default]
+    Flying,
+    Perched {
+        // seconds left on the ground
+        remaining: f32,
+    },
+    TakingOff {
+        // seconds left before the boid may land again
+        remaining: f32,
+    },
+}
+
+impl Perching {
+    pub fn is_perched(&self) -> bool {
+        matches!(self, Perching::Perched { .. })
+    }
+}
+
+/// Lands diving boids, holds perched ones still whatever steered them this
+/// step, and lets them take off once rested.
+pub(crate) fn perch(
+    tick: Res<SimTick>,
+    settings: Res<PerchSettings>,
+    bounds: Res<Bounds>,
+    mut rng: ResMut<SimRng>,
+    mut boids: Query<(&mut Transform, &mut Physics, &mut Perching), With<Boid>>,
+) {
+    let dt = tick.step();
+    for (mut transform, mut physics, mut perching) in boids.iter_mut() {
+        match *perching {
+            Perching::Flying => {
+                let diving = physics.velocity.y < 0.;
+                let above_ground = transform.translation.y - bounds.min.y;
+                if !settings.enabled || !diving || above_ground > settings.height {
+                    continue;
+                }
+                // settle level with the ground, facing the way it flew
+                let level = Vec3::X * physics.velocity.x.signum();
+                if let Some(rotation) = sim::heading(level) {
+                    transform.rotation = rotation;
+                }
+                transform.translation.y = transform.translation.y.max(bounds.min.y);
+                physics.velocity = Vec3::ZERO;
+                physics.acceleration = Vec3::ZERO;
+                let remaining = if settings.duration.is_empty() {
+                    settings.duration.start
+                } else {
+                    rng.gen_range(settings.duration.clone())
+                };
+                *perching = Perching::Perched { remaining };
+            }
+            Perching::Perched { remaining } => {
+                physics.velocity = Vec3::ZERO;
+                physics.acceleration = Vec3::ZERO;
+                let remaining = remaining - dt;
+                if remaining > 0. {
+                    *perching = Perching::Perched { remaining };
+                    continue;
+                }
+                // launch up and onwards, the way it faces
+                let facing = transform.rotation * Vec3::Y;
+                physics.velocity = Vec3::new(facing.x, 1., 0.).normalize() * physics.max_speed;
+                *perching = Perching::TakingOff {
+                    remaining: settings.cooldown,
+                };
+            }
+            Perching::TakingOff { remaining } => {
+                let remaining = remaining - dt;
+                *perching = if remaining > 0. {
+                    Perching::TakingOff { remaining }
+                } else {
+                    Perching::Flying
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    bounds::Bounds,
+    flock::FlockConfig,
+    lod::Lod,
+    render::{FlockView, RenderOrder},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+    tick::SimTick,
+};
+
+/// How far to either side of its heading a boid sniffs, in radians.
+const SENSOR_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Scent at which boids feel half the pull of `Pheromones::weight`.
+const HALF_PULL_SCENT: f32 = 0.5;
+
+/// Scent trails, as ants lay: while `enabled`, boids leave `deposit` scent
+/// per second in the `ScentGrid` cell they fly through, which evaporates by
+/// `evaporation` of itself per second, and steer up the scent gradient they
+/// sense `sensor_distance` ahead of them. Well trodden routes grow stronger
+/// and draw more boids, until the flock keeps to a few lanes.
+/// `Shift+U` switches trails on and off, `U` shows the scent.
+#[derive(Resource)]
+pub struct Pheromones {
+    pub enabled: bool,
+    pub deposit: f32,
+    pub evaporation: f32,
+    pub weight: f32,
+    pub sensor_distance: f32,
+    pub cell_size: f32,
+    pub visible: bool,
+}
+
+impl Default for Pheromones {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deposit: 1.,
+            evaporation: 0.2,
+            weight: 0.8,
+            sensor_distance: 40.,
+            cell_size: 20.,
+            visible: false,
+        }
+    }
+}
+
+/// Scent left in each cell of `Bounds`, see `Pheromones`. Rebuilt, and
+/// emptied, when the bounds or the cell size change.
+#[derive(Resource, Default)]
+pub struct ScentGrid {
+    origin: Vec2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    scent: Vec<f32>,
+}
+
+impl ScentGrid {
+    fn new(bounds: &Bounds, cell_size: f32) -> Self {
+        let size = ((bounds.max - bounds.min) / cell_size)
+            .ceil()
+            .max(Vec2::ONE);
+        let (columns, rows) = (size.x as usize, size.y as usize);
+        Self {
+            origin: bounds.min,
+            cell_size,
+            columns,
+            rows,
+            scent: vec![0.; columns * rows],
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> Option<usize> {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+        if cell.x < 0. || cell.y < 0. {
+            return None;
+        }
+        let (column, row) = (cell.x as usize, cell.y as usize);
+        (column < self.columns && row < self.rows).then_some(row * self.columns + column)
+    }
+
+    fn cell_center(&self, index: usize) -> Vec2 {
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+        self.origin + (Vec2::new(column, row) + 0.5) * self.cell_size
+    }
+
+    /// Scent at `position`, none outside the grid.
+    pub fn scent_at(&self, position: Vec2) -> f32 {
+        self.cell(position).map_or(0., |cell| self.scent[cell])
+    }
+
+    /// Wipes every trail.
+    pub fn clear(&mut self) {
+        self.scent.fill(0.);
+    }
+}
+
+/// Evaporates the scent, then has every boid leave some where it is.
+pub(crate) fn lay_scent(
+    tick: Res<SimTick>,
+    pheromones: Res<Pheromones>,
+    bounds: Res<Bounds>,
+    mut grid: ResMut<ScentGrid>,
+    boids: Query<&Transform, With<Boid>>,
+) {
+    let cell_size = pheromones.cell_size.max(1.);
+    if bounds.is_changed() || grid.cell_size != cell_size {
+        *grid = ScentGrid::new(&bounds, cell_size);
+    }
+    if !pheromones.enabled {
+        return;
+    }
+
+    let dt = tick.step();
+    let kept = (-pheromones.evaporation * dt).exp();
+    for scent in grid.scent.iter_mut() {
+        *scent *= kept;
+    }
+    for transform in boids.iter() {
+        if let Some(cell) = grid.cell(transform.translation.truncate()) {
+            grid.scent[cell] += pheromones.deposit * dt;
+        }
+    }
+}
+
+/// Steers boids towards the strongest scent among three spots ahead of
+/// them, straight on and to either side, pulling harder on stronger trails.
+pub(crate) fn follow_scent(
+    config: Res<FlockConfig>,
+    pheromones: Res<Pheromones>,
+    grid: Res<ScentGrid>,
+    mut boids: Query<(&Transform, &Physics, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+) {
+    if !pheromones.enabled || pheromones.weight == 0. {
+        return;
+    }
+
+    boids.par_for_each_mut(PAR_BATCH_SIZE, |(transform, physics, mut forces, lod)| {
+        if lod.is_some_and(Lod::skips_steering) {
+            return;
+        }
+        let Some(heading) = physics.velocity.truncate().try_normalize() else {
+            return;
+        };
+        let position = transform.translation.truncate();
+        let (direction, strongest) = [-SENSOR_ANGLE, 0., SENSOR_ANGLE]
+            .into_iter()
+            .map(|angle| {
+                let direction = Vec2::from_angle(angle).rotate(heading);
+                let scent = grid.scent_at(position + direction * pheromones.sensor_distance);
+                (direction, scent)
+            })
+            .fold((Vec2::ZERO, 0.), |(sum, strongest), (direction, scent)| {
+                (sum + direction * scent, f32::max(strongest, scent))
+            });
+        let Some(direction) = direction.try_normalize() else {
+            return;
+        };
+
+        let desired = direction * physics.max_speed;
+        let pull = strongest / (strongest + HALF_PULL_SCENT);
+        forces.behavior("pheromones").add(
+            steer_towards(desired.extend(0.), physics, config.response_curve),
+            pheromones.weight * pull,
+        );
+    });
+}
+
+/// `U` shows or hides the scent, `Shift+U` switches trails on and off and
+/// wipes the ones laid so far.
+pub(crate) fn toggle_pheromones(
+    keyboard: Res<Input<KeyCode>>,
+    mut pheromones: ResMut<Pheromones>,
+    mut grid: ResMut<ScentGrid>,
+) {
+    if !keyboard.just_pressed(KeyCode::U) {
+        return;
+    }
+    if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        pheromones.enabled = !pheromones.enabled;
+        grid.clear();
+    } else {
+        pheromones.visible = !pheromones.visible;
+    }
+}
+
+/// Cell of the `ScentGrid` drawn on screen.
+#[derive(Component)]
+pub(crate) struct ScentCell(usize);
+
+/// Shades each cell by its scent, against the strongest one. Cells are
+/// spawned again whenever the grid is rebuilt.
+pub(crate) fn draw_scent(
+    mut commands: Commands,
+    pheromones: Res<Pheromones>,
+    grid: Res<ScentGrid>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mut cells: Query<(Entity, &ScentCell, &mut DrawMode, &mut Visibility)>,
+) {
+    if !pheromones.visible {
+        if pheromones.is_changed() {
+            for (_, _, _, mut visibility) in cells.iter_mut() {
+                visibility.is_visible = false;
+            }
+        }
+        return;
+    }
+
+    if cells.iter().len() != grid.scent.len() {
+        for (entity, _, _, _) in cells.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let cell = shapes::Rectangle {
+            extents: Vec2::splat(grid.cell_size),
+            ..default()
+        };
+        for index in 0..grid.scent.len() {
+            commands
+                .spawn(GeometryBuilder::build_as(
+                    &cell,
+                    DrawMode::Fill(FillMode::color(Color::NONE)),
+                    Transform::from_translation(grid.cell_center(index).extend(order.background)),
+                ))
+                .insert(Visibility::INVISIBLE)
+                .insert(view.layers)
+                .insert(ScentCell(index));
+        }
+        return;
+    }
+
+    let strongest = grid
+        .scent
+        .iter()
+        .copied()
+        .fold(0., f32::max)
+        .max(f32::EPSILON);
+    for (_, cell, mut mode, mut visibility) in cells.iter_mut() {
+        let strength = grid.scent[cell.0] / strongest;
+        *mode = DrawMode::Fill(FillMode::color(Color::rgba(0.7, 0.3, 1., 0.6 * strength)));
+        // faded out cells are not worth drawing
+        visibility.is_visible = strength > 0.01;
+    }
+}
@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    bounds::{BoundaryMode, Bounds},
+    flock::{FlockConfig, FlockId},
+    grid::{GridEntry, SpatialGrid},
+    lod::Lod,
+    neighbors::NeighborIndex,
+    obstacle::Obstacle,
+    playfield::Playfield,
+    sim::{self, MotionLimits},
+    species::Species,
+    tick::{Interpolated, SimTick},
+};
+
+/// How the physics step moves the boids, trading accuracy for cost.
+#[derive(Resource, Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[reflect(Resource)]
+pub enum Integrator {
+    /// Positions move at the velocity before the step: the cheapest, and
+    /// the least stable at low step rates
+    Euler,
+    /// Positions move at the velocity after the step
+    #[default]
+    SemiImplicitEuler,
+    /// Positions move at the velocity halfway through the step, velocities
+    /// gain the average of the last two accelerations: smoother paths for
+    /// a little more work
+    VelocityVerlet,
+}
+
+impl Integrator {
+    /// Position and velocity after a step of `dt` seconds, see the
+    /// `integrate` functions of `sim`.
+    pub fn integrate(
+        self,
+        position: Vec3,
+        velocity: Vec3,
+        acceleration: Vec3,
+        previous_acceleration: Vec3,
+        dt: f32,
+        limits: &MotionLimits,
+    ) -> (Vec3, Vec3) {
+        match self {
+            Integrator::Euler => sim::integrate_euler(position, velocity, acceleration, dt, limits),
+            Integrator::SemiImplicitEuler => {
+                sim::integrate(position, velocity, acceleration, dt, limits)
+            }
+            Integrator::VelocityVerlet => sim::integrate_verlet(
+                position,
+                velocity,
+                acceleration,
+                previous_acceleration,
+                dt,
+                limits,
+            ),
+        }
+    }
+}
+
+/// Sub-steps each physics step is cut into, so fast boids do not fly
+/// through thin obstacles, each other or the edges between two steps: with
+/// every top speed covering up to `max_travel` world units in a step, boids
+/// move in one, and in as many as the fastest takes otherwise, up to `max`.
+/// Obstacles, the playfield, the bounds and hard collisions are checked for
+/// at every sub-step.
+#[derive(Resource, Reflect, Clone, Copy)]
+#[reflect(Resource)]
+pub struct Substeps {
+    pub max_travel: f32,
+    pub max: u32,
+}
+
+impl Default for Substeps {
+    fn default() -> Self {
+        Self {
+            max_travel: 10.,
+            max: 8,
+        }
+    }
+}
+
+impl Substeps {
+    /// Sub-steps for a boid flying at up to `max_speed`, in steps of `dt`
+    /// seconds.
+    pub fn count(&self, max_speed: f32, dt: f32) -> u32 {
+        let needed = (max_speed * dt / self.max_travel.max(f32::EPSILON)).ceil();
+        needed.clamp(1., self.max.max(1) as f32) as u32
+    }
+}
+
+/// Stops a boid moving from `from` to `to` at the first obstacle, or edge of
+/// the playfield, in its way, keeping only the part of its velocity along
+/// the surface.
+fn collide(
+    obstacles: &[(Vec2, &Obstacle)],
+    playfield: &Playfield,
+    from: Vec3,
+    to: Vec3,
+    velocity: Vec3,
+) -> (Vec3, Vec3) {
+    let hit = obstacles
+        .iter()
+        .filter_map(|(center, obstacle)| obstacle.sweep(*center, from.truncate(), to.truncate()))
+        .chain(playfield.sweep(from.truncate(), to.truncate()))
+        .min_by(|a, b| a.t.total_cmp(&b.t));
+    let Some(hit) = hit else {
+        return (to, velocity);
+    };
+
+    let normal = hit.normal.extend(0.);
+    // a hair off the surface, so the next sub-step starts outside
+    let stop = from.lerp(to, hit.t) + normal * 0.01;
+    (stop, velocity - normal * velocity.dot(normal).min(0.))
+}
+
+/// Where a body `physics_system` moves is between two sub-steps.
+struct Body {
+    entity: Entity,
+    boid: bool,
+    position: Vec3,
+    velocity: Vec3,
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn physics_system(
+    tick: Res<SimTick>,
+    config: Res<FlockConfig>,
+    integrator: Res<Integrator>,
+    substeps: Res<Substeps>,
+    mode: Res<BoundaryMode>,
+    bounds: Res<Bounds>,
+    playfield: Res<Playfield>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Physics,
+            Option<&Lod>,
+            Option<&mut Interpolated>,
+            Option<&Boid>,
+        ),
+        Without<Obstacle>,
+    >,
+) {
+    let dt = tick.step();
+    // share of the remaining turn made each step, the same whatever the rate
+    let blend = 1. - (-config.rotation_smoothing * dt).exp();
+    let obstacles: Vec<(Vec2, &Obstacle)> = obstacles
+        .iter()
+        .map(|(transform, obstacle)| (transform.translation.truncate(), obstacle))
+        .collect();
+    // a playfield keeps boids in instead of the bounds
+    let bounded = !playfield.is_active();
+
+    let mut items: Vec<_> = query.iter_mut().collect();
+    let mut bodies: Vec<Body> = items
+        .iter()
+        .map(|(entity, transform, physics, _, _, boid)| Body {
+            entity: *entity,
+            boid: boid.is_some(),
+            position: transform.translation,
+            velocity: physics.velocity,
+        })
+        .collect();
+    // all in step, for the boids to collide at the same sub-steps
+    let count = items
+        .iter()
+        .map(|(_, _, physics, ..)| substeps.count(physics.max_speed, dt))
+        .max()
+        .unwrap_or(1);
+    for substep in 0..count {
+        for ((_, _, physics, _, interpolated, _), body) in items.iter_mut().zip(&mut bodies) {
+            let limits = MotionLimits {
+                drag: config.drag,
+                max_turn_rate: physics.max_turn_rate,
+                max_speed: physics.max_speed,
+            };
+            // the acceleration of the step before only leads into the first
+            let previous_acceleration = match substep {
+                0 => physics.previous_acceleration,
+                _ => physics.acceleration,
+            };
+            let (next, next_velocity) = integrator.integrate(
+                body.position,
+                body.velocity,
+                physics.acceleration,
+                previous_acceleration,
+                dt / count as f32,
+                &limits,
+            );
+            (body.position, body.velocity) =
+                collide(&obstacles, &playfield, body.position, next, next_velocity);
+
+            if !(body.boid && bounded) {
+                continue;
+            }
+            let position = body.position.truncate();
+            let Some(inside) = bounds.bring_back(*mode, position, &mut body.velocity) else {
+                continue;
+            };
+            if let (BoundaryMode::Wrap, Some(interpolated)) = (*mode, interpolated.as_mut()) {
+                // jump straight there instead of sliding across the window
+                interpolated.snap(inside);
+            }
+            body.position = inside.extend(body.position.z);
+        }
+        if config.hard_collisions {
+            push_apart(&mut bodies, config.collision_radius * 2.);
+        }
+    }
+
+    for ((_, transform, physics, lod, ..), body) in items.iter_mut().zip(bodies) {
+        transform.translation = body.position;
+        // standing still keeps the last heading
+        if let Some(heading) = sim::heading(body.velocity) {
+            // reduced boids snap to their heading
+            transform.rotation = match lod.is_some_and(Lod::is_reduced) {
+                true => heading,
+                false => transform.rotation.slerp(heading, blend),
+            };
+        }
+        physics.velocity = body.velocity;
+
+        physics.previous_acceleration = physics.acceleration;
+        physics.acceleration = Vec3::ZERO;
+    }
+}
+
+/// Pushes overlapping boids, `diameter` across, apart, each taking half the
+/// overlap. Boids closing in on each other also trade part of their relative
+/// velocity, as in a soft bump.
+fn push_apart(bodies: &mut [Body], diameter: f32) {
+    // share of the closing velocity traded in a bump
+    const EXCHANGE: f32 = 0.25;
+
+    let mut grid = SpatialGrid::new(diameter);
+    for body in bodies.iter().filter(|body| body.boid) {
+        grid.insert(GridEntry {
+            entity: body.entity,
+            position: body.position,
+            velocity: body.velocity,
+            species: Species::default(),
+            flock: FlockId::default(),
+        });
+    }
+
+    let corrections: Vec<(usize, Vec3, Vec3)> = bodies
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| body.boid)
+        .filter_map(|(index, body)| {
+            let mut push = Vec3::ZERO;
+            let mut bump = Vec3::ZERO;
+            for other in grid.neighbors_within(body.position, diameter) {
+                if other.entity == body.entity {
+                    continue;
+                }
+                let offset = body.position - other.position;
+                // boids right on top of each other get an arbitrary side
+                let away = offset.try_normalize().unwrap_or(Vec3::X);
+                push += away * (diameter - offset.length()) / 2.;
+
+                let closing = (other.velocity - body.velocity).dot(away);
+                if closing > 0. {
+                    bump += away * closing * EXCHANGE;
+                }
+            }
+            (push != Vec3::ZERO).then_some((index, push, bump))
+        })
+        .collect();
+
+    for (index, push, bump) in corrections {
+        bodies[index].position += push;
+        bodies[index].velocity += bump;
+    }
+}
+
+/// Adds `force`, divided by the mass, to the acceleration integrated on the
+/// next physics step.
+pub fn apply_force(physics: &mut Physics, force: &Vec3) {
+    physics.acceleration += *force / physics.mass.max(f32::EPSILON);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// World with a boid at `x` flying 60 units a step to the right, within
+    /// bounds 100 either way of the origin.
+    fn fast_boid(mode: BoundaryMode, playfield: Playfield, x: f32) -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(SimTick::default());
+        world.insert_resource(FlockConfig {
+            drag: 0.,
+            ..default()
+        });
+        world.insert_resource(Integrator::default());
+        world.insert_resource(Substeps::default());
+        world.insert_resource(mode);
+        world.insert_resource(Bounds {
+            min: Vec2::splat(-100.),
+            max: Vec2::splat(100.),
+        });
+        world.insert_resource(playfield);
+        let boid = world
+            .spawn((
+                Boid,
+                Transform::from_xyz(x, 0., 0.),
+                Physics {
+                    velocity: Vec3::X * 3600.,
+                    max_speed: 3600.,
+                    ..default()
+                },
+                Interpolated::new(Vec2::new(x, 0.)),
+            ))
+            .id();
+        (world, boid)
+    }
+
+    #[test]
+    fn boids_bouncing_within_a_step_hit_obstacles_on_the_way_back() {
+        let (mut world, boid) = fast_boid(BoundaryMode::Bounce, Playfield::default(), 95.);
+        world.spawn((
+            Transform::from_xyz(70., 0., 0.),
+            Obstacle::Polyline {
+                points: vec![Vec2::new(0., -50.), Vec2::new(0., 50.)],
+            },
+        ));
+
+        SystemStage::single(physics_system).run(&mut world);
+
+        let x = world.get::<Transform>(boid).unwrap().translation.x;
+        assert!(x > 70. && x < 100., "went through the wall to {x}");
+    }
+
+    #[test]
+    fn fast_boids_stay_within_the_playfield() {
+        let playfield = Playfield::new(vec![
+            Vec2::new(-50., -50.),
+            Vec2::new(50., -50.),
+            Vec2::new(50., 50.),
+            Vec2::new(-50., 50.),
+        ]);
+        let (mut world, boid) = fast_boid(BoundaryMode::None, playfield, 30.);
+
+        SystemStage::single(physics_system).run(&mut world);
+
+        let x = world.get::<Transform>(boid).unwrap().translation.x;
+        assert!(x < 50., "left the playfield to {x}");
+    }
+}
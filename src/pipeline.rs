@@ -0,0 +1,95 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::replay::Replay;
+
+/// Labels of the parts of the boid pipeline, to order the systems of an app
+/// around them, as in `my_system.after(BoidSet::Steering)`. Each step of
+/// `FixedUpdateStage` first moves the boids by the forces of the step before,
+/// then has them perceive each other where they landed and steer; rendering
+/// runs in `CoreStage::Update`.
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BoidSet {
+    /// Neighbor search structures and navigation grids
+    Perception,
+    /// Steering forces, added up into each boid's acceleration
+    Steering,
+    /// Moving the boids by their acceleration, and wrapping them around or
+    /// bouncing them off the bounds
+    Integrate,
+    /// Size, color and sprites of the boids, with `BoidPlugin`
+    Render,
+}
+
+/// Label of the systems despawning boids in `FixedUpdateStage`. Commands are
+/// applied in the order of their systems at the end of the stage, and
+/// inserting a component on a boid despawned before panics, so systems
+/// inserting components on boids run before these. `energy::spend_energy`
+/// despawns boids too, but inserts as well, so it runs between the two.
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct DespawnBoids;
+
+/// Which parts of the boid pipeline run. Turning one off leaves it to the
+/// app: with `integrate` off, the acceleration the forces add up to stays on
+/// each boid's `Physics`, for an integrator of the app's own to move it by
+/// and clear.
+#[derive(Resource)]
+pub struct BoidPipeline {
+    pub perception: bool,
+    pub steering: bool,
+    pub integrate: bool,
+    pub render: bool,
+}
+
+impl Default for BoidPipeline {
+    fn default() -> Self {
+        Self {
+            perception: true,
+            steering: true,
+            integrate: true,
+            render: true,
+        }
+    }
+}
+
+impl BoidPipeline {
+    pub fn runs(&self, set: BoidSet) -> bool {
+        match set {
+            BoidSet::Perception => self.perception,
+            BoidSet::Steering => self.steering,
+            BoidSet::Integrate => self.integrate,
+            BoidSet::Render => self.render,
+        }
+    }
+}
+
+/// Whether the simulating part `set` of the pipeline runs: on, and no
+/// `Replay` driving the boids instead.
+fn simulates(set: BoidSet, pipeline: &BoidPipeline, replay: Option<Res<Replay>>) -> ShouldRun {
+    match replay.is_none() && pipeline.runs(set) {
+        true => ShouldRun::Yes,
+        false => ShouldRun::No,
+    }
+}
+
+/// Run criterion of `BoidSet::Perception`.
+pub(crate) fn perceiving(pipeline: Res<BoidPipeline>, replay: Option<Res<Replay>>) -> ShouldRun {
+    simulates(BoidSet::Perception, &pipeline, replay)
+}
+
+/// Run criterion of `BoidSet::Steering`.
+pub(crate) fn steering(pipeline: Res<BoidPipeline>, replay: Option<Res<Replay>>) -> ShouldRun {
+    simulates(BoidSet::Steering, &pipeline, replay)
+}
+
+/// Run criterion of `BoidSet::Integrate`.
+pub(crate) fn integrating(pipeline: Res<BoidPipeline>, replay: Option<Res<Replay>>) -> ShouldRun {
+    simulates(BoidSet::Integrate, &pipeline, replay)
+}
+
+/// Run criterion of `BoidSet::Render`.
+pub(crate) fn rendering(pipeline: Res<BoidPipeline>) -> ShouldRun {
+    match pipeline.render {
+        true => ShouldRun::Yes,
+        false => ShouldRun::No,
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    lod::Lod,
+    obstacle::{probe_edges, Hit, AVOIDANCE_PRIORITY},
+    render::{CursorPosition, FlockView, RenderOrder},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Closed polygon the flock is kept inside, in place of the rectangular
+/// `Bounds` of the window. Boids closer to its edges than
+/// `FlockConfig::boundary_margin` steer back in, those outside head
+/// straight back, and none fly out through its edges. Without at least three points there is no playfield and
+/// `BoundaryMode` applies to `Bounds` as usual.
+#[derive(Resource, Clone, Default)]
+pub struct Playfield {
+    pub points: Vec<Vec2>,
+}
+
+impl Playfield {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Self { points }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.points.len() >= 3
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points.iter().copied().zip(next.copied())
+    }
+
+    /// Whether `position` lies inside, by the even-odd rule.
+    pub fn contains(&self, position: Vec2) -> bool {
+        polygon_contains(&self.points, position)
+    }
+
+    /// Point of the edges nearest `position`.
+    pub fn nearest_edge_point(&self, position: Vec2) -> Option<Vec2> {
+        self.edges()
+            .map(|(a, b)| {
+                let edge = b - a;
+                let t = ((position - a).dot(edge) / edge.length_squared().max(f32::EPSILON))
+                    .clamp(0., 1.);
+                a + edge * t
+            })
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+    }
+
+    /// Where a boid moving from `from` to `to` runs into the edges on its
+    /// way out. Boids outside, left there as the playfield was drawn, are
+    /// let in.
+    pub(crate) fn sweep(&self, from: Vec2, to: Vec2) -> Option<Hit> {
+        if !self.is_active() || !self.contains(from) {
+            return None;
+        }
+        let edges = self.points.iter().zip(self.points.iter().cycle().skip(1));
+        probe_edges(edges, Vec2::ZERO, from, to)
+    }
+
+    /// Push back inside felt at `position`: growing from nothing `margin`
+    /// away from the edges to 1 on them, and 2 outside, enough to turn
+    /// boids flying straight out around.
+    pub fn push(&self, position: Vec2, margin: f32) -> Vec2 {
+        if !self.is_active() {
+            return Vec2::ZERO;
+        }
+        let Some(edge) = self.nearest_edge_point(position) else {
+            return Vec2::ZERO;
+        };
+        if !self.contains(position) {
+            return (edge - position).normalize_or_zero() * 2.;
+        }
+        let offset = position - edge;
+        let depth = 1. - offset.length() / margin.max(f32::EPSILON);
+        offset.normalize_or_zero() * depth.max(0.)
+    }
+}
+
+/// Whether `position` lies inside the closed polygon through `points`, by
+/// the even-odd rule.
+pub(crate) fn polygon_contains(points: &[Vec2], position: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+        if (a.y > position.y) != (b.y > position.y) {
+            let x = a.x + (position.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if position.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Outline of the `Playfield`.
+#[derive(Component)]
+pub(crate) struct PlayfieldOutline;
+
+pub(crate) fn spawn_playfield_outline(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+) {
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &PathBuilder::new().build(),
+            DrawMode::Stroke(StrokeMode::new(Color::DARK_GRAY, 3.)),
+            Transform::from_xyz(0., 0., order.obstacles),
+        ))
+        .insert(view.layers)
+        .insert(PlayfieldOutline);
+}
+
+fn outline(points: &[Vec2], closed: bool) -> Path {
+    let mut path = PathBuilder::new();
+    if let Some(first) = points.first() {
+        path.move_to(*first);
+        for point in &points[1..] {
+            path.line_to(*point);
+        }
+        if closed {
+            path.close();
+        }
+    }
+    path.build()
+}
+
+pub(crate) fn draw_playfield(
+    playfield: Res<Playfield>,
+    mut outlines: Query<&mut Path, With<PlayfieldOutline>>,
+) {
+    if !playfield.is_changed() {
+        return;
+    }
+    for mut path in outlines.iter_mut() {
+        *path = if playfield.is_active() {
+            outline(&playfield.points, true)
+        } else {
+            PathBuilder::new().build()
+        };
+    }
+}
+
+/// Drag with `Alt` and the left mouse button to draw a new playfield, closed
+/// on release. An `Alt` click removes it.
+pub(crate) fn sketch_playfield(
+    mut sketch: Local<Option<Vec<Vec2>>>,
+    mut playfield: ResMut<Playfield>,
+    mouse: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    mut outlines: Query<&mut Path, With<PlayfieldOutline>>,
+) {
+    // points closer than this are merged
+    const MIN_STEP: f32 = 20.;
+
+    let alt = keyboard.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if mouse.just_pressed(MouseButton::Left) && alt {
+        *sketch = Some(cursor.0.into_iter().collect());
+    }
+    let Some(points) = sketch.as_mut() else {
+        return;
+    };
+
+    if let Some(position) = cursor.0 {
+        if points
+            .last()
+            .is_none_or(|last| last.distance(position) >= MIN_STEP)
+        {
+            points.push(position);
+        }
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        for mut path in outlines.iter_mut() {
+            *path = outline(points, false);
+        }
+        return;
+    }
+
+    let points = sketch.take().unwrap_or_default();
+    // a click, or a scribble too small to hold a flock
+    *playfield = if points.len() >= 3 {
+        Playfield::new(points)
+    } else {
+        Playfield::default()
+    };
+}
+
+/// Steers boids near the edges of the playfield, or outside it, back in.
+pub(crate) fn contain(
+    playfield: Res<Playfield>,
+    config: Res<FlockConfig>,
+    mut query: Query<(&Transform, &Physics, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+) {
+    if !playfield.is_active() {
+        return;
+    }
+
+    query.par_for_each_mut(PAR_BATCH_SIZE, |(transform, physics, mut forces, lod)| {
+        if lod.is_some_and(Lod::skips_steering) {
+            return;
+        }
+        let push = playfield.push(transform.translation.truncate(), config.boundary_margin);
+        if push == Vec2::ZERO {
+            return;
+        }
+
+        let heading = physics.velocity.truncate().normalize_or_zero();
+        let desired = (heading + push).normalize_or_zero() * physics.max_speed;
+        let force = steer_towards(desired.extend(0.), physics, config.response_curve);
+        forces.behavior("playfield").add_with_priority(
+            force,
+            push.length().min(1.),
+            AVOIDANCE_PRIORITY,
+        );
+    });
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, BoidBuilder, Physics},
+    energy::Energy,
+    flock::{FlockConfig, FlockId},
+    food::FoodEaten,
+    hud::Hud,
+    render::FlockView,
+    rng::SimRng,
+    stats::FlockStats,
+    tick::SimTick,
+};
+
+/// Heritable factors of a boid, scaling its flock's top speed and flocking
+/// weights. Boids without them fly as factors of 1.
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct Traits {
+    pub speed: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+}
+
+impl Default for Traits {
+    fn default() -> Self {
+        Self {
+            speed: 1.,
+            separation: 1.,
+            alignment: 1.,
+            cohesion: 1.,
+        }
+    }
+}
+
+impl Traits {
+    /// Copy with every factor shifted by up to `amount` of itself, either way.
+    pub fn mutated(&self, rng: &mut impl Rng, amount: f32) -> Self {
+        let amount = amount.abs();
+        let mut mutate = |factor: f32| {
+            if amount == 0. {
+                factor
+            } else {
+                factor * (1. + rng.gen_range(-amount..=amount))
+            }
+        };
+        Self {
+            speed: mutate(self.speed),
+            separation: mutate(self.separation),
+            alignment: mutate(self.alignment),
+            cohesion: mutate(self.cohesion),
+        }
+    }
+}
+
+/// Boids that eat their way past `threshold` of their maximum energy split
+/// in two, sharing the energy, the offspring's `Traits` mutated by up to
+/// `mutation`. The population stops growing at `FlockConfig::max_boids`.
+#[derive(Resource)]
+pub struct Reproduction {
+    // none for boids that never split
+    pub threshold: Option<f32>,
+    pub mutation: f32,
+}
+
+impl Default for Reproduction {
+    fn default() -> Self {
+        Self {
+            threshold: None,
+            mutation: 0.1,
+        }
+    }
+}
+
+/// Boid count sampled every `interval` simulated seconds, keeping the last
+/// `capacity` samples. Drawn as a graph under the HUD.
+#[derive(Resource)]
+pub struct PopulationHistory {
+    pub interval: f32,
+    pub capacity: usize,
+    samples: VecDeque<usize>,
+    // simulated seconds since the last sample
+    since: f32,
+}
+
+impl Default for PopulationHistory {
+    fn default() -> Self {
+        Self {
+            interval: 1.,
+            capacity: 120,
+            samples: VecDeque::new(),
+            since: f32::INFINITY,
+        }
+    }
+}
+
+impl PopulationHistory {
+    /// Samples from the oldest to the latest.
+    pub fn samples(&self) -> impl Iterator<Item = usize> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// Drops every sample, the next one being taken right away.
+    pub(crate) fn clear(&mut self) {
+        self.samples.clear();
+        self.since = f32::INFINITY;
+    }
+}
+
+/// Splits boids that just ate past the `Reproduction` threshold.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn reproduce(
+    mut commands: Commands,
+    reproduction: Res<Reproduction>,
+    config: Res<FlockConfig>,
+    view: Res<FlockView>,
+    mut rng: ResMut<SimRng>,
+    mut eaten: EventReader<FoodEaten>,
+    mut parents: Query<(&Transform, &Physics, &mut Energy, Option<&FlockId>)>,
+    traits_query: Query<&Traits>,
+    boids: Query<(), With<Boid>>,
+) {
+    let Some(threshold) = reproduction.threshold else {
+        eaten.clear();
+        return;
+    };
+    let mut population = boids.iter().count();
+
+    for FoodEaten { boid, .. } in eaten.iter() {
+        if population >= config.max_boids {
+            break;
+        }
+        let Ok((transform, physics, mut energy, flock)) = parents.get_mut(*boid) else {
+            continue;
+        };
+        if energy.fraction() < threshold {
+            continue;
+        }
+
+        let parent_traits = traits_query.get(*boid).copied().unwrap_or_default();
+        let traits = parent_traits.mutated(rng.as_mut(), reproduction.mutation);
+        energy.current /= 2.;
+        // side by side, the offspring on the left of its parent
+        let side = physics
+            .velocity
+            .truncate()
+            .try_normalize()
+            .map_or(Vec2::Y, Vec2::perp);
+        let position = transform.translation + (side * config.boid_width).extend(0.);
+        let mut bundle = BoidBuilder::new()
+            .config(&config)
+            .view(&view)
+            .at(position)
+            .velocity(physics.velocity)
+            .mass(physics.mass)
+            .flock(flock.copied().unwrap_or_default().0)
+            .build();
+        bundle.physics.max_speed =
+            physics.max_speed / parent_traits.speed.max(f32::EPSILON) * traits.speed;
+        commands.spawn(bundle).insert((*energy, traits));
+        population += 1;
+    }
+}
+
+pub(crate) fn record_population(
+    tick: Res<SimTick>,
+    stats: Res<FlockStats>,
+    mut history: ResMut<PopulationHistory>,
+) {
+    // only new samples count as changes, for the graph
+    let since = &mut history.bypass_change_detection().since;
+    *since += tick.step();
+    if *since < history.interval {
+        return;
+    }
+    history.since = 0.;
+    history.samples.push_back(stats.boid_count);
+    while history.samples.len() > history.capacity {
+        history.samples.pop_front();
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct PopulationGraph;
+
+/// Bar of the graph, the `0`th the oldest sample.
+#[derive(Component)]
+pub(crate) struct PopulationBar(usize);
+
+pub(crate) fn spawn_population_graph(mut commands: Commands, history: Res<PopulationHistory>) {
+    let bar_width = 100. / history.capacity.max(1) as f32;
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(240.), Val::Px(60.)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(8.),
+                    right: Val::Px(8.),
+                    ..default()
+                },
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            ..default()
+        })
+        .insert(PopulationGraph)
+        .with_children(|graph| {
+            for bar in 0..history.capacity {
+                graph
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(bar_width), Val::Percent(0.)),
+                            ..default()
+                        },
+                        background_color: Color::YELLOW_GREEN.into(),
+                        ..default()
+                    })
+                    .insert(PopulationBar(bar));
+            }
+        });
+}
+
+/// Shows the population over time along with the HUD, scaled to the
+/// largest sample.
+pub(crate) fn draw_population_graph(
+    hud: Res<Hud>,
+    history: Res<PopulationHistory>,
+    mut graph: Query<&mut Visibility, With<PopulationGraph>>,
+    mut bars: Query<(&PopulationBar, &mut Style)>,
+) {
+    for mut visibility in graph.iter_mut() {
+        visibility.is_visible = hud.visible;
+    }
+    if !hud.visible || !(history.is_changed() || hud.is_changed()) {
+        return;
+    }
+
+    let largest = history.samples().max().unwrap_or(0).max(1) as f32;
+    // the latest sample on the right
+    let offset = history.capacity.saturating_sub(history.samples.len());
+    for (PopulationBar(bar), mut style) in bars.iter_mut() {
+        let sample = bar
+            .checked_sub(offset)
+            .and_then(|index| history.samples.get(index))
+            .copied()
+            .unwrap_or(0);
+        style.size.height = Val::Percent(sample as f32 / largest * 100.);
+    }
+}
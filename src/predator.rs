@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, Physics},
+    bounds::{BoidDespawned, Bounds},
+    emitter::Edge,
+    flock::FlockConfig,
+    physics::apply_force,
+    render::{FlockView, RenderOrder},
+    rng::SimRng,
+    sim,
+    steering::{steer_towards, steering_params, SteeringForces},
+    tick::Interpolated,
+};
+
+/// Priority of fleeing in `SteeringForces`: staying alive beats everything.
+pub(crate) const FLEE_PRIORITY: i32 = 2;
+
+/// Hunter chasing the nearest boid, and catching those it gets within
+/// `CATCH_DISTANCE` of. Boids with `Evade` run from it.
+#[derive(Component)]
+pub struct Predator;
+
+/// Boids closer than this to a predator are caught, and despawn.
+pub const CATCH_DISTANCE: f32 = 20.;
+
+/// Sent when a predator catches a boid, along with `BoidDespawned`.
+pub struct PredatorCaughtPrey {
+    pub predator: Entity,
+    pub prey: Entity,
+}
+
+/// Makes a boid run from `point` while closer than `radius` to it.
+#[derive(Component)]
+pub struct Flee {
+    pub point: Vec3,
+    pub radius: f32,
+}
+
+/// Makes a boid run from predators closer than `radius`, aiming away from
+/// where they are heading rather than where they are.
+#[derive(Component)]
+pub struct Evade {
+    pub radius: f32,
+}
+
+impl Default for Evade {
+    fn default() -> Self {
+        Self { radius: 200. }
+    }
+}
+
+/// Force turning `physics` straight away from `point`.
+pub fn flee_from(position: Vec3, point: Vec3, physics: &Physics, config: &FlockConfig) -> Vec3 {
+    let params = steering_params(physics, config.response_curve);
+    sim::steer_flee(position, physics.velocity, point, &params)
+}
+
+pub(crate) fn flee(
+    config: Res<FlockConfig>,
+    mut query: Query<(&Transform, &Flee, &Physics, &mut SteeringForces)>,
+) {
+    for (transform, flee, physics, mut forces) in query.iter_mut() {
+        let position = transform.translation;
+        if position.distance(flee.point) < flee.radius {
+            let force = flee_from(position, flee.point, physics, &config);
+            forces
+                .behavior("flee")
+                .add_with_priority(force, 1., FLEE_PRIORITY);
+        }
+    }
+}
+
+pub(crate) fn evade(
+    config: Res<FlockConfig>,
+    predators: Query<(&Transform, &Physics), With<Predator>>,
+    mut boids: Query<(&Transform, &Evade, &Physics, &mut SteeringForces), With<Boid>>,
+) {
+    for (transform, evade, physics, mut forces) in boids.iter_mut() {
+        let position = transform.translation;
+        for (predator, predator_physics) in predators.iter() {
+            let distance = position.distance(predator.translation);
+            if distance >= evade.radius {
+                continue;
+            }
+
+            // the closer the predator, the less time it has to change course
+            let closing_speed = physics.max_speed + predator_physics.velocity.length();
+            let lookahead = distance / closing_speed.max(f32::EPSILON);
+            let predicted = predator.translation + predator_physics.velocity * lookahead;
+            let force = flee_from(position, predicted, physics, &config);
+            forces
+                .behavior("flee")
+                .add_with_priority(force, 1., FLEE_PRIORITY);
+        }
+    }
+}
+
+pub(crate) fn chase_nearest_boid(
+    config: Res<FlockConfig>,
+    boids: Query<&Transform, With<Boid>>,
+    mut predators: Query<(&Transform, &mut Physics), With<Predator>>,
+) {
+    for (transform, mut physics) in predators.iter_mut() {
+        let position = transform.translation;
+        let nearest = boids.iter().map(|boid| boid.translation).min_by(|a, b| {
+            a.distance_squared(position)
+                .total_cmp(&b.distance_squared(position))
+        });
+        let Some(nearest) = nearest else {
+            continue;
+        };
+
+        let desired = (nearest - position).normalize_or_zero() * physics.max_speed;
+        let force = steer_towards(desired, &physics, config.response_curve);
+        apply_force(physics.as_mut(), &force);
+    }
+}
+
+/// Despawns the boids predators got close enough to catch, each caught once
+/// even with several predators on it.
+pub(crate) fn catch_prey(
+    mut commands: Commands,
+    predators: Query<(Entity, &Transform), With<Predator>>,
+    boids: Query<(Entity, &Transform), With<Boid>>,
+    mut caught: EventWriter<PredatorCaughtPrey>,
+    mut despawned: EventWriter<BoidDespawned>,
+) {
+    let mut eaten = Vec::new();
+    for (predator, transform) in predators.iter() {
+        let position = transform.translation.truncate();
+        for (prey, boid) in boids.iter() {
+            if eaten.contains(&prey)
+                || boid.translation.truncate().distance(position) >= CATCH_DISTANCE
+            {
+                continue;
+            }
+            eaten.push(prey);
+            commands.entity(prey).despawn_recursive();
+            caught.send(PredatorCaughtPrey { predator, prey });
+            despawned.send(BoidDespawned { entity: prey });
+        }
+    }
+}
+
+pub(crate) fn spawn_predator_on_key(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    bounds: Res<Bounds>,
+    config: Res<FlockConfig>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !keyboard.just_pressed(KeyCode::P) {
+        return;
+    }
+
+    let position = Edge::Top.point(&bounds, rng.gen());
+    spawn_predator(&mut commands, &view, &order, &config, position);
+}
+
+/// Spawns a predator at `position`, a little faster than the flock.
+pub fn spawn_predator(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    config: &FlockConfig,
+    position: Vec2,
+) -> Entity {
+    let triangle = shapes::Polygon {
+        points: vec![
+            Vec2::new(-20., -35.),
+            Vec2::new(20., -35.),
+            Vec2::new(0., 35.),
+        ],
+        closed: true,
+    };
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &triangle,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::RED),
+                outline_mode: StrokeMode::new(Color::WHITE, 1.),
+            },
+            Transform::from_translation(position.extend(order.target)),
+        ))
+        .insert(Physics {
+            // a little faster than the flock so it can catch stragglers
+            max_speed: config.max_speed * 1.2,
+            max_force: config.max_force,
+            ..default()
+        })
+        .insert(Interpolated::new(position))
+        .insert(view.layers)
+        .insert(Predator)
+        .id()
+}
@@ -0,0 +1,401 @@
+use bevy::{ecs::system::CommandQueue, math::Rect, prelude::*};
+
+use crate::{
+    attractor::Attractor,
+    boid::{Boid, BoidShape},
+    bounds::Bounds,
+    emitter::{BoidSpawner, Edge, Emitter},
+    flock::{reset_flock, FlockConfig, Flocks},
+    flow_field::{FlowField, FollowFlowField},
+    food::Food,
+    formation::Formation,
+    game::{CaptureScore, START_RATE},
+    goal::GoalZone,
+    map::{ObstacleMap, ObstacleMapFile, ObstacleMapHandle},
+    navigation::Navigation,
+    obstacle::{spawn_obstacle, Obstacle},
+    playfield::Playfield,
+    predator::{spawn_predator, Predator},
+    render::{FlockView, RenderOrder},
+    steering::Arbitration,
+    target::{MainTarget, TargetPin},
+};
+
+/// Ready-made scene: flock settings along with the obstacles, predators,
+/// emitters and flow field around the flock.
+///
+/// Inserted before `SimulationPlugin`, its scene is spawned on startup, the
+/// flock taking the `FlockConfig` inserted alongside, if any. Sending
+/// `LoadPreset` replaces the whole world with another one, config included;
+/// the resource then holds the preset last loaded.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Preset {
+    /// The flock chases the target past an obstacle
+    #[default]
+    SeekDemo,
+    /// Hundreds of small boids and nothing else
+    BigFlock,
+    /// The seek demo with a predator hunting the flock, fed by an emitter
+    PredatorHunt,
+    /// A stream of boids threading between rocks and walls
+    ObstacleCourse,
+    /// Boids drifting along noise currents
+    FlowField,
+    /// Two rooms joined by a door, the flock queueing through it
+    Doorway,
+    /// A game: catch the boids fleeing the target before they overrun the
+    /// screen, see `CaptureScore`
+    Capture,
+    /// The flock finding its way through the maze of `maps/maze.map.txt`
+    Maze,
+}
+
+impl Preset {
+    /// In the order of their number keys.
+    pub const ALL: [Preset; 8] = [
+        Preset::SeekDemo,
+        Preset::BigFlock,
+        Preset::PredatorHunt,
+        Preset::ObstacleCourse,
+        Preset::FlowField,
+        Preset::Doorway,
+        Preset::Capture,
+        Preset::Maze,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::SeekDemo => "Seek Demo",
+            Preset::BigFlock => "Big Flock",
+            Preset::PredatorHunt => "Predator Hunt",
+            Preset::ObstacleCourse => "Obstacle Course",
+            Preset::FlowField => "Flow Field",
+            Preset::Doorway => "Doorway",
+            Preset::Capture => "Capture",
+            Preset::Maze => "Maze",
+        }
+    }
+
+    /// Flock the preset starts with.
+    pub fn config(self) -> FlockConfig {
+        let config = FlockConfig::default();
+        match self {
+            Preset::SeekDemo | Preset::PredatorHunt => config,
+            Preset::BigFlock => FlockConfig {
+                count: 600,
+                max_boids: 800,
+                boid_length: 20.,
+                boid_width: 12.,
+                // heading lines only clutter a crowd this size
+                shape: BoidShape {
+                    heading_line: 0.,
+                    ..default()
+                },
+                neighbor_radius: 50.,
+                separation_radius: 25.,
+                // starlings, again
+                max_neighbors: Some(7),
+                neighbor_refresh: 4,
+                ..config
+            },
+            Preset::ObstacleCourse => FlockConfig {
+                count: 30,
+                boid_length: 30.,
+                boid_width: 18.,
+                separation_radius: 40.,
+                // threading gaps is where context steering shines
+                arbitration: Arbitration::Context,
+                ..config
+            },
+            Preset::FlowField => FlockConfig {
+                count: 150,
+                max_boids: 300,
+                boid_length: 30.,
+                boid_width: 18.,
+                // darts with a notched tail, riding the currents
+                shape: BoidShape {
+                    points: vec![
+                        Vec2::new(-0.5, -0.5),
+                        Vec2::new(0., -0.2),
+                        Vec2::new(0.5, -0.5),
+                        Vec2::new(0., 0.5),
+                    ],
+                    heading_line: 0.,
+                    ..default()
+                },
+                cohesion_weight: 0.5,
+                ..config
+            },
+            Preset::Doorway => FlockConfig {
+                count: 60,
+                // all in the left room
+                spawn_area: Rect::new(-350., -250., -120., 250.),
+                boid_length: 24.,
+                boid_width: 14.,
+                separation_radius: 30.,
+                queueing: Some(60.),
+                ..config
+            },
+            Preset::Capture => FlockConfig {
+                count: 10,
+                // the game is lost once the flock is full
+                max_boids: 60,
+                ..config
+            },
+            Preset::Maze => FlockConfig {
+                count: 30,
+                // all in the top left room
+                spawn_area: Rect::new(-350., 250., -250., 350.),
+                boid_length: 24.,
+                boid_width: 14.,
+                separation_radius: 30.,
+                ..config
+            },
+        }
+    }
+
+    /// Where the preset pins the main target, if it does.
+    pub fn target(self) -> Option<Vec2> {
+        match self {
+            // in the right room
+            Preset::Doorway => Some(Vec2::new(250., 0.)),
+            // in the bottom right room
+            Preset::Maze => Some(Vec2::new(300., -300.)),
+            _ => None,
+        }
+    }
+
+    /// Obstacle map the preset is built around, relative to the assets
+    /// folder.
+    pub fn map(self) -> Option<&'static str> {
+        match self {
+            Preset::Maze => Some("maps/maze.map.txt"),
+            _ => None,
+        }
+    }
+
+    /// Queues the spawning of everything but the flock.
+    pub fn spawn_scene(
+        self,
+        commands: &mut Commands,
+        view: &FlockView,
+        order: &RenderOrder,
+        config: &FlockConfig,
+        bounds: &Bounds,
+    ) {
+        match self {
+            Preset::SeekDemo | Preset::PredatorHunt => {
+                // the seek demo has no stream of its own, see `--emitter`
+                if self == Preset::PredatorHunt {
+                    commands.spawn(Emitter::new(Edge::Left, 2., 120.));
+                }
+                spawn_obstacle(
+                    commands,
+                    view,
+                    order,
+                    Obstacle::Circle { radius: 40. },
+                    Vec2::new(150., 100.),
+                );
+                if self == Preset::PredatorHunt {
+                    let corner = Vec2::new(bounds.min.x, bounds.max.y);
+                    spawn_predator(commands, view, order, config, corner);
+                }
+            }
+            Preset::BigFlock => {}
+            Preset::Capture => {
+                for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                    commands.spawn(Emitter::new(edge, START_RATE / 4., 100.));
+                }
+            }
+            Preset::ObstacleCourse => {
+                commands.spawn(Emitter::new(Edge::Left, 4., 120.));
+                for (radius, position) in [
+                    (50., Vec2::new(-150., 120.)),
+                    (60., Vec2::new(0., -110.)),
+                    (40., Vec2::new(160., 160.)),
+                    (30., Vec2::new(200., -200.)),
+                ] {
+                    spawn_obstacle(commands, view, order, Obstacle::Circle { radius }, position);
+                }
+                for (from, to) in [
+                    (Vec2::new(-60., 260.), Vec2::new(-60., 60.)),
+                    (Vec2::new(80., -280.), Vec2::new(80., -40.)),
+                ] {
+                    let points = vec![Vec2::ZERO, to - from];
+                    spawn_obstacle(commands, view, order, Obstacle::Polyline { points }, from);
+                }
+            }
+            Preset::FlowField => {
+                commands.insert_resource(FlowField::from_noise(bounds, 40., 7, 300.));
+            }
+            Preset::Maze => {
+                // a map given on the command line comes first
+                let map = self.map().unwrap_or_default().to_string();
+                commands.add(move |world: &mut World| {
+                    if !world.contains_resource::<ObstacleMapFile>() {
+                        world.insert_resource(ObstacleMapFile(map));
+                    }
+                });
+                commands.insert_resource(Navigation {
+                    enabled: true,
+                    ..default()
+                });
+            }
+            Preset::Doorway => {
+                const DOOR_WIDTH: f32 = 60.;
+                // walls just inside the bounds, so boids stay in the rooms
+                let (min, max) = (bounds.min + 10., bounds.max - 10.);
+                let walls = vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+                spawn_obstacle(
+                    commands,
+                    view,
+                    order,
+                    Obstacle::Polygon { points: walls },
+                    Vec2::ZERO,
+                );
+                for (from, to) in [(min.y, -DOOR_WIDTH / 2.), (DOOR_WIDTH / 2., max.y)] {
+                    let points = vec![Vec2::ZERO, Vec2::new(0., to - from)];
+                    spawn_obstacle(
+                        commands,
+                        view,
+                        order,
+                        Obstacle::Polyline { points },
+                        Vec2::new(0., from),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Sent to switch to another preset, see `load_preset`.
+pub struct LoadPreset(pub Preset);
+
+/// Replaces the world with `preset`'s: despawns obstacles, predators,
+/// emitters, spawners, goal zones, food, attractors and formations, drops the
+/// flow field and obstacle map, turns navigation off, applies the preset's
+/// config and spawns its scene and a fresh flock. The main
+/// target is pinned where the preset wants it, if anywhere, and released
+/// when leaving such a preset.
+pub fn load_preset(world: &mut World, preset: Preset) {
+    let previous = world.get_resource::<Preset>().copied();
+    let mut scenery = world.query_filtered::<Entity, Or<(
+        With<Obstacle>,
+        With<Predator>,
+        With<Emitter>,
+        With<BoidSpawner>,
+        With<Food>,
+        With<GoalZone>,
+        With<Attractor>,
+        With<Formation>,
+    )>>();
+    let scenery: Vec<Entity> = scenery.iter(world).collect();
+    for entity in scenery {
+        despawn_with_children_recursive(world, entity);
+    }
+    world.remove_resource::<FlowField>();
+    world.remove_resource::<ObstacleMapFile>();
+    world.remove_resource::<ObstacleMapHandle>();
+    world.remove_resource::<ObstacleMap>();
+    world.insert_resource(Navigation::default());
+    world.insert_resource(Playfield::default());
+    world.insert_resource(preset.config());
+    world.insert_resource(Flocks::default());
+    world.insert_resource(CaptureScore::default());
+    world.insert_resource(preset);
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    preset.spawn_scene(
+        &mut commands,
+        world.resource::<FlockView>(),
+        world.resource::<RenderOrder>(),
+        world.resource::<FlockConfig>(),
+        world.resource::<Bounds>(),
+    );
+    queue.apply(world);
+
+    if let Some(position) = preset.target() {
+        let mut targets = world.query_filtered::<&mut Transform, With<MainTarget>>();
+        for mut target in targets.iter_mut(world) {
+            target.translation = position.extend(target.translation.z);
+        }
+        world.resource_mut::<TargetPin>().pinned = true;
+    } else if previous.and_then(Preset::target).is_some() {
+        world.resource_mut::<TargetPin>().pinned = false;
+    }
+
+    reset_flock(world);
+    info!("loaded the {} preset", preset.name());
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spawn_preset_scene(
+    mut commands: Commands,
+    preset: Option<Res<Preset>>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    config: Res<FlockConfig>,
+    bounds: Res<Bounds>,
+    mut pin: ResMut<TargetPin>,
+    mut targets: Query<&mut Transform, With<MainTarget>>,
+) {
+    let Some(preset) = preset else {
+        return;
+    };
+    preset.spawn_scene(&mut commands, &view, &order, &config, &bounds);
+    if let Some(position) = preset.target() {
+        for mut target in targets.iter_mut() {
+            target.translation = position.extend(target.translation.z);
+        }
+        pin.pinned = true;
+    }
+}
+
+pub(crate) fn load_preset_on_request(world: &mut World) {
+    let requested = world.resource_mut::<Events<LoadPreset>>().drain().last();
+    if let Some(LoadPreset(preset)) = requested {
+        load_preset(world, preset);
+    }
+}
+
+/// In the flow field preset, every boid drifts with the currents, including
+/// those spawned after the flock.
+pub(crate) fn join_flow_field(
+    mut commands: Commands,
+    preset: Option<Res<Preset>>,
+    boids: Query<Entity, (Added<Boid>, Without<FollowFlowField>)>,
+) {
+    if preset.as_deref() != Some(&Preset::FlowField) {
+        return;
+    }
+    for boid in boids.iter() {
+        commands.entity(boid).insert(FollowFlowField::default());
+    }
+}
+
+/// `Shift` and a number key load the preset of that number.
+pub(crate) fn switch_preset_on_key(
+    keyboard: Res<Input<KeyCode>>,
+    mut load: EventWriter<LoadPreset>,
+) {
+    const KEYS: [KeyCode; 8] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+    ];
+
+    if !keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        return;
+    }
+    for (key, preset) in KEYS.into_iter().zip(Preset::ALL) {
+        if keyboard.just_pressed(key) {
+            load.send(LoadPreset(preset));
+        }
+    }
+}
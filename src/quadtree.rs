@@ -0,0 +1,182 @@
+use bevy::{math::Rect, prelude::*};
+
+use crate::{grid::GridEntry, neighbors::NeighborIndex};
+
+/// Boids held by a node before it splits into four.
+const CAPACITY: usize = 8;
+/// Nodes this deep never split, so boids piled on one spot do not recurse
+/// forever.
+const MAX_DEPTH: u32 = 12;
+
+/// Boids sorted into a tree of squares, each split in four once it holds
+/// more than a few boids. Adapts to the flock's density where a grid has a
+/// fixed cell size, at the cost of walking the tree on lookups.
+#[derive(Default)]
+pub struct Quadtree {
+    entries: Vec<GridEntry>,
+    // root first
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    bounds: Rect,
+    depth: u32,
+    children: Option<[usize; 4]>,
+    // indices into `Quadtree::entries`, for leaves only
+    entries: Vec<usize>,
+}
+
+impl Node {
+    fn new(bounds: Rect, depth: u32) -> Self {
+        Self {
+            bounds,
+            depth,
+            children: None,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Quadtree {
+    /// Child of `node` holding `position`.
+    fn child_at(&self, node: &Node, children: [usize; 4], position: Vec2) -> usize {
+        let center = node.bounds.center();
+        let index = (position.x >= center.x) as usize + 2 * (position.y >= center.y) as usize;
+        children[index]
+    }
+
+    fn insert(&mut self, index: usize) {
+        let position = self.entries[index].position.truncate();
+        let mut node = 0;
+        while let Some(children) = self.nodes[node].children {
+            node = self.child_at(&self.nodes[node], children, position);
+        }
+        self.nodes[node].entries.push(index);
+
+        if self.nodes[node].entries.len() > CAPACITY && self.nodes[node].depth < MAX_DEPTH {
+            self.split(node);
+        }
+    }
+
+    fn split(&mut self, node: usize) {
+        let Rect { min, max } = self.nodes[node].bounds;
+        let center = (min + max) / 2.;
+        let depth = self.nodes[node].depth + 1;
+        let first = self.nodes.len();
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let corner = Vec2::new(
+                if x == 0 { min.x } else { center.x },
+                if y == 0 { min.y } else { center.y },
+            );
+            let bounds = Rect::from_corners(corner, corner + (max - min) / 2.);
+            self.nodes.push(Node::new(bounds, depth));
+        }
+        self.nodes[node].children = Some([first, first + 1, first + 2, first + 3]);
+
+        for index in std::mem::take(&mut self.nodes[node].entries) {
+            self.insert(index);
+        }
+    }
+}
+
+impl NeighborIndex for Quadtree {
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.nodes.clear();
+    }
+
+    fn rebuild(&mut self, entries: Vec<GridEntry>, _radius: f32) {
+        self.clear();
+        let Some(first) = entries.first() else {
+            return;
+        };
+
+        // a square root around all the boids keeps the nodes square
+        let (min, max) = entries.iter().fold(
+            (first.position.truncate(), first.position.truncate()),
+            |(min, max), entry| {
+                let position = entry.position.truncate();
+                (min.min(position), max.max(position))
+            },
+        );
+        let half_size = ((max - min).max_element() / 2.).max(1.);
+        let root = Rect::from_center_half_size((min + max) / 2., Vec2::splat(half_size));
+        self.nodes.push(Node::new(root, 0));
+
+        self.entries = entries;
+        for index in 0..self.entries.len() {
+            self.insert(index);
+        }
+    }
+
+    fn neighbors_within(
+        &self,
+        position: Vec3,
+        radius: f32,
+    ) -> Box<dyn Iterator<Item = &GridEntry> + '_> {
+        let center = position.truncate();
+        let mut found = Vec::new();
+        let mut stack: Vec<usize> = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            // skip nodes the circle's bounding box misses
+            let closest = center.clamp(node.bounds.min, node.bounds.max);
+            if closest.distance(center) >= radius {
+                continue;
+            }
+            match node.children {
+                Some(children) => stack.extend(children),
+                None => found.extend(
+                    node.entries
+                        .iter()
+                        .map(|index| &self.entries[*index])
+                        .filter(|entry| entry.position.distance(position) < radius),
+                ),
+            }
+        }
+        Box::new(found.into_iter())
+    }
+
+    fn outlines(&self) -> Vec<Rect> {
+        self.nodes
+            .iter()
+            .filter(|node| node.children.is_none() && !node.entries.is_empty())
+            .map(|node| node.bounds)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flock::FlockId, species::Species};
+
+    #[test]
+    fn boids_on_one_spot_are_all_found() {
+        // no extent at all, and more boids than a node holds
+        let entries = (0..CAPACITY as u32 * 3)
+            .map(|index| GridEntry {
+                entity: Entity::from_raw(index),
+                position: Vec3::new(5., 5., 0.),
+                velocity: Vec3::ZERO,
+                species: Species::default(),
+                flock: FlockId::default(),
+            })
+            .collect();
+        let mut tree = Quadtree::default();
+        tree.rebuild(entries, 10.);
+
+        assert_eq!(
+            tree.neighbors_within(Vec3::new(5., 6., 0.), 2.).count(),
+            CAPACITY * 3
+        );
+        assert!(tree
+            .outlines()
+            .iter()
+            .all(|rect| rect.size().min_element() > 0.));
+    }
+}
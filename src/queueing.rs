@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    lod::Lod,
+    neighbors::{NeighborIndex, SpatialIndex},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Priority of queueing in `SteeringForces`, with separation: a boid held
+/// up by the one ahead brakes even while it seeks hard.
+const QUEUE_PRIORITY: i32 = 1;
+
+/// Reynolds' queueing, with `FlockConfig::queueing`: a boid with another
+/// straight ahead within the braking distance slows down towards that one's
+/// speed, the more the closer it is, so crowds file through narrow gaps
+/// instead of jamming them.
+pub(crate) fn queue(
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    lods: Query<&Lod>,
+    mut boids: Query<(Entity, &Transform, &Physics, &mut SteeringForces), With<Boid>>,
+) {
+    let Some(braking_distance) = config.queueing.filter(|distance| *distance > 0.) else {
+        return;
+    };
+
+    boids.par_for_each_mut(
+        PAR_BATCH_SIZE,
+        |(entity, transform, physics, mut forces)| {
+            if lods.get(entity).is_ok_and(Lod::skips_steering) {
+                return;
+            }
+            let Some(heading) = physics.velocity.try_normalize() else {
+                return;
+            };
+            let position = transform.translation;
+            // the nearest boid in a lane as wide as a boid ahead
+            let ahead = index
+                .others_within(entity, position, braking_distance)
+                .filter_map(|other| {
+                    let offset = other.position - position;
+                    let along = offset.dot(heading);
+                    let aside = (offset - heading * along).length();
+                    (along > 0. && aside < config.boid_width).then_some((along, other))
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+            let Some((distance, other)) = ahead else {
+                return;
+            };
+
+            let speed = physics.velocity.length();
+            let ahead_speed = other.velocity.dot(heading).max(0.);
+            if speed <= ahead_speed {
+                return;
+            }
+            let room = (distance / braking_distance).clamp(0., 1.);
+            let desired = heading * (ahead_speed + (speed - ahead_speed) * room);
+            let force = steer_towards(desired, physics, config.response_curve);
+            forces
+                .behavior("queue")
+                .add_with_priority(force, 1., QUEUE_PRIORITY);
+        },
+    );
+}
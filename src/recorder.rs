@@ -0,0 +1,76 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    app::AppExit,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{stats::FlockStats, tick::SimTick};
+
+/// Appends a row of metrics to a CSV file after every simulation step, for
+/// parameter sweeps analyzed offline. The frame rate is left empty without
+/// `FrameTimeDiagnosticsPlugin`.
+#[derive(Resource)]
+pub struct MetricsRecorder {
+    writer: BufWriter<File>,
+}
+
+impl MetricsRecorder {
+    /// Creates the file at `path`, replacing any previous one, and writes the
+    /// header row.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "tick,boid_count,avg_speed,polarization,fps")?;
+        Ok(Self { writer })
+    }
+}
+
+pub(crate) fn record_metrics(
+    mut commands: Commands,
+    tick: Res<SimTick>,
+    stats: Res<FlockStats>,
+    diagnostics: Option<Res<Diagnostics>>,
+    recorder: Option<ResMut<MetricsRecorder>>,
+) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+
+    let fps = diagnostics
+        .as_ref()
+        .and_then(|diagnostics| diagnostics.get(FrameTimeDiagnosticsPlugin::FPS))
+        .and_then(|fps| fps.smoothed())
+        .map_or(String::new(), |fps| format!("{fps:.1}"));
+    let row = writeln!(
+        recorder.writer,
+        "{},{},{},{},{fps}",
+        tick.steps(),
+        stats.boid_count,
+        stats.average_speed,
+        stats.polarization,
+    );
+    if let Err(error) = row {
+        warn!("stopped recording metrics: {error}");
+        commands.remove_resource::<MetricsRecorder>();
+    }
+}
+
+/// Writes out the rows still buffered when the app exits.
+pub(crate) fn flush_metrics_on_exit(
+    mut exit: EventReader<AppExit>,
+    recorder: Option<ResMut<MetricsRecorder>>,
+) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+    if let Some(mut recorder) = recorder {
+        if let Err(error) = recorder.writer.flush() {
+            warn!("could not save the last metrics: {error}");
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{CameraProjection, RenderTarget, Viewport},
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::RenderLayers,
+    },
+    sprite::Mesh2dHandle,
+};
+use bevy_prototype_lyon::{prelude::*, render::Shape};
+
+use crate::{
+    boid::{BodySize, Boid, BoidId},
+    camera::DetailCamera,
+    flock::FlockConfig,
+    obstacle::Obstacle,
+    target::Target,
+};
+
+/// Camera looking at the flock.
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Where the flock is drawn. An app embedding the flock next to its own content
+/// can give it a dedicated render layer and restrict its camera to a viewport
+/// instead of the whole window.
+#[derive(Resource, Default)]
+pub struct FlockView {
+    pub layers: RenderLayers,
+    pub viewport: Option<Viewport>,
+    // cameras with a lower priority are drawn first, use a negative value to
+    // keep the flock behind other cameras
+    pub priority: isize,
+}
+
+/// Depth of each kind of entity. Later layers are drawn on top, so
+/// obstacles, the target and debug overlays stay visible however many boids
+/// fly over them. `apply_render_order` keeps boids, obstacles, targets and
+/// `Overlay`s at their depth.
+#[derive(Resource)]
+pub struct RenderOrder {
+    // layers drawn behind the flock, such as heatmaps
+    pub background: f32,
+    pub trails: f32,
+    pub boids: f32,
+    pub obstacles: f32,
+    pub target: f32,
+    pub overlays: f32,
+}
+
+impl Default for RenderOrder {
+    fn default() -> Self {
+        Self {
+            background: 50.,
+            trails: 90.,
+            boids: 100.,
+            obstacles: 200.,
+            target: 300.,
+            overlays: 500.,
+        }
+    }
+}
+
+/// Marks debug drawings and other overlays, drawn over everything else at
+/// `RenderOrder::overlays`.
+#[derive(Component)]
+pub struct Overlay;
+
+/// How boids are drawn. `Shapes` tessellates an outlined triangle for each
+/// boid with lyon, the pretty default for a few hundred boids. `Sprites`
+/// draws them all with one shared triangle image, tinted with each boid's
+/// color, which the renderer batches into a single draw call: the way to go
+/// for tens of thousands of boids, without the outline or the heading line.
+/// Boids spawned after a change are drawn the new way.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BoidRendering {
+    #[default]
+    Shapes,
+    Sprites,
+}
+
+/// Triangle image shared by the boids drawn as sprites.
+#[derive(Resource)]
+pub(crate) struct BoidSprite(Handle<Image>);
+
+pub(crate) fn setup_boid_sprite(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    // texels across and along the triangle, stretched to the boid's size
+    const WIDTH: u32 = 32;
+    const LENGTH: u32 = 64;
+
+    let mut data = Vec::with_capacity((WIDTH * LENGTH * 4) as usize);
+    for row in 0..LENGTH {
+        // the apex is on the first row, pointing up like the lyon triangle
+        let half_width = (row as f32 + 0.5) / LENGTH as f32 * WIDTH as f32 / 2.;
+        for column in 0..WIDTH {
+            let from_center = (column as f32 + 0.5 - WIDTH as f32 / 2.).abs();
+            // a texel of smoothing along the slanted edges
+            let coverage = (half_width - from_center + 0.5).clamp(0., 1.);
+            data.extend_from_slice(&[255, 255, 255, (coverage * 255.) as u8]);
+        }
+    }
+
+    let image = Image::new(
+        Extent3d {
+            width: WIDTH,
+            height: LENGTH,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    commands.insert_resource(BoidSprite(images.add(image)));
+}
+
+/// Swaps the lyon shape of new boids for a sprite under `BoidRendering::Sprites`.
+/// Their `DrawMode` stays, as the color the sprite is tinted with, so this
+/// runs once their flock has colored it.
+pub(crate) fn draw_boids_as_sprites(
+    mut commands: Commands,
+    rendering: Res<BoidRendering>,
+    sprite: Res<BoidSprite>,
+    config: Res<FlockConfig>,
+    boids: Query<(Entity, &DrawMode), Added<Boid>>,
+) {
+    if *rendering != BoidRendering::Sprites {
+        return;
+    }
+    for (entity, mode) in boids.iter() {
+        commands
+            .entity(entity)
+            .remove::<(Path, Shape, Mesh2dHandle)>()
+            .insert(Sprite {
+                color: fill_color(mode),
+                custom_size: Some(
+                    Vec2::new(config.boid_width, config.boid_length) * config.shape.scale,
+                ),
+                ..default()
+            })
+            .insert(sprite.0.clone());
+    }
+}
+
+/// Tints boid sprites with the fill color of their `DrawMode`, which the
+/// flock coloring systems keep up to date. Only boids have both.
+pub(crate) fn tint_sprites(mut boids: Query<(&DrawMode, &mut Sprite), Changed<DrawMode>>) {
+    for (mode, mut sprite) in boids.iter_mut() {
+        sprite.color = fill_color(mode);
+    }
+}
+
+fn fill_color(mode: &DrawMode) -> Color {
+    match mode {
+        DrawMode::Outlined { fill_mode, .. } | DrawMode::Fill(fill_mode) => fill_mode.color,
+        DrawMode::Stroke(stroke_mode) => stroke_mode.color,
+    }
+}
+
+pub(crate) fn setup_camera(mut commands: Commands, view: Res<FlockView>) {
+    // Add a camera so we can see the debug-render.
+    commands
+        .spawn(Camera2dBundle {
+            camera: Camera {
+                viewport: view.viewport.clone(),
+                priority: view.priority,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(view.layers)
+        .insert(MainCamera);
+}
+
+/// World position of the cursor, `None` while it is outside the flock's
+/// viewport.
+#[derive(Resource, Default)]
+pub struct CursorPosition(pub Option<Vec2>);
+
+/// Where the first finger on the screen is, or was lifted on this frame,
+/// measured from the bottom of the window as the cursor is.
+fn touch_position(touches: &Touches, window: &Window) -> Option<Vec2> {
+    let touch = touches
+        .iter()
+        .next()
+        .or_else(|| touches.iter_just_released().next())?;
+    let position = touch.position();
+    // bevy only turns touches upright on phones
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        Some(position)
+    } else {
+        Some(Vec2::new(position.x, window.height() - position.y))
+    }
+}
+
+/// Flock camera the cursor was last over, if any.
+#[derive(Resource, Default)]
+pub(crate) struct HoveredCamera(pub Option<Entity>);
+
+/// Tracks the mouse cursor, or on touch screens the first finger, in the
+/// world, as seen through whichever of the flock's cameras it is over.
+pub(crate) fn track_cursor(
+    // need to get window dimensions
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    // query to get camera transform, as panned and zoomed on this frame
+    main_cameras: Query<(Entity, &Camera, &Transform, &OrthographicProjection), With<MainCamera>>,
+    // drawn over the main camera when the screen is split
+    detail_cameras: Query<
+        (Entity, &Camera, &Transform, &OrthographicProjection),
+        With<DetailCamera>,
+    >,
+    mut cursor: ResMut<CursorPosition>,
+    mut hovered: ResMut<HoveredCamera>,
+) {
+    cursor.0 = None;
+    hovered.0 = None;
+
+    let cameras = detail_cameras.iter().chain(main_cameras.iter());
+    for (entity, camera, camera_transform, projection) in cameras {
+        if !camera.is_active {
+            continue;
+        }
+        let position = cursor_in_view(&windows, &touches, camera, camera_transform, projection);
+        if position.is_some() {
+            cursor.0 = position;
+            hovered.0 = Some(entity);
+            return;
+        }
+    }
+}
+
+/// World position of the cursor seen through `camera`, `None` while it is
+/// outside the camera's viewport.
+fn cursor_in_view(
+    windows: &Windows,
+    touches: &Touches,
+    camera: &Camera,
+    camera_transform: &Transform,
+    projection: &OrthographicProjection,
+) -> Option<Vec2> {
+    // get the window that the camera is displaying to (or the primary window)
+    let window = if let RenderTarget::Window(id) = camera.target {
+        windows.get(id)
+    } else {
+        windows.get_primary()
+    }?;
+
+    // check if the cursor is inside the window and get its position
+    let screen_pos = window
+        .cursor_position()
+        .or_else(|| touch_position(touches, window))?;
+
+    // get the part of the window the camera renders to, measured from the top
+    let (viewport_min, viewport_max) = camera.logical_viewport_rect()?;
+    let viewport_size = viewport_max - viewport_min;
+
+    // the cursor is measured from the bottom of the window
+    let viewport_pos = Vec2::new(
+        screen_pos.x - viewport_min.x,
+        screen_pos.y - (window.height() - viewport_max.y),
+    );
+    if viewport_pos.cmplt(Vec2::ZERO).any() || viewport_pos.cmpgt(viewport_size).any() {
+        return None;
+    }
+
+    // convert viewport position [0..resolution] to ndc [-1..1] (gpu coordinates)
+    let ndc = (viewport_pos / viewport_size) * 2.0 - Vec2::ONE;
+
+    // matrix for undoing the projection and camera transform; the camera's
+    // own matrices only catch up with zooming and panning at the end of the
+    // frame
+    let ndc_to_world =
+        camera_transform.compute_matrix() * projection.get_projection_matrix().inverse();
+
+    // use it to convert ndc to world-space coordinates
+    let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+
+    // reduce it to a 2D value
+    Some(world_pos.truncate())
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn apply_render_order(
+    order: Res<RenderOrder>,
+    mut query: Query<(&mut Transform, AnyOf<(&Boid, &Obstacle, &Target, &Overlay)>)>,
+) {
+    for (mut transform, kind) in query.iter_mut() {
+        transform.translation.z = match kind {
+            (Some(_), ..) => order.boids,
+            (_, Some(_), ..) => order.obstacles,
+            (_, _, Some(_), _) => order.target,
+            _ => order.overlays,
+        };
+    }
+}
+
+/// Gentle scale pulse making a slow flock look alive, each boid phase
+/// shifted by its `BoidId` so they don't pulse in unison. Only drawn unless
+/// `spacing` is set, in which case the `BodySize` itself pulses with the
+/// simulation clock, and the room boids keep from their neighbors with it.
+#[derive(Resource)]
+pub struct Breathing {
+    // fraction of the base size added and removed at the peaks
+    pub amplitude: f32,
+    // pulses per second
+    pub frequency: f32,
+    pub spacing: bool,
+}
+
+impl Breathing {
+    /// Fraction of its size the boid `id` gains, or loses when negative,
+    /// `seconds` into the pulse.
+    pub fn pulse(&self, id: BoidId, seconds: f32) -> f32 {
+        // golden angle steps spread the phases evenly whatever the boid count
+        let phase = id.0 as f32 * 2.399;
+        let wave = (seconds * self.frequency * std::f32::consts::TAU + phase).sin();
+        self.amplitude * wave
+    }
+}
+
+/// Scales boids to their `BodySize`, pulsing with the `Breathing` if any.
+pub(crate) fn scale_boids(
+    time: Res<Time>,
+    breathing: Option<Res<Breathing>>,
+    mut query: Query<(&mut Transform, &BoidId, &BodySize)>,
+) {
+    let elapsed = time.elapsed_seconds();
+    for (mut transform, id, size) in query.iter_mut() {
+        let pulse = match breathing.as_deref() {
+            // the size already pulses
+            Some(breathing) if !breathing.spacing => breathing.pulse(*id, elapsed),
+            _ => 0.,
+        };
+        transform.scale = Vec3::splat(size.0 * (1. + pulse));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{
+        boid::{grow_boids, Physics},
+        tick::SimTick,
+    };
+
+    const BREATHING: Breathing = Breathing {
+        amplitude: 0.1,
+        frequency: 0.5,
+        spacing: false,
+    };
+
+    #[test]
+    fn scale_stays_within_the_pulse() {
+        let mut world = World::new();
+        world.insert_resource(BREATHING);
+        let start = Instant::now();
+        let mut time = Time::default();
+        time.update_with_instant(start);
+        world.insert_resource(time);
+        let boids: Vec<_> = (0..8)
+            .map(|id| {
+                world
+                    .spawn((Transform::default(), BoidId(id), BodySize(2.)))
+                    .id()
+            })
+            .collect();
+
+        let mut stage = SystemStage::single(scale_boids);
+        let (mut smallest, mut largest) = (f32::MAX, f32::MIN);
+        for frame in 1..=120 {
+            let now = start + Duration::from_millis(frame * 50);
+            world.resource_mut::<Time>().update_with_instant(now);
+            stage.run(&mut world);
+            for &boid in &boids {
+                let scale = world.get::<Transform>(boid).unwrap().scale.x;
+                smallest = smallest.min(scale);
+                largest = largest.max(scale);
+            }
+        }
+        assert!(smallest >= 2. * 0.9 - 1e-4 && largest <= 2. * 1.1 + 1e-4);
+        // it does pulse, most of the way to the peaks
+        assert!(smallest < 1.85 && largest > 2.15);
+    }
+
+    #[test]
+    fn spacing_pulses_the_body_size_only_when_asked() {
+        for spacing in [false, true] {
+            let mut world = World::new();
+            world.insert_resource(Breathing {
+                spacing,
+                ..BREATHING
+            });
+            world.insert_resource(SimTick::default());
+            world.insert_resource(FlockConfig::default());
+            let boid = world
+                .spawn((Boid, BoidId(1), Physics::default(), BodySize::default()))
+                .id();
+
+            SystemStage::single(grow_boids).run(&mut world);
+
+            let size = world.get::<BodySize>(boid).unwrap().0;
+            match spacing {
+                false => assert_eq!(size, 1.),
+                true => {
+                    assert_ne!(size, 1.);
+                    assert!((size - 1.).abs() <= BREATHING.amplitude);
+                }
+            }
+        }
+    }
+}
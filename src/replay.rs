@@ -0,0 +1,242 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::{app::AppExit, ecs::schedule::ShouldRun, prelude::*, utils::HashMap};
+
+use crate::{
+    boid::{Boid, BoidBuilder, BoidId, Physics},
+    flock::{FlockConfig, FlockId},
+    render::{FlockView, RenderOrder},
+    sim,
+    tick::SimTick,
+};
+
+/// Start of every recording, followed by the step rate as an `f32`.
+const MAGIC: &[u8; 8] = b"BOIDREC1";
+
+/// State of one boid at the end of a step.
+#[derive(Clone, Copy)]
+struct RecordedBoid {
+    id: u32,
+    flock: u32,
+    position: Vec2,
+    velocity: Vec2,
+}
+
+impl RecordedBoid {
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.id.to_le_bytes())?;
+        writer.write_all(&self.flock.to_le_bytes())?;
+        for value in [
+            self.position.x,
+            self.position.y,
+            self.velocity.x,
+            self.velocity.y,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        Ok(Self {
+            id: read_u32(reader)?,
+            flock: read_u32(reader)?,
+            position: Vec2::new(read_f32(reader)?, read_f32(reader)?),
+            velocity: Vec2::new(read_f32(reader)?, read_f32(reader)?),
+        })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+/// Writes the position and velocity of every boid after each simulation
+/// step to a binary file, which `Replay` plays back.
+///
+/// After the header, each step is the number of boids as a `u32` followed by,
+/// for every boid, its `BoidId` and `FlockId` as `u32`s and its position and
+/// velocity as `f32`s, all little-endian.
+#[derive(Resource)]
+pub struct FlightRecorder {
+    writer: BufWriter<File>,
+}
+
+impl FlightRecorder {
+    /// Creates the file at `path`, replacing any previous one, for a
+    /// simulation stepping at `rate` steps per second.
+    pub fn create(path: impl AsRef<Path>, rate: f32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&rate.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+}
+
+/// Plays back a file written by `FlightRecorder`, one recorded step per
+/// simulation step, in place of steering and physics. The boids stay where
+/// they are once the recording runs out.
+#[derive(Resource)]
+pub struct Replay {
+    rate: f32,
+    steps: Vec<Vec<RecordedBoid>>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a boid recording",
+            ));
+        }
+        let rate = read_f32(&mut reader)?;
+
+        let mut steps = Vec::new();
+        loop {
+            let count = match read_u32(&mut reader) {
+                Ok(count) => count,
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            };
+            let boids = (0..count)
+                .map(|_| RecordedBoid::read(&mut reader))
+                .collect::<io::Result<_>>()?;
+            steps.push(boids);
+        }
+        Ok(Self {
+            rate,
+            steps,
+            next: 0,
+        })
+    }
+
+    /// Steps in the recording.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Run criterion of the steering and physics systems, which stand aside
+/// while a `Replay` drives the boids.
+pub(crate) fn simulating(replay: Option<Res<Replay>>) -> ShouldRun {
+    match replay {
+        Some(_) => ShouldRun::No,
+        None => ShouldRun::Yes,
+    }
+}
+
+pub(crate) fn record_flight(
+    mut commands: Commands,
+    recorder: Option<ResMut<FlightRecorder>>,
+    boids: Query<(&Transform, &Physics, &BoidId, Option<&FlockId>), With<Boid>>,
+) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+
+    // boids spawned during the step get their id on the next frame
+    let count = boids.iter().count() as u32;
+    let mut write = || -> io::Result<()> {
+        recorder.writer.write_all(&count.to_le_bytes())?;
+        for (transform, physics, id, flock) in boids.iter() {
+            let boid = RecordedBoid {
+                id: id.0,
+                flock: flock.map_or(0, |flock| flock.0),
+                position: transform.translation.truncate(),
+                velocity: physics.velocity.truncate(),
+            };
+            boid.write(&mut recorder.writer)?;
+        }
+        Ok(())
+    };
+    if let Err(error) = write() {
+        warn!("stopped recording: {error}");
+        commands.remove_resource::<FlightRecorder>();
+    }
+}
+
+/// Writes out the steps still buffered when the app exits.
+pub(crate) fn flush_recording_on_exit(
+    mut exit: EventReader<AppExit>,
+    recorder: Option<ResMut<FlightRecorder>>,
+) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+    if let Some(mut recorder) = recorder {
+        if let Err(error) = recorder.writer.flush() {
+            warn!("could not save the end of the recording: {error}");
+        }
+    }
+}
+
+/// Puts the boids where the recording has them at this step, spawning the
+/// ones missing and despawning the ones it does not list.
+pub(crate) fn play_back(
+    mut commands: Commands,
+    replay: Option<ResMut<Replay>>,
+    mut tick: ResMut<SimTick>,
+    config: Res<FlockConfig>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mut boids: Query<(Entity, &BoidId, &mut Transform, &mut Physics), With<Boid>>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    tick.rate = replay.rate;
+    let Some(step) = replay.steps.get(replay.next) else {
+        return;
+    };
+
+    let mut recorded: HashMap<u32, RecordedBoid> =
+        step.iter().map(|boid| (boid.id, *boid)).collect();
+    for (entity, id, mut transform, mut physics) in boids.iter_mut() {
+        let Some(boid) = recorded.remove(&id.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        transform.translation = boid.position.extend(transform.translation.z);
+        if let Some(heading) = sim::heading(boid.velocity.extend(0.)) {
+            transform.rotation = heading;
+        }
+        physics.velocity = boid.velocity.extend(0.);
+    }
+
+    // in id order, so entities are spawned the same way on every run
+    let mut missing: Vec<RecordedBoid> = recorded.into_values().collect();
+    missing.sort_by_key(|boid| boid.id);
+    for boid in missing {
+        let bundle = BoidBuilder::new()
+            .config(&config)
+            .view(&view)
+            .at(boid.position.extend(order.boids))
+            .velocity(boid.velocity.extend(0.))
+            .flock(boid.flock)
+            .build();
+        commands.spawn(bundle).insert(BoidId(boid.id));
+    }
+    replay.next += 1;
+    if replay.next == replay.len() {
+        info!("replay finished after {} steps", replay.len());
+    }
+}
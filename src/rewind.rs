@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    boid::{Boid, BoidBuilder, BoidId, Physics},
+    energy::Energy,
+    flock::{FlockConfig, FlockId},
+    render::{FlockView, RenderOrder},
+    state::AppState,
+    tick::{Interpolated, SimTick, SimulationControl},
+};
+
+/// State of one boid at the end of a step.
+#[derive(Clone, Copy)]
+struct RewoundBoid {
+    id: u32,
+    flock: u32,
+    translation: Vec3,
+    rotation: Quat,
+    velocity: Vec3,
+    acceleration: Vec3,
+    mass: f32,
+    energy: Option<f32>,
+}
+
+struct RewindFrame {
+    step: u64,
+    boids: Vec<RewoundBoid>,
+}
+
+/// The boids at each of the last `seconds` of simulation steps. Holding `,`
+/// pauses the simulation and scrubs back through them, one step per frame;
+/// resuming, or stepping with `.`, goes on from the step shown and drops the
+/// ones after it. Only the boids are rewound, not the target, obstacles or
+/// random numbers, so the run does not repeat itself from there.
+#[derive(Resource)]
+pub struct RewindBuffer {
+    pub seconds: f32,
+    frames: VecDeque<RewindFrame>,
+    // frame shown while scrubbing, `None` at the latest step
+    cursor: Option<usize>,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self {
+            seconds: 10.,
+            frames: VecDeque::new(),
+            cursor: None,
+        }
+    }
+}
+
+impl RewindBuffer {
+    /// Steps held.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+pub(crate) fn record_rewind_frame(
+    tick: Res<SimTick>,
+    buffer: Option<ResMut<RewindBuffer>>,
+    boids: Query<(Entity, &Transform, &Physics, &BoidId), With<Boid>>,
+    flock_ids: Query<&FlockId>,
+    energies: Query<&Energy>,
+) {
+    let Some(mut buffer) = buffer else {
+        return;
+    };
+    // the simulation goes on from the step rewound to
+    if let Some(cursor) = buffer.cursor.take() {
+        buffer.frames.truncate(cursor + 1);
+    }
+
+    // boids spawned during the step get their id on the next frame
+    let boids = boids
+        .iter()
+        .map(|(entity, transform, physics, id)| RewoundBoid {
+            id: id.0,
+            flock: flock_ids.get(entity).copied().unwrap_or_default().0,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            velocity: physics.velocity,
+            acceleration: physics.acceleration,
+            mass: physics.mass,
+            energy: energies.get(entity).ok().map(|energy| energy.current),
+        })
+        .collect();
+    buffer.frames.push_back(RewindFrame {
+        step: tick.steps(),
+        boids,
+    });
+
+    let capacity = (buffer.seconds * tick.rate).ceil().max(1.) as usize;
+    while buffer.frames.len() > capacity {
+        buffer.frames.pop_front();
+    }
+}
+
+/// While `,` is held, pauses the simulation and puts the boids back one more
+/// step, spawning the ones since despawned and despawning the ones since
+/// spawned.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn rewind(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    config: Res<FlockConfig>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mut buffer: ResMut<RewindBuffer>,
+    mut tick: ResMut<SimTick>,
+    mut control: ResMut<SimulationControl>,
+    mut state: ResMut<State<AppState>>,
+    mut boids: Query<
+        (
+            Entity,
+            &BoidId,
+            &mut Transform,
+            &mut Physics,
+            &mut Interpolated,
+        ),
+        With<Boid>,
+    >,
+    mut energies: Query<&mut Energy>,
+) {
+    if !keyboard.pressed(KeyCode::Comma) || *state.current() == AppState::MainMenu {
+        return;
+    }
+    if *state.current() == AppState::Running {
+        // a transition may already be queued this frame
+        let _ = state.set(AppState::Paused);
+        // no step before the state changes
+        control.paused = true;
+    }
+
+    // the latest frame is the step the boids are at
+    let index = match buffer.cursor {
+        Some(cursor) => cursor.checked_sub(1),
+        None => buffer.frames.len().checked_sub(2),
+    };
+    let Some(index) = index else {
+        return;
+    };
+    buffer.cursor = Some(index);
+    let frame = &buffer.frames[index];
+    tick.rewind_to(frame.step);
+
+    let mut saved: HashMap<u32, RewoundBoid> =
+        frame.boids.iter().map(|boid| (boid.id, *boid)).collect();
+    for (entity, id, mut transform, mut physics, mut interpolated) in boids.iter_mut() {
+        let Some(boid) = saved.remove(&id.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        transform.translation = boid.translation;
+        transform.rotation = boid.rotation;
+        interpolated.reset(boid.translation.truncate(), boid.rotation);
+        physics.velocity = boid.velocity;
+        physics.acceleration = boid.acceleration;
+        if let (Some(current), Ok(mut energy)) = (boid.energy, energies.get_mut(entity)) {
+            energy.current = current;
+        }
+    }
+
+    // in id order, so entities are spawned the same way on every run
+    let mut missing: Vec<RewoundBoid> = saved.into_values().collect();
+    missing.sort_by_key(|boid| boid.id);
+    for boid in missing {
+        let bundle = BoidBuilder::new()
+            .config(&config)
+            .view(&view)
+            .at(boid.translation.truncate().extend(order.boids))
+            .velocity(boid.velocity)
+            .mass(boid.mass)
+            .flock(boid.flock)
+            .build();
+        commands.spawn(bundle).insert(BoidId(boid.id));
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+/// Seed of the simulation's random numbers, so runs can be reproduced.
+#[derive(Resource, Clone, Copy)]
+pub struct SimSeed(pub u64);
+
+/// Source of every random number in the simulation (spawn positions, wander
+/// jitter...), seeded from `SimSeed` on startup: two runs with the same seed
+/// and the same input play out identically, step for step.
+#[derive(Resource)]
+pub struct SimRng(StdRng);
+
+impl SimRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// Seeds `SimRng` before the flock is spawned, at random when there is no
+/// `SimSeed`.
+pub(crate) fn seed_rng(mut commands: Commands, seed: Option<Res<SimSeed>>) {
+    let seed = seed.map_or_else(rand::random, |seed| seed.0);
+    info!("random seed: {}", seed);
+    commands.insert_resource(SimRng::seeded(seed));
+}
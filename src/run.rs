@@ -0,0 +1,52 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{stats::FlockStats, tick::SimTick};
+
+/// Stops the app after a fixed duration and prints a summary of the flock,
+/// for benchmarks, scripted captures and headless runs.
+#[derive(Resource, Clone, Copy)]
+pub enum RunDuration {
+    /// Simulated seconds.
+    Simulated(f32),
+    /// Real seconds.
+    WallClock(f32),
+    /// Simulation steps.
+    Steps(u64),
+}
+
+pub(crate) fn exit_after_run_duration(
+    time: Res<Time>,
+    tick: Res<SimTick>,
+    duration: Option<Res<RunDuration>>,
+    stats: Res<FlockStats>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(duration) = duration else {
+        return;
+    };
+
+    let done = match *duration {
+        RunDuration::Simulated(seconds) => tick.elapsed() >= seconds,
+        RunDuration::WallClock(seconds) => time.startup().elapsed().as_secs_f32() >= seconds,
+        RunDuration::Steps(steps) => tick.steps() >= steps,
+    };
+    if !done {
+        return;
+    }
+
+    println!(
+        "ran for {} steps ({:.2}s simulated, {:.2}s real): {} boids, \
+         average speed {:.3}, polarization {:.3}, spread {:.1}, nearest neighbor {}",
+        tick.steps(),
+        tick.elapsed(),
+        time.startup().elapsed().as_secs_f32(),
+        stats.boid_count,
+        stats.average_speed,
+        stats.polarization,
+        stats.spread,
+        stats
+            .nearest_neighbor_distance
+            .map_or("none".to_string(), |distance| format!("{distance:.1}")),
+    );
+    exit.send(AppExit);
+}
@@ -0,0 +1,278 @@
+use std::{
+    fs,
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssets,
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d,
+            ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, MapMode, Origin3d, TextureAspect,
+            TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        view::RenderLayers,
+        Extract, RenderApp, RenderStage,
+    },
+    tasks::IoTaskPool,
+};
+
+use crate::render::MainCamera;
+
+/// Folder `F12` saves screenshots to, relative to the working directory.
+#[derive(Resource)]
+pub struct ScreenshotDir(pub PathBuf);
+
+impl Default for ScreenshotDir {
+    fn default() -> Self {
+        Self(PathBuf::from("screenshots"))
+    }
+}
+
+/// Saves what the main camera sees to a PNG when `F12` is pressed.
+///
+/// A second camera renders the same view to an image, which is copied back
+/// from the GPU once drawn and written out off the main thread.
+pub(crate) struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        let captured = CapturedFrames::default();
+        app.init_resource::<ScreenshotDir>()
+            .insert_resource(captured.clone())
+            .add_system(take_screenshot)
+            .add_system(save_screenshots);
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(captured)
+            .init_resource::<FrameCopies>()
+            .add_system_to_stage(RenderStage::Extract, extract_screenshots)
+            .add_system_to_stage(RenderStage::Cleanup, copy_frames);
+    }
+}
+
+/// Camera drawing the view for a screenshot, despawned once it is taken.
+#[derive(Component, Clone)]
+pub(crate) struct ScreenshotCamera {
+    image: Handle<Image>,
+    path: PathBuf,
+}
+
+/// Frames copied back from the GPU, handed from the render world to the main
+/// one.
+#[derive(Resource, Clone, Default)]
+struct CapturedFrames(Arc<Mutex<Vec<(PathBuf, Image)>>>);
+
+/// Screenshot cameras of the frame being rendered.
+#[derive(Resource, Default)]
+struct PendingScreenshots(Vec<ScreenshotCamera>);
+
+/// Copy of a drawn frame, waiting for the GPU to map it.
+struct FrameCopy {
+    path: PathBuf,
+    buffer: Buffer,
+    size: Extent3d,
+    // bytes per row in the buffer, padded as the GPU requires
+    padded_row: u32,
+    mapped: Arc<AtomicBool>,
+}
+
+#[derive(Resource, Default)]
+struct FrameCopies(Vec<FrameCopy>);
+
+fn take_screenshot(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    dir: Res<ScreenshotDir>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    camera: Query<(&Transform, &OrthographicProjection, Option<&RenderLayers>), With<MainCamera>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F12) {
+        return;
+    }
+    let (Some(window), Ok((transform, projection, layers))) =
+        (windows.get_primary(), camera.get_single())
+    else {
+        return;
+    };
+    if let Err(error) = fs::create_dir_all(&dir.0) {
+        warn!("could not create {}: {error}", dir.0.display());
+        return;
+    }
+
+    let size = Extent3d {
+        width: window.physical_width(),
+        height: window.physical_height(),
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.texture_descriptor.usage |= TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+    let image = images.add(image);
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    let path = dir.0.join(format!("flock-{millis}.png"));
+
+    // the image has no scale factor, so the projection makes up for it
+    let projection = OrthographicProjection {
+        scale: projection.scale / window.scale_factor() as f32,
+        ..projection.clone()
+    };
+    commands
+        .spawn(Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image.clone()),
+                ..default()
+            },
+            projection,
+            transform: *transform,
+            ..default()
+        })
+        .insert(layers.copied().unwrap_or_default())
+        .insert(ScreenshotCamera { image, path });
+}
+
+fn extract_screenshots(mut commands: Commands, cameras: Extract<Query<&ScreenshotCamera>>) {
+    commands.insert_resource(PendingScreenshots(cameras.iter().cloned().collect()));
+}
+
+/// Copies the image of every screenshot camera into a buffer once drawn, and
+/// hands the buffers the GPU has since mapped over to the main world.
+fn copy_frames(
+    pending: Res<PendingScreenshots>,
+    gpu_images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    captured: Res<CapturedFrames>,
+    mut copies: ResMut<FrameCopies>,
+) {
+    // rows of a texture copied to a buffer must be a multiple of this long
+    const ROW_ALIGNMENT: u32 = 256;
+
+    for camera in &pending.0 {
+        if copies.0.iter().any(|copy| copy.path == camera.path) {
+            continue;
+        }
+        let Some(gpu_image) = gpu_images.get(&camera.image) else {
+            continue;
+        };
+
+        let size = Extent3d {
+            width: gpu_image.size.x as u32,
+            height: gpu_image.size.y as u32,
+            depth_or_array_layers: 1,
+        };
+        let padded_row = (size.width * 4).div_ceil(ROW_ALIGNMENT) * ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("screenshot"),
+            size: (padded_row * size.height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &gpu_image.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        queue.submit([encoder.finish()]);
+
+        // the GPU maps the buffer during a later submission
+        let mapped = Arc::new(AtomicBool::new(false));
+        let flag = mapped.clone();
+        buffer.slice(..).map_async(MapMode::Read, move |result| {
+            flag.store(result.is_ok(), Ordering::Release);
+        });
+        copies.0.push(FrameCopy {
+            path: camera.path.clone(),
+            buffer,
+            size,
+            padded_row,
+            mapped,
+        });
+    }
+
+    let (ready, waiting) = std::mem::take(&mut copies.0)
+        .into_iter()
+        .partition(|copy| copy.mapped.load(Ordering::Acquire));
+    copies.0 = waiting;
+    for copy in ready {
+        let row = copy.size.width as usize * 4;
+        let data = copy
+            .buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks(copy.padded_row as usize)
+            .flat_map(|padded| &padded[..row])
+            .copied()
+            .collect();
+        copy.buffer.unmap();
+        let image = Image::new(
+            copy.size,
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        captured.0.lock().unwrap().push((copy.path, image));
+    }
+}
+
+fn save_screenshots(
+    mut commands: Commands,
+    captured: Res<CapturedFrames>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<(Entity, &ScreenshotCamera)>,
+) {
+    let frames = std::mem::take(&mut *captured.0.lock().unwrap());
+    for (path, image) in frames {
+        for (entity, camera) in cameras.iter() {
+            if camera.path == path {
+                commands.entity(entity).despawn();
+                images.remove(&camera.image);
+            }
+        }
+
+        IoTaskPool::get()
+            .spawn(async move {
+                let saved = image
+                    .try_into_dynamic()
+                    .and_then(|image| Ok(image.save(&path)?));
+                match saved {
+                    Ok(()) => info!("saved a screenshot to {}", path.display()),
+                    Err(error) => warn!("could not save {}: {error}", path.display()),
+                }
+            })
+            .detach();
+    }
+}
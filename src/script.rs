@@ -0,0 +1,174 @@
+use std::sync::Mutex;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, FLOAT};
+
+use crate::{
+    boid::{Boid, Physics, Steering},
+    flock::FlockConfig,
+    lod::Lod,
+    neighbors::{NeighborIndex, SpatialIndex},
+    steering::{SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Steering behavior written in rhai. The script defines
+/// `fn steer(position, velocity, neighbors, target)`, called for every boid
+/// on every step, which returns the force to add. Positions, velocities and
+/// forces are `Vec2`s, made with `vec2(x, y)`, and `neighbors` is an array
+/// of maps with a `position` and a `velocity`, of the boids within
+/// `FlockConfig::neighbor_radius`.
+#[derive(TypeUuid)]
+#[uuid = "3b9e51a4-27c8-4d0f-a6e2-8f14c07d95b3"]
+pub struct SteeringScript {
+    ast: AST,
+}
+
+/// Path of a `SteeringScript` to load, relative to the assets folder, and
+/// to run on top of the built-in behaviors. The script is loaded on startup
+/// and again whenever it changes if the `AssetPlugin` watches for changes.
+#[derive(Resource)]
+pub struct SteeringScriptFile(pub String);
+
+#[derive(Resource)]
+pub(crate) struct SteeringScriptHandle(Handle<SteeringScript>);
+
+/// Engine running the scripts, with `Vec2` and its math registered.
+#[derive(Resource)]
+pub(crate) struct ScriptEngine(Engine);
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine
+            .register_type_with_name::<Vec2>("Vec2")
+            .register_fn("vec2", |x: FLOAT, y: FLOAT| Vec2::new(x as f32, y as f32))
+            .register_get_set(
+                "x",
+                |v: &mut Vec2| v.x as FLOAT,
+                |v: &mut Vec2, x: FLOAT| v.x = x as f32,
+            )
+            .register_get_set(
+                "y",
+                |v: &mut Vec2| v.y as FLOAT,
+                |v: &mut Vec2, y: FLOAT| v.y = y as f32,
+            )
+            .register_fn("+", |a: Vec2, b: Vec2| a + b)
+            .register_fn("-", |a: Vec2, b: Vec2| a - b)
+            .register_fn("-", |a: Vec2| -a)
+            .register_fn("*", |a: Vec2, k: FLOAT| a * k as f32)
+            .register_fn("*", |k: FLOAT, a: Vec2| a * k as f32)
+            .register_fn("/", |a: Vec2, k: FLOAT| a / k as f32)
+            .register_fn("length", |a: Vec2| a.length() as FLOAT)
+            .register_fn("distance", |a: Vec2, b: Vec2| a.distance(b) as FLOAT)
+            .register_fn("dot", |a: Vec2, b: Vec2| a.dot(b) as FLOAT)
+            .register_fn("normalize", |a: Vec2| a.normalize_or_zero())
+            .register_fn("clamp_length", |a: Vec2, max: FLOAT| {
+                a.clamp_length_max(max as f32)
+            })
+            .register_fn("to_string", |a: &mut Vec2| a.to_string())
+            .register_fn("to_debug", |a: &mut Vec2| a.to_string());
+        Self(engine)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SteeringScriptLoader;
+
+impl AssetLoader for SteeringScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            // functions are looked up when called, so any engine compiles it
+            let ast = Engine::new().compile(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(SteeringScript { ast }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+pub(crate) fn load_steering_script(
+    mut commands: Commands,
+    file: Option<Res<SteeringScriptFile>>,
+    asset_server: Res<AssetServer>,
+) {
+    if let Some(file) = file {
+        let handle = asset_server.load(file.0.as_str());
+        commands.insert_resource(SteeringScriptHandle(handle));
+    }
+}
+
+/// Adds the force of the `SteeringScript`, once it is loaded. Errors are
+/// logged once, until the script fails differently.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_steering_script(
+    engine: Res<ScriptEngine>,
+    handle: Option<Res<SteeringScriptHandle>>,
+    scripts: Res<Assets<SteeringScript>>,
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    mut last_error: Local<Option<String>>,
+    lods: Query<&Lod>,
+    mut boids: Query<(Entity, &Transform, &Physics, &Steering, &mut SteeringForces), With<Boid>>,
+) {
+    let Some(script) = handle.and_then(|handle| scripts.get(&handle.0)) else {
+        return;
+    };
+
+    let error = Mutex::new(None);
+    boids.par_for_each_mut(
+        PAR_BATCH_SIZE,
+        |(entity, transform, physics, steering, mut forces)| {
+            if lods.get(entity).is_ok_and(Lod::skips_steering) {
+                return;
+            }
+            let position = transform.translation;
+            let neighbors: Array = index
+                .others_within(entity, position, config.neighbor_radius)
+                .map(|other| {
+                    let mut neighbor = Map::new();
+                    neighbor.insert("position".into(), Dynamic::from(other.position.truncate()));
+                    neighbor.insert("velocity".into(), Dynamic::from(other.velocity.truncate()));
+                    Dynamic::from_map(neighbor)
+                })
+                .collect();
+            let result = engine.0.call_fn::<Vec2>(
+                &mut Scope::new(),
+                &script.ast,
+                "steer",
+                (
+                    position.truncate(),
+                    physics.velocity.truncate(),
+                    neighbors,
+                    steering.target.truncate(),
+                ),
+            );
+            match result {
+                Ok(force) => forces.behavior("script").add(force.extend(0.), 1.),
+                Err(failure) => {
+                    error.lock().unwrap().get_or_insert(failure.to_string());
+                }
+            }
+        },
+    );
+
+    let error = error.into_inner().unwrap();
+    if error.is_some() && error != *last_error {
+        warn!(
+            "steering script failed: {}",
+            error.as_deref().unwrap_or_default()
+        );
+    }
+    *last_error = error;
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{BodySize, Boid, Physics, Selected},
+    debug::{add_arrow, FORCE_SCALE, VELOCITY_SCALE},
+    flock::{FlockConfig, FlockId},
+    flocking::influencing_neighbors,
+    hud::Hud,
+    neighbors::SpatialIndex,
+    perception::Perception,
+    render::{CursorPosition, FlockView, Overlay, RenderOrder},
+    species::{Species, SpeciesRelations},
+    steering::SteeringForces,
+};
+
+/// Whether the selected boid is being inspected: clicking a boid selects and
+/// inspects it, clicking away from the boids stops inspecting. An inspected
+/// boid has its neighbors ringed (red for those it keeps away from, green for
+/// the flockmates it follows), its velocity and steering force drawn, and its
+/// state listed in the bottom left corner.
+#[derive(Resource, Default)]
+pub struct Inspection {
+    pub active: bool,
+}
+
+/// Part of the inspection drawing.
+#[derive(Component, Clone, Copy)]
+pub(crate) enum InspectionGizmo {
+    Crowd,
+    Flockmates,
+    Velocity,
+    SteeringForce,
+}
+
+#[derive(Component)]
+pub(crate) struct InspectionText;
+
+pub(crate) fn spawn_inspection(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    hud: Res<Hud>,
+    asset_server: Res<AssetServer>,
+) {
+    for (gizmo, color) in [
+        (InspectionGizmo::Crowd, Color::RED),
+        (InspectionGizmo::Flockmates, Color::LIME_GREEN),
+        (InspectionGizmo::Velocity, Color::GREEN),
+        (InspectionGizmo::SteeringForce, Color::FUCHSIA),
+    ] {
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &PathBuilder::new().build(),
+                DrawMode::Stroke(StrokeMode::new(color, 2.)),
+                Transform::from_xyz(0., 0., order.overlays),
+            ))
+            .insert(view.layers)
+            .insert(Overlay)
+            .insert(gizmo);
+    }
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(hud.font.as_str()),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(8.),
+                    left: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(InspectionText);
+}
+
+/// Left clicking a boid selects and inspects it, clicking anywhere else stops
+/// inspecting.
+pub(crate) fn select_on_click(
+    mut commands: Commands,
+    mouse: Res<Input<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    mut pressed_at: Local<Option<Vec2>>,
+    mut inspection: ResMut<Inspection>,
+    boids: Query<(Entity, &Transform), With<Boid>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    // world units around the cursor a boid can be picked in
+    const PICK_RADIUS: f32 = 30.;
+    // further than this between press and release, a click is a drag
+    const MAX_CLICK_DRIFT: f32 = 10.;
+
+    if mouse.just_pressed(MouseButton::Left) {
+        *pressed_at = cursor.0;
+    }
+    if !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let (Some(start), Some(position)) = (pressed_at.take(), cursor.0) else {
+        return;
+    };
+    if start.distance(position) >= MAX_CLICK_DRIFT {
+        return;
+    }
+
+    let nearest = boids
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(position)))
+        .filter(|(_, distance)| *distance <= PICK_RADIUS)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let Some((entity, _)) = nearest else {
+        inspection.active = false;
+        return;
+    };
+    for previous in selected.iter() {
+        commands.entity(previous).remove::<Selected>();
+    }
+    commands.entity(entity).insert(Selected);
+    inspection.active = true;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_inspection(
+    inspection: Res<Inspection>,
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    relations: Res<SpeciesRelations>,
+    selected: Query<Entity, With<Selected>>,
+    boids: Query<(&Transform, &Physics, &SteeringForces, Option<&Perception>), With<Boid>>,
+    flocks: Query<&FlockId>,
+    species_query: Query<&Species>,
+    sizes: Query<&BodySize>,
+    mut gizmos: Query<(&InspectionGizmo, &mut Path)>,
+    mut text: Query<(&mut Text, &mut Visibility), With<InspectionText>>,
+) {
+    let inspected = selected
+        .get_single()
+        .ok()
+        .filter(|_| inspection.active)
+        .and_then(|entity| {
+            let flock = flocks.get(entity).copied().unwrap_or_default();
+            boids.get(entity).ok().map(|boid| (entity, boid, flock))
+        });
+    if let Ok((mut text, mut visibility)) = text.get_single_mut() {
+        visibility.is_visible = inspected.is_some();
+        if let Some((entity, (transform, physics, forces, _), flock)) = inspected {
+            let position = transform.translation;
+            let force = forces.applied();
+            text.sections[0].value = format!(
+                "boid {entity:?}, flock {}\n\
+                 position ({:.1}, {:.1})\n\
+                 velocity ({:.1}, {:.1}), speed {:.1} / {:.1}\n\
+                 steering force ({:.1}, {:.1}), {:.1} / {:.1}\n\
+                 mass {:.2}",
+                flock.0,
+                position.x,
+                position.y,
+                physics.velocity.x,
+                physics.velocity.y,
+                physics.velocity.length(),
+                physics.max_speed,
+                force.x,
+                force.y,
+                force.length(),
+                physics.max_force,
+                physics.mass,
+            );
+        }
+    }
+
+    let neighbors = inspected.map(|(entity, (transform, physics, _, perception), flock)| {
+        influencing_neighbors(
+            &index,
+            &config,
+            &relations,
+            entity,
+            transform,
+            physics,
+            perception,
+            species_query.get(entity).copied().unwrap_or_default(),
+            flock,
+            sizes.get(entity).map_or(1., |size| size.0),
+        )
+    });
+    for (gizmo, mut path) in gizmos.iter_mut() {
+        let mut builder = PathBuilder::new();
+        if let (Some((_, (transform, physics, forces, _), _)), Some((crowd, flockmates))) =
+            (inspected, &neighbors)
+        {
+            let from = transform.translation.truncate();
+            match gizmo {
+                InspectionGizmo::Crowd | InspectionGizmo::Flockmates => {
+                    let (entries, radius) = match gizmo {
+                        InspectionGizmo::Crowd => (crowd, 14.),
+                        _ => (flockmates, 18.),
+                    };
+                    for entry in entries {
+                        let center = entry.position.truncate();
+                        builder.move_to(center + Vec2::X * radius);
+                        builder.arc(center, Vec2::splat(radius), std::f32::consts::TAU, 0.);
+                    }
+                }
+                InspectionGizmo::Velocity => {
+                    let to = from + physics.velocity.truncate() * VELOCITY_SCALE;
+                    add_arrow(&mut builder, from, to, 8.);
+                }
+                InspectionGizmo::SteeringForce => {
+                    let to = from + forces.applied().truncate() * FORCE_SCALE;
+                    add_arrow(&mut builder, from, to, 8.);
+                }
+            }
+        }
+        *path = builder.build();
+    }
+}
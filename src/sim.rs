@@ -0,0 +1,413 @@
+//! Steering and physics math as plain functions of positions and velocities,
+//! usable without a bevy `App`. The systems of the crate apply them to the
+//! boids' components.
+
+use bevy::math::{Quat, Vec2, Vec3};
+
+use crate::steering::ResponseCurve;
+
+/// How fast a boid may fly and how hard it may steer.
+#[derive(Clone, Copy)]
+pub struct SteeringParams {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub response_curve: ResponseCurve,
+}
+
+/// Force along `direction` of `strength`, from 0 to 1 and capped there,
+/// shaped by the response curve into a share of `max_force`.
+fn curved(direction: Vec3, strength: f32, params: &SteeringParams) -> Vec3 {
+    let strength = strength.clamp(0., 1.);
+    direction.normalize_or_zero() * params.response_curve.apply(strength) * params.max_force
+}
+
+/// Force turning `velocity` towards the `desired` velocity, as strong as the
+/// gap between the two is next to `max_speed`.
+pub fn steer_towards(desired: Vec3, velocity: Vec3, params: &SteeringParams) -> Vec3 {
+    let error = desired - velocity;
+    curved(
+        error,
+        error.length() / params.max_speed.max(f32::EPSILON),
+        params,
+    )
+}
+
+/// Force heading at full speed towards `target`.
+pub fn steer_seek(position: Vec3, velocity: Vec3, target: Vec3, params: &SteeringParams) -> Vec3 {
+    let desired = (target - position).normalize_or_zero() * params.max_speed;
+    steer_towards(desired, velocity, params)
+}
+
+/// Force heading towards `target`, slowing down within `slowing_radius` to
+/// come to rest on it.
+pub fn steer_arrive(
+    position: Vec3,
+    velocity: Vec3,
+    target: Vec3,
+    slowing_radius: f32,
+    params: &SteeringParams,
+) -> Vec3 {
+    let offset = target - position;
+    let speed = params.max_speed * (offset.length() / slowing_radius).min(1.);
+    steer_towards(offset.normalize_or_zero() * speed, velocity, params)
+}
+
+/// Force heading at full speed straight away from `threat`.
+pub fn steer_flee(position: Vec3, velocity: Vec3, threat: Vec3, params: &SteeringParams) -> Vec3 {
+    let desired = (position - threat).normalize_or_zero() * params.max_speed;
+    steer_towards(desired, velocity, params)
+}
+
+/// Push away from the `neighbors`, given as positions and velocities, the
+/// stronger the closer they are within `radius`, up to `max_force` for a
+/// neighbor right on top; those beyond it do not push. Neighbors closing in
+/// count up to `1 + closing_weight` times as much.
+pub fn steer_separation(
+    position: Vec3,
+    velocity: Vec3,
+    neighbors: impl IntoIterator<Item = (Vec3, Vec3)>,
+    radius: f32,
+    closing_weight: f32,
+    params: &SteeringParams,
+) -> Vec3 {
+    let mut push = Vec3::ZERO;
+    for (other_position, other_velocity) in neighbors {
+        let offset = position - other_position;
+        let distance = offset.length();
+        let Some(away) = offset.try_normalize() else {
+            continue;
+        };
+
+        // positive when the gap between the two boids is shrinking
+        let closing_speed = (other_velocity - velocity).dot(away);
+        let urgency =
+            1. + closing_weight * closing_speed.max(0.) / params.max_speed.max(f32::EPSILON);
+        push += away * (1. - distance / radius).max(0.) * urgency;
+    }
+
+    curved(push, push.length(), params)
+}
+
+/// Unit vector along the mean of `velocities`, if they move at all.
+pub fn average_heading(velocities: impl IntoIterator<Item = Vec3>) -> Option<Vec3> {
+    velocities.into_iter().sum::<Vec3>().try_normalize()
+}
+
+/// Force matching the mean heading of the neighbors, from their velocities.
+pub fn steer_alignment(
+    velocity: Vec3,
+    neighbor_velocities: impl IntoIterator<Item = Vec3>,
+    params: &SteeringParams,
+) -> Vec3 {
+    match average_heading(neighbor_velocities) {
+        Some(heading) => steer_towards(heading * params.max_speed, velocity, params),
+        None => Vec3::ZERO,
+    }
+}
+
+/// Force heading towards the centroid of the neighbors, from their positions.
+pub fn steer_cohesion(
+    position: Vec3,
+    velocity: Vec3,
+    neighbor_positions: impl IntoIterator<Item = Vec3>,
+    params: &SteeringParams,
+) -> Vec3 {
+    let (sum, count) = neighbor_positions
+        .into_iter()
+        .fold((Vec3::ZERO, 0), |(sum, count), other| {
+            (sum + other, count + 1)
+        });
+    if count == 0 {
+        return Vec3::ZERO;
+    }
+
+    steer_seek(position, velocity, sum / count as f32, params)
+}
+
+/// How much a boid's velocity may change in a step.
+#[derive(Clone, Copy)]
+pub struct MotionLimits {
+    // share of the velocity lost per second, as in `FlockConfig::drag`
+    pub drag: f32,
+    // radians per second, infinite for no limit
+    pub max_turn_rate: f32,
+    pub max_speed: f32,
+}
+
+/// `velocity` after gaining `gain` over a step of `dt` seconds: damped by
+/// the drag, turned by at most the turn rate and capped to the top speed.
+fn accelerate(velocity: Vec3, gain: Vec3, dt: f32, limits: &MotionLimits) -> Vec3 {
+    let damping = (-limits.drag * dt).exp();
+    limit_turn(
+        velocity,
+        (velocity + gain) * damping,
+        limits.max_turn_rate * dt,
+    )
+    .clamp_length_max(limits.max_speed)
+}
+
+/// Position and velocity after a step of `dt` seconds under `acceleration`,
+/// with semi-implicit Euler: the new velocity, within the `limits`, moves
+/// the position.
+pub fn integrate(
+    position: Vec3,
+    velocity: Vec3,
+    acceleration: Vec3,
+    dt: f32,
+    limits: &MotionLimits,
+) -> (Vec3, Vec3) {
+    let velocity = accelerate(velocity, acceleration * dt, dt, limits);
+    (position + velocity * dt, velocity)
+}
+
+/// Like `integrate`, with explicit Euler: the position moves at the old
+/// velocity. The cheapest scheme, and the first to drift or blow up at low
+/// step rates.
+pub fn integrate_euler(
+    position: Vec3,
+    velocity: Vec3,
+    acceleration: Vec3,
+    dt: f32,
+    limits: &MotionLimits,
+) -> (Vec3, Vec3) {
+    (
+        position + velocity * dt,
+        accelerate(velocity, acceleration * dt, dt, limits),
+    )
+}
+
+/// Like `integrate`, with velocity Verlet: the position moves at the
+/// velocity half a step ahead, and the velocity gains the average of the
+/// `previous_acceleration` and this one, since steering only tells the
+/// acceleration at the new position on the next step.
+pub fn integrate_verlet(
+    position: Vec3,
+    velocity: Vec3,
+    acceleration: Vec3,
+    previous_acceleration: Vec3,
+    dt: f32,
+    limits: &MotionLimits,
+) -> (Vec3, Vec3) {
+    let midway = accelerate(velocity, acceleration * dt / 2., dt / 2., limits);
+    let gain = (previous_acceleration + acceleration) * dt / 2.;
+    (
+        position + midway * dt,
+        accelerate(velocity, gain, dt, limits),
+    )
+}
+
+/// `velocity` turned back towards the heading of `previous` if it strays
+/// more than `max_angle` radians from it, keeping its speed.
+pub fn limit_turn(previous: Vec3, velocity: Vec3, max_angle: f32) -> Vec3 {
+    let (Some(from), Some(to)) = (
+        previous.truncate().try_normalize(),
+        velocity.truncate().try_normalize(),
+    ) else {
+        return velocity;
+    };
+    let angle = from.angle_between(to);
+    if angle.abs() <= max_angle {
+        return velocity;
+    }
+
+    let heading = Vec2::from_angle(max_angle.copysign(angle)).rotate(from);
+    (heading * velocity.truncate().length()).extend(velocity.z)
+}
+
+/// Rotation of a boid pointing along `velocity`, the boid's shape pointing up
+/// the y axis. `None` standing still.
+pub fn heading(velocity: Vec3) -> Option<Quat> {
+    (velocity != Vec3::ZERO)
+        .then(|| Quat::from_rotation_z(velocity.angle_between(Vec3::Y) * -velocity.x.signum()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: SteeringParams = SteeringParams {
+        max_speed: 100.,
+        max_force: 50.,
+        response_curve: ResponseCurve::Linear,
+    };
+
+    #[test]
+    fn closing_in_raises_separation() {
+        let position = Vec3::ZERO;
+        let velocity = Vec3::ZERO;
+        let neighbor = Vec3::new(20., 0., 0.);
+        let push = |neighbor_velocity| {
+            steer_separation(
+                position,
+                velocity,
+                [(neighbor, neighbor_velocity)],
+                50.,
+                1.,
+                &PARAMS,
+            )
+        };
+
+        let still = push(Vec3::ZERO);
+        let closing = push(Vec3::new(-60., 0., 0.));
+        let leaving = push(Vec3::new(60., 0., 0.));
+        // away from the neighbor
+        assert!(still.x < 0.);
+        assert!(closing.length() > still.length());
+        assert!(closing.normalize().abs_diff_eq(still.normalize(), 1e-5));
+        // only closing in counts, not moving away
+        assert_eq!(leaving, still);
+    }
+
+    #[test]
+    fn neighbors_beyond_the_radius_do_not_push() {
+        let near = (Vec3::new(20., 0., 0.), Vec3::ZERO);
+        let far = (Vec3::new(-80., 0., 0.), Vec3::ZERO);
+        let push = |neighbors: &[(Vec3, Vec3)]| {
+            steer_separation(
+                Vec3::ZERO,
+                Vec3::ZERO,
+                neighbors.iter().copied(),
+                50.,
+                1.,
+                &PARAMS,
+            )
+        };
+
+        assert_eq!(push(&[far]), Vec3::ZERO);
+        assert_eq!(push(&[near, far]), push(&[near]));
+    }
+
+    #[test]
+    fn ease_in_is_gentler_on_small_errors() {
+        let velocity = Vec3::new(100., 0., 0.);
+        // a slight turn
+        let desired = Vec3::new(98., 10., 0.);
+        let linear = steer_towards(desired, velocity, &PARAMS);
+        let ease_in = steer_towards(
+            desired,
+            velocity,
+            &SteeringParams {
+                response_curve: ResponseCurve::EaseIn,
+                ..PARAMS
+            },
+        );
+        assert!(linear.length() > 0.);
+        assert!(ease_in.length() < linear.length());
+        assert!(ease_in.normalize().abs_diff_eq(linear.normalize(), 1e-5));
+        // not yet saturated, the curve still has room to act
+        assert!(linear.length() < PARAMS.max_force);
+    }
+
+    #[test]
+    fn seek_and_flee_are_opposite() {
+        let target = Vec3::new(100., 50., 0.);
+        let seek = steer_seek(Vec3::ZERO, Vec3::ZERO, target, &PARAMS);
+        let flee = steer_flee(Vec3::ZERO, Vec3::ZERO, target, &PARAMS);
+        assert!(seek.normalize().abs_diff_eq(target.normalize(), 1e-5));
+        assert!(flee.abs_diff_eq(-seek, 1e-4));
+        // from rest, the error is a full max_speed
+        assert!((seek.length() - PARAMS.max_force).abs() < 1e-3);
+    }
+
+    #[test]
+    fn arrive_brakes_on_the_target() {
+        let velocity = Vec3::new(0., 50., 0.);
+        let far = steer_arrive(Vec3::ZERO, velocity, Vec3::new(0., 500., 0.), 100., &PARAMS);
+        let on = steer_arrive(Vec3::ZERO, velocity, Vec3::ZERO, 100., &PARAMS);
+        // speeding up far away, braking on the target
+        assert!(far.y > 0.);
+        assert!(on.y < 0.);
+        assert_eq!(on.x, 0.);
+    }
+
+    #[test]
+    fn lone_boids_neither_align_nor_gather() {
+        let velocity = Vec3::new(30., 0., 0.);
+        assert_eq!(steer_alignment(velocity, [], &PARAMS), Vec3::ZERO);
+        assert_eq!(
+            steer_cohesion(Vec3::ZERO, velocity, [], &PARAMS),
+            Vec3::ZERO
+        );
+        assert_eq!(
+            steer_separation(Vec3::ZERO, velocity, [], 50., 1., &PARAMS),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn cohesion_heads_for_the_centroid() {
+        let neighbors = [Vec3::new(100., 0., 0.), Vec3::new(0., 100., 0.)];
+        let force = steer_cohesion(Vec3::ZERO, Vec3::ZERO, neighbors, &PARAMS);
+        assert!(force
+            .normalize()
+            .abs_diff_eq(Vec3::new(1., 1., 0.).normalize(), 1e-5));
+    }
+
+    const LIMITS: MotionLimits = MotionLimits {
+        drag: 0.,
+        max_turn_rate: f32::INFINITY,
+        max_speed: 100.,
+    };
+
+    #[test]
+    fn integration_caps_the_speed() {
+        let integrators: [fn(Vec3) -> (Vec3, Vec3); 3] = [
+            |acceleration| integrate(Vec3::ZERO, Vec3::ZERO, acceleration, 1., &LIMITS),
+            |acceleration| integrate_euler(Vec3::ZERO, Vec3::ZERO, acceleration, 1., &LIMITS),
+            |acceleration| {
+                integrate_verlet(
+                    Vec3::ZERO,
+                    Vec3::ZERO,
+                    acceleration,
+                    acceleration,
+                    1.,
+                    &LIMITS,
+                )
+            },
+        ];
+        for step in integrators {
+            let (_, velocity) = step(Vec3::new(1000., 0., 0.));
+            assert!((velocity.length() - LIMITS.max_speed).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn euler_moves_at_the_old_velocity() {
+        let velocity = Vec3::new(10., 0., 0.);
+        let acceleration = Vec3::new(0., 20., 0.);
+        let (position, _) = integrate_euler(Vec3::ZERO, velocity, acceleration, 0.5, &LIMITS);
+        assert_eq!(position, Vec3::new(5., 0., 0.));
+        let (position, _) = integrate(Vec3::ZERO, velocity, acceleration, 0.5, &LIMITS);
+        assert_eq!(position, Vec3::new(5., 5., 0.));
+    }
+
+    #[test]
+    fn drag_slows_a_coasting_boid() {
+        let limits = MotionLimits {
+            drag: 0.5,
+            ..LIMITS
+        };
+        let velocity = Vec3::new(50., 0., 0.);
+        let (_, slowed) = integrate(Vec3::ZERO, velocity, Vec3::ZERO, 1., &limits);
+        assert!((slowed.x - 50. * (-0.5f32).exp()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn turns_are_limited_keeping_the_speed() {
+        let previous = Vec3::new(10., 0., 0.);
+        let turned = limit_turn(previous, Vec3::new(0., 20., 0.), 0.5);
+        assert!((turned.length() - 20.).abs() < 1e-4);
+        assert!((Vec2::X.angle_between(turned.truncate()) - 0.5).abs() < 1e-5);
+        let slight = Vec3::new(20., 1., 0.);
+        assert_eq!(limit_turn(previous, slight, 0.5), slight);
+    }
+
+    #[test]
+    fn heading_points_the_shape_along_the_velocity() {
+        assert_eq!(heading(Vec3::ZERO), None);
+        for velocity in [Vec3::X, Vec3::NEG_X, Vec3::new(-3., -4., 0.)] {
+            let up = heading(velocity).unwrap() * Vec3::Y;
+            assert!(up.abs_diff_eq(velocity.normalize(), 1e-5));
+        }
+    }
+}
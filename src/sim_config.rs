@@ -0,0 +1,292 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid::BoidShape,
+    flock::{FlockConfig, FlockId, FlockSettings, Flocks, ResetFlock},
+    playfield::Playfield,
+    rng::{SimRng, SimSeed},
+};
+
+/// Simulation parameters read from a RON file, see `SimConfigFile`. Missing
+/// fields keep the defaults of `FlockConfig`.
+#[derive(Serialize, Deserialize, TypeUuid)]
+#[uuid = "6f1d3c2e-8a4b-4f67-9d21-5b0e7c9a1f34"]
+#[serde(default)]
+pub struct SimConfig {
+    pub count: usize,
+    pub max_boids: usize,
+    // energy new boids start with, they never tire without
+    pub energy: Option<f32>,
+    // smallest and largest mass of new boids
+    pub mass: (f32, f32),
+    // draw heavier boids bigger
+    pub scale_by_mass: bool,
+    // and well fed boids bigger than starving ones
+    pub scale_by_energy: bool,
+    pub max_speed: f32,
+    pub max_force: f32,
+    // in degrees per second
+    pub max_turn_rate: f32,
+    pub drag: f32,
+    // distance from the target boids slow down within, none to seek at full
+    // speed
+    pub arrive: Option<f32>,
+    // seconds ahead boids aim of a moving target, none to aim at it
+    pub pursue: Option<f32>,
+    // braking distance of boids queueing behind each other, none without
+    pub queueing: Option<f32>,
+    pub hard_collisions: bool,
+    pub collision_radius: f32,
+    // boids slide past each other rather than push apart, see
+    // `ReciprocalAvoidance`
+    pub reciprocal_avoidance: bool,
+    pub neighbor_radius: f32,
+    pub max_neighbors: Option<usize>,
+    // in degrees
+    pub field_of_view: f32,
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+    pub boid_length: f32,
+    pub boid_width: f32,
+    // outline of the boids, see `BoidShape`
+    pub shape: BoidShape,
+    // seed of the random numbers, unless one is given with `SimSeed`
+    pub seed: Option<u64>,
+    // corners of the polygon the flock is kept inside, see `Playfield`
+    pub playfield: Option<Vec<(f32, f32)>>,
+    // flocks, numbered in order, see `Flocks`
+    pub flocks: Vec<SimFlock>,
+}
+
+/// Settings of one flock in a `SimConfig`. Missing values are taken from the
+/// rest of the config.
+#[derive(Serialize, Deserialize)]
+pub struct SimFlock {
+    // red, green and blue from 0 to 1
+    pub color: [f32; 3],
+    pub max_speed: Option<f32>,
+    pub separation_weight: Option<f32>,
+    pub alignment_weight: Option<f32>,
+    pub cohesion_weight: Option<f32>,
+    pub reciprocal_avoidance: Option<bool>,
+    pub shape: Option<BoidShape>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self::from_flock(&FlockConfig::default(), &Flocks::default())
+    }
+}
+
+impl SimConfig {
+    /// Parses a config from the text of a RON file, for apps without an
+    /// `AssetServer` to load it through `SimConfigFile`.
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    /// Settings of `config` and `flocks`, without a seed.
+    pub fn from_flock(config: &FlockConfig, flocks: &Flocks) -> Self {
+        let flocks = flocks
+            .ids()
+            .into_iter()
+            .filter_map(|id| flocks.get(id))
+            .map(|settings| {
+                let [r, g, b, _] = settings.color.as_rgba_f32();
+                SimFlock {
+                    color: [r, g, b],
+                    max_speed: Some(settings.max_speed),
+                    separation_weight: Some(settings.separation_weight),
+                    alignment_weight: Some(settings.alignment_weight),
+                    cohesion_weight: Some(settings.cohesion_weight),
+                    reciprocal_avoidance: Some(settings.reciprocal_avoidance),
+                    shape: settings.shape.clone(),
+                }
+            })
+            .collect();
+        Self {
+            count: config.count,
+            max_boids: config.max_boids,
+            energy: config.energy,
+            mass: (config.spawn_mass.start, config.spawn_mass.end),
+            scale_by_mass: config.scale_by_mass,
+            scale_by_energy: config.scale_by_energy,
+            max_speed: config.max_speed,
+            max_force: config.max_force,
+            max_turn_rate: config.max_turn_rate.to_degrees(),
+            drag: config.drag,
+            arrive: config.arrive,
+            pursue: config.pursue,
+            queueing: config.queueing,
+            hard_collisions: config.hard_collisions,
+            collision_radius: config.collision_radius,
+            reciprocal_avoidance: config.reciprocal_avoidance,
+            neighbor_radius: config.neighbor_radius,
+            max_neighbors: config.max_neighbors,
+            field_of_view: config.field_of_view.to_degrees(),
+            separation_radius: config.separation_radius,
+            separation_weight: config.separation_weight,
+            alignment_weight: config.alignment_weight,
+            cohesion_weight: config.cohesion_weight,
+            boid_length: config.boid_length,
+            boid_width: config.boid_width,
+            shape: config.shape.clone(),
+            seed: None,
+            playfield: None,
+            flocks,
+        }
+    }
+
+    /// The playfield of the config, none without one.
+    pub fn playfield(&self) -> Playfield {
+        let points = self.playfield.iter().flatten();
+        Playfield::new(points.map(|(x, y)| Vec2::new(*x, *y)).collect())
+    }
+
+    /// Copies the settings into `config` and `flocks`. The seed and the
+    /// playfield are left to the caller.
+    pub fn apply(&self, config: &mut FlockConfig, flocks: &mut Flocks) {
+        config.count = self.count;
+        config.max_boids = self.max_boids;
+        config.energy = self.energy;
+        config.spawn_mass = self.mass.0..self.mass.1;
+        config.scale_by_mass = self.scale_by_mass;
+        config.scale_by_energy = self.scale_by_energy;
+        config.max_speed = self.max_speed;
+        config.max_force = self.max_force;
+        config.max_turn_rate = self.max_turn_rate.to_radians();
+        config.drag = self.drag;
+        config.arrive = self.arrive;
+        config.pursue = self.pursue;
+        config.queueing = self.queueing;
+        config.hard_collisions = self.hard_collisions;
+        config.collision_radius = self.collision_radius;
+        config.reciprocal_avoidance = self.reciprocal_avoidance;
+        config.neighbor_radius = self.neighbor_radius;
+        config.max_neighbors = self.max_neighbors;
+        config.field_of_view = self.field_of_view.to_radians();
+        config.separation_radius = self.separation_radius;
+        config.separation_weight = self.separation_weight;
+        config.alignment_weight = self.alignment_weight;
+        config.cohesion_weight = self.cohesion_weight;
+        config.boid_length = self.boid_length;
+        config.boid_width = self.boid_width;
+        config.shape = self.shape.clone();
+
+        *flocks = Flocks::default();
+        for (id, flock) in self.flocks.iter().enumerate() {
+            let [r, g, b] = flock.color;
+            let defaults = FlockSettings::from_config(config, Color::rgb(r, g, b));
+            flocks.insert(
+                FlockId(id as u32),
+                FlockSettings {
+                    max_speed: flock.max_speed.unwrap_or(defaults.max_speed),
+                    separation_weight: flock
+                        .separation_weight
+                        .unwrap_or(defaults.separation_weight),
+                    alignment_weight: flock.alignment_weight.unwrap_or(defaults.alignment_weight),
+                    cohesion_weight: flock.cohesion_weight.unwrap_or(defaults.cohesion_weight),
+                    reciprocal_avoidance: flock
+                        .reciprocal_avoidance
+                        .unwrap_or(defaults.reciprocal_avoidance),
+                    shape: flock.shape.clone(),
+                    ..defaults
+                },
+            );
+        }
+    }
+}
+
+/// Path of a `SimConfig` to load, relative to the assets folder. When present
+/// the file is loaded on startup and applied to `FlockConfig` and `Flocks`,
+/// and again whenever it changes if the `AssetPlugin` watches for changes.
+/// The flock is respawned once the file is first loaded, later changes of the
+/// boid count and shape only affect boids spawned afterwards.
+#[derive(Resource)]
+pub struct SimConfigFile(pub String);
+
+#[derive(Resource)]
+pub(crate) struct SimConfigHandle(Handle<SimConfig>);
+
+#[derive(Default)]
+pub(crate) struct SimConfigLoader;
+
+impl AssetLoader for SimConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config: SimConfig = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+pub(crate) fn load_sim_config(
+    mut commands: Commands,
+    file: Option<Res<SimConfigFile>>,
+    asset_server: Res<AssetServer>,
+) {
+    if let Some(file) = file {
+        let handle = asset_server.load(file.0.as_str());
+        commands.insert_resource(SimConfigHandle(handle));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_sim_config(
+    mut events: EventReader<AssetEvent<SimConfig>>,
+    handle: Option<Res<SimConfigHandle>>,
+    configs: Res<Assets<SimConfig>>,
+    mut config: ResMut<FlockConfig>,
+    mut flocks: ResMut<Flocks>,
+    mut playfield: ResMut<Playfield>,
+    mut reset: EventWriter<ResetFlock>,
+    seed: Option<Res<SimSeed>>,
+    mut rng: ResMut<SimRng>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    for event in events.iter() {
+        let (AssetEvent::Created { handle: loaded } | AssetEvent::Modified { handle: loaded }) =
+            event
+        else {
+            continue;
+        };
+        if *loaded != handle.0 {
+            continue;
+        }
+        let sim_config = configs.get(loaded);
+        if let Some(sim_config) = sim_config {
+            sim_config.apply(&mut config, &mut flocks);
+            *playfield = sim_config.playfield();
+        }
+        // the flock spawned on startup predates the file, bring it in line,
+        // seed included unless the app read it beforehand as a `SimSeed`
+        if matches!(event, AssetEvent::Created { .. }) {
+            if let (Some(file_seed), None) =
+                (sim_config.and_then(|sim_config| sim_config.seed), &seed)
+            {
+                *rng = SimRng::seeded(file_seed);
+            }
+            reset.send(ResetFlock);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boid::{Boid, BoidBuilder, Physics, Steering},
+    energy::Energy,
+    flock::{FlockConfig, FlockId, Flocks},
+    neighbors::{NeighborIndex, SpatialIndex},
+    obstacle::{spawn_obstacle, Obstacle},
+    render::{FlockView, RenderOrder},
+    sim_config::SimConfig,
+};
+
+/// File `F5` saves the flock to and `F9` restores it from, relative to the
+/// working directory.
+#[derive(Resource)]
+pub struct SnapshotFile(pub PathBuf);
+
+impl Default for SnapshotFile {
+    fn default() -> Self {
+        Self(PathBuf::from("snapshot.ron"))
+    }
+}
+
+/// State of the flock at one moment: every boid, the obstacles and the
+/// settings a `SimConfig` holds, written as RON.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub config: SimConfig,
+    pub boids: Vec<SavedBoid>,
+    pub obstacles: Vec<SavedObstacle>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedBoid {
+    pub flock: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub velocity: Vec3,
+    pub acceleration: Vec3,
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub max_turn_rate: f32,
+    pub mass: f32,
+    pub target: Vec3,
+    pub max_seek_range: f32,
+    // energy left, for boids that tire
+    #[serde(default)]
+    pub energy: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedObstacle {
+    pub position: Vec2,
+    pub shape: Obstacle,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// Pressing `F5` saves the flock to the `SnapshotFile`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn quick_save(
+    keyboard: Res<Input<KeyCode>>,
+    file: Res<SnapshotFile>,
+    config: Res<FlockConfig>,
+    flocks: Res<Flocks>,
+    boids: Query<(Entity, &Transform, &Physics, &Steering), With<Boid>>,
+    flock_ids: Query<&FlockId>,
+    energies: Query<&Energy>,
+    obstacles: Query<(&Transform, &Obstacle)>,
+) {
+    if !keyboard.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    let boids = boids
+        .iter()
+        .map(|(entity, transform, physics, steering)| SavedBoid {
+            flock: flock_ids.get(entity).copied().unwrap_or_default().0,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            velocity: physics.velocity,
+            acceleration: physics.acceleration,
+            max_speed: physics.max_speed,
+            max_force: physics.max_force,
+            max_turn_rate: physics.max_turn_rate,
+            mass: physics.mass,
+            target: steering.target,
+            max_seek_range: steering.max_seek_range,
+            energy: energies.get(entity).ok().map(|energy| energy.current),
+        })
+        .collect();
+    let obstacles = obstacles
+        .iter()
+        .map(|(transform, obstacle)| SavedObstacle {
+            position: transform.translation.truncate(),
+            shape: obstacle.clone(),
+        })
+        .collect();
+    let snapshot = Snapshot {
+        config: SimConfig::from_flock(&config, &flocks),
+        boids,
+        obstacles,
+    };
+    match snapshot.save(&file.0) {
+        Ok(()) => info!("saved the flock to {}", file.0.display()),
+        Err(error) => warn!("could not save the flock to {}: {error}", file.0.display()),
+    }
+}
+
+/// Pressing `F9` replaces the flock and the obstacles with those of the
+/// `SnapshotFile`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn quick_load(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    file: Res<SnapshotFile>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    mut config: ResMut<FlockConfig>,
+    mut flocks: ResMut<Flocks>,
+    mut index: ResMut<SpatialIndex>,
+    boids: Query<Entity, With<Boid>>,
+    obstacles: Query<Entity, With<Obstacle>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    let snapshot = match Snapshot::load(&file.0) {
+        Ok(snapshot) => snapshot,
+        Err(error) => {
+            warn!(
+                "could not load the flock from {}: {error}",
+                file.0.display()
+            );
+            return;
+        }
+    };
+    for entity in boids.iter().chain(obstacles.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    index.clear();
+
+    snapshot.config.apply(&mut config, &mut flocks);
+    for boid in snapshot.boids {
+        let mut bundle = BoidBuilder::new()
+            .config(&config)
+            .view(&view)
+            .at(boid.translation)
+            .velocity(boid.velocity)
+            .mass(boid.mass)
+            .flock(boid.flock)
+            .build();
+        bundle.shape.transform.rotation = boid.rotation;
+        bundle.physics = Physics {
+            velocity: boid.velocity,
+            acceleration: boid.acceleration,
+            previous_acceleration: Vec3::ZERO,
+            max_speed: boid.max_speed,
+            max_force: boid.max_force,
+            max_turn_rate: boid.max_turn_rate,
+            mass: boid.mass,
+        };
+        bundle.steering = Steering {
+            target: boid.target,
+            max_seek_range: boid.max_seek_range,
+        };
+        let mut entity = commands.spawn(bundle);
+        if let Some(current) = boid.energy {
+            entity.insert(Energy {
+                current,
+                ..Energy::new(config.energy.unwrap_or(current))
+            });
+        }
+    }
+    for obstacle in snapshot.obstacles {
+        spawn_obstacle(
+            &mut commands,
+            &view,
+            &order,
+            obstacle.shape,
+            obstacle.position,
+        );
+    }
+    info!("loaded the flock from {}", file.0.display());
+}
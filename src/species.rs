@@ -0,0 +1,116 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    boid::Physics,
+    flock::FlockConfig,
+    lod::Lod,
+    neighbors::SpatialIndex,
+    perception::{perceived_neighbors, Perception},
+    predator::{flee_from, FLEE_PRIORITY},
+    steering::{steer_towards, SteeringForces, PAR_BATCH_SIZE},
+};
+
+/// Kind of boid, deciding how it reacts to others through `SpeciesRelations`.
+/// Boids without one are of species 0.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Species(pub u32);
+
+/// How a boid reacts to a neighbor of some species.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    Ignore,
+    /// Run from it, as prey from a predator.
+    Avoid,
+    /// Go after the nearest one, as a predator after prey.
+    Chase,
+    /// Separate from, align with and gather around it.
+    FlockWith,
+}
+
+/// Interaction matrix between species. Unless told otherwise boids flock with
+/// their own species and ignore the others.
+#[derive(Resource, Default)]
+pub struct SpeciesRelations {
+    reactions: HashMap<(Species, Species), Reaction>,
+}
+
+impl SpeciesRelations {
+    /// Makes boids of `species` react to `other` with `reaction`.
+    pub fn set(&mut self, species: Species, other: Species, reaction: Reaction) {
+        self.reactions.insert((species, other), reaction);
+    }
+
+    pub fn reaction(&self, species: Species, other: Species) -> Reaction {
+        match self.reactions.get(&(species, other)) {
+            Some(reaction) => *reaction,
+            None if species == other => Reaction::FlockWith,
+            None => Reaction::Ignore,
+        }
+    }
+}
+
+/// Flees the species a boid avoids and chases the nearest boid of the ones
+/// it hunts. Boids are handled in parallel, as in `flocking`.
+pub(crate) fn react_to_species(
+    config: Res<FlockConfig>,
+    relations: Res<SpeciesRelations>,
+    index: Res<SpatialIndex>,
+    species_query: Query<&Species>,
+    lods: Query<&Lod>,
+    mut boids: Query<(
+        Entity,
+        &Transform,
+        &Physics,
+        Option<&Perception>,
+        &mut SteeringForces,
+    )>,
+) {
+    if relations.reactions.is_empty() {
+        return;
+    }
+
+    boids.par_for_each_mut(
+        PAR_BATCH_SIZE,
+        |(entity, transform, physics, perception, mut forces)| {
+            if lods.get(entity).is_ok_and(Lod::skips_steering) {
+                return;
+            }
+            let species = species_query.get(entity).copied().unwrap_or_default();
+            let position = transform.translation;
+            let radius = perception.map_or(config.neighbor_radius, |perception| perception.radius);
+
+            let mut prey: Option<Vec3> = None;
+            for other in
+                perceived_neighbors(&*index, entity, transform, physics, perception, radius)
+            {
+                match relations.reaction(species, other.species) {
+                    Reaction::Avoid => {
+                        let force = flee_from(position, other.position, physics, &config);
+                        forces.behavior("avoid species").add_with_priority(
+                            force,
+                            1.,
+                            FLEE_PRIORITY,
+                        );
+                    }
+                    Reaction::Chase => {
+                        let closer = prey.is_none_or(|prey| {
+                            other.position.distance_squared(position)
+                                < prey.distance_squared(position)
+                        });
+                        if closer {
+                            prey = Some(other.position);
+                        }
+                    }
+                    Reaction::Ignore | Reaction::FlockWith => {}
+                }
+            }
+
+            if let Some(prey) = prey {
+                let desired = (prey - position).normalize_or_zero() * physics.max_speed;
+                forces
+                    .behavior("chase")
+                    .add(steer_towards(desired, physics, config.response_curve), 1.);
+            }
+        },
+    );
+}
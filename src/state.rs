@@ -0,0 +1,179 @@
+use bevy::{app::AppExit, ecs::schedule::ShouldRun, prelude::*};
+
+use crate::{
+    attractor::CursorStir,
+    hud::Hud,
+    preset::{LoadPreset, Preset},
+    tick::{SimTick, SimulationControl},
+};
+
+/// Screen the app is on. The simulation only steps while `Running`; while
+/// `Paused` it is frozen but overlays and camera controls keep working, and
+/// the `MainMenu` is drawn over the frozen flock.
+///
+/// `BoidPlugin` starts on the menu, unless the state was added to the app
+/// before it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppState {
+    MainMenu,
+    Running,
+    Paused,
+}
+
+#[derive(Component)]
+pub(crate) struct Menu;
+
+#[derive(Component, Clone, Copy)]
+pub(crate) enum MenuButton {
+    Start,
+    // starts over on a preset
+    Preset(Preset),
+    Quit,
+}
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Run criterion of the systems acting on the world from the cursor, which
+/// must leave the menu alone.
+pub(crate) fn outside_menu(state: Res<State<AppState>>) -> ShouldRun {
+    match state.current() {
+        AppState::MainMenu => ShouldRun::No,
+        AppState::Running | AppState::Paused => ShouldRun::Yes,
+    }
+}
+
+/// Run criterion of the systems acting on mouse clicks, which leave them to
+/// the menu, and to `CursorStir` while it is on.
+pub(crate) fn clicking_world(state: Res<State<AppState>>, stir: Res<CursorStir>) -> ShouldRun {
+    match stir.enabled {
+        true => ShouldRun::No,
+        false => outside_menu(state),
+    }
+}
+
+/// Pauses the simulation in every state but `Running`.
+pub(crate) fn pause_outside_running(
+    state: Res<State<AppState>>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if state.is_changed() {
+        control.paused = *state.current() != AppState::Running;
+    }
+}
+
+/// `Space` pauses or resumes the simulation, `Escape` opens the menu or goes
+/// back from it to the paused simulation.
+pub(crate) fn switch_state(keyboard: Res<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    let next = if keyboard.just_pressed(KeyCode::Escape) {
+        match state.current() {
+            AppState::MainMenu => AppState::Paused,
+            AppState::Running | AppState::Paused => AppState::MainMenu,
+        }
+    } else if keyboard.just_pressed(KeyCode::Space) {
+        match state.current() {
+            AppState::Running => AppState::Paused,
+            AppState::Paused => AppState::Running,
+            AppState::MainMenu => return,
+        }
+    } else {
+        return;
+    };
+    // a transition may already be queued this frame
+    let _ = state.set(next);
+}
+
+pub(crate) fn spawn_menu(
+    mut commands: Commands,
+    hud: Res<Hud>,
+    tick: Res<SimTick>,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(hud.font.as_str());
+    let text_style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    let start = if tick.steps() > 0 { "Resume" } else { "Start" };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..default()
+        })
+        .insert(Menu)
+        .with_children(|menu| {
+            menu.spawn(
+                TextBundle::from_section("Flock", text_style(48.)).with_style(Style {
+                    margin: UiRect::all(Val::Px(16.)),
+                    ..default()
+                }),
+            );
+            let presets = Preset::ALL
+                .into_iter()
+                .map(|preset| (MenuButton::Preset(preset), preset.name()));
+            let buttons = std::iter::once((MenuButton::Start, start))
+                .chain(presets)
+                .chain([(MenuButton::Quit, "Quit")]);
+            for (button, label) in buttons {
+                menu.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(200.), Val::Px(48.)),
+                        margin: UiRect::all(Val::Px(6.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .insert(button)
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(label, text_style(24.)));
+                });
+            }
+        });
+}
+
+pub(crate) fn despawn_menu(mut commands: Commands, menus: Query<Entity, With<Menu>>) {
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+pub(crate) fn press_menu_buttons(
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+    mut load: EventWriter<LoadPreset>,
+    mut buttons: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered => HOVERED_BUTTON_COLOR,
+            Interaction::Clicked | Interaction::None => BUTTON_COLOR,
+        }
+        .into();
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            MenuButton::Start => {
+                let _ = state.set(AppState::Running);
+            }
+            MenuButton::Preset(preset) => {
+                load.send(LoadPreset(*preset));
+                let _ = state.set(AppState::Running);
+            }
+            MenuButton::Quit => exit.send(AppExit),
+        }
+    }
+}
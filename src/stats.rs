@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    flock::FlockConfig,
+    neighbors::{NeighborIndex, SpatialIndex},
+};
+
+/// Standard measures of how well the flock flocks, updated after every
+/// simulation step.
+#[derive(Resource, Default, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+pub struct FlockStats {
+    pub boid_count: usize,
+    pub average_speed: f32,
+    // length of the mean heading: 1 when all boids fly the same way, near 0
+    // when they scatter
+    pub polarization: f32,
+    pub centroid: Vec2,
+    // root mean square distance of the boids to the centroid
+    pub spread: f32,
+    // mean distance of the boids to their nearest neighbor, leaving out those
+    // without one within `FlockConfig::neighbor_radius`
+    pub nearest_neighbor_distance: Option<f32>,
+}
+
+pub(crate) fn update_flock_stats(
+    config: Res<FlockConfig>,
+    index: Res<SpatialIndex>,
+    boids: Query<(Entity, &Transform, &Physics), With<Boid>>,
+    mut stats: ResMut<FlockStats>,
+) {
+    let boid_count = boids.iter().count();
+    if boid_count == 0 {
+        *stats = FlockStats::default();
+        return;
+    }
+    let count = boid_count as f32;
+
+    let mut speed_sum = 0.;
+    let mut heading_sum = Vec3::ZERO;
+    let mut position_sum = Vec2::ZERO;
+    for (_, transform, physics) in boids.iter() {
+        speed_sum += physics.velocity.length();
+        heading_sum += physics.velocity.normalize_or_zero();
+        position_sum += transform.translation.truncate();
+    }
+    let centroid = position_sum / count;
+
+    let spread = (boids
+        .iter()
+        .map(|(_, transform, _)| transform.translation.truncate().distance_squared(centroid))
+        .sum::<f32>()
+        / count)
+        .sqrt();
+
+    let (distance_sum, with_neighbor) = boids
+        .iter()
+        .filter_map(|(entity, transform, _)| {
+            let position = transform.translation;
+            index
+                .others_within(entity, position, config.neighbor_radius)
+                .map(|other| other.position.distance(position))
+                .min_by(f32::total_cmp)
+        })
+        .fold((0., 0), |(sum, count), distance| {
+            (sum + distance, count + 1)
+        });
+
+    *stats = FlockStats {
+        boid_count,
+        average_speed: speed_sum / count,
+        polarization: heading_sum.length() / count,
+        centroid,
+        spread,
+        nearest_neighbor_distance: (with_neighbor > 0).then(|| distance_sum / with_neighbor as f32),
+    };
+}
@@ -0,0 +1,521 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    attractor::CursorStir,
+    boid::{Boid, Physics, Steering},
+    flock::FlockConfig,
+    formation::FormationSlot,
+    leader::Following,
+    lod::Lod,
+    navigation::Navigating,
+    path::FollowPath,
+    perching::Perching,
+    physics::apply_force,
+    rng::SimRng,
+    sim::{self, SteeringParams},
+    target::TargetPin,
+    tick::SimTick,
+};
+
+/// How strongly boids react to a steering error, from 0 (none) to 1 (an
+/// error of `max_speed` or more), as a share of `max_force`.
+#[derive(Clone, Copy, Reflect, FromReflect)]
+pub enum ResponseCurve {
+    /// Force proportional to the error.
+    Linear,
+    /// Gentle on small errors, ramping up for large ones.
+    EaseIn,
+    /// Eager on small errors, leveling off for large ones.
+    EaseOut,
+}
+
+impl ResponseCurve {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => t,
+            ResponseCurve::EaseIn => t * t,
+            ResponseCurve::EaseOut => 1. - (1. - t) * (1. - t),
+        }
+    }
+}
+
+/// How the steering behaviors of a boid are combined into one force.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Reflect, FromReflect)]
+pub enum Arbitration {
+    /// The forces of every behavior are weighted and summed, by priority.
+    #[default]
+    WeightedSum,
+    /// Each boid picks the single most interesting direction among the
+    /// least dangerous ones, see `context_steering`.
+    Context,
+}
+
+/// Forces the steering behaviors want a boid to feel this step, blended by
+/// `accumulate_steering` into a single force of at most `max_force`.
+///
+/// Forces of higher priority are served first: lower priorities only get what
+/// is left of `max_force` once the higher ones are satisfied, so separation or
+/// avoidance are not drowned out by a strong seek.
+#[derive(Component, Default)]
+pub struct SteeringForces {
+    forces: Vec<(i32, Vec3, &'static str)>,
+    // name of the next force added, see `behavior`
+    behavior: Option<&'static str>,
+    // force replacing the blend on this step, see `replace_all`
+    replacement: Option<(Vec3, &'static str)>,
+    // blended force applied on the last step
+    applied: Vec3,
+    // behavior with the largest part in it
+    dominant: Option<&'static str>,
+}
+
+impl SteeringForces {
+    /// Names the behavior the next force added comes from, as in
+    /// `forces.behavior("seek").add(force, 1.)`. Unnamed forces are
+    /// reported as `"unnamed"` by `dominant`.
+    pub fn behavior(&mut self, name: &'static str) -> &mut Self {
+        self.behavior = Some(name);
+        self
+    }
+
+    /// Adds `force`, scaled by `weight`, at the default priority of 0.
+    pub fn add(&mut self, force: Vec3, weight: f32) {
+        self.add_with_priority(force, weight, 0);
+    }
+
+    pub fn add_with_priority(&mut self, force: Vec3, weight: f32, priority: i32) {
+        let name = self.behavior.take().unwrap_or("unnamed");
+        self.forces.push((priority, force * weight, name));
+    }
+
+    /// Has `accumulate_steering` apply `force` on this step, whatever the
+    /// other behaviors add.
+    pub fn replace_all(&mut self, force: Vec3) {
+        let name = self.behavior.take().unwrap_or("unnamed");
+        self.replacement = Some((force, name));
+    }
+
+    /// Force `accumulate_steering` applied on the last step.
+    pub fn applied(&self) -> Vec3 {
+        self.applied
+    }
+
+    /// Behavior which added the largest force to the one applied on the
+    /// last step, if any did.
+    pub fn dominant(&self) -> Option<&'static str> {
+        self.dominant
+    }
+}
+
+/// Has `accumulate_steering` send a `SteeringTrace` for every boid it
+/// steers, off by default as it is costly in large flocks.
+#[derive(Resource, Default)]
+pub struct SteeringTracing {
+    pub enabled: bool,
+}
+
+/// How the force `accumulate_steering` applied to a boid on simulation step
+/// `step` breaks down, for tests to check how behaviors combine or tools to
+/// plot it over time.
+#[derive(Clone, Debug)]
+pub struct SteeringTrace {
+    pub entity: Entity,
+    pub step: u64,
+    /// Every force added, from the highest priority down. Those of lower
+    /// priorities may be cut short or left out of `force` once `max_force`
+    /// is used up.
+    pub contributions: Vec<SteeringContribution>,
+    /// Force applied, at most `max_force` long.
+    pub force: Vec3,
+}
+
+/// One force added to a `SteeringForces`, as weighted.
+#[derive(Clone, Copy, Debug)]
+pub struct SteeringContribution {
+    pub behavior: &'static str,
+    pub priority: i32,
+    pub force: Vec3,
+}
+
+/// Makes a boid arrive at its target instead of seeking it: it slows down
+/// once within `slowing_radius` and comes to rest on the target rather than
+/// overshooting and circling it.
+#[derive(Component)]
+pub struct Arrive {
+    pub slowing_radius: f32,
+}
+
+impl Default for Arrive {
+    fn default() -> Self {
+        Self {
+            slowing_radius: 150.,
+        }
+    }
+}
+
+/// Makes a boid pursue its target instead of seeking it: it aims where the
+/// target will be, judging from how the target has been moving, rather than
+/// where it is. The further the target, the further ahead the boid looks, up
+/// to `max_prediction` seconds.
+#[derive(Component)]
+pub struct Pursue {
+    pub max_prediction: f32,
+}
+
+impl Default for Pursue {
+    fn default() -> Self {
+        Self { max_prediction: 1. }
+    }
+}
+
+/// Steers boids towards their target, unless they follow a leader, hold a
+/// place in a formation or are on their way along a planned path, or the
+/// mouse draws them to the cursor instead. Boids without an `Arrive` of their
+/// own arrive as set by `FlockConfig::arrive`, or while the target is pinned
+/// as with the default `Arrive`, so the flock settles on it.
+pub(crate) fn seek(
+    config: Res<FlockConfig>,
+    pin: Res<TargetPin>,
+    stir: Option<Res<CursorStir>>,
+    arrives: Query<&Arrive>,
+    in_formation: Query<(), With<FormationSlot>>,
+    planned: Query<&FollowPath, With<Navigating>>,
+    mut query: Query<
+        (Entity, &Transform, &Steering, &Physics, &mut SteeringForces),
+        Without<Following>,
+    >,
+) {
+    if stir.is_some_and(|stir| stir.attracts()) {
+        return;
+    }
+    query.par_for_each_mut(
+        PAR_BATCH_SIZE,
+        |(entity, transform, steering, physics, mut forces)| {
+            if in_formation.contains(entity)
+                || planned
+                    .get(entity)
+                    .is_ok_and(|path| path.current().is_some())
+            {
+                return;
+            }
+            let position = transform.translation;
+            if position.distance(steering.target) > steering.max_seek_range {
+                return;
+            }
+            let params = steering_params(physics, config.response_curve);
+            let slowing_radius = match arrives.get(entity) {
+                Ok(arrive) => Some(arrive.slowing_radius),
+                Err(_) => config
+                    .arrive
+                    .or_else(|| pin.pinned.then(|| Arrive::default().slowing_radius)),
+            };
+            let (name, force) = match slowing_radius {
+                Some(slowing_radius) => (
+                    "arrive",
+                    sim::steer_arrive(
+                        position,
+                        physics.velocity,
+                        steering.target,
+                        slowing_radius,
+                        &params,
+                    ),
+                ),
+                None => (
+                    "seek",
+                    sim::steer_seek(position, physics.velocity, steering.target, &params),
+                ),
+            };
+            forces.behavior(name).add(force, 1.);
+        },
+    );
+}
+
+/// Makes a boid meander when it has no target in range: it steers towards a
+/// point on a circle ahead of it, which drifts randomly around the circle.
+#[derive(Component)]
+pub struct Wander {
+    // how far ahead of the boid the circle is
+    pub distance: f32,
+    pub radius: f32,
+    // how fast the point may drift around the circle, in radians per second
+    pub jitter: f32,
+    // position of the point on the circle, relative to the heading
+    angle: f32,
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self {
+            distance: 60.,
+            radius: 30.,
+            jitter: 6.,
+            angle: 0.,
+        }
+    }
+}
+
+pub(crate) fn wander(
+    config: Res<FlockConfig>,
+    tick: Res<SimTick>,
+    mut rng: ResMut<SimRng>,
+    mut query: Query<
+        (
+            &Transform,
+            &Steering,
+            &Physics,
+            &mut Wander,
+            &mut SteeringForces,
+        ),
+        Without<Following>,
+    >,
+) {
+    for (transform, steering, physics, mut wander, mut forces) in query.iter_mut() {
+        if transform.translation.distance(steering.target) <= steering.max_seek_range {
+            continue;
+        }
+
+        let max_drift = wander.jitter * tick.step();
+        wander.angle += rng.gen_range(-max_drift..=max_drift);
+
+        let heading = physics
+            .velocity
+            .truncate()
+            .try_normalize()
+            .unwrap_or(Vec2::Y);
+        let offset = heading.rotate(Vec2::from_angle(wander.angle)) * wander.radius;
+        let desired = (heading * wander.distance + offset).normalize_or_zero() * physics.max_speed;
+        forces.behavior("wander").add(
+            steer_towards(desired.extend(0.), physics, config.response_curve),
+            1.,
+        );
+    }
+}
+
+/// Blends the forces gathered in `SteeringForces` and applies the result.
+/// Steering behaviors must run before it in `FixedUpdateStage`.
+/// Boids whose `Lod` skips steering on this step get the force of the last
+/// one again, perched boids none at all. A force set with
+/// `SteeringForces::replace_all` is applied alone, still within `max_force`.
+pub fn accumulate_steering(
+    tick: Res<SimTick>,
+    tracing: Res<SteeringTracing>,
+    mut traces: EventWriter<SteeringTrace>,
+    mut query: Query<(Entity, &mut Physics, &mut SteeringForces, Option<&Lod>), With<Boid>>,
+    perching: Query<&Perching>,
+) {
+    for (entity, mut physics, mut forces, lod) in query.iter_mut() {
+        let mut trace = |contributions, force| {
+            if tracing.enabled {
+                traces.send(SteeringTrace {
+                    entity,
+                    step: tick.steps(),
+                    contributions,
+                    force,
+                });
+            }
+        };
+
+        // perched boids sit still whatever pulls at them
+        if perching.get(entity).is_ok_and(Perching::is_perched) {
+            forces.forces.clear();
+            forces.replacement = None;
+            forces.applied = Vec3::ZERO;
+            forces.dominant = None;
+            trace(Vec::new(), Vec3::ZERO);
+            continue;
+        }
+        if lod.is_some_and(Lod::skips_steering) {
+            forces.forces.clear();
+            forces.replacement = None;
+            let applied = forces.applied;
+            apply_force(physics.as_mut(), &applied);
+            trace(Vec::new(), applied);
+            continue;
+        }
+        if let Some((replacement, name)) = forces.replacement.take() {
+            forces.forces.clear();
+            let force = replacement.clamp_length_max(physics.max_force);
+            forces.applied = force;
+            forces.dominant = Some(name);
+            apply_force(physics.as_mut(), &force);
+            let contribution = SteeringContribution {
+                behavior: name,
+                priority: 0,
+                force: replacement,
+            };
+            trace(vec![contribution], force);
+            continue;
+        }
+
+        // highest priority first, and in a set order within a priority so the
+        // sum does not depend on the order the behaviors happened to run in
+        forces.forces.sort_by(|(a, force_a, _), (b, force_b, _)| {
+            b.cmp(a)
+                .then(force_a.x.total_cmp(&force_b.x))
+                .then(force_a.y.total_cmp(&force_b.y))
+                .then(force_a.z.total_cmp(&force_b.z))
+        });
+
+        let mut total = Vec3::ZERO;
+        let mut remaining = physics.max_force;
+        let mut dominant: Option<(f32, &'static str)> = None;
+        for group in forces.forces.chunk_by(|(a, ..), (b, ..)| a == b) {
+            for (_, force, name) in group {
+                let length = force.length();
+                if length > 0. && dominant.is_none_or(|(largest, _)| length > largest) {
+                    dominant = Some((length, name));
+                }
+            }
+            let force: Vec3 = group.iter().map(|(_, force, _)| *force).sum();
+            let force = force.clamp_length_max(remaining);
+            total += force;
+            remaining -= force.length();
+            if remaining <= 0. {
+                break;
+            }
+        }
+        let contributions = match tracing.enabled {
+            true => forces
+                .forces
+                .iter()
+                .map(|&(priority, force, behavior)| SteeringContribution {
+                    behavior,
+                    priority,
+                    force,
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+        forces.forces.clear();
+        forces.applied = total;
+        forces.dominant = dominant.map(|(_, name)| name);
+
+        apply_force(physics.as_mut(), &total);
+        trace(contributions, total);
+    }
+}
+
+/// Boids handed to each task by the steering systems that run in parallel.
+pub(crate) const PAR_BATCH_SIZE: usize = 64;
+
+/// Time over which the debug overlay shows the steering force acting on the
+/// velocity, in seconds. One 60 Hz frame, which the flock was originally
+/// tuned at.
+pub(crate) const RESPONSE_TIME: f32 = 1. / 60.;
+
+/// Force turning `physics` towards the `desired` velocity, see
+/// `sim::steer_towards`.
+pub fn steer_towards(desired: Vec3, physics: &Physics, curve: ResponseCurve) -> Vec3 {
+    sim::steer_towards(desired, physics.velocity, &steering_params(physics, curve))
+}
+
+/// Limits of `physics` for the functions of `sim`.
+pub(crate) fn steering_params(physics: &Physics, curve: ResponseCurve) -> SteeringParams {
+    SteeringParams {
+        max_speed: physics.max_speed,
+        max_force: physics.max_force,
+        response_curve: curve,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+
+    use super::*;
+    use crate::{
+        bounds::{BoundaryMode, Bounds},
+        physics::{physics_system, Integrator, Substeps},
+        playfield::Playfield,
+    };
+
+    fn seeking_boid(world: &mut World, position: Vec3) -> Entity {
+        world
+            .spawn((
+                Transform::from_translation(position),
+                Steering {
+                    target: Vec3::ZERO,
+                    max_seek_range: 200.,
+                },
+                Physics {
+                    max_speed: 100.,
+                    max_force: 50.,
+                    ..default()
+                },
+                SteeringForces::default(),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn no_seek_beyond_max_seek_range() {
+        ComputeTaskPool::init(TaskPool::default);
+        let mut world = World::new();
+        world.insert_resource(FlockConfig::default());
+        world.insert_resource(TargetPin::default());
+        let near = seeking_boid(&mut world, Vec3::new(150., 0., 0.));
+        let far = seeking_boid(&mut world, Vec3::new(250., 0., 0.));
+
+        SystemStage::single(seek).run(&mut world);
+
+        let forces = |entity| world.get::<SteeringForces>(entity).unwrap().forces.clone();
+        let near = forces(near);
+        assert_eq!(near.len(), 1);
+        assert_eq!(near[0].2, "seek");
+        // towards the target, at the origin
+        assert!(near[0].1.x < 0.);
+        assert!(forces(far).is_empty());
+    }
+
+    /// Speed of a boid flying at full speed at the target from 100 units
+    /// away, after a few steps.
+    fn speed_near_target(pinned: bool, config: FlockConfig) -> f32 {
+        ComputeTaskPool::init(TaskPool::default);
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(TargetPin { pinned });
+        world.insert_resource(SimTick::default());
+        world.insert_resource(SteeringTracing::default());
+        world.insert_resource(Events::<SteeringTrace>::default());
+        world.insert_resource(Integrator::default());
+        world.insert_resource(Substeps::default());
+        world.insert_resource(BoundaryMode::default());
+        world.insert_resource(Bounds::default());
+        world.insert_resource(Playfield::default());
+        let boid = seeking_boid(&mut world, Vec3::new(100., 0., 0.));
+        world.entity_mut(boid).insert(Boid);
+        world.get_mut::<Physics>(boid).unwrap().velocity = Vec3::new(-100., 0., 0.);
+
+        let mut step = SystemStage::single_threaded()
+            .with_system(seek)
+            .with_system(accumulate_steering.after(seek))
+            .with_system(physics_system.after(accumulate_steering));
+        for _ in 0..10 {
+            step.run(&mut world);
+        }
+        world.get::<Physics>(boid).unwrap().velocity.length()
+    }
+
+    #[test]
+    fn boids_slow_down_near_a_pinned_target() {
+        let free = speed_near_target(false, FlockConfig::default());
+        let pinned = speed_near_target(true, FlockConfig::default());
+        assert!(pinned < 100. - 1.);
+        assert!(pinned < free);
+    }
+
+    #[test]
+    fn config_arrive_slows_boids_down_near_the_target() {
+        let seeking = speed_near_target(false, FlockConfig::default());
+        let arriving = speed_near_target(
+            false,
+            FlockConfig {
+                arrive: Some(150.),
+                ..default()
+            },
+        );
+        assert!(arriving < 100. - 1.);
+        assert!(arriving < seeking);
+    }
+}
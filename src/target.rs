@@ -0,0 +1,486 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+
+use crate::{
+    boid::{Boid, Physics, Steering},
+    bounds::Bounds,
+    flock::FlockConfig,
+    render::{CursorPosition, FlockView, RenderOrder},
+    rng::SimRng,
+    steering::Pursue,
+    tick::{Interpolated, SimTick},
+};
+
+/// Point boids seek. Each boid heads for the nearest one.
+#[derive(Component)]
+pub struct Target;
+
+/// Velocity of a target, estimated from its successive positions since
+/// targets are moved by hand rather than by physics. Boids with `Pursue`, or
+/// all of them with `FlockConfig::pursue`, aim ahead of targets that have one.
+#[derive(Component, Default)]
+pub struct TargetMotion {
+    pub velocity: Vec3,
+    previous: Option<Vec3>,
+}
+
+/// The target spawned on startup, the one moved by the cursor as set by
+/// `TargetMode`. Targets added with `G` stay where they are put.
+#[derive(Component)]
+pub struct MainTarget;
+
+/// When pinned the target stops following the cursor and stays where it was,
+/// letting the flock settle on a fixed point: boids arrive at it instead of
+/// overshooting it at full speed.
+#[derive(Resource, Default)]
+pub struct TargetPin {
+    pub pinned: bool,
+}
+
+/// How the target is moved. `Tab` cycles through the modes.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetMode {
+    /// The target sticks to the cursor.
+    #[default]
+    FollowCursor,
+    /// The target jumps to where the left mouse button is clicked, without
+    /// dragging (a drag draws an obstacle).
+    ClickToPlace,
+    /// The target drives itself as set by `Autopilot`, for demos and
+    /// benchmarks running without a mouse.
+    Autopilot,
+    /// The target is left alone for the app to move it.
+    Scripted,
+}
+
+impl TargetMode {
+    pub fn next(self) -> Self {
+        match self {
+            TargetMode::FollowCursor => TargetMode::ClickToPlace,
+            TargetMode::ClickToPlace => TargetMode::Autopilot,
+            TargetMode::Autopilot => TargetMode::Scripted,
+            TargetMode::Scripted => TargetMode::FollowCursor,
+        }
+    }
+}
+
+/// Route of the target in `TargetMode::Autopilot`. Driven by simulated time
+/// and `SimRng`, so it replays identically for a given seed.
+#[derive(Resource, Clone)]
+pub enum Autopilot {
+    /// Lissajous curve around the origin, `amplitude` world units from it
+    /// and `frequency` loops per second along each axis.
+    Lissajous {
+        amplitude: Vec2,
+        frequency: Vec2,
+        // phase of the x axis ahead of the y axis, in radians
+        phase: f32,
+    },
+    /// Straight lines to random points within `Bounds`, at `speed` world
+    /// units per second.
+    RandomWaypoints { speed: f32 },
+    /// Positions recorded every `interval` seconds, played back in a loop.
+    Recorded { points: Vec<Vec2>, interval: f32 },
+}
+
+impl Default for Autopilot {
+    fn default() -> Self {
+        Autopilot::Lissajous {
+            amplitude: Vec2::new(250., 250.),
+            frequency: Vec2::new(0.04, 0.06),
+            phase: std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
+pub(crate) fn spawn_target(mut commands: Commands, view: Res<FlockView>, order: Res<RenderOrder>) {
+    let target = spawn_target_at(&mut commands, &view, &order, Vec2::ZERO);
+    commands.entity(target).insert(MainTarget);
+}
+
+fn spawn_target_at(
+    commands: &mut Commands,
+    view: &FlockView,
+    order: &RenderOrder,
+    position: Vec2,
+) -> Entity {
+    let shape = shapes::Rectangle {
+        extents: Vec2 { x: 10., y: 10. },
+        ..Default::default()
+    };
+
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::RED),
+                outline_mode: StrokeMode::new(Color::WHITE, 1.),
+            },
+            Transform::from_translation(position.extend(order.target)),
+        ))
+        .insert(view.layers)
+        .insert(Target)
+        .insert(TargetMotion::default())
+        .id()
+}
+
+/// Moves the main target along the `Autopilot` route.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn drive_target(
+    tick: Res<SimTick>,
+    mode: Res<TargetMode>,
+    pin: Res<TargetPin>,
+    autopilot: Res<Autopilot>,
+    bounds: Res<Bounds>,
+    mut rng: ResMut<SimRng>,
+    mut waypoint: Local<Option<Vec2>>,
+    mut target_query: Query<&mut Transform, With<MainTarget>>,
+) {
+    if *mode != TargetMode::Autopilot || pin.pinned {
+        return;
+    }
+    let Ok(mut target) = target_query.get_single_mut() else {
+        return;
+    };
+
+    let time = tick.elapsed();
+    let current = target.translation.truncate();
+    let position = match &*autopilot {
+        Autopilot::Lissajous {
+            amplitude,
+            frequency,
+            phase,
+        } => {
+            let angle = *frequency * time * std::f32::consts::TAU;
+            *amplitude * Vec2::new((angle.x + phase).sin(), angle.y.sin())
+        }
+        Autopilot::RandomWaypoints { speed } => {
+            let step = speed * tick.step();
+            let next = match *waypoint {
+                Some(next) if next.distance(current) > step => next,
+                _ => Vec2::new(
+                    rng.gen_range(bounds.min.x..=bounds.max.x),
+                    rng.gen_range(bounds.min.y..=bounds.max.y),
+                ),
+            };
+            *waypoint = Some(next);
+            current + (next - current).clamp_length_max(step)
+        }
+        Autopilot::Recorded { points, interval } => {
+            if points.is_empty() {
+                return;
+            }
+            // blend between the two recorded points around the current time
+            let t = time / interval.max(f32::EPSILON);
+            let index = t as usize;
+            let from = points[index % points.len()];
+            let to = points[(index + 1) % points.len()];
+            from.lerp(to, t.fract())
+        }
+    };
+    target.translation = position.extend(target.translation.z);
+}
+
+pub(crate) fn track_target_motion(
+    tick: Res<SimTick>,
+    mut targets: Query<(&Transform, &mut TargetMotion)>,
+) {
+    // seconds over which the estimate is smoothed: the cursor moves once per
+    // frame, so a step can see it jump or stand still
+    const SMOOTHING: f32 = 0.1;
+
+    let blend = tick.step() / (SMOOTHING + tick.step());
+    for (transform, mut motion) in targets.iter_mut() {
+        let position = transform.translation;
+        if let Some(previous) = motion.previous {
+            let velocity = (position - previous) / tick.step();
+            motion.velocity = motion.velocity.lerp(velocity, blend);
+        }
+        motion.previous = Some(position);
+    }
+}
+
+/// Points boids at the nearest target, or ahead of it with `Pursue` or
+/// `FlockConfig::pursue`.
+pub(crate) fn seek_target(
+    config: Res<FlockConfig>,
+    mut boid_query: Query<(&Transform, &Physics, &mut Steering, Option<&Pursue>), With<Boid>>,
+    target_query: Query<(&Transform, Option<&TargetMotion>), With<Target>>,
+) {
+    let targets: Vec<(Vec3, Vec3)> = target_query
+        .iter()
+        .map(|(transform, motion)| {
+            let velocity = motion.map_or(Vec3::ZERO, |motion| motion.velocity);
+            (transform.translation, velocity)
+        })
+        .collect();
+    for (transform, physics, mut steering, pursue) in boid_query.iter_mut() {
+        let position = transform.translation;
+        // without any target, boids keep heading for the last one
+        let Some((target, velocity)) = targets
+            .iter()
+            .min_by(|(a, _), (b, _)| a.distance(position).total_cmp(&b.distance(position)))
+        else {
+            continue;
+        };
+
+        let max_prediction = pursue.map(|pursue| pursue.max_prediction).or(config.pursue);
+        steering.target = match max_prediction {
+            Some(max_prediction) => {
+                // roughly the time it takes to get there
+                let prediction = (target.distance(position) / physics.max_speed.max(f32::EPSILON))
+                    .min(max_prediction);
+                *target + *velocity * prediction
+            }
+            None => *target,
+        };
+    }
+}
+
+/// Boids closer than this to their target have reached it.
+pub const TARGET_REACHED_DISTANCE: f32 = 10.;
+
+/// Sent when a boid comes within `TARGET_REACHED_DISTANCE` of its target.
+/// It reaches it again only once it has flown twice as far off.
+pub struct TargetReached {
+    pub entity: Entity,
+    pub target: Vec3,
+}
+
+/// Marks a boid that reached its target and has not left it since.
+#[derive(Component)]
+pub(crate) struct AtTarget;
+
+pub(crate) fn reach_target(
+    mut commands: Commands,
+    boids: Query<(Entity, &Transform, &Steering, &Interpolated), With<Boid>>,
+    at_target: Query<(), With<AtTarget>>,
+    mut reached: EventWriter<TargetReached>,
+) {
+    for (entity, transform, steering, interpolated) in boids.iter() {
+        let position = transform.translation.truncate();
+        let target = steering.target.truncate();
+        let distance = position.distance(target);
+        // nearest the boid came along the way it went this step, so fast
+        // boids do not fly past the target between two steps
+        let from = interpolated.previous_position();
+        let way = position - from;
+        let along =
+            ((target - from).dot(way) / way.length_squared().max(f32::EPSILON)).clamp(0., 1.);
+        let closest = target.distance(from + way * along);
+
+        let at_target = at_target.contains(entity);
+        if !at_target && closest < TARGET_REACHED_DISTANCE {
+            commands.entity(entity).insert(AtTarget);
+            reached.send(TargetReached {
+                entity,
+                target: steering.target,
+            });
+        } else if at_target && distance > TARGET_REACHED_DISTANCE * 2. {
+            commands.entity(entity).remove::<AtTarget>();
+        }
+    }
+}
+
+/// `G` adds a target at the cursor, `Shift+G` removes the added target
+/// nearest to it.
+pub(crate) fn place_targets(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    cursor: Res<CursorPosition>,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    targets: Query<(Entity, &Transform), With<Target>>,
+    main_target: Query<(), With<MainTarget>>,
+) {
+    if !keyboard.just_pressed(KeyCode::G) {
+        return;
+    }
+    let Some(position) = cursor.0 else {
+        return;
+    };
+
+    if keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        let nearest = targets
+            .iter()
+            .filter(|(target, _)| !main_target.contains(*target))
+            .min_by(|(_, a), (_, b)| {
+                let a = a.translation.truncate().distance(position);
+                let b = b.translation.truncate().distance(position);
+                a.total_cmp(&b)
+            });
+        if let Some((target, _)) = nearest {
+            commands.entity(target).despawn_recursive();
+        }
+    } else {
+        spawn_target_at(&mut commands, &view, &order, position);
+    }
+}
+
+pub(crate) fn toggle_target_pin(keyboard: Res<Input<KeyCode>>, mut pin: ResMut<TargetPin>) {
+    if keyboard.just_pressed(KeyCode::F) {
+        pin.pinned = !pin.pinned;
+    }
+}
+
+pub(crate) fn cycle_target_mode(keyboard: Res<Input<KeyCode>>, mut mode: ResMut<TargetMode>) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        *mode = mode.next();
+        info!("target mode: {:?}", *mode);
+    }
+}
+
+pub(crate) fn move_target(
+    pin: Res<TargetPin>,
+    mode: Res<TargetMode>,
+    cursor: Res<CursorPosition>,
+    mouse: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut pressed_at: Local<Option<Vec2>>,
+    mut target_query: Query<&mut Transform, With<MainTarget>>,
+) {
+    // further than this between press and release, a click is a drag
+    const MAX_CLICK_DRIFT: f32 = 10.;
+
+    // a tap is a click
+    let pressed = mouse.just_pressed(MouseButton::Left) || touches.any_just_pressed();
+    let released = mouse.just_released(MouseButton::Left) || touches.any_just_released();
+    if pressed {
+        *pressed_at = cursor.0;
+    }
+    if pin.pinned {
+        return;
+    }
+
+    let destination = match *mode {
+        TargetMode::FollowCursor => cursor.0,
+        TargetMode::ClickToPlace if released => match (pressed_at.take(), cursor.0) {
+            (Some(start), Some(end)) if start.distance(end) < MAX_CLICK_DRIFT => Some(end),
+            _ => None,
+        },
+        TargetMode::ClickToPlace | TargetMode::Autopilot | TargetMode::Scripted => None,
+    };
+    if let (Some(world_pos), Ok(mut target)) = (destination, target_query.get_single_mut()) {
+        target.translation = world_pos.extend(target.translation.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        flock::{spawn_flock, FlockConfig, Flocks},
+        obstacle::Obstacle,
+        render::{apply_render_order, Overlay},
+    };
+
+    fn world_with_target(pinned: bool) -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(TargetPin { pinned });
+        world.insert_resource(TargetMode::FollowCursor);
+        world.insert_resource(CursorPosition(Some(Vec2::new(10., 20.))));
+        world.insert_resource(Input::<MouseButton>::default());
+        world.insert_resource(Touches::default());
+        let target = world
+            .spawn((Transform::from_xyz(10., 20., 5.), MainTarget))
+            .id();
+        (world, target)
+    }
+
+    fn move_cursor_to(world: &mut World, position: Vec2) {
+        world.resource_mut::<CursorPosition>().0 = Some(position);
+        SystemStage::single(move_target).run(world);
+    }
+
+    #[test]
+    fn pinned_target_ignores_cursor() {
+        let (mut world, target) = world_with_target(true);
+        move_cursor_to(&mut world, Vec2::new(-150., 80.));
+        let transform = world.get::<Transform>(target).unwrap();
+        assert_eq!(transform.translation, Vec3::new(10., 20., 5.));
+    }
+
+    #[test]
+    fn free_target_follows_cursor() {
+        let (mut world, target) = world_with_target(false);
+        move_cursor_to(&mut world, Vec2::new(-150., 80.));
+        let transform = world.get::<Transform>(target).unwrap();
+        assert_eq!(transform.translation, Vec3::new(-150., 80., 5.));
+    }
+
+    /// Where a boid 100 units from a target moving up at 50 units per second
+    /// aims, with `pursue` as `FlockConfig::pursue`.
+    fn aim(pursue: Option<f32>) -> Vec3 {
+        let mut world = World::new();
+        world.insert_resource(FlockConfig {
+            pursue,
+            ..default()
+        });
+        world.spawn((
+            Transform::default(),
+            Target,
+            TargetMotion {
+                velocity: Vec3::new(0., 50., 0.),
+                previous: None,
+            },
+        ));
+        let boid = world
+            .spawn((
+                Boid,
+                Transform::from_xyz(100., 0., 0.),
+                Physics {
+                    max_speed: 100.,
+                    ..default()
+                },
+                Steering::default(),
+            ))
+            .id();
+        SystemStage::single(seek_target).run(&mut world);
+        world.get::<Steering>(boid).unwrap().target
+    }
+
+    #[test]
+    fn pursuing_boids_aim_ahead_of_the_target() {
+        assert_eq!(aim(None), Vec3::ZERO);
+        // a second away at full speed
+        assert_eq!(aim(Some(2.)), Vec3::new(0., 50., 0.));
+        assert_eq!(aim(Some(0.5)), Vec3::new(0., 25., 0.));
+    }
+
+    #[test]
+    fn target_obstacles_and_overlays_are_drawn_above_the_flock() {
+        let mut world = World::new();
+        world.insert_resource(FlockView::default());
+        world.insert_resource(RenderOrder::default());
+        world.insert_resource(FlockConfig::default());
+        world.insert_resource(Flocks::default());
+        world.insert_resource(SimRng::seeded(0));
+        SystemStage::single(spawn_flock).run(&mut world);
+        SystemStage::single(spawn_target).run(&mut world);
+        let obstacle = world
+            .spawn((Transform::default(), Obstacle::Circle { radius: 10. }))
+            .id();
+        let overlay = world.spawn((Transform::default(), Overlay)).id();
+        // the policy places everything whatever depth it started at
+        for mut transform in world.query::<&mut Transform>().iter_mut(&mut world) {
+            transform.translation.z = 0.;
+        }
+        SystemStage::single(apply_render_order).run(&mut world);
+
+        let z = |world: &World, entity| world.get::<Transform>(entity).unwrap().translation.z;
+        let target = world
+            .query_filtered::<Entity, With<MainTarget>>()
+            .single(&world);
+        let boids: Vec<Entity> = world
+            .query_filtered::<Entity, With<Boid>>()
+            .iter(&world)
+            .collect();
+        assert!(!boids.is_empty());
+        for boid in boids {
+            assert!(z(&world, boid) < z(&world, obstacle));
+        }
+        assert!(z(&world, obstacle) < z(&world, target));
+        assert!(z(&world, target) < z(&world, overlay));
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+/// Stage running the simulation at a fixed rate, see `SimTick`.
+#[derive(StageLabel)]
+pub struct FixedUpdateStage;
+
+/// Stage blending boid transforms between the last two simulation ticks, run
+/// right after `FixedUpdateStage`.
+#[derive(StageLabel)]
+pub struct InterpolateStage;
+
+/// Rate of the simulation. Physics and steering advance in steps of exactly
+/// `1 / rate` seconds however fast frames are drawn, and boids are drawn
+/// in between the last two steps.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SimTick {
+    // steps per second
+    pub rate: f32,
+    // time not yet simulated, less than one step after the stage ran
+    accumulator: f32,
+    // steps simulated since startup
+    steps: u64,
+}
+
+impl Default for SimTick {
+    fn default() -> Self {
+        Self {
+            rate: 60.,
+            accumulator: 0.,
+            steps: 0,
+        }
+    }
+}
+
+impl SimTick {
+    /// Duration of one step, in seconds.
+    pub fn step(&self) -> f32 {
+        1. / self.rate
+    }
+
+    /// Steps simulated since startup.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Simulated time since startup, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.steps as f32 * self.step()
+    }
+
+    /// Goes back to an earlier step, for `RewindBuffer`.
+    pub(crate) fn rewind_to(&mut self, steps: u64) {
+        self.steps = steps;
+        self.accumulator = 0.;
+    }
+
+    /// How far the current frame is between the last step and the next, from
+    /// 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step()).clamp(0., 1.)
+    }
+}
+
+/// Pause, single-step and speed of the simulation. Only `FixedUpdateStage`
+/// is affected, drawing and input go on at the frame rate.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SimulationControl {
+    pub paused: bool,
+    // simulated seconds per real second, from `MIN_TIME_SCALE` to
+    // `MAX_TIME_SCALE`
+    pub time_scale: f32,
+    // run exactly one step on the next frame, while paused
+    pub step_requested: bool,
+    // run exactly one step per frame whatever the time it took, so headless
+    // runs go as fast as the machine allows
+    pub lockstep: bool,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            time_scale: 1.,
+            step_requested: false,
+            lockstep: false,
+        }
+    }
+}
+
+impl SimulationControl {
+    pub const MIN_TIME_SCALE: f32 = 0.1;
+    pub const MAX_TIME_SCALE: f32 = 10.;
+}
+
+/// Simulated position and rotation of a moving entity at the last two steps,
+/// which its `Transform` is interpolated between for drawing.
+#[derive(Component)]
+pub(crate) struct Interpolated {
+    previous: (Vec2, Quat),
+    current: (Vec2, Quat),
+}
+
+impl Interpolated {
+    pub(crate) fn new(position: Vec2) -> Self {
+        Self {
+            previous: (position, Quat::IDENTITY),
+            current: (position, Quat::IDENTITY),
+        }
+    }
+
+    /// Drops the motion of the current step, for entities moved in one jump
+    /// that should not be drawn sliding from their old position.
+    pub(crate) fn snap(&mut self, position: Vec2) {
+        self.previous.0 = position;
+    }
+
+    /// Where the entity was at the start of the step.
+    pub(crate) fn previous_position(&self) -> Vec2 {
+        self.previous.0
+    }
+
+    /// Puts the entity at `position` on both steps, so it is drawn there
+    /// until it moves again.
+    pub(crate) fn reset(&mut self, position: Vec2, rotation: Quat) {
+        self.previous = (position, rotation);
+        self.current = (position, rotation);
+    }
+}
+
+/// Run criterion of `FixedUpdateStage`: runs it once per step owed.
+pub(crate) fn fixed_tick(
+    time: Res<Time>,
+    mut tick: ResMut<SimTick>,
+    mut control: ResMut<SimulationControl>,
+    mut looping: Local<bool>,
+) -> ShouldRun {
+    if !*looping {
+        if control.paused {
+            if !control.step_requested {
+                return ShouldRun::No;
+            }
+            // owe exactly one more step, leaving the interpolation where it was
+            control.step_requested = false;
+            tick.accumulator += tick.step();
+        } else if control.lockstep {
+            tick.accumulator = tick.step();
+        } else {
+            // past a few frames' worth of steps behind, drop time rather than
+            // spiral into ever longer frames
+            let max_lag = tick.step() * 5. * control.time_scale.max(1.);
+            let delta = time.delta_seconds() * control.time_scale;
+            tick.accumulator = (tick.accumulator + delta).min(max_lag);
+        }
+    }
+
+    if tick.accumulator >= tick.step() {
+        tick.accumulator -= tick.step();
+        *looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        *looping = false;
+        ShouldRun::No
+    }
+}
+
+/// Puts moving entities back at their simulated state before a step.
+pub(crate) fn begin_tick(mut boids: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in boids.iter_mut() {
+        let (position, rotation) = interpolated.current;
+        transform.translation = position.extend(transform.translation.z);
+        transform.rotation = rotation;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+/// Records the state moving entities reached at the end of a step.
+pub(crate) fn end_tick(
+    mut tick: ResMut<SimTick>,
+    mut boids: Query<(&Transform, &mut Interpolated)>,
+) {
+    tick.steps += 1;
+    for (transform, mut interpolated) in boids.iter_mut() {
+        interpolated.current = (transform.translation.truncate(), transform.rotation);
+    }
+}
+
+pub(crate) fn interpolate_transforms(
+    tick: Res<SimTick>,
+    mut boids: Query<(&mut Transform, &Interpolated)>,
+) {
+    let alpha = tick.alpha();
+    for (mut transform, interpolated) in boids.iter_mut() {
+        let (previous_position, previous_rotation) = interpolated.previous;
+        let (current_position, current_rotation) = interpolated.current;
+        let position = previous_position.lerp(current_position, alpha);
+        transform.translation = position.extend(transform.translation.z);
+        transform.rotation = previous_rotation.slerp(current_rotation, alpha);
+    }
+}
+
+/// `.` advances the simulation by one step while paused, `+` and `-` speed it
+/// up or slow it down. Pausing goes through `AppState`.
+pub(crate) fn control_simulation(
+    keyboard: Res<Input<KeyCode>>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if control.paused && keyboard.just_pressed(KeyCode::Period) {
+        control.step_requested = true;
+    }
+
+    let faster = keyboard.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]);
+    let slower = keyboard.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]);
+    if faster || slower {
+        let factor = if faster { 2. } else { 0.5 };
+        control.time_scale = (control.time_scale * factor).clamp(
+            SimulationControl::MIN_TIME_SCALE,
+            SimulationControl::MAX_TIME_SCALE,
+        );
+        info!("simulation speed: {}x", control.time_scale);
+    }
+}
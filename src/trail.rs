@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    boid::{Boid, Physics},
+    lod::Lod,
+    render::{FlockView, RenderOrder},
+    tick::SimTick,
+};
+
+/// Trails are drawn in this many bands, more transparent towards the tail.
+const BANDS: usize = 4;
+
+/// Positions a boid went through lately, drawn behind it as a fading line
+/// while `TrailSettings::enabled`.
+#[derive(Component, Default)]
+pub struct Trail {
+    // oldest first
+    points: VecDeque<Vec2>,
+    // simulated seconds since the last point was recorded
+    since_sample: f32,
+}
+
+/// Length and sampling of the `Trail`s, toggled with `T`.
+#[derive(Resource)]
+pub struct TrailSettings {
+    pub enabled: bool,
+    // points kept per trail
+    pub length: usize,
+    // simulated seconds between two points
+    pub interval: f32,
+    pub color: Color,
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            length: 20,
+            interval: 0.05,
+            color: Color::rgb(0.6, 0.7, 1.),
+        }
+    }
+}
+
+/// One of the `BANDS` paths the trails are drawn in, from the tail (0) to
+/// the head.
+#[derive(Component)]
+pub(crate) struct TrailBand(usize);
+
+pub(crate) fn spawn_trail_bands(
+    mut commands: Commands,
+    view: Res<FlockView>,
+    order: Res<RenderOrder>,
+    settings: Res<TrailSettings>,
+) {
+    for band in 0..BANDS {
+        let mut color = settings.color;
+        color.set_a(0.6 * (band + 1) as f32 / BANDS as f32);
+        commands
+            .spawn(GeometryBuilder::build_as(
+                &PathBuilder::new().build(),
+                DrawMode::Stroke(StrokeMode::new(color, 1.)),
+                Transform::from_xyz(0., 0., order.trails),
+            ))
+            .insert(view.layers)
+            .insert(TrailBand(band));
+    }
+}
+
+pub(crate) fn toggle_trails(keyboard: Res<Input<KeyCode>>, mut settings: ResMut<TrailSettings>) {
+    if keyboard.just_pressed(KeyCode::T) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+pub(crate) fn record_trails(
+    tick: Res<SimTick>,
+    settings: Res<TrailSettings>,
+    mut boids: Query<(&Transform, &Physics, &mut Trail, Option<&Lod>), With<Boid>>,
+) {
+    for (transform, physics, mut trail, lod) in boids.iter_mut() {
+        if !settings.enabled || lod.is_some_and(Lod::is_reduced) {
+            trail.points.clear();
+            continue;
+        }
+
+        trail.since_sample += tick.step();
+        if trail.since_sample < settings.interval && !trail.points.is_empty() {
+            continue;
+        }
+        trail.since_sample = 0.;
+
+        let position = transform.translation.truncate();
+        // a boid wrapped to the other edge starts a new trail rather than
+        // streaking across the view
+        let max_step = physics.max_speed * settings.interval.max(tick.step()) * 2.;
+        if trail
+            .points
+            .back()
+            .is_some_and(|last| last.distance(position) > max_step)
+        {
+            trail.points.clear();
+        }
+        trail.points.push_back(position);
+        while trail.points.len() > settings.length {
+            trail.points.pop_front();
+        }
+    }
+}
+
+pub(crate) fn draw_trails(
+    settings: Res<TrailSettings>,
+    boids: Query<(&Transform, &Trail), With<Boid>>,
+    mut bands: Query<(&TrailBand, &mut Path)>,
+) {
+    let mut builders: Vec<PathBuilder> = (0..BANDS).map(|_| PathBuilder::new()).collect();
+    if settings.enabled {
+        for (transform, trail) in boids.iter() {
+            // the drawn position of the boid closes the trail
+            let points: Vec<Vec2> = trail
+                .points
+                .iter()
+                .copied()
+                .chain(std::iter::once(transform.translation.truncate()))
+                .collect();
+            let segments = points.len() - 1;
+            for (i, segment) in points.windows(2).enumerate() {
+                let builder = &mut builders[i * BANDS / segments];
+                builder.move_to(segment[0]);
+                builder.line_to(segment[1]);
+            }
+        }
+    }
+
+    for (band, mut path) in bands.iter_mut() {
+        *path = std::mem::replace(&mut builders[band.0], PathBuilder::new()).build();
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+use crate::{
+    flock::{FlockConfig, FlockSettings, Flocks},
+    hud::Hud,
+};
+
+/// Flocking weight the arrow keys adjust, picked with `1`, `2` and `3`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TunedWeight {
+    #[default]
+    Separation,
+    Alignment,
+    Cohesion,
+}
+
+impl TunedWeight {
+    fn name(self) -> &'static str {
+        match self {
+            TunedWeight::Separation => "separation",
+            TunedWeight::Alignment => "alignment",
+            TunedWeight::Cohesion => "cohesion",
+        }
+    }
+
+    fn of_config(self, config: &mut FlockConfig) -> &mut f32 {
+        match self {
+            TunedWeight::Separation => &mut config.separation_weight,
+            TunedWeight::Alignment => &mut config.alignment_weight,
+            TunedWeight::Cohesion => &mut config.cohesion_weight,
+        }
+    }
+
+    fn of_flock(self, settings: &mut FlockSettings) -> &mut f32 {
+        match self {
+            TunedWeight::Separation => &mut settings.separation_weight,
+            TunedWeight::Alignment => &mut settings.alignment_weight,
+            TunedWeight::Cohesion => &mut settings.cohesion_weight,
+        }
+    }
+}
+
+/// Keyboard tuning of the flock, without the egui panel: `1`, `2` and `3`
+/// pick the separation, alignment or cohesion weight, `Up` and `Down` raise
+/// or lower it, for every flock at once, and `[` and `]` shrink or grow the
+/// perception radius. New values are flashed at the top of the window.
+#[derive(Resource)]
+pub struct Tuning {
+    pub weight: TunedWeight,
+    pub weight_step: f32,
+    pub radius_step: f32,
+    // seconds values stay on screen after a change
+    pub flash_duration: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            weight: TunedWeight::default(),
+            weight_step: 0.1,
+            radius_step: 10.,
+            flash_duration: 2.,
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct TuningFlash {
+    // elapsed seconds at which the text is hidden again
+    until: f64,
+}
+
+pub(crate) fn spawn_tuning_flash(
+    mut commands: Commands,
+    hud: Res<Hud>,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(hud.font.as_str()),
+                    font_size: 20.,
+                    color: Color::YELLOW,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(8.),
+                    right: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(TuningFlash { until: 0. });
+}
+
+pub(crate) fn tune_with_keys(
+    time: Res<Time>,
+    keyboard: Res<Input<KeyCode>>,
+    mut tuning: ResMut<Tuning>,
+    mut config: ResMut<FlockConfig>,
+    mut flocks: ResMut<Flocks>,
+    mut flash: Query<(&mut Text, &mut Visibility, &mut TuningFlash)>,
+) {
+    // same bounds as the egui panel's sliders
+    const MAX_WEIGHT: f32 = 5.;
+    const RADIUS_RANGE: (f32, f32) = (10., 400.);
+
+    // with `Shift`, number keys switch presets instead
+    let shift = keyboard.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    for (key, weight) in [
+        (KeyCode::Key1, TunedWeight::Separation),
+        (KeyCode::Key2, TunedWeight::Alignment),
+        (KeyCode::Key3, TunedWeight::Cohesion),
+    ] {
+        if !shift && keyboard.just_pressed(key) {
+            tuning.weight = weight;
+        }
+    }
+    let weight_delta = if keyboard.just_pressed(KeyCode::Up) {
+        tuning.weight_step
+    } else if keyboard.just_pressed(KeyCode::Down) {
+        -tuning.weight_step
+    } else {
+        0.
+    };
+    let radius_delta = if keyboard.just_pressed(KeyCode::RBracket) {
+        tuning.radius_step
+    } else if keyboard.just_pressed(KeyCode::LBracket) {
+        -tuning.radius_step
+    } else {
+        0.
+    };
+
+    let message = if weight_delta != 0. {
+        let weight = tuning.weight;
+        let value = weight.of_config(&mut config);
+        *value = (*value + weight_delta).clamp(0., MAX_WEIGHT);
+        let mut message = format!("{} {:.1}", weight.name(), *value);
+        // flocks have weights of their own, shift them all alike
+        for id in flocks.ids() {
+            let Some(mut settings) = flocks.get(id).cloned() else {
+                continue;
+            };
+            let value = weight.of_flock(&mut settings);
+            *value = (*value + weight_delta).clamp(0., MAX_WEIGHT);
+            message += &format!(", flock {} {:.1}", id.0, *value);
+            flocks.insert(id, settings);
+        }
+        Some(message)
+    } else if radius_delta != 0. {
+        let (min, max) = RADIUS_RANGE;
+        config.neighbor_radius = (config.neighbor_radius + radius_delta).clamp(min, max);
+        Some(format!("perception radius {:.0}", config.neighbor_radius))
+    } else if !shift && keyboard.any_just_pressed([KeyCode::Key1, KeyCode::Key2, KeyCode::Key3]) {
+        let value = *tuning.weight.of_config(config.bypass_change_detection());
+        Some(format!("tuning {} ({value:.1})", tuning.weight.name()))
+    } else {
+        None
+    };
+
+    let Ok((mut text, mut visibility, mut flash)) = flash.get_single_mut() else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    if let Some(message) = message {
+        text.sections[0].value = message;
+        flash.until = now + f64::from(tuning.flash_duration);
+    }
+    visibility.is_visible = now < flash.until;
+}
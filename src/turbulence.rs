@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use noise::{NoiseFn, Perlin};
+
+use crate::{boid::Boid, steering::SteeringForces, tick::SimTick};
+
+/// Small chaotic steering force from Perlin noise over position and time,
+/// so even an idle flock keeps stirring. Unlike `WindGusts`, which drift
+/// slowly over wide fronts, it varies over a few boid lengths and changes
+/// quickly. Off while `strength` is 0.
+#[derive(Resource)]
+pub struct Turbulence {
+    noise: Perlin,
+    // world units per second squared
+    pub strength: f32,
+    // world units per noise period, smaller values give finer eddies
+    pub scale: f32,
+    // how fast the eddies change, in noise units per second
+    pub speed: f32,
+}
+
+impl Default for Turbulence {
+    fn default() -> Self {
+        Self::new(0, 0., 60., 0.5)
+    }
+}
+
+impl Turbulence {
+    pub fn new(seed: u32, strength: f32, scale: f32, speed: f32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            strength,
+            scale,
+            speed,
+        }
+    }
+
+    pub fn sample(&self, position: Vec2, time: f32) -> Vec2 {
+        let p = position / self.scale;
+        let t = (time * self.speed) as f64;
+        // as for gusts, two offset samples give the two components
+        let x = self.noise.get([p.x as f64, p.y as f64, t]);
+        let y = self.noise.get([p.x as f64 - 19.1, p.y as f64 + 53.9, t]);
+        Vec2::new(x as f32, y as f32) * self.strength
+    }
+}
+
+pub(crate) fn apply_turbulence(
+    tick: Res<SimTick>,
+    turbulence: Res<Turbulence>,
+    mut boids: Query<(&Transform, &mut SteeringForces), With<Boid>>,
+) {
+    if turbulence.strength == 0. {
+        return;
+    }
+    let elapsed = tick.elapsed();
+    for (transform, mut forces) in boids.iter_mut() {
+        let force = turbulence.sample(transform.translation.truncate(), elapsed);
+        forces.behavior("turbulence").add(force.extend(0.), 1.);
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::{
+    boid::{Boid, Physics},
+    debug::add_arrow,
+    physics::apply_force,
+    render::{FlockView, MainCamera, Overlay, RenderOrder},
+    tick::SimTick,
+};
+
+/// Wind pushing every boid. Without gusts it is a uniform force; with gusts a
+/// scrolling layered Perlin field is added on top, so boids in different parts
+/// of the window feel different wind.
+#[derive(Resource)]
+pub struct Wind {
+    pub direction: Vec2,
+    // world units per second squared
+    pub strength: f32,
+    pub gusts: Option<WindGusts>,
+}
+
+pub struct WindGusts {
+    noise: Fbm<Perlin>,
+    // world units per noise period, larger values give wider gust fronts
+    pub scale: f32,
+    pub strength: f32,
+    // how fast the field drifts through time, in noise units per second
+    pub scroll_speed: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::X,
+            strength: 0.,
+            gusts: None,
+        }
+    }
+}
+
+impl Wind {
+    pub fn sample(&self, position: Vec2, time: f32) -> Vec2 {
+        let uniform = self.direction.normalize_or_zero() * self.strength;
+        match &self.gusts {
+            Some(gusts) => uniform + gusts.sample(position, time),
+            None => uniform,
+        }
+    }
+}
+
+impl WindGusts {
+    pub fn new(seed: u32, scale: f32, strength: f32, scroll_speed: f32) -> Self {
+        Self {
+            noise: Fbm::<Perlin>::new(seed).set_octaves(3),
+            scale,
+            strength,
+            scroll_speed,
+        }
+    }
+
+    pub fn sample(&self, position: Vec2, time: f32) -> Vec2 {
+        let p = position / self.scale;
+        let t = (time * self.scroll_speed) as f64;
+        // two decorrelated samples of the same field give the x and y components
+        let x = self.noise.get([p.x as f64, p.y as f64, t]);
+        let y = self.noise.get([p.x as f64 + 31.7, p.y as f64 - 47.3, t]);
+        Vec2::new(x as f32, y as f32) * self.strength
+    }
+}
+
+pub(crate) fn apply_wind(
+    tick: Res<SimTick>,
+    wind: Res<Wind>,
+    mut query: Query<(&Transform, &mut Physics), With<Boid>>,
+) {
+    let elapsed = tick.elapsed();
+    for (transform, mut physics) in query.iter_mut() {
+        let force = wind.sample(transform.translation.truncate(), elapsed);
+        apply_force(physics.as_mut(), &force.extend(0.));
+    }
+}
+
+/// Arrow in the top right corner showing the wind at the center of the view.
+#[derive(Component)]
+pub(crate) struct WindArrow;
+
+pub(crate) fn spawn_wind_arrow(
+    mut commands: Commands,
+    order: Res<RenderOrder>,
+    view: Res<FlockView>,
+) {
+    commands
+        .spawn(GeometryBuilder::build_as(
+            &PathBuilder::new().build(),
+            DrawMode::Stroke(StrokeMode::new(Color::rgba(0.6, 0.8, 1., 0.8), 2.)),
+            Transform::from_xyz(0., 0., order.overlays),
+        ))
+        .insert(view.layers)
+        .insert(Overlay)
+        .insert(WindArrow);
+}
+
+/// Keeps the wind arrow pinned to the corner of the window, whatever the
+/// camera does, pointing along the wind with a length growing with its
+/// strength. It is hidden while there is no wind at all.
+pub(crate) fn draw_wind_arrow(
+    windows: Res<Windows>,
+    tick: Res<SimTick>,
+    wind: Res<Wind>,
+    camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut arrow: Query<&mut Transform, (With<WindArrow>, Without<MainCamera>)>,
+    mut drawing: Query<(&mut Path, &mut Visibility), With<WindArrow>>,
+) {
+    // in pixels: the dial and the longest arrow, and the gap to the corner
+    const RADIUS: f32 = 24.;
+    const MARGIN: Vec2 = Vec2::new(48., 72.);
+    // pixels of arrow per world unit per second squared of wind
+    const SCALE: f32 = 0.5;
+
+    let (Some(window), Ok((camera, projection))) = (windows.get_primary(), camera.get_single())
+    else {
+        return;
+    };
+    let (Ok(mut transform), Ok((mut path, mut visibility))) =
+        (arrow.get_single_mut(), drawing.get_single_mut())
+    else {
+        return;
+    };
+    visibility.is_visible = wind.strength != 0. || wind.gusts.is_some();
+    if !visibility.is_visible {
+        return;
+    }
+
+    let corner = Vec2::new(window.width(), window.height()) / 2. - MARGIN;
+    let offset = camera.rotation * (corner * projection.scale).extend(0.);
+    transform.translation = (camera.translation + offset)
+        .truncate()
+        .extend(transform.translation.z);
+    transform.rotation = camera.rotation;
+    transform.scale = Vec3::new(projection.scale, projection.scale, 1.);
+
+    let force = wind.sample(camera.translation.truncate(), tick.elapsed());
+    // the arrow turns with the view, so undo the camera's rotation
+    let local = (camera.rotation.inverse() * force.extend(0.)).truncate();
+    let half = local.clamp_length_max(RADIUS / SCALE) * SCALE / 2.;
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::X * RADIUS);
+    builder.arc(Vec2::ZERO, Vec2::splat(RADIUS), std::f32::consts::TAU, 0.);
+    if half != Vec2::ZERO {
+        add_arrow(&mut builder, -half, half, 6.);
+    }
+    *path = builder.build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gusts_differ_far_apart() {
+        let wind = Wind {
+            direction: Vec2::X,
+            strength: 20.,
+            gusts: Some(WindGusts::new(7, 200., 60., 0.1)),
+        };
+        let here = wind.sample(Vec2::new(-300., -250.), 1.);
+        let there = wind.sample(Vec2::new(350., 280.), 1.);
+        assert!(here.distance(there) > 1., "{here} and {there}");
+    }
+
+    #[test]
+    fn uniform_without_gusts() {
+        let wind = Wind {
+            direction: Vec2::Y,
+            strength: 20.,
+            gusts: None,
+        };
+        let here = wind.sample(Vec2::new(-300., -250.), 1.);
+        let there = wind.sample(Vec2::new(350., 280.), 1.);
+        assert_eq!(here, there);
+        assert_eq!(here, Vec2::new(0., 20.));
+    }
+}
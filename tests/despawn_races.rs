@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy_lyon_boid::{
+    BoidBuilder, BoidDespawned, BoundaryMode, Bounds, Energy, FlockConfig, FlockId, Formation,
+    FormationShape, LeaderSettings, Navigation, Predator, SimulationControl, SimulationPlugin,
+    Target,
+};
+
+/// A headless app without a flock, despawning boids leaving its bounds.
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(SimulationPlugin)
+        .insert_resource(Bounds {
+            min: Vec2::splat(-400.),
+            max: Vec2::splat(400.),
+        })
+        .insert_resource(BoundaryMode::Despawn)
+        .insert_resource(SimulationControl {
+            lockstep: true,
+            ..default()
+        })
+        .insert_resource(FlockConfig {
+            count: 0,
+            ..default()
+        });
+    // startup
+    app.update();
+    app
+}
+
+/// Runs a step, and whether it despawned `boid`.
+fn step_despawns(app: &mut App, boid: Entity) -> bool {
+    app.update();
+    let events = app.world.resource::<Events<BoidDespawned>>();
+    let despawned = events
+        .get_reader()
+        .iter(events)
+        .any(|event| event.entity == boid);
+    despawned && app.world.get_entity(boid).is_none()
+}
+
+/// A boid which flew a step, then was left outside the bounds.
+fn boid_left_out(app: &mut App) -> Entity {
+    let boid = app.world.spawn(BoidBuilder::new().build()).id();
+    app.update();
+    app.insert_resource(Bounds {
+        min: Vec2::splat(100.),
+        max: Vec2::splat(400.),
+    });
+    boid
+}
+
+#[test]
+fn boids_exhausted_as_they_leave_the_bounds_despawn() {
+    let mut app = app();
+    let boid = boid_left_out(&mut app);
+    app.world.entity_mut(boid).insert(Energy {
+        current: 0.,
+        ..default()
+    });
+    assert!(step_despawns(&mut app, boid));
+}
+
+#[test]
+fn boids_made_leaders_as_they_leave_the_bounds_despawn() {
+    let mut app = app();
+    let boid = boid_left_out(&mut app);
+    app.insert_resource(LeaderSettings {
+        auto: true,
+        ..default()
+    });
+    assert!(step_despawns(&mut app, boid));
+}
+
+#[test]
+fn boids_given_slots_as_they_leave_the_bounds_despawn() {
+    let mut app = app();
+    let boid = boid_left_out(&mut app);
+    let anchor = app.world.spawn(TransformBundle::default()).id();
+    app.world.spawn(Formation::new(
+        FormationShape::V,
+        anchor,
+        FlockId::default(),
+    ));
+    assert!(step_despawns(&mut app, boid));
+}
+
+#[test]
+fn boids_navigating_as_they_leave_the_bounds_despawn() {
+    let mut app = app();
+    let boid = boid_left_out(&mut app);
+    app.insert_resource(Navigation {
+        enabled: true,
+        ..default()
+    });
+    assert!(step_despawns(&mut app, boid));
+}
+
+#[test]
+fn boids_caught_as_they_reach_the_target_despawn() {
+    let mut app = app();
+    // away from the main target, to reach the one put on it next
+    let boid = app
+        .world
+        .spawn(BoidBuilder::new().at(Vec3::new(200., 0., 0.)).build())
+        .id();
+    app.update();
+    let position = app.world.get::<Transform>(boid).unwrap().translation;
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position)),
+        Target,
+    ));
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position)),
+        Predator,
+    ));
+    assert!(step_despawns(&mut app, boid));
+}
+
+#[test]
+fn boids_spawned_out_of_the_bounds_despawn() {
+    let mut app = app();
+    let boid = app
+        .world
+        .spawn(BoidBuilder::new().at(Vec3::new(500., 0., 0.)).build())
+        .id();
+    assert!(step_despawns(&mut app, boid));
+}
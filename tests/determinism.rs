@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use bevy_lyon_boid::{BoidId, Bounds, FlockConfig, SimSeed, SimulationControl, SimulationPlugin};
+
+/// Positions of every boid after `steps` steps of a headless run seeded with
+/// `seed`, by boid.
+fn run(seed: u64, steps: usize) -> Vec<(u32, Vec3)> {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(SimulationPlugin)
+        .insert_resource(Bounds {
+            min: Vec2::splat(-400.),
+            max: Vec2::splat(400.),
+        })
+        .insert_resource(SimulationControl {
+            lockstep: true,
+            ..default()
+        })
+        .insert_resource(FlockConfig {
+            count: 30,
+            ..default()
+        })
+        .insert_resource(SimSeed(seed));
+    for _ in 0..steps {
+        app.update();
+    }
+
+    let mut boids: Vec<_> = app
+        .world
+        .query::<(&BoidId, &Transform)>()
+        .iter(&app.world)
+        .map(|(id, transform)| (id.0, transform.translation))
+        .collect();
+    boids.sort_by_key(|(id, _)| *id);
+    boids
+}
+
+#[test]
+fn same_seed_same_flight() {
+    let first = run(7, 120);
+    assert_eq!(first.len(), 30);
+    assert_eq!(first, run(7, 120));
+    assert_ne!(first, run(8, 120));
+}
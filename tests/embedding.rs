@@ -0,0 +1,64 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_lyon_boid::{Boid, FlockView, MainTarget, RenderOrder, SimulationPlugin};
+
+/// Flock embedded behind an app's own camera and UI, on render layer 1. The
+/// simulation spawns the same entities `BoidPlugin` draws, without needing a
+/// window or a GPU.
+fn embedded_app() -> App {
+    let mut app = App::new();
+    app.insert_resource(FlockView {
+        layers: RenderLayers::layer(1),
+        priority: -1,
+        ..default()
+    })
+    .add_plugins(MinimalPlugins)
+    .add_plugin(SimulationPlugin)
+    .add_startup_system(spawn_ui);
+    app
+}
+
+fn spawn_ui(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+    commands.spawn(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(200.), Val::Percent(100.)),
+            ..default()
+        },
+        background_color: Color::rgba(0., 0., 0., 0.8).into(),
+        ..default()
+    });
+}
+
+#[test]
+fn flock_stays_on_its_layer_behind_the_ui() {
+    let mut app = embedded_app();
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let flock_layer = RenderLayers::layer(1);
+    let world = &mut app.world;
+    // the app's camera sees only the default layer, so draws none of the flock
+    let mut cameras = world.query_filtered::<Option<&RenderLayers>, With<Camera>>();
+    let ui_layers = *cameras.single(world).unwrap_or(&RenderLayers::default());
+    assert!(!ui_layers.intersects(&flock_layer));
+    let mut nodes = world.query::<&Node>();
+    assert_eq!(nodes.iter(world).count(), 1);
+
+    let order = world.resource::<RenderOrder>();
+    let (boids_z, target_z) = (order.boids, order.target);
+    let mut boids = world.query_filtered::<(&Transform, &RenderLayers), With<Boid>>();
+    let mut count = 0;
+    for (transform, layers) in boids.iter(world) {
+        assert_eq!(*layers, flock_layer);
+        assert_eq!(transform.translation.z, boids_z);
+        count += 1;
+    }
+    assert!(count > 0);
+
+    let mut target = world.query_filtered::<(&Transform, &RenderLayers), With<MainTarget>>();
+    let (transform, layers) = target.single(world);
+    assert_eq!(*layers, flock_layer);
+    assert!(transform.translation.z > boids_z);
+    assert_eq!(transform.translation.z, target_z);
+}
@@ -0,0 +1,53 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_lyon_boid::{Bounds, RunDuration, SimTick, SimulationControl, SimulationPlugin};
+
+/// Headless app as run by `--headless`, stepping once per update.
+fn headless_app(duration: RunDuration) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(SimulationPlugin)
+        .insert_resource(Bounds {
+            min: Vec2::splat(-400.),
+            max: Vec2::splat(400.),
+        })
+        .insert_resource(SimulationControl {
+            lockstep: true,
+            ..default()
+        })
+        .insert_resource(duration);
+    app
+}
+
+/// Updates `app` until it asks to exit, at most `max_updates` times, and
+/// returns how many updates it took.
+fn run_until_exit(app: &mut App, max_updates: usize) -> Option<usize> {
+    let mut exits = app.world.resource::<Events<AppExit>>().get_reader();
+    for update in 1..=max_updates {
+        app.update();
+        let events = app.world.resource::<Events<AppExit>>();
+        if exits.iter(events).next().is_some() {
+            return Some(update);
+        }
+    }
+    None
+}
+
+#[test]
+fn exits_after_the_simulated_seconds() {
+    let mut app = headless_app(RunDuration::Simulated(2.));
+    let updates = run_until_exit(&mut app, 1000).expect("the run never ended");
+
+    let tick = app.world.resource::<SimTick>();
+    assert!(tick.elapsed() >= 2.);
+    // within a step of the requested duration
+    assert!(tick.elapsed() < 2. + tick.step() + 1e-4);
+    // one step per update, with a few updates of startup at most
+    assert!(updates as u64 <= tick.steps() + 2);
+}
+
+#[test]
+fn exits_after_the_steps() {
+    let mut app = headless_app(RunDuration::Steps(30));
+    run_until_exit(&mut app, 1000).expect("the run never ended");
+    assert_eq!(app.world.resource::<SimTick>().steps(), 30);
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use bevy_lyon_boid::{
+    Boid, Bounds, FlockConfig, Physics, SimSeed, SimulationControl, SimulationPlugin,
+    SteeringTrace, SteeringTracing,
+};
+
+#[test]
+fn traces_break_down_the_applied_force() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(SimulationPlugin)
+        .insert_resource(Bounds {
+            min: Vec2::splat(-400.),
+            max: Vec2::splat(400.),
+        })
+        .insert_resource(SimulationControl {
+            lockstep: true,
+            ..default()
+        })
+        .insert_resource(FlockConfig {
+            count: 20,
+            ..default()
+        })
+        .insert_resource(SimSeed(5))
+        .insert_resource(SteeringTracing { enabled: true });
+    for _ in 0..30 {
+        app.update();
+    }
+
+    let events = app.world.resource::<Events<SteeringTrace>>();
+    let traces: Vec<SteeringTrace> = events.get_reader().iter(events).cloned().collect();
+    let last = traces.iter().map(|trace| trace.step).max().unwrap();
+    let traces: Vec<_> = traces
+        .into_iter()
+        .filter(|trace| trace.step == last)
+        .collect();
+    // one for every boid
+    let boids = app
+        .world
+        .query_filtered::<Entity, With<Boid>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(traces.len(), boids);
+
+    for trace in &traces {
+        let physics = app.world.get::<Physics>(trace.entity).unwrap();
+        assert!(
+            trace.force.length() <= physics.max_force + 1e-3,
+            "{trace:?}"
+        );
+        // from the highest priority down
+        assert!(
+            trace
+                .contributions
+                .windows(2)
+                .all(|pair| pair[0].priority >= pair[1].priority),
+            "{trace:?}"
+        );
+        for behavior in ["separation", "alignment", "cohesion"] {
+            assert!(
+                trace
+                    .contributions
+                    .iter()
+                    .any(|contribution| contribution.behavior == behavior),
+                "no {behavior} in {trace:?}"
+            );
+        }
+        // nothing is cut short while the forces fit in `max_force`
+        let total: Vec3 = trace
+            .contributions
+            .iter()
+            .map(|contribution| contribution.force)
+            .sum();
+        if trace
+            .contributions
+            .iter()
+            .map(|contribution| contribution.force.length())
+            .sum::<f32>()
+            < physics.max_force
+        {
+            assert!(trace.force.abs_diff_eq(total, 1e-3), "{trace:?}");
+        }
+    }
+}